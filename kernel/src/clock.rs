@@ -1,13 +1,49 @@
+use alloc::collections::BTreeMap;
 use alloc::format;
-use alloc::string::String;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use alloc::string::{String, ToString};
+use core::fmt::{Display, Formatter};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
 use smoltcp::time::{Duration, Instant};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
 
 static TICKS: AtomicUsize = AtomicUsize::new(0);
 
+/// Unix time in milliseconds at boot, as computed by the SNTP client
+static UNIX_TIME_AT_BOOT: AtomicI64 = AtomicI64::new(UNSYNCHRONIZED);
+const UNSYNCHRONIZED: i64 = i64::MIN;
+
+/// Deadline and waker of the pending timers, by timer id. A timer polled again replaces its entry.
+static TIMERS: Mutex<BTreeMap<u64, (Instant, Waker)>> = Mutex::new(BTreeMap::new());
+
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(0);
+
 /// This tick interrupt handler is assumed to be called once per millisecond
 pub fn tick_handler() {
     TICKS.fetch_add(1, Ordering::Relaxed);
+    wake_expired_timers();
+}
+
+fn wake_expired_timers() {
+    // A timer may be registering itself, it will be woken at the next tick
+    let Some(mut timers) = TIMERS.try_lock() else {
+        return;
+    };
+
+    let now = Clock::now();
+
+    timers.retain(|_, (deadline, waker)| {
+        if *deadline <= now {
+            waker.wake_by_ref();
+            false
+        }
+        else {
+            true
+        }
+    });
 }
 
 pub struct Clock;
@@ -28,10 +64,154 @@ impl Clock {
     pub fn elapsed(instant: Instant) -> Duration {
         Clock::now() - instant
     }
+
+    pub fn is_synchronized() -> bool {
+        UNIX_TIME_AT_BOOT.load(Ordering::Relaxed) != UNSYNCHRONIZED
+    }
+
+    /// Set the unix time in milliseconds corresponding to the given instant
+    pub fn synchronize(instant: Instant, unix_millis: i64) {
+        UNIX_TIME_AT_BOOT.store(unix_millis - instant.total_millis(), Ordering::Relaxed);
+    }
+
+    /// Unix time in milliseconds of the given instant, if the clock is synchronized
+    pub fn unix_millis(instant: Instant) -> Option<i64> {
+        let unix_time_at_boot = UNIX_TIME_AT_BOOT.load(Ordering::Relaxed);
+
+        match unix_time_at_boot {
+            UNSYNCHRONIZED => None,
+            _ => Some(unix_time_at_boot + instant.total_millis())
+        }
+    }
+
+    pub fn utc(instant: Instant) -> Option<DateTime> {
+        Clock::unix_millis(instant).map(DateTime::from_unix_millis)
+    }
+
+    pub fn utc_now() -> Option<DateTime> {
+        Clock::utc(Clock::now())
+    }
+
+    /// Format an instant as a UTC date when the clock is synchronized, as a time since boot otherwise
+    pub fn format_instant(instant: Instant) -> String {
+        match Clock::utc(instant) {
+            Some(date_time) => date_time.to_string(),
+            None => instant.to_string()
+        }
+    }
+}
+
+/// UTC date and time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millisecond: u16,
+}
+
+impl DateTime {
+    pub fn from_unix_millis(unix_millis: i64) -> Self {
+        let millis_of_day = unix_millis.rem_euclid(86_400_000);
+        let days = unix_millis.div_euclid(86_400_000);
+
+        // Civil from days (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: (millis_of_day / 3_600_000) as u8,
+            minute: (millis_of_day / 60_000 % 60) as u8,
+            second: (millis_of_day / 1000 % 60) as u8,
+            millisecond: (millis_of_day % 1000) as u16,
+        }
+    }
+}
+
+impl Display for DateTime {
+    /// RFC 3339 format
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.millisecond
+        )
+    }
+}
+
+/// Future that completes once its deadline is reached, without blocking the executor
+pub struct Timer {
+    id: u64,
+    deadline: Instant,
+}
+
+impl Timer {
+    pub fn at(deadline: Instant) -> Self {
+        Self {
+            id: NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed),
+            deadline,
+        }
+    }
+
+    pub fn after(duration: Duration) -> Self {
+        Self::at(Clock::now() + duration)
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if Clock::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        // The tick handler also locks the timers
+        interrupts::without_interrupts(|| {
+            let mut timers = TIMERS.lock();
+
+            // Polled again by a select loop, with the same task most of the time
+            if timers.get(&self.id).is_some_and(|(_, waker)| waker.will_wake(context.waker())) {
+                return;
+            }
+
+            timers.insert(self.id, (self.deadline, context.waker().clone()));
+        });
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        // Cancelled before its deadline, such as the losing branch of a select
+        interrupts::without_interrupts(|| {
+            TIMERS.lock().remove(&self.id);
+        });
+    }
 }
 
 pub fn sleep(seconds: u64) {
     let start = Clock::now();
     let duration = Duration::from_secs(seconds);
     while Clock::elapsed(start) < duration {}
-}
\ No newline at end of file
+}
@@ -13,6 +13,8 @@ use spin::Mutex;
 use x86_64::instructions::interrupts;
 use x86_64::VirtAddr;
 use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::driver::LinkStatus;
+use crate::devices::network::statistics::DriverStatistics;
 
 const GOOLOG_TARGET: &str = "E1000";
 
@@ -52,11 +54,36 @@ const REG_RDH: u16 = 0x2810;        // RX Descriptor Head
 const REG_RDT: u16 = 0x2818;        // RX Descriptor Tail
 const REG_RAL0: u16 = 0x5400;       // Receive Address Low (0)
 const REG_RAH0: u16 = 0x5404;       // Receive Address High (0)
+const RAH_AV: u32 = 1 << 31;        // Address Valid
+const REG_MTA: u16 = 0x5200;        // Multicast Table Array
+
+// Receive address slots, the first one holding the MAC address of the interface
+const RECEIVE_ADDRESSES: usize = 16;
+pub const MAX_SECONDARY_MACS: usize = RECEIVE_ADDRESSES - 1;
+
+// Multicast Table Array, a 4096 bits hash filter
+const MTA_REGISTERS: usize = 128;
+
+// Statistics Registers, cleared when read
+const REG_CRCERRS: u16 = 0x4000;    // CRC Error Count
+const REG_ALGNERRC: u16 = 0x4004;   // Alignment Error Count
+const REG_RXERRC: u16 = 0x400C;     // RX Error Count
+const REG_MPC: u16 = 0x4010;        // Missed Packets Count
+const REG_ECOL: u16 = 0x4018;       // Excessive Collisions Count
+const REG_LATECOL: u16 = 0x4020;    // Late Collisions Count
+const REG_COLC: u16 = 0x4028;       // Collision Count
+const REG_RLEC: u16 = 0x4040;       // Receive Length Error Count
 
 // Control Register Bits
 const CTRL_RESET: u32 = 1 << 26;    // Reset
 const CTRL_SLU: u32 = 1 << 6;       // Set Link Up
 
+// Status Register Bits
+const STATUS_FD: u32 = 1 << 0;        // Full Duplex
+const STATUS_LU: u32 = 1 << 1;        // Link Up
+const STATUS_SPEED_SHIFT: u32 = 6;    // Link Speed, 2 bits
+const STATUS_SPEED_MASK: u32 = 0b11;
+
 // Transmit Control Register Bits
 const TCTL_EN: u32 = 1 << 1;        // Transmit Enable
 const TCTL_PSP: u32 = 1 << 3;       // Pad Short Packets
@@ -72,6 +99,7 @@ const RCTL_UPE: u32 = 1 << 3;       // Unicast Promiscuous Enable
 const RCTL_MPE: u32 = 1 << 4;       // Multicast Promiscuous Enable
 #[allow(unused)]
 const RCTL_LBM_NONE: u32 = 0 << 6;  // No Loopback
+const RCTL_LPE: u32 = 1 << 5;       // Long Packet Enable
 const RCTL_BAM: u32 = 1 << 15;      // Broadcast Accept Mode
 const RCTL_SECRC: u32 = 1 << 26;    // Strip Ethernet CRC
 
@@ -83,11 +111,8 @@ const RCTL_BSIZE_512: u32 = 2 << 16;
 #[allow(unused)]
 const RCTL_BSIZE_1024: u32 = 1 << 16;
 const RCTL_BSIZE_2048: u32 = 0 << 16;
-#[allow(unused)]
 const RCTL_BSIZE_4096: u32 = (3 << 16) | (1 << 25);
-#[allow(unused)]
 const RCTL_BSIZE_8192: u32 = (2 << 16) | (1 << 25);
-#[allow(unused)]
 const RCTL_BSIZE_16384: u32 = (1 << 16) | (1 << 25);
 
 // Interrupt Bits
@@ -112,6 +137,13 @@ const TX_DESC_STATUS_DD: u8 = 1 << 0; // Descriptor Done
 // Receive Descriptor Bits
 const RX_DESC_STATUS_DD: u8 = 1 << 0; // Descriptor Done
 const RX_DESC_STATUS_EOP: u8 = 1 << 1; // End of Packet
+const RX_DESC_ERRORS_CE: u8 = 1 << 0;  // CRC Error
+const RX_DESC_ERRORS_SE: u8 = 1 << 1;  // Symbol Error
+const RX_DESC_ERRORS_SEQ: u8 = 1 << 2; // Sequence Error
+const RX_DESC_ERRORS_CXE: u8 = 1 << 4; // Carrier Extension Error
+const RX_DESC_ERRORS_RXE: u8 = 1 << 7; // RX Data Error
+// Errors of the frame itself, the checksum errors are left to smoltcp
+const RX_DESC_ERRORS_FRAME: u8 = RX_DESC_ERRORS_CE | RX_DESC_ERRORS_SE | RX_DESC_ERRORS_SEQ | RX_DESC_ERRORS_CXE | RX_DESC_ERRORS_RXE;
 
 // Number of descriptors
 const RX_DESCRIPTORS: usize = 32;
@@ -121,6 +153,14 @@ const TX_DESCRIPTORS: usize = 32;
 pub const RX_BUFFER_SIZE: usize = 2048;
 pub const TX_BUFFER_SIZE: usize = 2048;
 
+// Ethernet header with a VLAN tag, and CRC
+const FRAME_OVERHEAD: usize = 22;
+// Largest frame without the long packet enable, VLAN tag included
+const MAX_STANDARD_FRAME_SIZE: usize = 1522;
+// Largest frame the controller handles
+const MAX_FRAME_SIZE: usize = 16128;
+pub const MAX_MTU: usize = MAX_FRAME_SIZE - FRAME_OVERHEAD;
+
 #[repr(C, align(16))]
 #[derive(Debug, Copy, Clone)]
 pub struct TxDescriptor {
@@ -156,19 +196,22 @@ struct E1000State {
     pub mmio_base: u64,
     pub rx: Mutex<E1000Rx>,
     pub tx: Mutex<E1000Tx>,
+    pub statistics: Mutex<DriverStatistics>,
 }
 
 #[derive(Debug)]
 pub struct E1000Rx {
     pub rx_descriptors: Box<[RxDescriptor; RX_DESCRIPTORS]>,
-    pub rx_buffers: Box<Vec<[u8; RX_BUFFER_SIZE]>>,
+    pub rx_buffers: Vec<Vec<u8>>,
     pub rx_cursor: usize,
+    /// Buffer size and long packet enable bits of the RCTL register
+    pub rctl_buffer: u32,
 }
 
 #[derive(Debug)]
 pub struct E1000Tx {
     pub tx_descriptors: Box<[TxDescriptor; TX_DESCRIPTORS]>,
-    pub tx_buffers: Box<Vec<[u8; TX_BUFFER_SIZE]>>,
+    pub tx_buffers: Vec<Vec<u8>>,
     pub tx_cursor: usize,
 }
 
@@ -220,21 +263,23 @@ impl E1000 {
             special: 0,
         }; RX_DESCRIPTORS];
 
-        let tx_buffers = vec![[0u8; TX_BUFFER_SIZE]; TX_DESCRIPTORS];
-        let rx_buffers = vec![[0u8; RX_BUFFER_SIZE]; RX_DESCRIPTORS];
+        let tx_buffers = vec![vec![0u8; TX_BUFFER_SIZE]; TX_DESCRIPTORS];
+        let rx_buffers = vec![vec![0u8; RX_BUFFER_SIZE]; RX_DESCRIPTORS];
 
         let state = E1000State {
             mmio_base,
             rx: Mutex::new(E1000Rx {
                 rx_descriptors: Box::from(rx_descriptors),
-                rx_buffers: Box::from(rx_buffers),
+                rx_buffers,
                 rx_cursor: 0,
+                rctl_buffer: RCTL_BSIZE_2048,
             }),
             tx: Mutex::new(E1000Tx {
                 tx_descriptors: Box::from(tx_descriptors),
-                tx_buffers: Box::from(tx_buffers),
+                tx_buffers,
                 tx_cursor: 0,
-            })
+            }),
+            statistics: Mutex::new(DriverStatistics::default()),
         };
 
         Self {
@@ -261,6 +306,9 @@ impl E1000 {
         // Read MAC address from device
         self.read_mac_address();
 
+        // No multicast group joined yet, the table is not cleared by the reset
+        self.set_multicast_filter(&[]);
+
         // Initialize rx/tx descriptors
         self.setup_rx_descriptors();
         self.setup_tx_descriptors();
@@ -332,8 +380,50 @@ impl E1000 {
         }
 
         // Set the MAC address in the device registers
-        self.write_register(REG_RAL0, (self.mac[3] as u32) << 24 | (self.mac[2] as u32) << 16 | (self.mac[1] as u32) << 8 | (self.mac[0] as u32));
-        self.write_register(REG_RAH0, (1 << 31) | (self.mac[5] as u32) << 8 | (self.mac[4] as u32));
+        self.write_receive_address(0, Some(self.mac));
+    }
+
+    /// Program a receive address slot, or invalidate it
+    fn write_receive_address(&self, slot: usize, mac: Option<[u8; 6]>) {
+        let offset = 8 * slot as u16;
+
+        match mac {
+            Some(mac) => {
+                self.write_register(REG_RAL0 + offset, (mac[3] as u32) << 24 | (mac[2] as u32) << 16 | (mac[1] as u32) << 8 | (mac[0] as u32));
+                self.write_register(REG_RAH0 + offset, RAH_AV | (mac[5] as u32) << 8 | (mac[4] as u32));
+            },
+            None => {
+                self.write_register(REG_RAH0 + offset, 0);
+                self.write_register(REG_RAL0 + offset, 0);
+            }
+        }
+    }
+
+    pub fn set_mac(&mut self, mac: [u8; 6]) {
+        self.mac = mac;
+        self.write_receive_address(0, Some(mac));
+    }
+
+    /// Accept the frames sent to these addresses as well, in the slots after the primary one
+    pub fn set_secondary_macs(&self, macs: &[[u8; 6]]) {
+        for slot in 1..RECEIVE_ADDRESSES {
+            self.write_receive_address(slot, macs.get(slot - 1).copied());
+        }
+    }
+
+    /// Accept the frames sent to these multicast addresses, along with the ones sharing their hash
+    pub fn set_multicast_filter(&self, macs: &[[u8; 6]]) {
+        let mut table = [0u32; MTA_REGISTERS];
+
+        for mac in macs {
+            // Bits 47:36 of the address, as selected by RCTL.MO = 00
+            let hash = ((mac[4] >> 4) as usize | (mac[5] as usize) << 4) & 0xFFF;
+            table[hash >> 5] |= 1 << (hash & 0x1F);
+        }
+
+        for (index, bits) in table.iter().enumerate() {
+            self.write_register(REG_MTA + 4 * index as u16, *bits);
+        }
     }
 
     fn setup_rx_descriptors(&self) {
@@ -421,10 +511,98 @@ impl E1000 {
         self.write_register(REG_TIPG, 0x0060200A); // Standard values for fiber/copper
     }
 
+    /// Start or stop receiving and transmitting, the receive ring is reset when started again
+    pub fn enable(&self, enabled: bool) {
+        match enabled {
+            true => {
+                self.reset_rx_ring();
+                self.configure_tx();
+            },
+            false => {
+                self.write_register(REG_RCTL, self.read_register(REG_RCTL) & !RCTL_EN);
+                self.write_register(REG_TCTL, self.read_register(REG_TCTL) & !TCTL_EN);
+            }
+        }
+    }
+
+    pub fn read_link_status(&self) -> LinkStatus {
+        let status = self.read_register(REG_STATUS);
+
+        LinkStatus {
+            carrier: (status & STATUS_LU) != 0,
+            speed: match (status >> STATUS_SPEED_SHIFT) & STATUS_SPEED_MASK {
+                0b00 => 10,
+                0b01 => 100,
+                _ => 1000
+            },
+            full_duplex: (status & STATUS_FD) != 0,
+        }
+    }
+
     fn configure_rx(&self) {
         // Setup receive control register
-        let rctl = RCTL_EN | RCTL_SBP | RCTL_BAM | RCTL_SECRC | RCTL_BSIZE_2048;
+        self.write_register(REG_RCTL, self.receive_control());
+    }
+
+    /// Receive control register value, for the current buffer size
+    fn receive_control(&self) -> u32 {
+        RCTL_EN | RCTL_SBP | RCTL_BAM | RCTL_SECRC | self.state.rx.lock().rctl_buffer
+    }
+
+    /// Resize the buffers for the frames of the given MTU, the receive ring is reset
+    pub fn set_mtu(&self, mtu: usize) {
+        let frame_size = mtu + FRAME_OVERHEAD;
+
+        let (rx_buffer_size, mut rctl_buffer) = if frame_size <= RX_BUFFER_SIZE {
+            (RX_BUFFER_SIZE, RCTL_BSIZE_2048)
+        }
+        else if frame_size <= 4096 {
+            (4096, RCTL_BSIZE_4096)
+        }
+        else if frame_size <= 8192 {
+            (8192, RCTL_BSIZE_8192)
+        }
+        else {
+            (16384, RCTL_BSIZE_16384)
+        };
+
+        if frame_size > MAX_STANDARD_FRAME_SIZE {
+            rctl_buffer |= RCTL_LPE;
+        }
+
+        let was_enabled = (self.read_register(REG_RCTL) & RCTL_EN) != 0;
+        self.write_register(REG_RCTL, 0);
+
+        {
+            let mut rx = self.state.rx.lock();
+            rx.rx_buffers = vec![vec![0u8; rx_buffer_size]; RX_DESCRIPTORS];
+            rx.rctl_buffer = rctl_buffer;
+        }
+
+        self.setup_rx_descriptors();
+
+        // An interface which is down stays so
+        let rctl = match was_enabled {
+            true => self.receive_control(),
+            false => self.receive_control() & !RCTL_EN
+        };
         self.write_register(REG_RCTL, rctl);
+
+        // The frames being sent are left to complete before their buffers are replaced
+        let mut tx = self.state.tx.lock();
+        let tx_buffer_size = frame_size.max(TX_BUFFER_SIZE);
+
+        for i in 0..TX_DESCRIPTORS {
+            #[allow(clippy::while_immutable_condition)]
+            while (tx.tx_descriptors[i].status & TX_DESC_STATUS_DD) == 0 && tx.tx_descriptors[i].cmd != 0 && was_enabled {
+                core::hint::spin_loop();
+            }
+
+            tx.tx_buffers[i] = vec![0u8; tx_buffer_size];
+
+            let buffer_addr = VirtAddr::new(tx.tx_buffers[i].as_ptr() as u64);
+            tx.tx_descriptors[i].buffer_addr = translate_addr(buffer_addr).unwrap().as_u64();
+        }
     }
 
     fn enable_interrupts(&self) {
@@ -454,7 +632,7 @@ impl E1000 {
         // Handle link status change
         if (interrupt_cause & INTERRUPT_LSC) != 0 {
             let status = self.read_register(REG_STATUS);
-            if (status & (1 << 1)) != 0 {
+            if (status & STATUS_LU) != 0 {
                 let ctrl = self.read_register(REG_CTRL);
                 if (ctrl & CTRL_SLU) == 0 {
                     self.write_register(REG_CTRL, ctrl | CTRL_SLU);
@@ -489,7 +667,7 @@ impl E1000 {
                 }
 
                 // Free any associated buffers if needed
-                tx.tx_buffers[i] = [0; TX_BUFFER_SIZE];
+                tx.tx_buffers[i].fill(0);
 
                 // Reset descriptor status
                 tx.tx_descriptors[i].status = 0;
@@ -515,8 +693,7 @@ impl E1000 {
         self.setup_rx_descriptors();
 
         // Re-enable receive
-        let rctl = RCTL_EN | RCTL_SBP | RCTL_BAM | RCTL_SECRC | RCTL_BSIZE_2048;
-        self.write_register(REG_RCTL, rctl);
+        self.write_register(REG_RCTL, self.receive_control());
     }
 
     fn process_rx_packets(&self) {
@@ -538,6 +715,7 @@ impl E1000 {
 
             if (desc.status & RX_DESC_STATUS_EOP) == 0 {
                 // Not end of packet - we don't handle multi-descriptor packets yet
+                self.state.statistics.lock().rx_errors += 1;
 
                 // Reset the descriptor
                 desc.status = 0;
@@ -553,9 +731,10 @@ impl E1000 {
             self.write_register(REG_RDT, i as u32);
 
             // Should be done before the descriptor reset but then rx cannot be borrowed as mutable twice
-            // We have a complete packet
+            // We have a complete packet, the bad ones are stored too and counted by the statistics registers
             let length = desc.length as usize;
-            if length > 0 {
+            let errors = desc.errors;
+            if length > 0 && (errors & RX_DESC_ERRORS_FRAME) == 0 {
                 // Copy the packet to a new buffer
                 let packet = rx.rx_buffers[i][0..length].to_vec();
                 self.frames.push(packet);
@@ -586,8 +765,14 @@ impl E1000 {
                 core::hint::spin_loop();
             }
 
+            // A truncated frame would be sent with a valid CRC
+            if buffer.len() > tx.tx_buffers[i].len() {
+                self.state.statistics.lock().tx_dropped += 1;
+                return;
+            }
+
             // Copy the data to the transmit buffer
-            let len = core::cmp::min(buffer.len(), TX_BUFFER_SIZE);
+            let len = buffer.len();
             tx.tx_buffers[i][0..len].copy_from_slice(&buffer[0..len]);
 
             // Setup the descriptor
@@ -607,4 +792,21 @@ impl E1000 {
     pub fn recv_sync(&mut self) -> Option<Vec<u8>> {
         self.frames.pop()
     }
+
+    /// Add the hardware statistics registers to the counters, reading them clears them
+    pub fn read_statistics(&self) -> DriverStatistics {
+        let mut statistics = self.state.statistics.lock();
+
+        let crc_errors = self.read_register(REG_CRCERRS) as u64;
+        statistics.rx_crc_errors += crc_errors;
+        statistics.rx_errors += crc_errors
+            + self.read_register(REG_ALGNERRC) as u64
+            + self.read_register(REG_RXERRC) as u64
+            + self.read_register(REG_RLEC) as u64;
+        statistics.rx_missed += self.read_register(REG_MPC) as u64;
+        statistics.tx_errors += self.read_register(REG_ECOL) as u64 + self.read_register(REG_LATECOL) as u64;
+        statistics.collisions += self.read_register(REG_COLC) as u64;
+
+        *statistics
+    }
 }
\ No newline at end of file
@@ -11,6 +11,8 @@ use x86_64::instructions::interrupts;
 use x86_64::instructions::port::Port;
 use x86_64::VirtAddr;
 use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::driver::LinkStatus;
+use crate::devices::network::statistics::DriverStatistics;
 
 const GOOLOG_TARGET: &str = "RTL8139";
 
@@ -23,6 +25,7 @@ const RX_BUF_LEN_WRAPPED: usize = RX_BUF_LEN + RX_BUF_PAD + RX_BUF_WRAP;
 
 // Bit flags specific to the RCR
 const APM: u32 = 0b10;
+const AM: u32 = 0b100;
 const AB: u32 = 0b1000;
 const WRAP: u32 = 0b1000_0000;
 const MXDMA_UNLIMITED: u32 = 0b111_0000_0000;
@@ -44,6 +47,38 @@ const RDU: u16 = 0b10000;
 const TDU: u16 = 0b1000_0000;
 const SYS_ERR: u16 = 0b1000_0000_0000_0000;
 
+// Values of the 93C46 command register
+const CFG9346_UNLOCK: u8 = 0b1100_0000;
+const CFG9346_LOCK: u8 = 0;
+
+// Bit flags of the MSR
+const MSR_LINKB: u8 = 0b100;
+const MSR_SPEED_10: u8 = 0b1000;
+
+// Bit flags of the BMCR
+const BMCR_DUPLEX: u16 = 1 << 8;
+
+// Bit flags of the receive status, in the header of each frame
+const RX_STATUS_FAE: u16 = 0b10;
+const RX_STATUS_CRC: u16 = 0b100;
+const RX_STATUS_LONG: u16 = 0b1000;
+const RX_STATUS_RUNT: u16 = 0b1_0000;
+const RX_STATUS_ISE: u16 = 0b10_0000;
+const RX_STATUS_ERRORS: u16 = RX_STATUS_FAE | RX_STATUS_CRC | RX_STATUS_LONG | RX_STATUS_RUNT | RX_STATUS_ISE;
+
+// Bit flags of the transmit status
+const TX_STATUS_TUN: u32 = 1 << 14;
+const TX_STATUS_OWC: u32 = 1 << 29;
+const TX_STATUS_TABT: u32 = 1 << 30;
+const TX_STATUS_CRS: u32 = 1 << 31;
+const TX_STATUS_NCC_SHIFT: u32 = 24;
+const TX_STATUS_NCC_MASK: u32 = 0b1111;
+
+// Largest frame the transmit status can describe
+const TX_MAX_SIZE: usize = 0x700;
+// Larger frames would not fit the extra room at the end of the receive buffer (RX_BUF_WRAP)
+pub const MAX_MTU: usize = 1500;
+
 #[derive(Debug)]
 pub struct RTL8139 {
     pub mac: [u8; 6],
@@ -64,12 +99,25 @@ struct Rtl8139State {
     #[allow(unused)]
     pub cpcr: Port<u16>,
     pub capr: Mutex<Port<u16>>,
+    /// Missed packet counter, 24 bits, cleared by any write
+    pub mpc: Mutex<Port<u32>>,
+    /// Media status
+    pub msr: Mutex<Port<u8>>,
+    /// 93C46 command register, which unlocks the configuration registers
+    pub cfg9346: Port<u8>,
+    /// Basic mode control of the PHY
+    pub bmcr: Mutex<Port<u16>>,
 
     // Registers holding our MAC bytes
     pub idr: [Port<u8>; 6],
+    // The same registers, only writable 32 bits at a time (IDR0-3 and IDR4-7)
+    pub idr_dwords: [Port<u32>; 2],
+    // Registers holding the 64 bits multicast hash filter
+    pub mar: [Port<u8>; 8],
 
     pub rx: Mutex<RTL8139Rx>,
     pub tx: Mutex<RTL8139Tx>,
+    pub statistics: Mutex<DriverStatistics>,
 }
 
 #[derive(Debug)]
@@ -125,6 +173,10 @@ impl RTL8139 {
             ack: Mutex::new(Port::new(base + 0x3e)),
             cpcr: Port::new(base + 0xe0),
             capr: Mutex::new(Port::new(base + 0x38)),
+            mpc: Mutex::new(Port::new(base + 0x4c)),
+            msr: Mutex::new(Port::new(base + 0x58)),
+            cfg9346: Port::new(base + 0x50),
+            bmcr: Mutex::new(Port::new(base + 0x62)),
 
             idr: [
                 Port::new(base + 0x00),
@@ -134,6 +186,22 @@ impl RTL8139 {
                 Port::new(base + 0x04),
                 Port::new(base + 0x05),
             ],
+
+            idr_dwords: [
+                Port::new(base + 0x00),
+                Port::new(base + 0x04),
+            ],
+
+            mar: [
+                Port::new(base + 0x08),
+                Port::new(base + 0x09),
+                Port::new(base + 0x0a),
+                Port::new(base + 0x0b),
+                Port::new(base + 0x0c),
+                Port::new(base + 0x0d),
+                Port::new(base + 0x0e),
+                Port::new(base + 0x0f),
+            ],
             
             rx: Mutex::new(RTL8139Rx {
                 rx_buffer: Box::from([0u8; RX_BUF_LEN_WRAPPED]),
@@ -154,7 +222,8 @@ impl RTL8139 {
                     Port::new(base + 0x1c),
                 ],
                 tx_cursor: 0,
-            })
+            }),
+            statistics: Mutex::new(DriverStatistics::default()),
         };
         debug!("RTL8139 preloaded");
 
@@ -200,10 +269,11 @@ impl RTL8139 {
         // Unsafe block specific for pre-launch NIC config
         unsafe {
             // Accept Physically Match packets
+            // Accept Multicast packets, filtered by the MAR registers
             // Accept Broadcast packets
             // Enable Max DMA burst
             // No RX Threshold
-            self.state.rcr.write(APM | AB | MXDMA_UNLIMITED | RXFTH_NONE | WRAP);
+            self.state.rcr.write(APM | AM | AB | MXDMA_UNLIMITED | RXFTH_NONE | WRAP);
 
             // Enable Tx on the CR register
             self.state.cmd_reg.lock().write(RX_ENABLE | TX_ENABLE);
@@ -238,7 +308,26 @@ impl RTL8139 {
 
         if (isr & RX_OK) != 0 {
             while (unsafe { self.state.cmd_reg.lock().read() } & RX_BUF_EMPTY) == 0 {
-                self.frames.push(self.state.rok());
+                if let Some(frame) = self.state.rok() {
+                    self.frames.push(frame);
+                }
+            }
+        }
+
+        if (isr & (RX_ERR | RDU | TX_ERR)) != 0 {
+            let mut statistics = self.state.statistics.lock();
+
+            if (isr & RX_ERR) != 0 {
+                statistics.rx_errors += 1;
+            }
+
+            // The receive buffer overflowed
+            if (isr & RDU) != 0 {
+                statistics.rx_missed += 1;
+            }
+
+            if (isr & TX_ERR) != 0 {
+                statistics.tx_errors += 1;
             }
         }
 
@@ -259,12 +348,91 @@ impl RTL8139 {
     pub fn recv_sync(&mut self) -> Option<Vec<u8>> {
         self.frames.pop()
     }
+
+    /// Start or stop receiving and transmitting. The NIC writes from the start of the receive
+    /// buffer once started again.
+    pub fn enable(&self, enabled: bool) {
+        match enabled {
+            true => {
+                let mut rx = self.state.rx.lock();
+                rx.rx_cursor = 0;
+
+                unsafe {
+                    self.state.cmd_reg.lock().write(RX_ENABLE | TX_ENABLE);
+                    self.state.capr.lock().write(0u16.wrapping_sub(0x10));
+                }
+            },
+            false => unsafe {
+                self.state.cmd_reg.lock().write(0);
+            }
+        }
+    }
+
+    pub fn read_link_status(&self) -> LinkStatus {
+        let msr = unsafe { self.state.msr.lock().read() };
+        let bmcr = unsafe { self.state.bmcr.lock().read() };
+
+        LinkStatus {
+            // The link bit is set when the link failed
+            carrier: (msr & MSR_LINKB) == 0,
+            speed: match (msr & MSR_SPEED_10) != 0 {
+                true => 10,
+                false => 100
+            },
+            full_duplex: (bmcr & BMCR_DUPLEX) != 0,
+        }
+    }
+
+    /// The IDR registers can only be written 32 bits at a time, once the configuration registers are unlocked
+    pub fn set_mac(&mut self, mac: [u8; 6]) {
+        unsafe {
+            self.state.cfg9346.write(CFG9346_UNLOCK);
+
+            self.state.idr_dwords[0].write(u32::from_le_bytes([mac[0], mac[1], mac[2], mac[3]]));
+            self.state.idr_dwords[1].write(u32::from_le_bytes([mac[4], mac[5], 0, 0]));
+
+            self.state.cfg9346.write(CFG9346_LOCK);
+        }
+
+        self.mac = mac;
+    }
+
+    /// Accept the frames sent to these multicast addresses, along with the ones sharing their hash
+    pub fn set_multicast_filter(&mut self, macs: &[[u8; 6]]) {
+        let mut filter = [0u8; 8];
+
+        for mac in macs {
+            // 6 most significant bits of the big-endian Ethernet CRC
+            let hash = (ethernet_crc(mac) >> 26) as usize;
+            filter[hash >> 3] |= 1 << (hash & 0x7);
+        }
+
+        for (port, byte) in self.state.mar.iter_mut().zip(filter) {
+            unsafe {
+                port.write(byte);
+            }
+        }
+    }
+
+    /// Add the missed packet counter to the counters, then clear it
+    pub fn read_statistics(&self) -> DriverStatistics {
+        let mut statistics = self.state.statistics.lock();
+        let mut mpc = self.state.mpc.lock();
+
+        unsafe {
+            statistics.rx_missed += (mpc.read() & 0xFF_FFFF) as u64;
+            mpc.write(0);
+        }
+
+        *statistics
+    }
 }
 
 impl Rtl8139State {
     /// Function called on a ROK interrupt from the RTL8139 NIC, it parses the data written into
-    /// the buffer as a ethernet frame and pushes it into our Vec.
-    fn rok(&self) -> Vec<u8> {
+    /// the buffer as a ethernet frame and pushes it into our Vec. The frames received with errors
+    /// are counted and skipped.
+    fn rok(&self) -> Option<Vec<u8>> {
         // A packet frame looks something like this
         // +--------------------------------------------+
         // | |     HEADER     |            |   DATA   | |
@@ -279,6 +447,7 @@ impl Rtl8139State {
         let mut rx = self.rx.lock();
         
         let buffer = &rx.rx_buffer[rx.rx_cursor..];
+        let status = u16::from_le_bytes(buffer[0..2].try_into().expect("Got wrong status"));
         let length = u16::from_le_bytes(buffer[2..4].try_into().expect("Got wrong len")) as usize;
 
         // NOTE: The length in the header will never be less than 64, if a packet is received that
@@ -297,7 +466,19 @@ impl Rtl8139State {
         // basically for some reason when receiving an `ACK` packet from a client as part of a
         // 3-way handshake the tcp packet is padded with extra zeroes at the end. My guessing is
         // that because the packet is less than 64 bytes the packet gets padded.
-        let frame = buffer[4..length].to_vec(); // skip 4 bytes length and dont copy 4 bytes crc at the end.
+        let frame = match status & RX_STATUS_ERRORS {
+            0 => Some(buffer[4..length].to_vec()), // skip 4 bytes length and dont copy 4 bytes crc at the end.
+            errors => {
+                let mut statistics = self.statistics.lock();
+                statistics.rx_errors += 1;
+
+                if (errors & RX_STATUS_CRC) != 0 {
+                    statistics.rx_crc_errors += 1;
+                }
+
+                None
+            }
+        };
 
         // Here we set the new index/cursor from where to read new packets, self.rx_cursor should
         // always point to the start of the header.
@@ -322,6 +503,12 @@ impl Rtl8139State {
     /// The caller must make sure that interrupts are disabled before calling and are re-enabled
     /// after calling or the program will deadlock.
     pub unsafe fn write(&self, data: &[u8]) {
+        // Larger frames would be truncated by the NIC
+        if data.len() > TX_MAX_SIZE {
+            self.statistics.lock().tx_dropped += 1;
+            return;
+        }
+
         // NOTE: Are we sure we absolutely need to disable interrupts? maybe we can bypass this
         //       with DMA.
         // We clone the inner PCI device to avoid a deadlock when we re-enable PCI interrupts for
@@ -335,12 +522,41 @@ impl Rtl8139State {
         tx.tx_dat[cursor].write(data_ptr as u32);
         tx.tx_cmd[cursor].write((data.len() as u32) & 0xfff);
 
-        loop {
-            if (tx.tx_cmd[cursor].read() & 0x8000) != 0 {
-                break;
+        let status = loop {
+            let status = tx.tx_cmd[cursor].read();
+            if (status & 0x8000) != 0 {
+                break status;
             }
+        };
+
+        let mut statistics = self.statistics.lock();
+        statistics.collisions += ((status >> TX_STATUS_NCC_SHIFT) & TX_STATUS_NCC_MASK) as u64;
+
+        if (status & (TX_STATUS_TUN | TX_STATUS_OWC | TX_STATUS_TABT | TX_STATUS_CRS)) != 0 {
+            statistics.tx_errors += 1;
         }
 
         tx.tx_cursor = (cursor + 1) % 4;
     }
-}
\ No newline at end of file
+}
+
+/// CRC-32 of the Ethernet FCS, computed most significant bit first
+fn ethernet_crc(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for byte in data {
+        let mut byte = *byte;
+
+        for _ in 0..8 {
+            let carry = ((crc >> 31) as u8 ^ byte) & 1;
+            crc <<= 1;
+            byte >>= 1;
+
+            if carry != 0 {
+                crc ^= 0x04C1_1DB7;
+            }
+        }
+    }
+
+    crc
+}
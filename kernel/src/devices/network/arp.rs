@@ -0,0 +1,206 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::pin::pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Poll, Waker};
+use futures_util::future::{select, Either};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, Ipv4Address};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// ARP packets awaited by arping and by the duplicate address detection, fed by the network controllers.
+/// Must be locked with interrupts disabled, since it is also updated from the timer interrupt.
+static ARP_WATCHES: Mutex<BTreeMap<u64, ArpWatch>> = Mutex::new(BTreeMap::new());
+static NEXT_WATCH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Smallest Ethernet frame, without its frame check sequence
+const MIN_FRAME_SIZE: usize = 60;
+/// Packets kept for a listener which does not read them
+const MAX_PENDING_PACKETS: usize = 64;
+
+/// Probes sent by the duplicate address detection before an address is considered free (RFC 5227)
+pub const PROBE_COUNT: u8 = 3;
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArpReceived {
+    pub operation: ArpOperation,
+    pub source_hardware_addr: EthernetAddress,
+    pub target_protocol_addr: Ipv4Address,
+    /// The frame was sent to the broadcast address
+    pub is_broadcast: bool,
+    pub received_at: Instant,
+}
+
+struct ArpWatch {
+    if_index: u32,
+    address: Ipv4Address,
+    /// Hardware address of the interface, its own packets are not reported
+    own_hardware_addr: EthernetAddress,
+    received: VecDeque<ArpReceived>,
+    waker: Option<Waker>,
+}
+
+/// Receives the ARP packets of an IPv4 address on an interface, until dropped
+pub struct ArpListener {
+    id: u64,
+}
+
+impl ArpListener {
+    /// Watch the packets sent from the given address, along with the probes of other hosts for it
+    pub fn new(if_index: u32, address: Ipv4Address, own_hardware_addr: EthernetAddress) -> Self {
+        let id = NEXT_WATCH_ID.fetch_add(1, Ordering::Relaxed);
+
+        let watch = ArpWatch {
+            if_index,
+            address,
+            own_hardware_addr,
+            received: VecDeque::new(),
+            waker: None,
+        };
+
+        interrupts::without_interrupts(|| {
+            ARP_WATCHES.lock().insert(id, watch);
+        });
+
+        Self { id }
+    }
+
+    pub async fn receive(&self) -> ArpReceived {
+        poll_fn(|context| interrupts::without_interrupts(|| {
+            let mut watches = ARP_WATCHES.lock();
+            let watch = watches.get_mut(&self.id).unwrap();
+
+            match watch.received.pop_front() {
+                Some(received) => Poll::Ready(received),
+                None => {
+                    watch.waker = Some(context.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }))
+        .await
+    }
+}
+
+impl Drop for ArpListener {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            ARP_WATCHES.lock().remove(&self.id);
+        });
+    }
+}
+
+/// Report an ARP packet received on the given interface to the listeners of its addresses
+pub fn observe_frame(if_index: u32, frame: &[u8]) {
+    let Ok(frame) = EthernetFrame::new_checked(frame) else {
+        return;
+    };
+
+    if frame.ethertype() != EthernetProtocol::Arp {
+        return;
+    }
+
+    let Ok(packet) = ArpPacket::new_checked(frame.payload()) else {
+        return;
+    };
+
+    let Ok(ArpRepr::EthernetIpv4 { operation, source_hardware_addr, source_protocol_addr, target_protocol_addr, .. }) = ArpRepr::parse(&packet) else {
+        return;
+    };
+
+    let received = ArpReceived {
+        operation,
+        source_hardware_addr,
+        target_protocol_addr,
+        is_broadcast: frame.dst_addr().is_broadcast(),
+        received_at: Clock::now(),
+    };
+
+    let mut watches = ARP_WATCHES.lock();
+
+    for watch in watches.values_mut() {
+        if watch.if_index != if_index || watch.own_hardware_addr == source_hardware_addr {
+            continue;
+        }
+
+        // Another host probing for the same address counts as well
+        let is_probe = source_protocol_addr.is_unspecified() && target_protocol_addr == watch.address;
+
+        if (source_protocol_addr == watch.address || is_probe) && watch.received.len() < MAX_PENDING_PACKETS {
+            watch.received.push_back(received);
+
+            if let Some(waker) = watch.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// ARP request broadcast from the given addresses.
+/// A probe is sent from 0.0.0.0, an announcement (gratuitous ARP) asks for its own address.
+pub fn request_frame(source_hardware_addr: EthernetAddress, source_protocol_addr: Ipv4Address, target_protocol_addr: Ipv4Address) -> Vec<u8> {
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr,
+        source_protocol_addr,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr,
+    };
+
+    let frame_size = EthernetFrame::<&[u8]>::header_len() + repr.buffer_len();
+    let mut buffer = vec![0u8; frame_size.max(MIN_FRAME_SIZE)];
+
+    let mut frame = EthernetFrame::new_unchecked(&mut buffer);
+    frame.set_src_addr(source_hardware_addr);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+
+    buffer
+}
+
+/// Interface index and hardware address of a device, `None` if it has no link layer to resolve addresses on
+pub fn device_identity(device: &Arc<Mutex<NetworkDevice<'static>>>) -> Option<(u32, EthernetAddress)> {
+    interrupts::without_interrupts(|| {
+        let device = device.lock();
+        device.mac().map(|hardware_addr| (device.network_controller.if_index, hardware_addr))
+    })
+}
+
+pub fn send_frame(device: &Arc<Mutex<NetworkDevice<'static>>>, frame: &[u8]) {
+    interrupts::without_interrupts(|| {
+        device.lock().network_controller.send_frame(frame);
+    });
+}
+
+/// Duplicate address detection (RFC 5227): probe the address, and return the hardware address of the host already using it
+pub async fn probe_address(device: &Arc<Mutex<NetworkDevice<'static>>>, address: Ipv4Address) -> Option<EthernetAddress> {
+    let (if_index, hardware_addr) = device_identity(device)?;
+    let listener = ArpListener::new(if_index, address, hardware_addr);
+
+    for _ in 0..PROBE_COUNT {
+        send_frame(device, &request_frame(hardware_addr, Ipv4Address::UNSPECIFIED, address));
+
+        if let Either::Left((received, _)) = select(pin!(listener.receive()), Timer::after(PROBE_INTERVAL)).await {
+            return Some(received.source_hardware_addr);
+        }
+    }
+
+    None
+}
+
+/// Announce an address taken by the interface, the neighbors update their caches (gratuitous ARP)
+pub fn announce_address(device: &Arc<Mutex<NetworkDevice<'static>>>, address: Ipv4Address) {
+    let Some((_, hardware_addr)) = device_identity(device) else {
+        return;
+    };
+
+    send_frame(device, &request_frame(hardware_addr, address, address));
+}
@@ -1,4 +1,7 @@
-use crate::devices::network::driver::NetworkDriver;
+use crate::devices::network::arp::observe_frame;
+use crate::devices::network::driver::{LinkStatus, NetworkDriver};
+use crate::devices::network::flow::FLOW_CACHE;
+use crate::devices::network::statistics::{InterfaceCounters, InterfaceStatistics};
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -7,23 +10,48 @@ use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
 use smoltcp::time::Instant;
 use spin::Mutex;
 
+pub const DEFAULT_MTU: usize = 1500;
+/// Smallest MTU of IPv4 (RFC 791)
+pub const MIN_MTU: usize = 68;
+pub const ETHERNET_HEADER_SIZE: usize = 14;
+
 #[derive(Debug)]
 pub struct NetworkController {
     pub driver: Arc<Mutex<dyn NetworkDriver>>,
+    /// Interface index, as exported by flow records
+    pub if_index: u32,
     pub rx_buffer: RefCell<Option<Vec<u8>>>,
-    pub capabilities: DeviceCapabilities
+    pub statistics: InterfaceStatistics,
+    pub capabilities: DeviceCapabilities,
+    /// Administrative state, nothing is received nor sent while down
+    pub enabled: bool,
+    /// Last link state reported by the NIC
+    pub link_status: LinkStatus,
+    /// Unicast addresses accepted besides the one of the interface
+    pub secondary_macs: Vec<[u8; 6]>,
 }
 
 impl NetworkController {
-    pub fn new(driver: Arc<Mutex<dyn NetworkDriver>>) -> NetworkController {
+    pub fn new(driver: Arc<Mutex<dyn NetworkDriver>>, if_index: u32) -> NetworkController {
+        let (medium, link_status) = {
+            let driver = driver.lock();
+            (driver.medium(), driver.link_status())
+        };
+
         let mut capabilities = DeviceCapabilities::default();
-        capabilities.medium = Medium::Ethernet;
-        capabilities.max_transmission_unit = 1500;
+        capabilities.medium = medium;
+        // smoltcp counts the link-layer header in the MTU
+        capabilities.max_transmission_unit = header_size(medium) + DEFAULT_MTU;
 
         Self {
             driver,
+            if_index,
             rx_buffer: RefCell::new(None),
-            capabilities
+            statistics: InterfaceStatistics::default(),
+            capabilities,
+            enabled: true,
+            link_status,
+            secondary_macs: Vec::new(),
         }
     }
 
@@ -31,13 +59,88 @@ impl NetworkController {
         let mut network_driver = self.driver.lock();
 
         if network_driver.handle_interrupt() {
-            if let Some(packet) = network_driver.receive_packet() {
-                *self.rx_buffer.borrow_mut() = Some(packet);
+            // The frames still queued when the interface went down are dropped
+            if let Some(mut packet) = network_driver.receive_packet().filter(|_| self.enabled) {
+                self.statistics.count_rx(&packet, self.capabilities.medium);
+
+                // Flows and ARP are read from the Ethernet frames
+                if self.capabilities.medium == Medium::Ethernet {
+                    FLOW_CACHE.lock().account_frame(self.if_index, &packet);
+                    observe_frame(self.if_index, &packet);
+                }
+
+                // smoltcp only accepts the frames sent to the address of the interface
+                if packet.len() >= 6 && self.secondary_macs.iter().any(|mac| packet[..6] == mac[..]) {
+                    packet[..6].copy_from_slice(&network_driver.mac());
+                }
+
+                if self.rx_buffer.borrow_mut().replace(packet).is_some() {
+                    self.statistics.count_rx_dropped();
+                }
             }
         }
 
         return true;
     }
+
+    /// Send a frame built outside of smoltcp, such as an ARP probe
+    pub fn send_frame(&self, frame: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        self.driver.lock().send_packet(frame);
+        self.statistics.count_tx(frame.len());
+    }
+
+    /// Start or stop the NIC
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.driver.lock().set_enabled(enabled);
+        self.enabled = enabled;
+        self.rx_buffer.borrow_mut().take();
+    }
+
+    pub fn set_secondary_macs(&mut self, macs: Vec<[u8; 6]>) {
+        self.driver.lock().set_secondary_macs(&macs);
+        self.secondary_macs = macs;
+    }
+
+    /// Largest IP packet sent by the interface
+    pub fn mtu(&self) -> usize {
+        self.capabilities.max_transmission_unit - header_size(self.capabilities.medium)
+    }
+
+    /// Change the MTU, which the NIC must support
+    pub fn set_mtu(&mut self, mtu: usize) {
+        self.driver.lock().set_mtu(mtu);
+        self.capabilities.max_transmission_unit = header_size(self.capabilities.medium) + mtu;
+    }
+
+    /// Read the link state from the NIC, and return it if it changed
+    pub fn update_link_status(&mut self) -> Option<LinkStatus> {
+        let link_status = self.driver.lock().link_status();
+
+        match link_status != self.link_status {
+            true => {
+                self.link_status = link_status;
+                Some(link_status)
+            },
+            false => None
+        }
+    }
+
+    /// Traffic and error counters of the interface
+    pub fn counters(&self) -> InterfaceCounters {
+        self.statistics.counters(self.driver.lock().statistics())
+    }
+}
+
+/// Link-layer header of the frames, none for the interfaces sending bare IP packets
+fn header_size(medium: Medium) -> usize {
+    match medium {
+        Medium::Ethernet => ETHERNET_HEADER_SIZE,
+        _ => 0
+    }
 }
 
 impl Device for NetworkController {
@@ -47,7 +150,7 @@ impl Device for NetworkController {
     fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
         //println!("recv");
 
-        if self.rx_buffer.borrow().is_some() {
+        if self.enabled && self.rx_buffer.borrow().is_some() {
             Some((
                 PhyRxToken { device: self },
                 PhyTxToken { device: self }
@@ -61,6 +164,10 @@ impl Device for NetworkController {
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
         //println!("send");
 
+        if !self.enabled {
+            return None;
+        }
+
         Some(PhyTxToken { device: self })
     }
 
@@ -102,6 +209,7 @@ impl<'a> TxToken for PhyTxToken<'a> {
 
         // Send the packet
         self.device.driver.lock().send_packet(&buffer);
+        self.device.statistics.count_tx(buffer.len());
 
         result
     }
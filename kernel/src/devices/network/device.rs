@@ -1,13 +1,26 @@
 use crate::clock::Clock;
 use crate::devices::network::controller::NetworkController;
+use crate::devices::network::multicast::{implicit_groups, multicast_mac};
+use crate::devices::network::slaac::AutoconfiguredAddress;
 use alloc::sync::Arc;
-use smoltcp::iface::{Interface, SocketSet};
+use alloc::vec::Vec;
+use smoltcp::iface::{Interface, MulticastError, Route, SocketSet};
+use smoltcp::phy::Medium;
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, Ipv4Address};
 use spin::Mutex;
 
 pub struct NetworkDevice<'a> {
     pub interface: Interface,
     pub network_controller: NetworkController,
-    pub sockets: Arc<Mutex<SocketSet<'a>>>
+    pub sockets: Arc<Mutex<SocketSet<'a>>>,
+    /// Addresses another host answered for when they were added
+    pub duplicate_addresses: Vec<Ipv4Address>,
+    /// Routes taken out of the interface while it is down
+    pub withdrawn_routes: Vec<Route>,
+    /// Multicast groups joined with ip maddr, smoltcp answering the IGMP and MLD queries for them
+    pub multicast_groups: Vec<IpAddress>,
+    /// IPv6 addresses configured by the kernel, the tentative and duplicate ones included
+    pub autoconfigured_addresses: Vec<AutoconfiguredAddress>,
 }
 
 impl NetworkDevice<'_> {
@@ -22,4 +35,106 @@ impl NetworkDevice<'_> {
             );
         }
     }
+
+    /// MAC address of the interface, `None` for the interfaces without a link layer such as the tunnels
+    pub fn mac(&self) -> Option<EthernetAddress> {
+        match self.network_controller.capabilities.medium {
+            Medium::Ethernet => Some(EthernetAddress::from_bytes(self.interface.hardware_addr().as_bytes())),
+            _ => None
+        }
+    }
+
+    /// Change the MAC address of the NIC and of the smoltcp interface
+    pub fn set_mac(&mut self, mac: EthernetAddress) {
+        self.network_controller.driver.lock().set_mac(mac.0);
+        self.interface.set_hardware_addr(HardwareAddress::Ethernet(mac));
+    }
+
+    /// Add an address to the smoltcp interface, false if it has no room left
+    pub fn add_ip_address(&mut self, cidr: IpCidr) -> bool {
+        let mut is_full = false;
+
+        self.interface.update_ip_addrs(|addresses| {
+            if !addresses.contains(&cidr) {
+                is_full = addresses.push(cidr).is_err();
+            }
+        });
+
+        // Solicited-node group of the IPv6 addresses
+        self.update_multicast_filter();
+        !is_full
+    }
+
+    pub fn remove_ip_address(&mut self, cidr: IpCidr) {
+        self.interface.update_ip_addrs(|addresses| addresses.retain(|address| *address != cidr));
+        self.update_multicast_filter();
+    }
+
+    pub fn join_multicast_group(&mut self, group: IpAddress) -> Result<(), MulticastError> {
+        self.interface.join_multicast_group(group)?;
+
+        if !self.multicast_groups.contains(&group) {
+            self.multicast_groups.push(group);
+        }
+
+        self.update_multicast_filter();
+        Ok(())
+    }
+
+    pub fn leave_multicast_group(&mut self, group: IpAddress) -> Result<(), MulticastError> {
+        self.interface.leave_multicast_group(group)?;
+        self.multicast_groups.retain(|joined| *joined != group);
+
+        self.update_multicast_filter();
+        Ok(())
+    }
+
+    /// Every group of the interface, the ones smoltcp joins on its own first
+    pub fn all_multicast_groups(&self) -> Vec<IpAddress> {
+        let mut groups = implicit_groups(&self.interface);
+
+        for group in &self.multicast_groups {
+            if !groups.contains(group) {
+                groups.push(*group);
+            }
+        }
+
+        groups
+    }
+
+    /// Program the NIC with the Ethernet addresses of the groups, to be called whenever they change
+    pub fn update_multicast_filter(&mut self) {
+        let macs: Vec<_> = self.all_multicast_groups()
+            .into_iter()
+            .map(multicast_mac)
+            .collect();
+
+        self.network_controller.driver.lock().set_multicast_filter(&macs);
+    }
+
+    /// Bring the interface up or down, its routes are withdrawn while it is down
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.network_controller.enabled == enabled {
+            return;
+        }
+
+        self.network_controller.set_enabled(enabled);
+
+        let withdrawn_routes = &mut self.withdrawn_routes;
+
+        self.interface
+            .routes_mut()
+            .update(|routes| match enabled {
+                true => {
+                    for route in withdrawn_routes.drain(..) {
+                        // Lost if the table was filled up in the meantime
+                        let _ = routes.push(route);
+                    }
+                },
+                false => {
+                    withdrawn_routes.extend(routes.iter().copied());
+                    routes.clear();
+                }
+            });
+    }
 }
\ No newline at end of file
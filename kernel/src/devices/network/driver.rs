@@ -1,8 +1,15 @@
 use alloc::vec::Vec;
+use core::fmt;
 use core::fmt::Debug;
 use strum::Display;
+use crate::devices::drivers::e1000;
 use crate::devices::drivers::e1000::E1000;
+use crate::devices::drivers::rtl8139;
 use crate::devices::drivers::rtl8139::RTL8139;
+use crate::devices::network::statistics::DriverStatistics;
+use crate::devices::network::tunnel::{TunnelDriver, TunnelMode, MAX_IP_PACKET_SIZE};
+use crate::devices::network::vxlan::{VxlanDriver, VXLAN_OVERHEAD};
+use smoltcp::phy::Medium;
 
 pub trait NetworkDriver: Send + Sync + Debug {
     fn mac(&self) -> [u8; 6];
@@ -11,12 +18,51 @@ pub trait NetworkDriver: Send + Sync + Debug {
     fn handle_interrupt(&mut self) -> bool;
     fn send_packet(&mut self, data: &[u8]);
     fn receive_packet(&mut self) -> Option<Vec<u8>>;
+    fn statistics(&self) -> DriverStatistics;
+    /// Start or stop receiving and transmitting
+    fn set_enabled(&mut self, enabled: bool);
+    fn link_status(&self) -> LinkStatus;
+    /// Largest MTU the NIC can send and receive
+    fn max_mtu(&self) -> usize;
+    fn set_mtu(&mut self, mtu: usize);
+    fn set_mac(&mut self, mac: [u8; 6]);
+    /// Unicast addresses the NIC can accept besides its own
+    fn max_secondary_macs(&self) -> usize;
+    fn set_secondary_macs(&mut self, macs: &[[u8; 6]]);
+    /// Accept the frames sent to these multicast addresses, a hash filter letting some others through
+    fn set_multicast_filter(&mut self, macs: &[[u8; 6]]);
+    /// Link layer of the packets sent and received
+    fn medium(&self) -> Medium;
+}
+
+/// State of the physical link, as reported by the NIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStatus {
+    pub carrier: bool,
+    /// In Mbit/s
+    pub speed: u32,
+    pub full_duplex: bool,
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.carrier {
+            true => write!(f, "{} Mbit/s {} duplex", self.speed, if self.full_duplex { "full" } else { "half" }),
+            false => write!(f, "no carrier")
+        }
+    }
 }
 
 #[derive(Debug, Display)]
 pub enum NetworkControllerType {
     RTL8139,
-    E1000
+    E1000,
+    #[strum(serialize = "GRE tunnel")]
+    Gre,
+    #[strum(serialize = "IPIP tunnel")]
+    Ipip,
+    #[strum(serialize = "VXLAN")]
+    Vxlan,
 }
 
 impl NetworkDriver for E1000 {
@@ -43,6 +89,46 @@ impl NetworkDriver for E1000 {
     fn receive_packet(&mut self) -> Option<Vec<u8>> {
         self.recv_sync()
     }
+
+    fn statistics(&self) -> DriverStatistics {
+        self.read_statistics()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enable(enabled);
+    }
+
+    fn link_status(&self) -> LinkStatus {
+        self.read_link_status()
+    }
+
+    fn max_mtu(&self) -> usize {
+        e1000::MAX_MTU
+    }
+
+    fn set_mtu(&mut self, mtu: usize) {
+        E1000::set_mtu(self, mtu);
+    }
+
+    fn set_mac(&mut self, mac: [u8; 6]) {
+        E1000::set_mac(self, mac);
+    }
+
+    fn max_secondary_macs(&self) -> usize {
+        e1000::MAX_SECONDARY_MACS
+    }
+
+    fn set_secondary_macs(&mut self, macs: &[[u8; 6]]) {
+        E1000::set_secondary_macs(self, macs);
+    }
+
+    fn set_multicast_filter(&mut self, macs: &[[u8; 6]]) {
+        E1000::set_multicast_filter(self, macs);
+    }
+
+    fn medium(&self) -> Medium {
+        Medium::Ethernet
+    }
 }
 
 impl NetworkDriver for RTL8139 {
@@ -69,4 +155,177 @@ impl NetworkDriver for RTL8139 {
     fn receive_packet(&mut self) -> Option<Vec<u8>> {
         self.recv_sync()
     }
-}
\ No newline at end of file
+
+    fn statistics(&self) -> DriverStatistics {
+        self.read_statistics()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enable(enabled);
+    }
+
+    fn link_status(&self) -> LinkStatus {
+        self.read_link_status()
+    }
+
+    fn max_mtu(&self) -> usize {
+        rtl8139::MAX_MTU
+    }
+
+    // Nothing to reprogram, the frames up to MAX_MTU fit the receive buffer
+    fn set_mtu(&mut self, _mtu: usize) {}
+
+    fn set_mac(&mut self, mac: [u8; 6]) {
+        RTL8139::set_mac(self, mac);
+    }
+
+    // The IDR registers hold a single address
+    fn max_secondary_macs(&self) -> usize {
+        0
+    }
+
+    fn set_secondary_macs(&mut self, _macs: &[[u8; 6]]) {}
+
+    fn set_multicast_filter(&mut self, macs: &[[u8; 6]]) {
+        RTL8139::set_multicast_filter(self, macs);
+    }
+
+    fn medium(&self) -> Medium {
+        Medium::Ethernet
+    }
+}
+
+impl NetworkDriver for TunnelDriver {
+    // No link layer, the packets are bare IP ones
+    fn mac(&self) -> [u8; 6] {
+        [0; 6]
+    }
+
+    fn device_name(&self) -> &str {
+        "Tunnel"
+    }
+
+    fn nic_type(&self) -> NetworkControllerType {
+        match self.mode {
+            TunnelMode::Gre => NetworkControllerType::Gre,
+            TunnelMode::Ipip => NetworkControllerType::Ipip
+        }
+    }
+
+    fn handle_interrupt(&mut self) -> bool {
+        !self.rx_queue.is_empty()
+    }
+
+    fn send_packet(&mut self, data: &[u8]) {
+        self.queue_tx(data);
+    }
+
+    fn receive_packet(&mut self) -> Option<Vec<u8>> {
+        self.rx_queue.pop_front()
+    }
+
+    fn statistics(&self) -> DriverStatistics {
+        self.statistics
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enable(enabled);
+    }
+
+    // Up as long as it exists, the remote end not being probed
+    fn link_status(&self) -> LinkStatus {
+        LinkStatus {
+            carrier: true,
+            speed: 0,
+            full_duplex: true,
+        }
+    }
+
+    fn max_mtu(&self) -> usize {
+        MAX_IP_PACKET_SIZE - self.mode.overhead()
+    }
+
+    // Nothing to reprogram, the underlying interface fragments the larger packets
+    fn set_mtu(&mut self, _mtu: usize) {}
+
+    fn set_mac(&mut self, _mac: [u8; 6]) {}
+
+    fn max_secondary_macs(&self) -> usize {
+        0
+    }
+
+    fn set_secondary_macs(&mut self, _macs: &[[u8; 6]]) {}
+
+    fn set_multicast_filter(&mut self, _macs: &[[u8; 6]]) {}
+
+    fn medium(&self) -> Medium {
+        Medium::Ip
+    }
+}
+
+impl NetworkDriver for VxlanDriver {
+    fn mac(&self) -> [u8; 6] {
+        self.mac
+    }
+
+    fn device_name(&self) -> &str {
+        "VXLAN"
+    }
+
+    fn nic_type(&self) -> NetworkControllerType {
+        NetworkControllerType::Vxlan
+    }
+
+    fn handle_interrupt(&mut self) -> bool {
+        !self.rx_queue.is_empty()
+    }
+
+    fn send_packet(&mut self, data: &[u8]) {
+        self.queue_tx(data);
+    }
+
+    fn receive_packet(&mut self) -> Option<Vec<u8>> {
+        self.rx_queue.pop_front()
+    }
+
+    fn statistics(&self) -> DriverStatistics {
+        self.statistics
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enable(enabled);
+    }
+
+    // Up as long as it exists, the remote VTEPs not being probed
+    fn link_status(&self) -> LinkStatus {
+        LinkStatus {
+            carrier: true,
+            speed: 0,
+            full_duplex: true,
+        }
+    }
+
+    fn max_mtu(&self) -> usize {
+        MAX_IP_PACKET_SIZE - VXLAN_OVERHEAD
+    }
+
+    // Nothing to reprogram, the underlying interface fragments the larger packets
+    fn set_mtu(&mut self, _mtu: usize) {}
+
+    fn set_mac(&mut self, mac: [u8; 6]) {
+        self.mac = mac;
+    }
+
+    fn max_secondary_macs(&self) -> usize {
+        0
+    }
+
+    fn set_secondary_macs(&mut self, _macs: &[[u8; 6]]) {}
+
+    // Every decapsulated frame is handed to smoltcp, which drops the packets of the groups not joined
+    fn set_multicast_filter(&mut self, _macs: &[[u8; 6]]) {}
+
+    fn medium(&self) -> Medium {
+        Medium::Ethernet
+    }
+}
@@ -0,0 +1,158 @@
+use crate::clock::Clock;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetFrame, EthernetProtocol, IpAddress, IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket, UdpPacket};
+use spin::{Lazy, Mutex};
+
+/// Flow cache fed by the network controllers.
+/// Must be locked with interrupts disabled, since it is also updated from the timer interrupt.
+pub static FLOW_CACHE: Lazy<Mutex<FlowCache>> = Lazy::new(|| Mutex::new(FlowCache::new()));
+
+pub const DEFAULT_ACTIVE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_INACTIVE_TIMEOUT: Duration = Duration::from_secs(15);
+pub const MAX_FLOWS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FlowKey {
+    pub ingress_interface: u32,
+    pub source: IpAddress,
+    pub destination: IpAddress,
+    pub protocol: u8,
+    pub source_port: u16,
+    pub destination_port: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlowRecord {
+    pub key: FlowKey,
+    pub bytes: u64,
+    pub packets: u64,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+}
+
+pub struct FlowCache {
+    pub flows: BTreeMap<FlowKey, FlowRecord>,
+    pub active_timeout: Duration,
+    pub inactive_timeout: Duration,
+    /// Packets that could not be accounted because the cache was full
+    pub untracked_packets: u64,
+}
+
+impl FlowCache {
+    pub fn new() -> Self {
+        Self {
+            flows: BTreeMap::new(),
+            active_timeout: DEFAULT_ACTIVE_TIMEOUT,
+            inactive_timeout: DEFAULT_INACTIVE_TIMEOUT,
+            untracked_packets: 0,
+        }
+    }
+
+    /// Account an ethernet frame received on the given interface
+    pub fn account_frame(&mut self, ingress_interface: u32, frame: &[u8]) {
+        let Some((key, length)) = parse_flow_key(ingress_interface, frame) else {
+            return;
+        };
+
+        let now = Clock::now();
+
+        if let Some(record) = self.flows.get_mut(&key) {
+            record.bytes += length as u64;
+            record.packets += 1;
+            record.last_seen = now;
+            return;
+        }
+
+        if self.flows.len() >= MAX_FLOWS {
+            self.untracked_packets += 1;
+            return;
+        }
+
+        self.flows.insert(key, FlowRecord {
+            key,
+            bytes: length as u64,
+            packets: 1,
+            first_seen: now,
+            last_seen: now,
+        });
+    }
+
+    /// Remove and return the flows that reached their active or inactive timeout
+    pub fn expire(&mut self, now: Instant) -> Vec<FlowRecord> {
+        let mut expired = Vec::new();
+        let active_timeout = self.active_timeout;
+        let inactive_timeout = self.inactive_timeout;
+
+        self.flows.retain(|_, record| {
+            if now - record.last_seen >= inactive_timeout || now - record.first_seen >= active_timeout {
+                expired.push(*record);
+                false
+            }
+            else {
+                true
+            }
+        });
+
+        expired
+    }
+}
+
+impl Default for FlowCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract the 5-tuple of an ethernet frame, along with the IP packet length
+fn parse_flow_key(ingress_interface: u32, frame: &[u8]) -> Option<(FlowKey, usize)> {
+    let frame = EthernetFrame::new_checked(frame).ok()?;
+
+    let (source, destination, protocol, payload, length) = match frame.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+            (
+                IpAddress::Ipv4(packet.src_addr()),
+                IpAddress::Ipv4(packet.dst_addr()),
+                packet.next_header(),
+                packet.payload(),
+                packet.total_len() as usize
+            )
+        },
+        EthernetProtocol::Ipv6 => {
+            let packet = Ipv6Packet::new_checked(frame.payload()).ok()?;
+            (
+                IpAddress::Ipv6(packet.src_addr()),
+                IpAddress::Ipv6(packet.dst_addr()),
+                packet.next_header(),
+                packet.payload(),
+                packet.total_len()
+            )
+        },
+        _ => return None
+    };
+
+    let (source_port, destination_port) = match protocol {
+        IpProtocol::Tcp => match TcpPacket::new_checked(payload) {
+            Ok(segment) => (segment.src_port(), segment.dst_port()),
+            Err(_) => (0, 0)
+        },
+        IpProtocol::Udp => match UdpPacket::new_checked(payload) {
+            Ok(datagram) => (datagram.src_port(), datagram.dst_port()),
+            Err(_) => (0, 0)
+        },
+        _ => (0, 0)
+    };
+
+    let key = FlowKey {
+        ingress_interface,
+        source,
+        destination,
+        protocol: protocol.into(),
+        source_port,
+        destination_port,
+    };
+
+    Some((key, length))
+}
@@ -5,7 +5,7 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use smoltcp::iface::{Config, Interface};
 use smoltcp::phy::{Loopback, Medium};
-use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 
 pub fn init_loopback_interface() -> Interface {
     let mut loopback = Loopback::new(Medium::Ethernet);
@@ -23,7 +23,11 @@ pub fn init_network_device_interface(network_controller: &mut NetworkController)
     let driver = network_controller.driver.lock();
     let mac = driver.mac();
     drop(driver);
-    let config = Config::new(EthernetAddress(mac).into());
+
+    let config = match network_controller.capabilities.medium {
+        Medium::Ip => Config::new(HardwareAddress::Ip),
+        _ => Config::new(EthernetAddress(mac).into())
+    };
 
     Interface::new(config, network_controller, Clock::now())
 }
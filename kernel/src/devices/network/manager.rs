@@ -4,26 +4,33 @@ use crate::devices::network::interface::{format_mac, init_loopback_interface, in
 use crate::devices::pic::pic::PIC;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use alloc::{format};
+use alloc::{format, vec};
 use alloc::string::String;
 use alloc::vec::Vec;
-use goolog::{info, trace};
+use goolog::{info, trace, warn};
 use smoltcp::iface::{Interface, SocketSet};
 use smoltcp::phy::Medium;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv6Cidr};
 use spin::{Lazy, Mutex};
 use crate::clock::Clock;
 use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::slaac::{link_local_address, AddressOrigin, AddressState, AutoconfiguredAddress, SLAAC_PREFIX_LENGTH};
 
 const GOOLOG_TARGET: &str = "NETWORK";
 
 pub const NETWORK_DEVICES_INTERRUPT_IRQ: u8 = 0x70;
 
+/// Interface index of the loopback, physical interfaces follow it
+pub const LOOPBACK_IF_INDEX: u32 = 1;
+
 pub static NETWORK_MANAGER: Lazy<Mutex<NetworkManager>> = Lazy::new(|| Mutex::new(NetworkManager::new()));
 
 pub struct NetworkManager<'a> {
     pub irq_to_devices: BTreeMap<u8, Vec<String>>,
     pub loopback: Loopback<'a>,
-    pub interfaces: BTreeMap<String, Arc<Mutex<NetworkDevice<'a>>>>
+    pub interfaces: BTreeMap<String, Arc<Mutex<NetworkDevice<'a>>>>,
+    /// Indexes are not reused, the flow collectors telling the interfaces apart with them
+    next_if_index: u32,
 }
 
 pub struct Loopback<'a> {
@@ -38,7 +45,7 @@ impl Default for NetworkManager<'_> {
     }
 }
 
-impl NetworkManager<'_> {
+impl<'a> NetworkManager<'a> {
     pub fn new() -> Self {
         NetworkManager {
             irq_to_devices: BTreeMap::new(),
@@ -48,6 +55,7 @@ impl NetworkManager<'_> {
                 sockets: Arc::new(Mutex::new(SocketSet::new(Vec::new()))),
             },
             interfaces: BTreeMap::new(),
+            next_if_index: LOOPBACK_IF_INDEX + 1,
         }
     }
     
@@ -58,17 +66,26 @@ impl NetworkManager<'_> {
             info!("MAC address: {}", format_mac(&driver.mac()));
         }
         
-        let mut network_controller = NetworkController::new(network_driver);
+        let if_index = self.next_if_index;
+        self.next_if_index += 1;
+
+        let mut network_controller = NetworkController::new(network_driver, if_index);
 
         let name = format!("eth{}", self.interfaces.len());
         let interface = init_network_device_interface(&mut network_controller);
         
-        let device = NetworkDevice {
+        let mut device = NetworkDevice {
             interface,
             network_controller,
             sockets: Arc::new(Mutex::new(SocketSet::new(Vec::new()))),
+            duplicate_addresses: Vec::new(),
+            withdrawn_routes: Vec::new(),
+            multicast_groups: Vec::new(),
+            autoconfigured_addresses: Vec::new(),
         };
 
+        add_link_local_address(&mut device);
+
         let device_index = self.interfaces.len();
         let number_lines = self.irq_to_devices.len();
 
@@ -91,6 +108,47 @@ impl NetworkManager<'_> {
         }
     }
 
+    /// Register an interface without NIC nor interrupt line, such as a tunnel or a VXLAN
+    pub fn register_virtual_device(&mut self, name: &str, network_driver: Arc<Mutex<dyn NetworkDriver>>) -> Arc<Mutex<NetworkDevice<'a>>> {
+        let if_index = self.next_if_index;
+        self.next_if_index += 1;
+
+        let mut network_controller = NetworkController::new(network_driver, if_index);
+        let interface = init_network_device_interface(&mut network_controller);
+
+        let mut device = NetworkDevice {
+            interface,
+            network_controller,
+            sockets: Arc::new(Mutex::new(SocketSet::new(Vec::new()))),
+            duplicate_addresses: Vec::new(),
+            withdrawn_routes: Vec::new(),
+            multicast_groups: Vec::new(),
+            autoconfigured_addresses: Vec::new(),
+        };
+
+        // The virtual Ethernet interfaces, such as VXLAN, get one like the NICs
+        if device.mac().is_some() {
+            add_link_local_address(&mut device);
+        }
+
+        let device = Arc::new(Mutex::new(device));
+        self.interfaces.insert(String::from(name), device.clone());
+        trace!("Interface: {}, index: {}", name, if_index);
+
+        device
+    }
+
+    /// Remove a virtual interface, dropping its addresses, routes and socket set.
+    /// The services open their sockets again on an interface later created under the same name.
+    pub fn unregister_virtual_device(&mut self, name: &str) {
+        // The NICs stay registered on their interrupt lines
+        if self.irq_to_devices.values().any(|devices| devices.iter().any(|device| device == name)) {
+            return;
+        }
+
+        self.interfaces.remove(name);
+    }
+
     pub fn handle_interrupt(&mut self, interrupt_line: u8) {
         let Some(devices) = self.irq_to_devices.get(&interrupt_line) else {
             return;
@@ -104,9 +162,64 @@ impl NetworkManager<'_> {
                 continue;
             };
 
+            let mut device = device.lock();
+
             // check the NIC's ISR/status register and clear its interrupt sources.
-            device.lock().network_controller.process_interrupt();
+            device.network_controller.process_interrupt();
+
+            if let Some(link_status) = device.network_controller.update_link_status() {
+                match link_status.carrier {
+                    true => info!("{}: link up, {}", device_index, link_status),
+                    false => warn!("{}: link down", device_index)
+                }
+            }
+        }
+    }
+
+    /// Find the interface holding the most specific valid route to the given address
+    pub fn find_route_interface(&self, address: &IpAddress) -> Option<String> {
+        let now = Clock::now();
+        let mut best_route: Option<(String, u8)> = None;
+
+        for (device_name, device) in &self.interfaces {
+            let mut device = device.lock();
+
+            device.interface
+                .routes_mut()
+                .update(|routes| {
+                    let prefix_len = routes
+                        .iter()
+                        .filter(|route| {
+                            if let Some(expires_at) = route.expires_at {
+                                if now > expires_at {
+                                    return false;
+                                }
+                            }
+                            route.cidr.contains_addr(address)
+                        })
+                        .map(|route| route.cidr.prefix_len())
+                        .max();
+
+                    if let Some(prefix_len) = prefix_len {
+                        if best_route.as_ref().map(|(_, best_len)| prefix_len > *best_len).unwrap_or(true) {
+                            best_route = Some((device_name.clone(), prefix_len));
+                        }
+                    }
+                });
         }
+
+        best_route.map(|(device_name, _)| device_name)
+    }
+
+    /// Socket sets of the loopback and of every interface, by interface name
+    pub fn socket_sets(&self) -> Vec<(String, Arc<Mutex<SocketSet<'a>>>)> {
+        let mut socket_sets = vec![(String::from("lo"), self.loopback.sockets.clone())];
+
+        for (name, device) in &self.interfaces {
+            socket_sets.push((name.clone(), device.lock().sockets.clone()));
+        }
+
+        socket_sets
     }
 
     pub fn poll_interfaces(&mut self) {
@@ -121,6 +234,24 @@ impl NetworkManager<'_> {
     }
 }
 
+/// Link-local address, the interface identifier built from the MAC address (RFC 4862)
+fn add_link_local_address(device: &mut NetworkDevice) {
+    let mac = device.network_controller.driver.lock().mac();
+    let link_local = Ipv6Cidr::new(link_local_address(mac), SLAAC_PREFIX_LENGTH);
+
+    device.add_ip_address(IpCidr::Ipv6(link_local));
+    device.autoconfigured_addresses.push(AutoconfiguredAddress {
+        cidr: link_local,
+        origin: AddressOrigin::LinkLocal,
+        state: AddressState::Preferred,
+        tentative_until: None,
+        preferred_until: None,
+        valid_until: None,
+    });
+
+    info!("Link-local address: {}", link_local.address());
+}
+
 impl Loopback<'_> {
     pub fn poll(&mut self) {
         let now = Clock::now();
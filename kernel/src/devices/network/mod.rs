@@ -3,4 +3,12 @@ pub mod interface;
 pub mod device;
 pub mod manager;
 pub mod interrupt;
+pub mod flow;
+pub mod statistics;
+pub mod sockets;
+pub mod arp;
+pub mod multicast;
+pub mod slaac;
+pub mod tunnel;
+pub mod vxlan;
 mod driver;
@@ -0,0 +1,55 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use smoltcp::iface::Interface;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
+/// All-systems group, joined by every IPv4 host
+pub const IPV4_ALL_SYSTEMS: Ipv4Address = Ipv4Address::new(224, 0, 0, 1);
+/// All-nodes group, joined by every IPv6 node
+pub const IPV6_ALL_NODES: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+/// All-routers group, joined by the interfaces sending router advertisements
+pub const IPV6_ALL_ROUTERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// Ethernet address the frames sent to a multicast group are addressed to (RFC 1112, RFC 2464)
+pub fn multicast_mac(group: IpAddress) -> [u8; 6] {
+    match group {
+        IpAddress::Ipv4(group) => {
+            let octets = group.octets();
+            [0x01, 0x00, 0x5E, octets[1] & 0x7F, octets[2], octets[3]]
+        },
+        IpAddress::Ipv6(group) => {
+            let octets = group.octets();
+            [0x33, 0x33, octets[12], octets[13], octets[14], octets[15]]
+        }
+    }
+}
+
+/// Groups joined by smoltcp on its own, from the addresses of the interface
+pub fn implicit_groups(interface: &Interface) -> Vec<IpAddress> {
+    let mut groups = vec![
+        IpAddress::Ipv4(IPV4_ALL_SYSTEMS),
+        IpAddress::Ipv6(IPV6_ALL_NODES),
+    ];
+
+    if interface.has_multicast_group(IPV6_ALL_ROUTERS) {
+        groups.push(IpAddress::Ipv6(IPV6_ALL_ROUTERS));
+    }
+
+    for cidr in interface.ip_addrs() {
+        if let IpCidr::Ipv6(cidr) = cidr {
+            let solicited_node = IpAddress::Ipv6(solicited_node(cidr.address()));
+
+            if !groups.contains(&solicited_node) {
+                groups.push(solicited_node);
+            }
+        }
+    }
+
+    groups
+}
+
+/// Solicited-node group of an IPv6 address, ff02::1:ffXX:XXXX (RFC 4291)
+pub fn solicited_node(address: Ipv6Address) -> Ipv6Address {
+    let segments = address.segments();
+    Ipv6Address::new(0xff02, 0, 0, 0, 0, 1, 0xff00 | (segments[6] & 0x00ff), segments[7])
+}
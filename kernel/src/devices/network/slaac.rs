@@ -0,0 +1,74 @@
+use smoltcp::time::Instant;
+use smoltcp::wire::{Ipv6Address, Ipv6Cidr};
+use strum::Display;
+
+/// Prefix length of the link-local and autoconfigured addresses, the other 64 bits being the interface identifier
+pub const SLAAC_PREFIX_LENGTH: u8 = 64;
+/// Neighbor discovery messages are only accepted from the link, sent with this hop limit (RFC 4861, 6.1)
+pub const NDISC_HOP_LIMIT: u8 = 255;
+
+/// State of an IPv6 address (RFC 4862)
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum AddressState {
+    /// Duplicate address detection is running, the address is not used yet
+    Tentative,
+    Preferred,
+    /// Valid but past its preferred lifetime
+    Deprecated,
+    /// Another host answered the duplicate address detection, the address is not used
+    Duplicate,
+}
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum AddressOrigin {
+    /// Generated when the interface was registered
+    LinkLocal,
+    /// Built from a prefix advertised by a router
+    Slaac,
+    /// Assigned from a prefix delegated with DHCPv6
+    Delegated,
+}
+
+/// IPv6 address configured by the kernel, along with its lifetimes
+#[derive(Debug, Clone, Copy)]
+pub struct AutoconfiguredAddress {
+    pub cidr: Ipv6Cidr,
+    pub origin: AddressOrigin,
+    pub state: AddressState,
+    /// End of the duplicate address detection
+    pub tentative_until: Option<Instant>,
+    /// `None` means "forever"
+    pub preferred_until: Option<Instant>,
+    /// `None` means "forever"
+    pub valid_until: Option<Instant>,
+}
+
+/// Modified EUI-64 interface identifier, the universal/local bit of the MAC address inverted (RFC 4291, appendix A)
+pub fn eui64_interface_id(mac: [u8; 6]) -> [u8; 8] {
+    [mac[0] ^ 0x02, mac[1], mac[2], 0xFF, 0xFE, mac[3], mac[4], mac[5]]
+}
+
+/// Address made of the first 64 bits of the prefix and of the interface identifier of the MAC address
+pub fn slaac_address(prefix: Ipv6Address, mac: [u8; 6]) -> Ipv6Address {
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&eui64_interface_id(mac));
+    Ipv6Address::from(octets)
+}
+
+/// fe80::/64 address of the interface
+pub fn link_local_address(mac: [u8; 6]) -> Ipv6Address {
+    slaac_address(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac)
+}
+
+/// fe80::/10
+pub fn is_link_local(address: Ipv6Address) -> bool {
+    address.segments()[0] & 0xFFC0 == 0xFE80
+}
+
+/// Prefix of the CIDR, the host bits cleared
+pub fn ipv6_network(cidr: Ipv6Cidr) -> Ipv6Cidr {
+    let mask = u128::MAX.checked_shl(128 - cidr.prefix_len() as u32).unwrap_or(0);
+    Ipv6Cidr::new(Ipv6Address::from(u128::from(cidr.address()) & mask), cidr.prefix_len())
+}
@@ -0,0 +1,95 @@
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::{route_interface, BoundSocket, SocketError};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::task::Poll;
+use smoltcp::socket::icmp::{Endpoint, PacketBuffer, PacketMetadata, RecvError, SendError, Socket};
+use smoltcp::wire::IpAddress;
+
+const BUFFER_SIZE: usize = 4096;
+const PACKET_METADATA_COUNT: usize = 8;
+
+/// ICMP socket bound on every interface, receiving the echo replies of its identifier
+pub struct IcmpSocket {
+    ident: u16,
+    sockets: Vec<BoundSocket>,
+}
+
+impl IcmpSocket {
+    pub fn bind(ident: u16) -> Result<Self, SocketError> {
+        let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+        let mut sockets = Vec::new();
+
+        for (interface_name, socket_set) in socket_sets {
+            let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; PACKET_METADATA_COUNT], vec![0; BUFFER_SIZE]);
+            let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; PACKET_METADATA_COUNT], vec![0; BUFFER_SIZE]);
+
+            let mut socket = Socket::new(rx_buffer, tx_buffer);
+            socket.bind(Endpoint::Ident(ident)).map_err(|_| SocketError::Unaddressable)?;
+
+            sockets.push(BoundSocket::new(interface_name, socket_set, socket));
+        }
+
+        Ok(Self {
+            ident,
+            sockets,
+        })
+    }
+
+    pub fn ident(&self) -> u16 {
+        self.ident
+    }
+
+    pub fn set_hop_limit(&self, hop_limit: Option<u8>) {
+        for socket in &self.sockets {
+            socket.with(|socket: &mut Socket| socket.set_hop_limit(hop_limit));
+        }
+    }
+
+    /// Receive an ICMP message from any interface, along with its sender
+    pub async fn recv_from(&self, buffer: &mut [u8]) -> Result<(usize, IpAddress), SocketError> {
+        poll_fn(|context| {
+            for socket in &self.sockets {
+                let received = socket.with(|socket: &mut Socket| match socket.recv_slice(buffer) {
+                    Ok(received) => Some(Ok(received)),
+                    // The message is dropped
+                    Err(RecvError::Truncated) => Some(Err(SocketError::PacketTooLarge)),
+                    Err(RecvError::Exhausted) => {
+                        socket.register_recv_waker(context.waker());
+                        None
+                    }
+                });
+
+                if let Some(received) = received {
+                    return Poll::Ready(received);
+                }
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Send an ICMP message from the interface holding the route to the remote address.
+    /// Its checksum is computed again when it leaves, along with the IP header.
+    pub async fn send_to(&self, message: &[u8], remote_addr: IpAddress) -> Result<(), SocketError> {
+        let interface_name = route_interface(&remote_addr)?;
+
+        let socket = self.sockets
+            .iter()
+            .find(|socket| socket.interface_name == interface_name)
+            .ok_or(SocketError::NoRoute(remote_addr))?;
+
+        poll_fn(|context| socket.with(|socket: &mut Socket| match socket.send_slice(message, remote_addr) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(SendError::Unaddressable) => Poll::Ready(Err(SocketError::Unaddressable)),
+            Err(SendError::BufferFull) if message.len() > socket.payload_send_capacity() => Poll::Ready(Err(SocketError::PacketTooLarge)),
+            Err(SendError::BufferFull) => {
+                socket.register_send_waker(context.waker());
+                Poll::Pending
+            }
+        }))
+        .await
+    }
+}
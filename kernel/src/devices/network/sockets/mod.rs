@@ -0,0 +1,288 @@
+pub mod tcp;
+pub mod udp;
+pub mod icmp;
+
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::task::executor::current_task;
+use crate::task::task::TaskId;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::ptr;
+use core::sync::atomic::{AtomicU16, Ordering};
+use smoltcp::iface::{Context, SocketHandle, SocketSet};
+use smoltcp::socket::AnySocket;
+use smoltcp::wire::IpAddress;
+use spin::Mutex;
+use thiserror::Error;
+use x86_64::instructions::interrupts;
+
+/// Start of the dynamic port range (RFC 6335), the local ports of the outgoing connections are taken from it
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+static NEXT_EPHEMERAL_PORT: AtomicU16 = AtomicU16::new(EPHEMERAL_PORT_START);
+
+/// Task which added each socket, by socket set address and handle.
+/// The weak reference keeps the address of a dropped set from being given to a new one while it has entries.
+static SOCKET_OWNERS: Mutex<BTreeMap<(usize, SocketHandle), (Weak<Mutex<SocketSet<'static>>>, TaskId)>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Error)]
+pub enum SocketError {
+    #[error("no interface found to reach {0}")]
+    NoRoute(IpAddress),
+
+    #[error("unknown interface {0}")]
+    UnknownInterface(String),
+
+    #[error("address or port not addressable")]
+    Unaddressable,
+
+    #[error("connection refused")]
+    ConnectionRefused,
+
+    #[error("connection reset")]
+    ConnectionReset,
+
+    #[error("connection timed out")]
+    TimedOut,
+
+    #[error("socket closed")]
+    Closed,
+
+    #[error("packet too large")]
+    PacketTooLarge,
+}
+
+/// Sockets added to a socket set for a while, removed from it when dropped, cancelled commands included
+pub struct OwnedSockets {
+    sockets: Arc<Mutex<SocketSet<'static>>>,
+    handles: Vec<SocketHandle>,
+}
+
+impl OwnedSockets {
+    pub fn new(sockets: Arc<Mutex<SocketSet<'static>>>) -> Self {
+        Self {
+            sockets,
+            handles: Vec::new(),
+        }
+    }
+
+    pub fn add<T: AnySocket<'static>>(&mut self, socket: T) -> SocketHandle {
+        let handle = interrupts::without_interrupts(|| {
+            let mut sockets = self.sockets.lock();
+            let handle = sockets.add(socket);
+            register_owner(&self.sockets, handle);
+            handle
+        });
+
+        self.handles.push(handle);
+        handle
+    }
+
+    /// Run a function on one of the sockets
+    pub fn with<T: AnySocket<'static>, R>(&self, handle: SocketHandle, function: impl FnOnce(&mut T) -> R) -> R {
+        interrupts::without_interrupts(|| function(self.sockets.lock().get_mut::<T>(handle)))
+    }
+}
+
+impl Drop for OwnedSockets {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            let mut sockets = self.sockets.lock();
+
+            for handle in self.handles.drain(..) {
+                sockets.remove(handle);
+                unregister_owner(&self.sockets, handle);
+            }
+        });
+    }
+}
+
+/// Socket of a single interface, removed from its socket set when dropped
+struct BoundSocket {
+    interface_name: String,
+    sockets: Arc<Mutex<SocketSet<'static>>>,
+    handle: SocketHandle,
+}
+
+impl BoundSocket {
+    fn new<T: AnySocket<'static>>(interface_name: String, sockets: Arc<Mutex<SocketSet<'static>>>, socket: T) -> Self {
+        let handle = interrupts::without_interrupts(|| {
+            let handle = sockets.lock().add(socket);
+            register_owner(&sockets, handle);
+            handle
+        });
+
+        Self {
+            interface_name,
+            sockets,
+            handle,
+        }
+    }
+
+    fn with<T: AnySocket<'static>, R>(&self, function: impl FnOnce(&mut T) -> R) -> R {
+        interrupts::without_interrupts(|| function(self.sockets.lock().get_mut::<T>(self.handle)))
+    }
+}
+
+impl Drop for BoundSocket {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            self.sockets.lock().remove(self.handle);
+            unregister_owner(&self.sockets, self.handle);
+        });
+    }
+}
+
+/// Socket a service keeps open across its polls, on the socket set of an interface
+pub struct ServiceSocket {
+    /// Dropped along with its interface, a new interface of the same name getting a new set
+    sockets: Weak<Mutex<SocketSet<'static>>>,
+    pub handle: SocketHandle,
+}
+
+impl ServiceSocket {
+    /// Add a socket to the locked set, the task being polled owning it
+    pub fn add<T: AnySocket<'static>>(socket_set: &Arc<Mutex<SocketSet<'static>>>, sockets: &mut SocketSet<'static>, socket: T) -> Self {
+        let handle = sockets.add(socket);
+        register_owner(socket_set, handle);
+
+        Self {
+            sockets: Arc::downgrade(socket_set),
+            handle,
+        }
+    }
+
+    /// Whether the handle belongs to the given set, and not to the one of a deleted interface
+    pub fn is_in(&self, socket_set: &Arc<Mutex<SocketSet<'static>>>) -> bool {
+        ptr::eq(self.sockets.as_ptr(), Arc::as_ptr(socket_set))
+    }
+
+    /// Remove the socket from its set, unless the set went away with its interface
+    pub fn close(self) {
+        if let Some(sockets) = self.sockets.upgrade() {
+            interrupts::without_interrupts(|| {
+                sockets.lock().remove(self.handle);
+                unregister_owner(&sockets, self.handle);
+            });
+        }
+    }
+}
+
+/// Close the sockets of a service kept by interface name whose interface was deleted, or deleted and created again
+pub fn close_stale_sockets(service_sockets: &mut BTreeMap<String, ServiceSocket>, socket_sets: &[(String, Arc<Mutex<SocketSet<'static>>>)]) {
+    let stale: Vec<String> = service_sockets
+        .iter()
+        .filter(|(name, socket)| !socket_sets.iter().any(|(interface_name, socket_set)| interface_name == *name && socket.is_in(socket_set)))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in stale {
+        if let Some(socket) = service_sockets.remove(&name) {
+            socket.close();
+        }
+    }
+}
+
+/// Record the task being polled as the owner of a socket just added to the set
+pub fn register_owner(sockets: &Arc<Mutex<SocketSet<'static>>>, handle: SocketHandle) {
+    let key = (Arc::as_ptr(sockets) as usize, handle);
+    let mut owners = SOCKET_OWNERS.lock();
+
+    // Sockets of the sets dropped along with their interface
+    owners.retain(|_, (sockets, _)| sockets.strong_count() > 0);
+
+    match current_task() {
+        Some(task_id) => owners.insert(key, (Arc::downgrade(sockets), task_id)),
+        None => owners.remove(&key)
+    };
+}
+
+/// Forget the owner of a socket removed from the set
+pub fn unregister_owner(sockets: &Arc<Mutex<SocketSet<'static>>>, handle: SocketHandle) {
+    SOCKET_OWNERS.lock().remove(&(Arc::as_ptr(sockets) as usize, handle));
+}
+
+/// Task which added a socket to the set, if it was added by one
+pub fn socket_owner(sockets: &Arc<Mutex<SocketSet<'static>>>, handle: SocketHandle) -> Option<TaskId> {
+    SOCKET_OWNERS.lock().get(&(Arc::as_ptr(sockets) as usize, handle)).map(|(_, task_id)| *task_id)
+}
+
+/// Local port of an outgoing connection
+fn ephemeral_port() -> u16 {
+    let port = NEXT_EPHEMERAL_PORT.fetch_add(1, Ordering::Relaxed);
+
+    // The counter wraps around the whole port range, which is a multiple of the dynamic one
+    EPHEMERAL_PORT_START + port.wrapping_sub(EPHEMERAL_PORT_START) % (u16::MAX - EPHEMERAL_PORT_START + 1)
+}
+
+/// Name of the interface holding the route to the given address, the loopback included
+fn route_interface(remote_addr: &IpAddress) -> Result<String, SocketError> {
+    if remote_addr.is_unspecified() {
+        return Err(SocketError::Unaddressable);
+    }
+
+    let is_loopback = match remote_addr {
+        IpAddress::Ipv4(address) => address.is_loopback(),
+        IpAddress::Ipv6(address) => address.is_loopback()
+    };
+
+    if is_loopback {
+        return Ok(String::from("lo"));
+    }
+
+    NETWORK_MANAGER
+        .lock()
+        .find_route_interface(remote_addr)
+        .ok_or(SocketError::NoRoute(*remote_addr))
+}
+
+/// Socket set of the given interface
+fn interface_sockets(interface_name: &str) -> Result<Arc<Mutex<SocketSet<'static>>>, SocketError> {
+    let manager = NETWORK_MANAGER.lock();
+
+    match interface_name {
+        "lo" => Ok(manager.loopback.sockets.clone()),
+        _ => manager.interfaces
+            .get(interface_name)
+            .map(|device| device.lock().sockets.clone())
+            .ok_or_else(|| SocketError::UnknownInterface(String::from(interface_name)))
+    }
+}
+
+/// Run a function with the context of the given interface, which the connecting sockets need
+fn with_interface_context<R>(interface_name: &str, function: impl FnOnce(&mut Context) -> R) -> Result<R, SocketError> {
+    let mut manager = NETWORK_MANAGER.lock();
+
+    match interface_name {
+        "lo" => Ok(function(manager.loopback.interface.context())),
+        _ => {
+            let device = manager.interfaces
+                .get(interface_name)
+                .ok_or_else(|| SocketError::UnknownInterface(String::from(interface_name)))?;
+
+            let result = function(device.lock().interface.context());
+            Ok(result)
+        }
+    }
+}
+
+/// Let an interface send what its sockets queued, such as the reset of an aborted connection.
+/// Skipped when the interface is busy, the next poll sending it.
+pub fn poll_interface(interface_name: &str) {
+    interrupts::without_interrupts(|| {
+        let Some(mut manager) = NETWORK_MANAGER.try_lock() else {
+            return;
+        };
+
+        match interface_name {
+            "lo" => manager.loopback.poll(),
+            _ => {
+                if let Some(mut device) = manager.interfaces.get(interface_name).and_then(|device| device.try_lock()) {
+                    device.poll();
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,279 @@
+use crate::clock::Timer;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::{ephemeral_port, interface_sockets, poll_interface, route_interface, with_interface_context, BoundSocket, SocketError};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::pin::pin;
+use core::task::{Context, Poll};
+use futures_util::future::{select, Either};
+use smoltcp::socket::tcp::{RecvError, Socket, SocketBuffer, State};
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
+
+pub const DEFAULT_BUFFER_SIZE: usize = 8192;
+/// Listening sockets kept per interface, each one holds a connection until its handshake is complete
+pub const DEFAULT_BACKLOG: usize = 1;
+
+/// Time given to the remote side to answer the handshake
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time given to the remote side to acknowledge the end of a connection
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listening sockets of a port, on every interface
+pub struct TcpListener {
+    port: u16,
+    listeners: BTreeMap<String, Vec<BoundSocket>>,
+    backlog: usize,
+    rx_buffer_size: usize,
+    tx_buffer_size: usize,
+}
+
+/// Established TCP connection, dropping it removes its socket and the remote side gets reset
+pub struct TcpStream {
+    socket: BoundSocket,
+}
+
+fn new_socket(rx_buffer_size: usize, tx_buffer_size: usize) -> Socket<'static> {
+    let rx_buffer = SocketBuffer::new(vec![0; rx_buffer_size]);
+    let tx_buffer = SocketBuffer::new(vec![0; tx_buffer_size]);
+    Socket::new(rx_buffer, tx_buffer)
+}
+
+impl TcpListener {
+    pub fn bind(port: u16) -> Result<Self, SocketError> {
+        Self::with_buffer_sizes(port, DEFAULT_BUFFER_SIZE, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Listen with the given buffer sizes, which the accepted connections keep
+    pub fn with_buffer_sizes(port: u16, rx_buffer_size: usize, tx_buffer_size: usize) -> Result<Self, SocketError> {
+        if port == 0 {
+            return Err(SocketError::Unaddressable);
+        }
+
+        Ok(Self {
+            port,
+            listeners: BTreeMap::new(),
+            backlog: DEFAULT_BACKLOG,
+            rx_buffer_size,
+            tx_buffer_size,
+        })
+    }
+
+    /// Number of connections which can be opening at the same time on an interface
+    pub fn set_backlog(&mut self, backlog: usize) {
+        self.backlog = backlog.max(1);
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.port
+    }
+
+    /// Wait for a connection on any interface, once its handshake is complete
+    pub async fn accept(&mut self) -> TcpStream {
+        poll_fn(|context| self.poll_accept(context)).await
+    }
+
+    fn poll_accept(&mut self, context: &mut Context) -> Poll<TcpStream> {
+        // The interfaces added since the last call are listened on as well
+        let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+        for (interface_name, sockets) in socket_sets {
+            let listeners = self.listeners.entry(interface_name.clone()).or_default();
+
+            while listeners.len() < self.backlog {
+                let mut socket = new_socket(self.rx_buffer_size, self.tx_buffer_size);
+                socket.listen(self.port).unwrap();
+                listeners.push(BoundSocket::new(interface_name.clone(), sockets.clone(), socket));
+            }
+
+            let established = listeners.iter().position(|listener| listener.with(|socket: &mut Socket| match socket.state() {
+                State::Listen | State::SynReceived => {
+                    socket.register_recv_waker(context.waker());
+                    false
+                },
+                _ => true
+            }));
+
+            // The listener becomes the connection, another one is created at the next call
+            if let Some(index) = established {
+                let socket = listeners.remove(index);
+                return Poll::Ready(TcpStream { socket });
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl TcpStream {
+    pub async fn connect(remote_endpoint: IpEndpoint) -> Result<Self, SocketError> {
+        Self::connect_with_buffer_sizes(remote_endpoint, DEFAULT_BUFFER_SIZE, DEFAULT_BUFFER_SIZE).await
+    }
+
+    /// Open a connection from the interface holding the route to the remote endpoint
+    pub async fn connect_with_buffer_sizes(remote_endpoint: IpEndpoint, rx_buffer_size: usize, tx_buffer_size: usize) -> Result<Self, SocketError> {
+        let interface_name = route_interface(&remote_endpoint.addr)?;
+        let sockets = interface_sockets(&interface_name)?;
+
+        let mut socket = new_socket(rx_buffer_size, tx_buffer_size);
+
+        with_interface_context(&interface_name, |interface_context| {
+            socket.connect(interface_context, remote_endpoint, ephemeral_port())
+        })?
+        .map_err(|_| SocketError::Unaddressable)?;
+
+        let stream = TcpStream {
+            socket: BoundSocket::new(interface_name, sockets, socket),
+        };
+
+        let is_connected = poll_fn(|context| stream.socket.with(|socket: &mut Socket| match socket.state() {
+            State::SynSent | State::SynReceived => {
+                socket.register_send_waker(context.waker());
+                Poll::Pending
+            },
+            // A reset answered the handshake
+            State::Closed => Poll::Ready(Err(SocketError::ConnectionRefused)),
+            _ => Poll::Ready(Ok(()))
+        }));
+
+        // smoltcp sends the SYN again until it is answered, the stream dropped aborts it
+        match select(pin!(is_connected), Timer::after(CONNECT_TIMEOUT)).await {
+            Either::Left((result, _)) => result.map(|_| stream),
+            Either::Right(_) => Err(SocketError::TimedOut)
+        }
+    }
+
+    pub fn interface_name(&self) -> &str {
+        &self.socket.interface_name
+    }
+
+    pub fn local_endpoint(&self) -> Option<IpEndpoint> {
+        self.socket.with(|socket: &mut Socket| socket.local_endpoint())
+    }
+
+    pub fn remote_endpoint(&self) -> Option<IpEndpoint> {
+        self.socket.with(|socket: &mut Socket| socket.remote_endpoint())
+    }
+
+    pub fn state(&self) -> State {
+        self.socket.with(|socket: &mut Socket| socket.state())
+    }
+
+    /// Read the received data, returns 0 once the remote side closed its half of the connection
+    pub async fn read(&self, buffer: &mut [u8]) -> Result<usize, SocketError> {
+        poll_fn(|context| self.poll_read(context, buffer)).await
+    }
+
+    pub fn poll_read(&self, context: &mut Context, buffer: &mut [u8]) -> Poll<Result<usize, SocketError>> {
+        if buffer.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        self.socket.with(|socket: &mut Socket| match socket.recv_slice(buffer) {
+            Ok(0) => {
+                socket.register_recv_waker(context.waker());
+                Poll::Pending
+            },
+            Ok(size) => Poll::Ready(Ok(size)),
+            Err(RecvError::Finished) => Poll::Ready(Ok(0)),
+            Err(RecvError::InvalidState) => Poll::Ready(Err(SocketError::ConnectionReset))
+        })
+    }
+
+    /// Fill the whole buffer, the end of the stream before that is an error
+    pub async fn read_exact(&self, mut buffer: &mut [u8]) -> Result<(), SocketError> {
+        while !buffer.is_empty() {
+            match self.read(buffer).await? {
+                0 => return Err(SocketError::Closed),
+                size => buffer = &mut buffer[size..]
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queue data to send, returns how much of it the socket took
+    pub async fn write(&self, data: &[u8]) -> Result<usize, SocketError> {
+        poll_fn(|context| self.poll_write(context, data)).await
+    }
+
+    pub fn poll_write(&self, context: &mut Context, data: &[u8]) -> Poll<Result<usize, SocketError>> {
+        if data.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        self.socket.with(|socket: &mut Socket| match socket.send_slice(data) {
+            Ok(0) => {
+                socket.register_send_waker(context.waker());
+                Poll::Pending
+            },
+            Ok(size) => Poll::Ready(Ok(size)),
+            Err(_) => Poll::Ready(Err(SocketError::Closed))
+        })
+    }
+
+    pub async fn write_all(&self, mut data: &[u8]) -> Result<(), SocketError> {
+        while !data.is_empty() {
+            let sent = self.write(data).await?;
+            data = &data[sent..];
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the remote side to acknowledge everything sent
+    pub async fn flush(&self) -> Result<(), SocketError> {
+        poll_fn(|context| self.socket.with(|socket: &mut Socket| {
+            if socket.send_queue() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !socket.is_active() {
+                return Poll::Ready(Err(SocketError::ConnectionReset));
+            }
+
+            socket.register_send_waker(context.waker());
+            Poll::Pending
+        }))
+        .await
+    }
+
+    /// Close the sending half, the remote side reads the end of the stream
+    pub fn shutdown(&self) {
+        self.socket.with(|socket: &mut Socket| socket.close());
+    }
+
+    /// Close the connection, letting the sent data go, and wait for the remote side to close it as well
+    pub async fn close(self) {
+        self.shutdown();
+
+        let is_closed = poll_fn(|context| self.socket.with(|socket: &mut Socket| match socket.state() {
+            State::Closed | State::TimeWait => Poll::Ready(()),
+            _ => {
+                socket.register_send_waker(context.waker());
+                Poll::Pending
+            }
+        }));
+
+        select(is_closed, Timer::after(CLOSE_TIMEOUT)).await;
+    }
+}
+
+impl Drop for TcpStream {
+    /// Reset the connection still open, before its socket is removed
+    fn drop(&mut self) {
+        let is_open = self.socket.with(|socket: &mut Socket| match socket.state() {
+            State::Closed | State::TimeWait => false,
+            _ => {
+                socket.abort();
+                true
+            }
+        });
+
+        if is_open {
+            poll_interface(&self.socket.interface_name);
+        }
+    }
+}
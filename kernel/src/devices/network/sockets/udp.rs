@@ -0,0 +1,135 @@
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::{ephemeral_port, interface_sockets, route_interface, BoundSocket, SocketError};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::task::{Context, Poll};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, RecvError, SendError, Socket};
+use smoltcp::wire::IpEndpoint;
+
+pub const DEFAULT_BUFFER_SIZE: usize = 8192;
+const PACKET_METADATA_COUNT: usize = 16;
+
+/// UDP port bound on one or every interface
+pub struct UdpSocket {
+    port: u16,
+    sockets: Vec<BoundSocket>,
+}
+
+fn new_socket(port: u16, buffer_size: usize) -> Result<Socket<'static>, SocketError> {
+    // Larger buffers hold more datagrams, about one per kilobyte
+    let packet_metadata_count = PACKET_METADATA_COUNT.max(buffer_size / 1024);
+
+    let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; packet_metadata_count], vec![0; buffer_size]);
+    let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; packet_metadata_count], vec![0; buffer_size]);
+
+    let mut socket = Socket::new(rx_buffer, tx_buffer);
+    socket.bind(port).map_err(|_| SocketError::Unaddressable)?;
+
+    Ok(socket)
+}
+
+impl UdpSocket {
+    /// Bind the given port on every interface, or an ephemeral one if it is 0
+    pub fn bind(port: u16) -> Result<Self, SocketError> {
+        Self::bind_with_buffer_size(port, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Bind on every interface with the given size for the receive and send buffers
+    pub fn bind_with_buffer_size(port: u16, buffer_size: usize) -> Result<Self, SocketError> {
+        let port = match port {
+            0 => ephemeral_port(),
+            port => port
+        };
+
+        let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+        let mut sockets = Vec::new();
+
+        for (interface_name, socket_set) in socket_sets {
+            sockets.push(BoundSocket::new(interface_name, socket_set, new_socket(port, buffer_size)?));
+        }
+
+        Ok(Self {
+            port,
+            sockets,
+        })
+    }
+
+    /// Bind the given port on a single interface, or an ephemeral one if it is 0
+    pub fn bind_interface(interface_name: &str, port: u16) -> Result<Self, SocketError> {
+        let port = match port {
+            0 => ephemeral_port(),
+            port => port
+        };
+
+        let socket_set = interface_sockets(interface_name)?;
+        let socket = BoundSocket::new(String::from(interface_name), socket_set, new_socket(port, DEFAULT_BUFFER_SIZE)?);
+
+        Ok(Self {
+            port,
+            sockets: vec![socket],
+        })
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn set_hop_limit(&self, hop_limit: Option<u8>) {
+        for socket in &self.sockets {
+            socket.with(|socket: &mut Socket| socket.set_hop_limit(hop_limit));
+        }
+    }
+
+    /// Receive a datagram from any of the bound interfaces, along with its sender
+    pub async fn recv_from(&self, buffer: &mut [u8]) -> Result<(usize, IpEndpoint), SocketError> {
+        poll_fn(|context| self.poll_recv_from(context, buffer)).await
+    }
+
+    pub fn poll_recv_from(&self, context: &mut Context, buffer: &mut [u8]) -> Poll<Result<(usize, IpEndpoint), SocketError>> {
+        for socket in &self.sockets {
+            let received = socket.with(|socket: &mut Socket| match socket.recv_slice(buffer) {
+                Ok((size, metadata)) => Some(Ok((size, metadata.endpoint))),
+                // The datagram is dropped
+                Err(RecvError::Truncated) => Some(Err(SocketError::PacketTooLarge)),
+                Err(RecvError::Exhausted) => {
+                    socket.register_recv_waker(context.waker());
+                    None
+                }
+            });
+
+            if let Some(received) = received {
+                return Poll::Ready(received);
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Send a datagram from the interface holding the route to the remote endpoint
+    pub async fn send_to(&self, data: &[u8], remote_endpoint: IpEndpoint) -> Result<(), SocketError> {
+        poll_fn(|context| self.poll_send_to(context, data, remote_endpoint)).await
+    }
+
+    pub fn poll_send_to(&self, context: &mut Context, data: &[u8], remote_endpoint: IpEndpoint) -> Poll<Result<(), SocketError>> {
+        let interface_name = match route_interface(&remote_endpoint.addr) {
+            Ok(interface_name) => interface_name,
+            Err(error) => return Poll::Ready(Err(error))
+        };
+
+        let Some(socket) = self.sockets.iter().find(|socket| socket.interface_name == interface_name) else {
+            return Poll::Ready(Err(SocketError::NoRoute(remote_endpoint.addr)));
+        };
+
+        socket.with(|socket: &mut Socket| match socket.send_slice(data, remote_endpoint) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(SendError::Unaddressable) => Poll::Ready(Err(SocketError::Unaddressable)),
+            Err(SendError::BufferFull) if data.len() > socket.payload_send_capacity() => Poll::Ready(Err(SocketError::PacketTooLarge)),
+            Err(SendError::BufferFull) => {
+                socket.register_send_waker(context.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use smoltcp::phy::Medium;
+use smoltcp::wire::{IpVersion, Ipv4Packet, Ipv6Packet};
+
+/// Traffic counters of a network interface
+#[derive(Debug, Default)]
+pub struct InterfaceStatistics {
+    pub rx_packets: AtomicU64,
+    pub rx_bytes: AtomicU64,
+    pub rx_multicast: AtomicU64,
+    /// Frames replaced by the next one before smoltcp consumed them
+    pub rx_dropped: AtomicU64,
+    pub tx_packets: AtomicU64,
+    pub tx_bytes: AtomicU64,
+}
+
+/// Error counters kept by a network driver, from its descriptors and hardware registers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DriverStatistics {
+    pub rx_errors: u64,
+    pub rx_crc_errors: u64,
+    /// Frames the NIC could not store, its receive buffer being full
+    pub rx_missed: u64,
+    pub tx_errors: u64,
+    /// Frames too large for the transmit buffers
+    pub tx_dropped: u64,
+    pub collisions: u64,
+}
+
+/// Every counter of an interface at a given time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InterfaceCounters {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub rx_missed: u64,
+    pub rx_multicast: u64,
+    pub rx_crc_errors: u64,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+    pub collisions: u64,
+}
+
+impl InterfaceStatistics {
+    pub fn count_rx(&self, frame: &[u8], medium: Medium) {
+        self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        self.rx_bytes.fetch_add(frame.len() as u64, Ordering::Relaxed);
+
+        let is_multicast = match medium {
+            // Group bit of the destination address, the broadcast address excluded
+            Medium::Ethernet => frame.len() >= 6 && frame[0] & 0x01 != 0 && frame[..6] != [0xFF; 6],
+            // Destination address of the bare IP packets
+            _ => match IpVersion::of_packet(frame) {
+                Ok(IpVersion::Ipv4) => Ipv4Packet::new_checked(frame).is_ok_and(|packet| packet.dst_addr().is_multicast()),
+                Ok(IpVersion::Ipv6) => Ipv6Packet::new_checked(frame).is_ok_and(|packet| packet.dst_addr().is_multicast()),
+                _ => false
+            }
+        };
+
+        if is_multicast {
+            self.rx_multicast.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn count_rx_dropped(&self) {
+        self.rx_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_tx(&self, length: usize) {
+        self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        self.tx_bytes.fetch_add(length as u64, Ordering::Relaxed);
+    }
+
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets.load(Ordering::Relaxed)
+    }
+
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets.load(Ordering::Relaxed)
+    }
+
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Counters of the interface, along with the ones of its driver
+    pub fn counters(&self, driver: DriverStatistics) -> InterfaceCounters {
+        InterfaceCounters {
+            rx_packets: self.rx_packets(),
+            rx_bytes: self.rx_bytes(),
+            rx_errors: driver.rx_errors,
+            rx_dropped: self.rx_dropped.load(Ordering::Relaxed),
+            rx_missed: driver.rx_missed,
+            rx_multicast: self.rx_multicast.load(Ordering::Relaxed),
+            rx_crc_errors: driver.rx_crc_errors,
+            tx_packets: self.tx_packets(),
+            tx_bytes: self.tx_bytes(),
+            tx_errors: driver.tx_errors,
+            tx_dropped: driver.tx_dropped,
+            collisions: driver.collisions,
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::devices::network::controller::DEFAULT_MTU;
+use crate::devices::network::statistics::DriverStatistics;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use no_std_clap_macros::EnumValuesArg;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::wire::{EthernetProtocol, IpProtocol, IpVersion, Ipv4Address, Ipv4Packet, Ipv4Repr};
+use strum::{Display, EnumString, VariantNames};
+
+/// Largest IPv4 packet, fragments reassembled
+pub const MAX_IP_PACKET_SIZE: usize = 65535;
+
+const IPV4_HEADER_SIZE: usize = 20;
+const GRE_HEADER_SIZE: usize = 4;
+
+/// Optional fields of the GRE header, 4 bytes each (RFC 2890)
+const GRE_CHECKSUM_PRESENT: u16 = 0x8000;
+const GRE_KEY_PRESENT: u16 = 0x2000;
+const GRE_SEQUENCE_PRESENT: u16 = 0x1000;
+const GRE_VERSION_MASK: u16 = 0x0007;
+
+/// Time to live of the encapsulated packets
+const TUNNEL_TTL: u8 = 64;
+
+/// Packets waiting to be moved to or from the underlying interface
+const QUEUE_SIZE: usize = 64;
+
+/// Encapsulation of the packets sent over a tunnel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumValuesArg, VariantNames, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum TunnelMode {
+    /// Generic Routing Encapsulation (RFC 2784), carrying IPv4 and IPv6
+    #[default]
+    Gre,
+    /// IPv4 in IPv4 (RFC 2003)
+    Ipip,
+}
+
+impl TunnelMode {
+    /// Protocol of the outer IPv4 header
+    pub fn protocol(&self) -> IpProtocol {
+        match self {
+            TunnelMode::Gre => IpProtocol::Unknown(47),
+            TunnelMode::Ipip => IpProtocol::Unknown(4)
+        }
+    }
+
+    /// Bytes added to each packet, the outer IPv4 header included
+    pub fn overhead(&self) -> usize {
+        match self {
+            TunnelMode::Gre => IPV4_HEADER_SIZE + GRE_HEADER_SIZE,
+            TunnelMode::Ipip => IPV4_HEADER_SIZE
+        }
+    }
+
+    /// MTU of a new tunnel, its packets fitting the default MTU of the underlying interface once encapsulated
+    pub fn default_mtu(&self) -> usize {
+        DEFAULT_MTU - self.overhead()
+    }
+}
+
+/// Virtual NIC of a tunnel interface, the tunnel task moving its packets to and from the underlying interface
+#[derive(Debug)]
+pub struct TunnelDriver {
+    pub mode: TunnelMode,
+    enabled: bool,
+    /// Packets sent by smoltcp, to encapsulate
+    pub tx_queue: VecDeque<Vec<u8>>,
+    /// Decapsulated packets, to hand to smoltcp
+    pub rx_queue: VecDeque<Vec<u8>>,
+    pub statistics: DriverStatistics,
+}
+
+impl TunnelDriver {
+    pub fn new(mode: TunnelMode) -> Self {
+        Self {
+            mode,
+            enabled: true,
+            tx_queue: VecDeque::new(),
+            rx_queue: VecDeque::new(),
+            statistics: DriverStatistics::default(),
+        }
+    }
+
+    pub fn enable(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.tx_queue.clear();
+            self.rx_queue.clear();
+        }
+    }
+
+    pub fn queue_tx(&mut self, packet: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        match self.tx_queue.len() < QUEUE_SIZE {
+            true => self.tx_queue.push_back(packet.to_vec()),
+            false => self.statistics.tx_dropped += 1
+        }
+    }
+
+    pub fn queue_rx(&mut self, packet: Vec<u8>) {
+        if !self.enabled {
+            return;
+        }
+
+        match self.rx_queue.len() < QUEUE_SIZE {
+            true => self.rx_queue.push_back(packet),
+            false => self.statistics.rx_missed += 1
+        }
+    }
+}
+
+/// Outer IPv4 packet carrying an inner IP packet, `None` if the mode cannot carry it
+pub fn encapsulate(mode: TunnelMode, local: Ipv4Address, remote: Ipv4Address, packet: &[u8]) -> Option<Vec<u8>> {
+    let ethertype = match IpVersion::of_packet(packet) {
+        Ok(IpVersion::Ipv4) => EthernetProtocol::Ipv4,
+        Ok(IpVersion::Ipv6) if mode == TunnelMode::Gre => EthernetProtocol::Ipv6,
+        _ => return None
+    };
+
+    let header_size = mode.overhead() - IPV4_HEADER_SIZE;
+
+    let ip_repr = Ipv4Repr {
+        src_addr: local,
+        dst_addr: remote,
+        next_header: mode.protocol(),
+        payload_len: header_size + packet.len(),
+        hop_limit: TUNNEL_TTL,
+    };
+
+    let mut buffer = vec![0u8; ip_repr.buffer_len() + ip_repr.payload_len];
+    let mut ip_packet = Ipv4Packet::new_unchecked(&mut buffer);
+    ip_repr.emit(&mut ip_packet, &ChecksumCapabilities::default());
+
+    let payload = ip_packet.payload_mut();
+
+    // No checksum, key nor sequence number, the version being 0
+    if mode == TunnelMode::Gre {
+        NetworkEndian::write_u16(&mut payload[2..4], ethertype.into());
+    }
+
+    payload[header_size..].copy_from_slice(packet);
+
+    Some(buffer)
+}
+
+/// Inner IP packet of an outer IPv4 packet received from the remote end
+pub fn decapsulate(mode: TunnelMode, local: Ipv4Address, remote: Ipv4Address, packet: &[u8]) -> Option<Vec<u8>> {
+    let ip_packet = Ipv4Packet::new_checked(packet).ok()?;
+
+    if ip_packet.src_addr() != remote || ip_packet.dst_addr() != local || ip_packet.next_header() != mode.protocol() {
+        return None;
+    }
+
+    let payload = ip_packet.payload();
+
+    let inner = match mode {
+        TunnelMode::Ipip => payload,
+        TunnelMode::Gre => {
+            if payload.len() < GRE_HEADER_SIZE {
+                return None;
+            }
+
+            let flags = NetworkEndian::read_u16(&payload[0..2]);
+            let ethertype = EthernetProtocol::from(NetworkEndian::read_u16(&payload[2..4]));
+
+            // Version 1 is PPTP (RFC 2637)
+            if flags & GRE_VERSION_MASK != 0 || !matches!(ethertype, EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6) {
+                return None;
+            }
+
+            let optional_fields = [GRE_CHECKSUM_PRESENT, GRE_KEY_PRESENT, GRE_SEQUENCE_PRESENT]
+                .iter()
+                .filter(|field| flags & **field != 0)
+                .count();
+
+            payload.get(GRE_HEADER_SIZE + 4 * optional_fields..)?
+        }
+    };
+
+    Some(inner.to_vec())
+}
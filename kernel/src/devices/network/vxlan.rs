@@ -0,0 +1,115 @@
+use crate::devices::network::controller::{DEFAULT_MTU, ETHERNET_HEADER_SIZE};
+use crate::devices::network::statistics::DriverStatistics;
+use crate::random::KernelRng;
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use rand_core::RngCore;
+
+/// UDP port of the VTEPs (RFC 7348, 5)
+pub const VXLAN_PORT: u16 = 4789;
+
+/// The VNI is 24 bits long
+pub const MAX_VNI: u32 = 0xFF_FFFF;
+
+const VXLAN_HEADER_SIZE: usize = 8;
+/// The VNI field is valid (RFC 7348, 5)
+const VXLAN_FLAG_VNI: u8 = 0x08;
+
+/// Outer IPv4 and UDP headers, VXLAN header and inner Ethernet header
+pub const VXLAN_OVERHEAD: usize = 20 + 8 + VXLAN_HEADER_SIZE + ETHERNET_HEADER_SIZE;
+
+/// Frames waiting to be moved to or from the underlying interface
+const QUEUE_SIZE: usize = 64;
+
+/// Virtual Ethernet NIC of a VXLAN interface, the VXLAN task moving its frames to and from the underlying interface
+#[derive(Debug)]
+pub struct VxlanDriver {
+    pub mac: [u8; 6],
+    enabled: bool,
+    /// Frames sent by smoltcp, to encapsulate
+    pub tx_queue: VecDeque<Vec<u8>>,
+    /// Decapsulated frames, to hand to smoltcp
+    pub rx_queue: VecDeque<Vec<u8>>,
+    pub statistics: DriverStatistics,
+}
+
+impl VxlanDriver {
+    /// Driver with a random locally administered MAC address
+    pub fn new() -> Self {
+        let mut mac = [0u8; 6];
+        KernelRng.fill_bytes(&mut mac);
+        mac[0] = (mac[0] & 0xFE) | 0x02;
+
+        Self {
+            mac,
+            enabled: true,
+            tx_queue: VecDeque::new(),
+            rx_queue: VecDeque::new(),
+            statistics: DriverStatistics::default(),
+        }
+    }
+
+    pub fn enable(&mut self, enabled: bool) {
+        self.enabled = enabled;
+
+        if !enabled {
+            self.tx_queue.clear();
+            self.rx_queue.clear();
+        }
+    }
+
+    pub fn queue_tx(&mut self, frame: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        match self.tx_queue.len() < QUEUE_SIZE {
+            true => self.tx_queue.push_back(frame.to_vec()),
+            false => self.statistics.tx_dropped += 1
+        }
+    }
+
+    pub fn queue_rx(&mut self, frame: Vec<u8>) {
+        if !self.enabled {
+            return;
+        }
+
+        match self.rx_queue.len() < QUEUE_SIZE {
+            true => self.rx_queue.push_back(frame),
+            false => self.statistics.rx_missed += 1
+        }
+    }
+}
+
+impl Default for VxlanDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// MTU of a new VXLAN interface, its frames fitting the default MTU of the underlying interface once encapsulated
+pub fn default_mtu() -> usize {
+    DEFAULT_MTU - VXLAN_OVERHEAD
+}
+
+/// UDP payload carrying an Ethernet frame
+pub fn encapsulate(vni: u32, frame: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0u8; VXLAN_HEADER_SIZE + frame.len()];
+
+    packet[0] = VXLAN_FLAG_VNI;
+    NetworkEndian::write_u24(&mut packet[4..7], vni);
+    packet[VXLAN_HEADER_SIZE..].copy_from_slice(frame);
+
+    packet
+}
+
+/// VNI and Ethernet frame of a UDP payload
+pub fn decapsulate(packet: &[u8]) -> Option<(u32, &[u8])> {
+    if packet.len() < VXLAN_HEADER_SIZE + ETHERNET_HEADER_SIZE || packet[0] & VXLAN_FLAG_VNI == 0 {
+        return None;
+    }
+
+    Some((NetworkEndian::read_u24(&packet[4..7]), &packet[VXLAN_HEADER_SIZE..]))
+}
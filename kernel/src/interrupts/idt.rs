@@ -40,7 +40,7 @@ pub static IDT: Lazy<Mutex<InterruptDescriptorTable>> = Lazy::new(|| {
     Mutex::new(idt)
 });
 
-pub static KEYBOARD: Lazy<RwLock<Keyboard<AnyLayout, ScancodeSet1>>> = Lazy::new(|| RwLock::new(Keyboard::new(ScancodeSet1::new(), AnyLayout::Us104Key(Us104Key), HandleControl::Ignore)));
+pub static KEYBOARD: Lazy<RwLock<Keyboard<AnyLayout, ScancodeSet1>>> = Lazy::new(|| RwLock::new(Keyboard::new(ScancodeSet1::new(), AnyLayout::Us104Key(Us104Key), HandleControl::MapLettersToUnicode)));
 
 pub fn init_idt() {
     let idt = IDT.lock();
@@ -112,6 +112,8 @@ extern "x86-interrupt" fn serial_interrupt_handler(_stack_frame: InterruptStackF
             0x0D => DecodedKey::Unicode('\n'),
             0x0A => DecodedKey::Unicode('\n'),
             0x08 => DecodedKey::RawKey(KeyCode::Backspace),
+            0x03 => DecodedKey::Unicode(task::terminal::CTRL_C),
+            0x04 => DecodedKey::Unicode(task::terminal::CTRL_D),
             0x21..=0x7E => DecodedKey::Unicode(scancode as char),
             _ => return
         };
@@ -16,8 +16,10 @@ pub mod memory;
 pub mod task;
 pub mod terminal;
 pub mod clock;
+pub mod random;
 pub mod logger;
 pub mod devices;
+pub mod services;
 
 pub fn init(rsdp: usize, physical_memory_offset: VirtAddr) {
     print!("\t> Initializing GDT... ");
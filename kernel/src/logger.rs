@@ -1,5 +1,7 @@
 use crate::clock::Clock;
 use crate::println;
+use crate::services::syslog::queue_log;
+use alloc::string::ToString;
 use core::fmt::Arguments;
 use goolog::log::Level;
 use yansi::{Color, Paint};
@@ -12,6 +14,10 @@ pub fn print_log(target: &str, level: Level, args: &Arguments) {
         Level::Debug => Color::Blue,
         Level::Trace => Color::Black
     };
-    let timestamp = Clock::format();
+    let timestamp = match Clock::utc_now() {
+        Some(date_time) => date_time.to_string(),
+        None => Clock::format()
+    };
     println!("[{} | {} | {}] {}", timestamp, level.white().bg(color), target, args);
+    queue_log(target, level, args);
 }
\ No newline at end of file
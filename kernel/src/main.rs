@@ -11,6 +11,18 @@ use goolog::init_logger;
 use goolog::log::{set_max_level, Level, LevelFilter};
 use retos_kernel::logger::print_log;
 use retos_kernel::memory::tables::{MAPPER, MEMORY_REGIONS};
+use retos_kernel::services::http::server::http_server;
+use retos_kernel::services::ipfix::export_flows;
+use retos_kernel::services::dhcpv6::delegate_prefixes;
+use retos_kernel::services::tunnel::forward_tunnels;
+use retos_kernel::services::vxlan::forward_vxlans;
+use retos_kernel::services::radvd::advertise_routers;
+use retos_kernel::services::slaac::autoconfigure_addresses;
+use retos_kernel::services::snmp::agent::snmp_agent;
+use retos_kernel::services::sntp::synchronize_clock;
+use retos_kernel::services::ssh::server::ssh_server;
+use retos_kernel::services::telnet::telnet_server;
+use retos_kernel::services::syslog::forward_logs;
 use retos_kernel::task::executor::{run_tasks, spawn_task};
 use retos_kernel::task::terminal;
 use retos_kernel::task::task::Task;
@@ -87,6 +99,18 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
 
     spawn_task(Task::new(String::from("Scan PCI"), async { scanpci().unwrap(); }));
     spawn_task(Task::new(String::from("Terminal"), terminal::handle_keyboard()));
+    spawn_task(Task::new(String::from("IPFIX exporter"), export_flows()));
+    spawn_task(Task::new(String::from("SNMP agent"), snmp_agent()));
+    spawn_task(Task::new(String::from("Syslog forwarder"), forward_logs()));
+    spawn_task(Task::new(String::from("SNTP client"), synchronize_clock()));
+    spawn_task(Task::new(String::from("SLAAC"), autoconfigure_addresses()));
+    spawn_task(Task::new(String::from("Router advertisements"), advertise_routers()));
+    spawn_task(Task::new(String::from("DHCPv6 prefix delegation"), delegate_prefixes()));
+    spawn_task(Task::new(String::from("Tunnels"), forward_tunnels()));
+    spawn_task(Task::new(String::from("VXLAN"), forward_vxlans()));
+    spawn_task(Task::new(String::from("Telnet server"), telnet_server()));
+    spawn_task(Task::new(String::from("SSH server"), ssh_server()));
+    spawn_task(Task::new(String::from("HTTP server"), http_server()));
     run_tasks();
 }
 
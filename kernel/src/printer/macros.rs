@@ -1,5 +1,14 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
 use core::fmt;
+use core::future::Future;
 use crate::printer::buffer::WRITER;
+use crate::task::executor::current_task;
+use crate::task::task::TaskId;
+use spin::Mutex;
+
+/// Output of the tasks printing elsewhere than the screen, such as the commands of remote sessions
+static OUTPUT_CAPTURES: Mutex<BTreeMap<TaskId, String>> = Mutex::new(BTreeMap::new());
 
 #[macro_export]
 macro_rules! println {
@@ -37,11 +46,118 @@ pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
     use x86_64::instructions::interrupts;
 
+    // Interrupt handlers always print to the screen
+    let captured = interrupts::are_enabled() && interrupts::without_interrupts(|| {
+        let Some(task_id) = current_task() else {
+            return false;
+        };
+
+        match OUTPUT_CAPTURES.lock().get_mut(&task_id) {
+            Some(output) => {
+                output.write_fmt(args).unwrap();
+                true
+            },
+            None => false
+        }
+    });
+
+    if !captured {
+        interrupts::without_interrupts(|| {
+            WRITER.write().write_fmt(args).unwrap();
+        });
+    }
+}
+
+/// Keep what the given task prints, until the capture is stopped
+pub fn start_capture(task_id: TaskId) {
+    use x86_64::instructions::interrupts;
+
     interrupts::without_interrupts(|| {
-        WRITER.write().write_fmt(args).unwrap();
+        OUTPUT_CAPTURES.lock().entry(task_id).or_default();
     });
 }
 
+/// Output of the given task since the last call
+pub fn take_captured(task_id: TaskId) -> String {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        OUTPUT_CAPTURES.lock().get_mut(&task_id).map(core::mem::take).unwrap_or_default()
+    })
+}
+
+/// Print to the screen again, returns the output not taken yet
+pub fn stop_capture(task_id: TaskId) -> String {
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        OUTPUT_CAPTURES.lock().remove(&task_id).unwrap_or_default()
+    })
+}
+
+/// Run a future, returning everything it printed instead of writing it to the screen
+pub async fn capture_output<F: Future>(future: F) -> (F::Output, String) {
+    use x86_64::instructions::interrupts;
+
+    let Some(task_id) = current_task() else {
+        return (future.await, String::new());
+    };
+
+    // The capture of the task, if any, resumes afterward
+    let previous_output = interrupts::without_interrupts(|| OUTPUT_CAPTURES.lock().remove(&task_id));
+    let guard = CaptureGuard { task_id, previous_output };
+
+    start_capture(task_id);
+    let result = future.await;
+    let output = take_captured(task_id);
+
+    drop(guard);
+
+    (result, output)
+}
+
+/// Restores the previous capture of a task, even when the capturing future is dropped
+struct CaptureGuard {
+    task_id: TaskId,
+    previous_output: Option<String>,
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        use x86_64::instructions::interrupts;
+
+        stop_capture(self.task_id);
+
+        if let Some(previous_output) = self.previous_output.take() {
+            interrupts::without_interrupts(|| {
+                OUTPUT_CAPTURES.lock().insert(self.task_id, previous_output);
+            });
+        }
+    }
+}
+
+pub fn is_output_captured() -> bool {
+    use x86_64::instructions::interrupts;
+
+    let Some(task_id) = current_task() else {
+        return false;
+    };
+
+    interrupts::without_interrupts(|| {
+        OUTPUT_CAPTURES.lock().contains_key(&task_id)
+    })
+}
+
+/// Writer following the output of the print macros, to render tables and such
+pub struct Output;
+
+impl fmt::Write for Output {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        _print(format_args!("{}", s));
+        Ok(())
+    }
+}
+
 #[doc(hidden)]
 pub fn _clear() {
     use x86_64::instructions::interrupts;
@@ -0,0 +1,109 @@
+use core::arch::x86_64::_rdtsc;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use goolog::warn;
+use rand_core::{CryptoRng, RngCore};
+use spin::Lazy;
+use x86_64::instructions::random::RdRand;
+
+const GOOLOG_TARGET: &str = "RANDOM";
+
+static RDRAND: Lazy<Option<RdRand>> = Lazy::new(RdRand::new);
+
+/// State of the fallback generator, used when the CPU has no RDRAND instruction
+static FALLBACK_STATE: AtomicU64 = AtomicU64::new(0);
+static FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Random number generator backed by the CPU (RDRAND).
+/// Without RDRAND, it falls back to the timestamp counter, which is not suitable for cryptography.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelRng;
+
+/// Random number generator for the keys and nonces, only available with RDRAND
+#[derive(Debug, Clone, Copy)]
+pub struct SecureRng(RdRand);
+
+impl KernelRng {
+    fn fallback_u64() -> u64 {
+        if !FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+            warn!("No RDRAND instruction, random numbers are derived from the timestamp counter");
+        }
+
+        // SplitMix64, with the timestamp counter mixed in
+        let timestamp = unsafe { _rdtsc() };
+        let state = FALLBACK_STATE.fetch_add(0x9E37_79B9_7F4A_7C15 ^ timestamp, Ordering::Relaxed);
+
+        let mut value = state.wrapping_add(0x9E37_79B9_7F4A_7C15 ^ timestamp);
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+}
+
+impl RngCore for KernelRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // RDRAND may fail transiently, it is retried a few times
+        if let Some(rdrand) = *RDRAND {
+            for _ in 0..10 {
+                if let Some(value) = rdrand.get_u64() {
+                    return value;
+                }
+            }
+        }
+
+        KernelRng::fallback_u64()
+    }
+
+    fn fill_bytes(&mut self, destination: &mut [u8]) {
+        for chunk in destination.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, destination: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(destination);
+        Ok(())
+    }
+}
+
+impl SecureRng {
+    /// None when the CPU has no RDRAND instruction
+    pub fn new() -> Option<Self> {
+        (*RDRAND).map(SecureRng)
+    }
+}
+
+impl RngCore for SecureRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // RDRAND may fail transiently, but repeated failures mean the generator is broken (Intel DRNG guide, 5.2.1)
+        for _ in 0..10 {
+            if let Some(value) = self.0.get_u64() {
+                return value;
+            }
+        }
+
+        panic!("RDRAND keeps failing");
+    }
+
+    fn fill_bytes(&mut self, destination: &mut [u8]) {
+        for chunk in destination.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, destination: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(destination);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SecureRng {}
@@ -0,0 +1,833 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::slaac::{ipv6_network, slaac_address, AddressOrigin, AddressState, AutoconfiguredAddress, SLAAC_PREFIX_LENGTH};
+use crate::devices::network::sockets::ServiceSocket;
+use crate::random::KernelRng;
+use crate::services::radvd::{AdvertisedPrefix, RADVD};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use goolog::{debug, info, warn};
+use rand_core::RngCore;
+use smoltcp::iface::{Route, SocketSet};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv6Address, Ipv6Cidr};
+use spin::Mutex;
+use strum::Display;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "DHCPV6";
+
+pub const CLIENT_PORT: u16 = 546;
+pub const SERVER_PORT: u16 = 547;
+
+/// All_DHCP_Relay_Agents_and_Servers (RFC 8415, 7.1)
+const ALL_DHCP_RELAY_AGENTS_AND_SERVERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+// Message types (RFC 8415, 7.3)
+const MSG_SOLICIT: u8 = 1;
+const MSG_ADVERTISE: u8 = 2;
+const MSG_REQUEST: u8 = 3;
+const MSG_RENEW: u8 = 5;
+const MSG_REBIND: u8 = 6;
+const MSG_REPLY: u8 = 7;
+const MSG_RELEASE: u8 = 8;
+
+// Options (RFC 8415, 21)
+const OPTION_CLIENTID: u16 = 1;
+const OPTION_SERVERID: u16 = 2;
+const OPTION_ELAPSED_TIME: u16 = 8;
+const OPTION_STATUS_CODE: u16 = 13;
+const OPTION_IA_PD: u16 = 25;
+const OPTION_IAPREFIX: u16 = 26;
+
+const STATUS_SUCCESS: u16 = 0;
+
+/// DUID based on the link-layer address, with an Ethernet hardware type (RFC 8415, 11.4)
+const DUID_LL: u16 = 3;
+const HARDWARE_TYPE_ETHERNET: u16 = 1;
+
+/// Identifier of the only IA_PD of the client
+const IAID: u32 = 1;
+const IA_PD_HEADER_SIZE: usize = 12;
+const IAPREFIX_SIZE: usize = 25;
+
+/// Initial and maximum retransmission timeouts of each exchange (RFC 8415, 7.6)
+const SOL_TIMEOUT: Duration = Duration::from_secs(1);
+const SOL_MAX_RT: Duration = Duration::from_secs(3600);
+const REQ_TIMEOUT: Duration = Duration::from_secs(1);
+const REQ_MAX_RT: Duration = Duration::from_secs(30);
+const REQ_MAX_RC: u8 = 10;
+const REN_TIMEOUT: Duration = Duration::from_secs(10);
+const REN_MAX_RT: Duration = Duration::from_secs(600);
+const REB_TIMEOUT: Duration = Duration::from_secs(10);
+const REB_MAX_RT: Duration = Duration::from_secs(600);
+
+const INFINITE_LIFETIME: u32 = 0xFFFF_FFFF;
+
+const TICK: Duration = Duration::from_millis(100);
+
+const SOCKET_PACKETS: usize = 4;
+const PACKET_SIZE: usize = 1280;
+
+pub static DHCPV6_CLIENT: Mutex<Dhcpv6Client> = Mutex::new(Dhcpv6Client::new());
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Dhcpv6State {
+    Stopped,
+    /// Looking for a server delegating prefixes
+    Soliciting,
+    /// Asking the chosen server for the prefix it advertised
+    Requesting,
+    Bound,
+    /// Extending the lease with the server which delegated the prefix, from T1
+    Renewing,
+    /// Extending the lease with any server, from T2
+    Rebinding,
+}
+
+/// Prefix delegated by a server
+pub struct Lease {
+    pub server: Ipv6Address,
+    server_id: Vec<u8>,
+    pub prefix: Ipv6Cidr,
+    /// `None` means "forever"
+    pub preferred_until: Option<Instant>,
+    /// `None` means "forever"
+    pub valid_until: Option<Instant>,
+    /// T1, `None` if the lease is never renewed
+    pub renew_at: Option<Instant>,
+    /// T2
+    pub rebind_at: Option<Instant>,
+}
+
+/// Prefix advertised by the server answering the solicitation first
+struct Offer {
+    server_id: Vec<u8>,
+    prefix: Ipv6Cidr,
+}
+
+/// DHCPv6 client requesting a delegated prefix on the WAN interface (RFC 8415),
+/// split into a /64 subnet for each LAN interface
+pub struct Dhcpv6Client {
+    /// Interface the prefix is requested on
+    pub wan: Option<String>,
+    /// Interfaces getting a subnet of the delegated prefix, in order
+    pub lan: Vec<String>,
+    /// Prefix length asked to the server, 0 leaving the choice to it
+    pub prefix_length_hint: u8,
+    pub state: Dhcpv6State,
+    pub lease: Option<Lease>,
+    /// Subnets assigned to the LAN interfaces
+    pub assignments: Vec<(String, Ipv6Cidr)>,
+    offer: Option<Offer>,
+    /// Socket on the WAN interface
+    socket: Option<ServiceSocket>,
+    transaction_id: [u8; 3],
+    exchange_started_at: Instant,
+    next_transmission_at: Instant,
+    retransmission_timeout: Duration,
+    transmissions: u8,
+    /// The LAN interfaces changed, the subnets are assigned again
+    reassign: bool,
+}
+
+/// DHCPv6 message received from a server
+struct Message<'a> {
+    msg_type: u8,
+    transaction_id: [u8; 3],
+    client_id: Option<&'a [u8]>,
+    server_id: Option<&'a [u8]>,
+    /// Only set when the server delegates a prefix
+    ia_pd: Option<IaPd>,
+}
+
+struct IaPd {
+    t1: u32,
+    t2: u32,
+    prefix: Ipv6Cidr,
+    preferred_lifetime: u32,
+    valid_lifetime: u32,
+}
+
+impl Dhcpv6Client {
+    pub const fn new() -> Self {
+        Self {
+            wan: None,
+            lan: Vec::new(),
+            prefix_length_hint: 0,
+            state: Dhcpv6State::Stopped,
+            lease: None,
+            assignments: Vec::new(),
+            offer: None,
+            socket: None,
+            transaction_id: [0; 3],
+            exchange_started_at: Instant::ZERO,
+            next_transmission_at: Instant::ZERO,
+            retransmission_timeout: Duration::ZERO,
+            transmissions: 0,
+            reassign: false,
+        }
+    }
+
+    pub fn start(&mut self, wan: &str, prefix_length_hint: u8) {
+        // Socket of a previous WAN interface
+        self.close_socket();
+
+        self.wan = Some(String::from(wan));
+        self.prefix_length_hint = prefix_length_hint;
+        self.begin_exchange(Dhcpv6State::Soliciting, Clock::now());
+    }
+
+    /// The delegated prefix is released by the next poll
+    pub fn stop(&mut self) {
+        self.state = Dhcpv6State::Stopped;
+        self.offer = None;
+    }
+
+    pub fn add_lan(&mut self, name: &str) {
+        self.lan.push(String::from(name));
+        self.reassign = true;
+    }
+
+    pub fn remove_lan(&mut self, name: &str) {
+        self.lan.retain(|lan| lan != name);
+        self.reassign = true;
+    }
+
+    fn begin_exchange(&mut self, state: Dhcpv6State, now: Instant) {
+        let transaction_id = KernelRng.next_u32().to_be_bytes();
+
+        self.state = state;
+        self.transaction_id.copy_from_slice(&transaction_id[1..]);
+        self.exchange_started_at = now;
+        self.next_transmission_at = now;
+        self.retransmission_timeout = Duration::ZERO;
+        self.transmissions = 0;
+    }
+
+    fn close_socket(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(wan) = self.wan.clone() else {
+            return;
+        };
+
+        // Nothing left to release nor to close
+        if self.state == Dhcpv6State::Stopped && self.lease.is_none() && self.socket.is_none() {
+            return;
+        }
+
+        let now = Clock::now();
+
+        if self.lease.as_ref().is_some_and(|lease| lease.valid_until.is_some_and(|valid_until| valid_until <= now)) {
+            let lease = self.lease.take().unwrap();
+            warn!("Delegated prefix {} expired", lease.prefix);
+            self.unassign();
+
+            if self.state != Dhcpv6State::Stopped {
+                self.begin_exchange(Dhcpv6State::Soliciting, now);
+            }
+        }
+
+        if self.reassign {
+            self.reassign = false;
+            self.unassign();
+            self.assign(now);
+        }
+
+        let Some(device) = NETWORK_MANAGER.lock().interfaces.get(&wan).cloned() else {
+            return;
+        };
+
+        // The messages are sent from the link-local address (RFC 8415, 13.1)
+        let (mac, is_ready) = interrupts::without_interrupts(|| {
+            let device = device.lock();
+
+            let has_link_local = device.autoconfigured_addresses
+                .iter()
+                .any(|address| address.origin == AddressOrigin::LinkLocal && address.state == AddressState::Preferred);
+
+            (device.mac(), device.network_controller.enabled && has_link_local)
+        });
+
+        // The DUID is built from the MAC address
+        let Some(mac) = mac else {
+            return;
+        };
+
+        let client_id = duid(mac);
+        let socket_set = device.lock().sockets.clone();
+
+        // The WAN interface was deleted and created again
+        if self.socket.as_ref().is_some_and(|socket| !socket.is_in(&socket_set)) {
+            self.close_socket();
+        }
+
+        if self.state == Dhcpv6State::Stopped {
+            self.release(&socket_set, &client_id, now);
+            return;
+        }
+
+        if !is_ready {
+            return;
+        }
+
+        let messages = {
+            let mut sockets = socket_set.lock();
+
+            let handle = match &self.socket {
+                Some(socket) => socket.handle,
+                None => {
+                    let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+                    let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+                    let mut socket = Socket::new(rx_buffer, tx_buffer);
+                    socket.bind(CLIENT_PORT).unwrap();
+
+                    let socket = ServiceSocket::add(&socket_set, &mut sockets, socket);
+                    let handle = socket.handle;
+                    self.socket = Some(socket);
+                    handle
+                }
+            };
+
+            let socket = sockets.get_mut::<Socket>(handle);
+            let mut messages = Vec::new();
+            let mut buffer = [0u8; PACKET_SIZE];
+
+            while let Ok((length, metadata)) = socket.recv_slice(&mut buffer) {
+                if let IpAddress::Ipv6(server) = metadata.endpoint.addr {
+                    messages.push((buffer[..length].to_vec(), server));
+                }
+            }
+
+            messages
+        };
+
+        for (message, server) in messages {
+            if let Some(message) = Message::parse(&message) {
+                self.handle_message(message, server, &client_id, now);
+            }
+        }
+
+        match self.state {
+            Dhcpv6State::Bound if self.lease.as_ref().is_some_and(|lease| lease.renew_at.is_some_and(|renew_at| renew_at <= now)) => {
+                debug!("Renewing the lease of the delegated prefix");
+                self.begin_exchange(Dhcpv6State::Renewing, now);
+            },
+            Dhcpv6State::Renewing if self.lease.as_ref().is_some_and(|lease| lease.rebind_at.is_some_and(|rebind_at| rebind_at <= now)) => {
+                warn!("No reply from the server which delegated the prefix, rebinding");
+                self.begin_exchange(Dhcpv6State::Rebinding, now);
+            },
+            Dhcpv6State::Requesting if self.transmissions >= REQ_MAX_RC => {
+                warn!("No reply to the request, soliciting again");
+                self.offer = None;
+                self.begin_exchange(Dhcpv6State::Soliciting, now);
+            },
+            _ => {}
+        }
+
+        if self.state == Dhcpv6State::Bound || now < self.next_transmission_at {
+            return;
+        }
+
+        let lease_prefix = self.lease.as_ref().map(|lease| lease.prefix);
+        let hint = (self.prefix_length_hint != 0).then(|| Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, self.prefix_length_hint));
+
+        let (msg_type, server_id, prefix, initial_timeout, max_timeout) = match self.state {
+            Dhcpv6State::Soliciting => (MSG_SOLICIT, None, hint, SOL_TIMEOUT, SOL_MAX_RT),
+            Dhcpv6State::Requesting => match &self.offer {
+                Some(offer) => (MSG_REQUEST, Some(offer.server_id.as_slice()), Some(offer.prefix), REQ_TIMEOUT, REQ_MAX_RT),
+                None => return
+            },
+            Dhcpv6State::Renewing => match &self.lease {
+                Some(lease) => (MSG_RENEW, Some(lease.server_id.as_slice()), lease_prefix, REN_TIMEOUT, REN_MAX_RT),
+                None => return
+            },
+            Dhcpv6State::Rebinding => (MSG_REBIND, None, lease_prefix, REB_TIMEOUT, REB_MAX_RT),
+            Dhcpv6State::Stopped | Dhcpv6State::Bound => return
+        };
+
+        let message = self.message(msg_type, &client_id, server_id, prefix, now);
+
+        let Some(socket) = &self.socket else {
+            return;
+        };
+
+        let mut sockets = socket_set.lock();
+        let socket = sockets.get_mut::<Socket>(socket.handle);
+
+        if socket.send_slice(&message, IpEndpoint::new(IpAddress::Ipv6(ALL_DHCP_RELAY_AGENTS_AND_SERVERS), SERVER_PORT)).is_err() {
+            debug!("Message dropped, the socket is full");
+        }
+
+        // Doubled after each transmission, with a random factor of +/- 10% (RFC 8415, 15)
+        let timeout = match self.transmissions {
+            0 => initial_timeout,
+            _ => (self.retransmission_timeout * 2).min(max_timeout)
+        };
+
+        let jitter = timeout.total_millis() / 10;
+        let timeout = Duration::from_millis(timeout.total_millis() - jitter + KernelRng.next_u64() % (2 * jitter + 1));
+
+        self.transmissions = self.transmissions.saturating_add(1);
+        self.retransmission_timeout = timeout;
+        self.next_transmission_at = now + timeout;
+    }
+
+    fn handle_message(&mut self, message: Message, server: Ipv6Address, client_id: &[u8], now: Instant) {
+        if message.transaction_id != self.transaction_id || message.client_id != Some(client_id) {
+            return;
+        }
+
+        let Some(server_id) = message.server_id else {
+            return;
+        };
+
+        match (self.state, message.msg_type) {
+            (Dhcpv6State::Soliciting, MSG_ADVERTISE) => match message.ia_pd {
+                // The first advertisement is taken, without waiting for the ones of other servers
+                Some(ia_pd) => {
+                    debug!("Server {} offers {}", server, ia_pd.prefix);
+
+                    self.offer = Some(Offer {
+                        server_id: server_id.to_vec(),
+                        prefix: ia_pd.prefix,
+                    });
+
+                    self.begin_exchange(Dhcpv6State::Requesting, now);
+                },
+                None => debug!("Server {} has no prefix to delegate", server)
+            },
+            (Dhcpv6State::Requesting | Dhcpv6State::Renewing | Dhcpv6State::Rebinding, MSG_REPLY) => match message.ia_pd {
+                Some(ia_pd) => self.bind(server, server_id, ia_pd, now),
+                None if self.state == Dhcpv6State::Requesting => {
+                    warn!("Server {} did not delegate a prefix, soliciting again", server);
+                    self.offer = None;
+                    self.begin_exchange(Dhcpv6State::Soliciting, now);
+                },
+                // Asked again until the lease expires
+                None => debug!("Server {} did not extend the lease", server)
+            },
+            _ => {}
+        }
+    }
+
+    fn bind(&mut self, server: Ipv6Address, server_id: &[u8], ia_pd: IaPd, now: Instant) {
+        let IaPd { t1, t2, prefix, preferred_lifetime, valid_lifetime } = ia_pd;
+
+        // Left to the client, at 0.5 and 0.8 times the preferred lifetime (RFC 8415, 21.21)
+        let (t1, t2) = match (t1, t2) {
+            (0, 0) => (preferred_lifetime / 2, preferred_lifetime / 5 * 4),
+            (t1, t2) => (t1, t2)
+        };
+
+        if self.lease.as_ref().is_some_and(|lease| lease.prefix != prefix) {
+            self.unassign();
+        }
+
+        match self.state {
+            Dhcpv6State::Requesting => info!("Delegated prefix {} from {}", prefix, server),
+            _ => debug!("Lease of {} extended", prefix)
+        }
+
+        self.lease = Some(Lease {
+            server,
+            server_id: server_id.to_vec(),
+            prefix,
+            preferred_until: lifetime_end(now, preferred_lifetime),
+            valid_until: lifetime_end(now, valid_lifetime),
+            renew_at: lifetime_end(now, t1),
+            rebind_at: lifetime_end(now, t2),
+        });
+
+        self.state = Dhcpv6State::Bound;
+        self.offer = None;
+        self.assign(now);
+    }
+
+    /// Assign a /64 of the delegated prefix to each LAN interface, along with its route and its advertisement.
+    /// Refreshes the lifetimes of the subnets already assigned.
+    fn assign(&mut self, now: Instant) {
+        let Some(lease) = &self.lease else {
+            return;
+        };
+
+        let prefix_len = lease.prefix.prefix_len();
+
+        if prefix_len > SLAAC_PREFIX_LENGTH {
+            warn!("Delegated prefix {} cannot be split into /{} subnets", lease.prefix, SLAAC_PREFIX_LENGTH);
+            return;
+        }
+
+        let subnet_count = 1u64.checked_shl((SLAAC_PREFIX_LENGTH - prefix_len) as u32).unwrap_or(u64::MAX);
+        let mut subnets = Vec::new();
+
+        for (index, name) in self.lan.iter().enumerate() {
+            if index as u64 >= subnet_count {
+                warn!("Delegated prefix {} has no subnet left for {}", lease.prefix, name);
+                break;
+            }
+
+            subnets.push((name.clone(), subnet(lease.prefix, index as u64)));
+        }
+
+        let network_manager = NETWORK_MANAGER.lock();
+
+        for (name, subnet) in &subnets {
+            let Some(device) = network_manager.interfaces.get(name) else {
+                continue;
+            };
+
+            // The network interrupts lock the device as well
+            interrupts::without_interrupts(|| {
+                let mut device = device.lock();
+                assign_subnet(name, &mut device, *subnet, lease.preferred_until, lease.valid_until);
+            });
+        }
+
+        drop(network_manager);
+
+        // Advertised to the hosts of the links when radvd runs on them
+        let mut radvd = RADVD.lock();
+
+        for (name, subnet) in &subnets {
+            let advertised = radvd.interface(name);
+            advertised.prefixes.retain(|prefix| prefix.cidr != *subnet);
+
+            advertised.prefixes.push(AdvertisedPrefix {
+                cidr: *subnet,
+                valid_lifetime: remaining_lifetime(now, lease.valid_until),
+                preferred_lifetime: remaining_lifetime(now, lease.preferred_until),
+            });
+
+            advertised.advertise_now();
+        }
+
+        self.assignments = subnets;
+    }
+
+    /// Remove the subnets from the LAN interfaces
+    fn unassign(&mut self) {
+        let assignments = core::mem::take(&mut self.assignments);
+        let network_manager = NETWORK_MANAGER.lock();
+
+        for (name, subnet) in &assignments {
+            let Some(device) = network_manager.interfaces.get(name) else {
+                continue;
+            };
+
+            interrupts::without_interrupts(|| {
+                let mut device = device.lock();
+                unassign_subnet(name, &mut device, *subnet);
+            });
+        }
+
+        drop(network_manager);
+
+        let mut radvd = RADVD.lock();
+
+        for (name, subnet) in &assignments {
+            if let Some(advertised) = radvd.interfaces.get_mut(name) {
+                advertised.prefixes.retain(|prefix| prefix.cidr != *subnet);
+            }
+        }
+    }
+
+    /// Give the delegated prefix back to the server, then close the socket once the release left.
+    /// The release is only sent once, the server reclaiming the prefix at the end of its lifetime anyway.
+    fn release(&mut self, sockets: &Mutex<SocketSet<'static>>, client_id: &[u8], now: Instant) {
+        let handle = self.socket.as_ref().map(|socket| socket.handle);
+
+        let Some(lease) = self.lease.take() else {
+            if handle.is_some_and(|handle| sockets.lock().get::<Socket>(handle).send_queue() == 0) {
+                self.close_socket();
+                info!("Stopped");
+            }
+
+            return;
+        };
+
+        self.unassign();
+
+        info!("Releasing {}", lease.prefix);
+
+        let Some(handle) = handle else {
+            return;
+        };
+
+        self.begin_exchange(Dhcpv6State::Stopped, now);
+        let message = self.message(MSG_RELEASE, client_id, Some(&lease.server_id), Some(lease.prefix), now);
+
+        let mut sockets = sockets.lock();
+        let socket = sockets.get_mut::<Socket>(handle);
+        let _ = socket.send_slice(&message, IpEndpoint::new(IpAddress::Ipv6(ALL_DHCP_RELAY_AGENTS_AND_SERVERS), SERVER_PORT));
+    }
+
+    fn message(&self, msg_type: u8, client_id: &[u8], server_id: Option<&[u8]>, prefix: Option<Ipv6Cidr>, now: Instant) -> Vec<u8> {
+        let mut message = vec![msg_type];
+        message.extend_from_slice(&self.transaction_id);
+
+        push_option(&mut message, OPTION_CLIENTID, client_id);
+
+        if let Some(server_id) = server_id {
+            push_option(&mut message, OPTION_SERVERID, server_id);
+        }
+
+        // Hundredths of a second since the first message of the exchange
+        let elapsed_time = ((now - self.exchange_started_at).total_millis() / 10).min(0xFFFF) as u16;
+        push_option(&mut message, OPTION_ELAPSED_TIME, &elapsed_time.to_be_bytes());
+
+        // T1 and T2 are left to the server
+        let mut ia_pd = vec![0u8; IA_PD_HEADER_SIZE];
+        NetworkEndian::write_u32(&mut ia_pd[0..4], IAID);
+
+        // The lifetimes are left to the server as well
+        if let Some(prefix) = prefix {
+            let mut ia_prefix = vec![0u8; IAPREFIX_SIZE];
+            ia_prefix[8] = prefix.prefix_len();
+            ia_prefix[9..25].copy_from_slice(&prefix.address().octets());
+            push_option(&mut ia_pd, OPTION_IAPREFIX, &ia_prefix);
+        }
+
+        push_option(&mut message, OPTION_IA_PD, &ia_pd);
+
+        message
+    }
+}
+
+impl Default for Dhcpv6Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Message<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let mut message = Message {
+            msg_type: data[0],
+            transaction_id: [data[1], data[2], data[3]],
+            client_id: None,
+            server_id: None,
+            ia_pd: None,
+        };
+
+        let mut status = STATUS_SUCCESS;
+
+        for (code, value) in options(&data[4..]) {
+            match code {
+                OPTION_CLIENTID => message.client_id = Some(value),
+                OPTION_SERVERID => message.server_id = Some(value),
+                OPTION_STATUS_CODE if value.len() >= 2 => status = NetworkEndian::read_u16(&value[0..2]),
+                OPTION_IA_PD if message.ia_pd.is_none() => message.ia_pd = IaPd::parse(value),
+                _ => {}
+            }
+        }
+
+        if status != STATUS_SUCCESS {
+            message.ia_pd = None;
+        }
+
+        Some(message)
+    }
+}
+
+impl IaPd {
+    /// `None` unless the IA_PD holds a usable prefix
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < IA_PD_HEADER_SIZE || NetworkEndian::read_u32(&data[0..4]) != IAID {
+            return None;
+        }
+
+        let t1 = NetworkEndian::read_u32(&data[4..8]);
+        let t2 = NetworkEndian::read_u32(&data[8..12]);
+
+        // Invalid timers, the IA_PD is ignored (RFC 8415, 21.21)
+        if t1 > t2 && t2 != 0 {
+            return None;
+        }
+
+        let mut prefix = None;
+
+        for (code, value) in options(&data[IA_PD_HEADER_SIZE..]) {
+            match code {
+                OPTION_STATUS_CODE if value.len() >= 2 && NetworkEndian::read_u16(&value[0..2]) != STATUS_SUCCESS => return None,
+                OPTION_IAPREFIX if value.len() >= IAPREFIX_SIZE && prefix.is_none() => {
+                    let preferred_lifetime = NetworkEndian::read_u32(&value[0..4]);
+                    let valid_lifetime = NetworkEndian::read_u32(&value[4..8]);
+                    let prefix_len = value[8];
+                    let address = Ipv6Address::from(<[u8; 16]>::try_from(&value[9..25]).unwrap());
+
+                    // Withdrawn or invalid prefix (RFC 8415, 18.2.10.1)
+                    if valid_lifetime == 0 || preferred_lifetime > valid_lifetime || prefix_len > 128 {
+                        continue;
+                    }
+
+                    prefix = Some((ipv6_network(Ipv6Cidr::new(address, prefix_len)), preferred_lifetime, valid_lifetime));
+                },
+                _ => {}
+            }
+        }
+
+        let (prefix, preferred_lifetime, valid_lifetime) = prefix?;
+
+        Some(IaPd {
+            t1,
+            t2,
+            prefix,
+            preferred_lifetime,
+            valid_lifetime,
+        })
+    }
+}
+
+/// Code and value of the options of a message or of an option
+fn options(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> + '_ {
+    core::iter::from_fn(move || {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let code = NetworkEndian::read_u16(&data[0..2]);
+        let length = NetworkEndian::read_u16(&data[2..4]) as usize;
+        let value = data.get(4..4 + length)?;
+
+        data = &data[4 + length..];
+        Some((code, value))
+    })
+}
+
+fn push_option(buffer: &mut Vec<u8>, code: u16, value: &[u8]) {
+    buffer.extend_from_slice(&code.to_be_bytes());
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+/// DUID of the client, built from the MAC address of the WAN interface
+fn duid(mac: EthernetAddress) -> Vec<u8> {
+    let mut duid = Vec::with_capacity(10);
+    duid.extend_from_slice(&DUID_LL.to_be_bytes());
+    duid.extend_from_slice(&HARDWARE_TYPE_ETHERNET.to_be_bytes());
+    duid.extend_from_slice(mac.as_bytes());
+    duid
+}
+
+/// The index-th /64 of the prefix
+fn subnet(prefix: Ipv6Cidr, index: u64) -> Ipv6Cidr {
+    let mut octets = prefix.address().octets();
+    let network = NetworkEndian::read_u64(&octets[..8]) | index;
+    NetworkEndian::write_u64(&mut octets[..8], network);
+
+    Ipv6Cidr::new(Ipv6Address::from(octets), SLAAC_PREFIX_LENGTH)
+}
+
+/// Configure the address of the interface in the subnet and the route to its hosts, or refresh their lifetimes
+fn assign_subnet(name: &str, device: &mut NetworkDevice<'static>, subnet: Ipv6Cidr, preferred_until: Option<Instant>, valid_until: Option<Instant>) {
+    let Some(mac) = device.mac() else {
+        return;
+    };
+
+    let cidr = Ipv6Cidr::new(slaac_address(subnet.address(), mac.0), SLAAC_PREFIX_LENGTH);
+
+    match device.autoconfigured_addresses.iter_mut().find(|address| address.cidr == cidr) {
+        Some(address) => {
+            address.preferred_until = preferred_until;
+            address.valid_until = valid_until;
+
+            if address.state == AddressState::Deprecated {
+                info!("{}: {} is preferred again", name, cidr);
+                address.state = AddressState::Preferred;
+            }
+        },
+        None => {
+            if !device.add_ip_address(IpCidr::Ipv6(cidr)) {
+                warn!("{}: no room left for {}", name, cidr);
+                return;
+            }
+
+            info!("{}: {} assigned from the delegated prefix", name, cidr);
+
+            device.autoconfigured_addresses.push(AutoconfiguredAddress {
+                cidr,
+                origin: AddressOrigin::Delegated,
+                state: AddressState::Preferred,
+                tentative_until: None,
+                preferred_until,
+                valid_until,
+            });
+        }
+    }
+
+    device.interface
+        .routes_mut()
+        .update(|routes| {
+            routes.retain(|route| route.cidr != IpCidr::Ipv6(subnet));
+
+            let route = Route {
+                cidr: IpCidr::Ipv6(subnet),
+                via_router: IpAddress::Ipv6(Ipv6Address::UNSPECIFIED),
+                preferred_until,
+                expires_at: valid_until,
+            };
+
+            if routes.push(route).is_err() {
+                warn!("{}: no room left for the route to {}", name, subnet);
+            }
+        });
+}
+
+fn unassign_subnet(name: &str, device: &mut NetworkDevice<'static>, subnet: Ipv6Cidr) {
+    let addresses: Vec<_> = device.autoconfigured_addresses
+        .iter()
+        .filter(|address| address.origin == AddressOrigin::Delegated && subnet.contains_addr(&address.cidr.address()))
+        .map(|address| address.cidr)
+        .collect();
+
+    for cidr in addresses {
+        device.remove_ip_address(IpCidr::Ipv6(cidr));
+        info!("{}: {} removed", name, cidr);
+    }
+
+    device.autoconfigured_addresses.retain(|address| address.origin != AddressOrigin::Delegated || !subnet.contains_addr(&address.cidr.address()));
+
+    device.interface
+        .routes_mut()
+        .update(|routes| routes.retain(|route| route.cidr != IpCidr::Ipv6(subnet)));
+}
+
+/// `None` for an infinite lifetime
+fn lifetime_end(now: Instant, lifetime: u32) -> Option<Instant> {
+    match lifetime {
+        INFINITE_LIFETIME => None,
+        lifetime => Some(now + Duration::from_secs(lifetime as u64))
+    }
+}
+
+/// Lifetime left, in the encoding of the advertisements
+fn remaining_lifetime(now: Instant, until: Option<Instant>) -> Duration {
+    match until {
+        None => Duration::from_secs(INFINITE_LIFETIME as u64),
+        Some(until) if until > now => until - now,
+        Some(_) => Duration::ZERO
+    }
+}
+
+/// Task requesting and renewing the delegated prefix
+pub async fn delegate_prefixes() {
+    loop {
+        Timer::after(TICK).await;
+        DHCPV6_CLIENT.lock().poll();
+    }
+}
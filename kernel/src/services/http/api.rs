@@ -0,0 +1,296 @@
+use crate::clock::Clock;
+use crate::printer::macros::capture_output;
+use crate::terminal::commands::ip::address::{ip_address_add, ip_address_delete, DadMode};
+use crate::terminal::commands::ip::interface::interfaces;
+use crate::terminal::commands::ip::route::{ip_route_add, ip_route_delete, routes};
+use crate::terminal::commands::ping::{ping, PingOptions};
+use crate::terminal::commands::top::memory_usage;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address};
+
+pub const STATUS_OK: u16 = 200;
+pub const STATUS_CREATED: u16 = 201;
+pub const STATUS_BAD_REQUEST: u16 = 400;
+pub const STATUS_UNAUTHORIZED: u16 = 401;
+pub const STATUS_NOT_FOUND: u16 = 404;
+pub const STATUS_METHOD_NOT_ALLOWED: u16 = 405;
+pub const STATUS_PAYLOAD_TOO_LARGE: u16 = 413;
+
+/// JSON response, along with its status code
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &ErrorResponse { error: message })
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+}
+
+#[derive(Serialize)]
+struct InterfaceResponse {
+    name: String,
+    nic: String,
+    mac: String,
+    addresses: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RouteResponse {
+    interface: String,
+    cidr: String,
+    gateway: String,
+    expires_at: Option<String>,
+    preferred_until: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PingResponse {
+    transmitted: u16,
+    received: u16,
+    round_trip_times_ms: Vec<i64>,
+}
+
+#[derive(Serialize)]
+struct TopResponse {
+    ram_percentage: f32,
+    ram_kib: usize,
+}
+
+#[derive(Serialize)]
+struct UptimeResponse {
+    uptime_ms: i64,
+    uptime: String,
+    utc: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AddressRequest {
+    address: String,
+    /// Duplicate address detection: off, refuse or flag
+    #[serde(default)]
+    dad: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RouteRequest {
+    interface: String,
+    cidr: String,
+    #[serde(default)]
+    gateway: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PingRequest {
+    address: String,
+    #[serde(default = "default_ping_count")]
+    count: u16,
+    #[serde(default = "default_ping_timeout")]
+    timeout: u64,
+    #[serde(default = "default_ping_size")]
+    size: u16,
+    #[serde(default = "default_ping_ttl")]
+    ttl: u8,
+}
+
+/// Same defaults as the ping command
+fn default_ping_count() -> u16 {
+    4
+}
+
+fn default_ping_timeout() -> u64 {
+    2
+}
+
+fn default_ping_size() -> u16 {
+    56
+}
+
+fn default_ping_ttl() -> u8 {
+    64
+}
+
+/// Route a request to its endpoint
+pub async fn handle_request(method: &str, path: &str, body: &[u8]) -> Response {
+    let segments: Vec<&str> = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    let result = match (method, segments.as_slice()) {
+        ("GET", ["api", "interfaces"]) => get_interfaces(),
+        ("POST", ["api", "interfaces", name, "addresses"]) => add_address(name, body).await,
+        ("DELETE", ["api", "interfaces", name, "addresses"]) => delete_address(name, body),
+        ("GET", ["api", "routes"]) => get_routes(),
+        ("POST", ["api", "routes"]) => add_route(body),
+        ("DELETE", ["api", "routes"]) => delete_route(body),
+        ("POST", ["api", "ping"]) => run_ping(body).await,
+        ("GET", ["api", "top"]) => get_top(),
+        ("GET", ["api", "uptime"]) => get_uptime(),
+        (_, ["api", "interfaces" | "routes" | "ping" | "top" | "uptime"] | ["api", "interfaces", _, "addresses"]) => {
+            Err(Response::error(STATUS_METHOD_NOT_ALLOWED, "Method not allowed"))
+        },
+        _ => Err(Response::error(STATUS_NOT_FOUND, "Not found"))
+    };
+
+    result.unwrap_or_else(|response| response)
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, Response> {
+    serde_json::from_slice(body).map_err(|error| Response::error(STATUS_BAD_REQUEST, &error.to_string()))
+}
+
+fn parse<T: FromStr>(value: &str, what: &str) -> Result<T, Response> {
+    T::from_str(value).map_err(|_| Response::error(STATUS_BAD_REQUEST, &format!("Invalid {} \"{}\"", what, value)))
+}
+
+/// Checks the interface exists, as the command arguments do
+fn check_interface(name: &str) -> Result<(), Response> {
+    match interfaces().iter().any(|interface| interface.name == name) {
+        true => Ok(()),
+        false => Err(Response::error(STATUS_NOT_FOUND, "Interface not found"))
+    }
+}
+
+fn get_interfaces() -> Result<Response, Response> {
+    let interfaces: Vec<InterfaceResponse> = interfaces()
+        .into_iter()
+        .map(|interface| InterfaceResponse {
+            name: interface.name,
+            nic: interface.nic_name,
+            mac: interface.mac,
+            addresses: interface.addresses.iter().map(|address| address.to_string()).collect(),
+        })
+        .collect();
+
+    Ok(Response::json(STATUS_OK, &interfaces))
+}
+
+async fn add_address(name: &str, body: &[u8]) -> Result<Response, Response> {
+    let request: AddressRequest = parse_body(body)?;
+    let address: IpCidr = parse(&request.address, "address")?;
+    let dad: DadMode = match &request.dad {
+        Some(dad) => parse(dad, "duplicate address detection mode")?,
+        None => DadMode::Off
+    };
+    check_interface(name)?;
+
+    ip_address_add(address, name, dad).await.map_err(|error| Response::error(STATUS_BAD_REQUEST, &error.to_string()))?;
+
+    Ok(Response::json(STATUS_CREATED, &request.address))
+}
+
+fn delete_address(name: &str, body: &[u8]) -> Result<Response, Response> {
+    let request: AddressRequest = parse_body(body)?;
+    let address: IpCidr = parse(&request.address, "address")?;
+    check_interface(name)?;
+
+    ip_address_delete(address, name).map_err(|error| Response::error(STATUS_NOT_FOUND, &error.to_string()))?;
+
+    Ok(Response::json(STATUS_OK, &request.address))
+}
+
+fn get_routes() -> Result<Response, Response> {
+    let routes: Vec<RouteResponse> = routes()
+        .into_iter()
+        .map(|(interface, route)| RouteResponse {
+            interface,
+            cidr: route.cidr.to_string(),
+            gateway: route.via_router.to_string(),
+            expires_at: route.expires_at.map(Clock::format_instant),
+            preferred_until: route.preferred_until.map(Clock::format_instant),
+        })
+        .collect();
+
+    Ok(Response::json(STATUS_OK, &routes))
+}
+
+fn add_route(body: &[u8]) -> Result<Response, Response> {
+    let request: RouteRequest = parse_body(body)?;
+    let cidr: IpCidr = parse(&request.cidr, "CIDR")?;
+
+    // Same default gateway as the route command
+    let gateway = match &request.gateway {
+        Some(gateway) => parse(gateway, "gateway")?,
+        None => IpAddress::Ipv4(Ipv4Address::UNSPECIFIED)
+    };
+
+    check_interface(&request.interface)?;
+
+    ip_route_add(cidr, &request.interface, gateway).map_err(|error| Response::error(STATUS_BAD_REQUEST, &error.to_string()))?;
+
+    Ok(Response::json(STATUS_CREATED, &request.cidr))
+}
+
+fn delete_route(body: &[u8]) -> Result<Response, Response> {
+    let request: RouteRequest = parse_body(body)?;
+    let cidr: IpCidr = parse(&request.cidr, "CIDR")?;
+    check_interface(&request.interface)?;
+
+    ip_route_delete(cidr, &request.interface).map_err(|error| Response::error(STATUS_NOT_FOUND, &error.to_string()))?;
+
+    Ok(Response::json(STATUS_OK, &request.cidr))
+}
+
+async fn run_ping(body: &[u8]) -> Result<Response, Response> {
+    let request: PingRequest = parse_body(body)?;
+    let address: IpAddress = parse(&request.address, "address")?;
+
+    let options = PingOptions {
+        count: request.count,
+        timeout: Duration::from_secs(request.timeout),
+        size: request.size,
+        ttl: request.ttl,
+        ..PingOptions::default()
+    };
+
+    // The replies are printed by the command, they are not shown on the console
+    let (result, _) = capture_output(ping(address, &options)).await;
+
+    let statistics = result.map_err(|error| Response::error(STATUS_BAD_REQUEST, &error.to_string()))?;
+
+    Ok(Response::json(STATUS_OK, &PingResponse {
+        transmitted: statistics.transmitted,
+        received: statistics.received,
+        round_trip_times_ms: statistics.round_trip_times,
+    }))
+}
+
+fn get_top() -> Result<Response, Response> {
+    let memory_usage = memory_usage();
+
+    Ok(Response::json(STATUS_OK, &TopResponse {
+        ram_percentage: memory_usage.percentage,
+        ram_kib: memory_usage.size_kib,
+    }))
+}
+
+fn get_uptime() -> Result<Response, Response> {
+    Ok(Response::json(STATUS_OK, &UptimeResponse {
+        uptime_ms: Clock::now().total_millis(),
+        uptime: Clock::format(),
+        utc: Clock::utc_now().map(|date_time| date_time.to_string()),
+    }))
+}
@@ -0,0 +1,328 @@
+use crate::clock::{Clock, Timer};
+use crate::services::http::api::{handle_request, Response, STATUS_BAD_REQUEST, STATUS_PAYLOAD_TOO_LARGE, STATUS_UNAUTHORIZED};
+use crate::services::tcp::{SessionGuard, SessionInfo, TcpConnection, TcpListeners};
+use crate::task::executor::spawn_task;
+use crate::task::task::Task;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use goolog::debug;
+use smoltcp::time::Duration;
+use spin::{Lazy, Mutex};
+
+const GOOLOG_TARGET: &str = "HTTP";
+
+pub const HTTP_PORT: u16 = 80;
+
+const MAX_CONNECTIONS: usize = 4;
+const MAX_REQUEST_SIZE: usize = 16384;
+
+const RX_BUFFER_SIZE: usize = 4096;
+const TX_BUFFER_SIZE: usize = 8192;
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+const CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Kept alive connections are closed after this long without a request
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub static HTTP_SERVER: Lazy<Mutex<HttpServer>> = Lazy::new(|| Mutex::new(HttpServer::new()));
+
+pub struct HttpServer {
+    pub port: Option<u16>,
+    pub connections: BTreeMap<u64, SessionInfo>,
+    pub served_requests: u64,
+    /// Bearer token the requests must carry, the server not starting without one
+    pub token: Option<String>,
+    listeners: TcpListeners,
+    next_connection_id: u64,
+}
+
+impl HttpServer {
+    pub fn new() -> Self {
+        Self {
+            port: None,
+            connections: BTreeMap::new(),
+            served_requests: 0,
+            token: None,
+            listeners: TcpListeners::new(RX_BUFFER_SIZE, TX_BUFFER_SIZE),
+            next_connection_id: 0,
+        }
+    }
+
+    /// Start listening on the given port, or stop the server and close the connections
+    pub fn set_port(&mut self, port: Option<u16>) {
+        self.port = port;
+        self.listeners.close();
+
+        if port.is_none() {
+            self.connections.clear();
+        }
+    }
+
+    /// Listen on every interface, and hand the accepted connections to new tasks
+    fn poll(&mut self) {
+        let Some(port) = self.port else {
+            return;
+        };
+
+        // New connections are refused once the connections are all taken
+        if self.connections.len() >= MAX_CONNECTIONS {
+            if self.listeners.is_listening() {
+                debug!("Maximum number of connections reached");
+                self.listeners.close();
+            }
+            return;
+        }
+
+        for connection in self.listeners.accept(port) {
+            let id = self.next_connection_id;
+            self.next_connection_id += 1;
+
+            let task_name = match connection.remote_endpoint {
+                Some(remote_endpoint) => {
+                    debug!("Connection {} opened from {} on {}", id, remote_endpoint, connection.interface_name);
+                    format!("HTTP connection {}", remote_endpoint)
+                },
+                None => {
+                    debug!("Connection {} opened on {}", id, connection.interface_name);
+                    String::from("HTTP connection")
+                }
+            };
+
+            self.connections.insert(id, SessionInfo::new(&connection));
+
+            spawn_task(Task::new(task_name, run_connection(id, connection)));
+        }
+    }
+
+    fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return false;
+        };
+
+        let Some(candidate) = authorization.and_then(|authorization| authorization.strip_prefix("Bearer ")) else {
+            return false;
+        };
+
+        // Constant time comparison
+        candidate.len() == token.len()
+            && candidate.bytes().zip(token.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+    }
+}
+
+impl Default for HttpServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Request line and headers of a request, the body follows them
+struct RequestHead {
+    method: String,
+    path: String,
+    content_length: usize,
+    authorization: Option<String>,
+    keep_alive: bool,
+    /// Length of the head, including the empty line
+    length: usize,
+}
+
+enum ParseResult {
+    Complete(RequestHead),
+    Incomplete,
+    Invalid(Response),
+}
+
+fn parse_head(received: &[u8]) -> ParseResult {
+    let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") else {
+        return match received.len() > MAX_REQUEST_SIZE {
+            true => ParseResult::Invalid(Response::error(STATUS_PAYLOAD_TOO_LARGE, "Request too large")),
+            false => ParseResult::Incomplete
+        };
+    };
+
+    let Ok(head) = core::str::from_utf8(&received[..end]) else {
+        return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Invalid request"));
+    };
+
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+
+    let (Some(method), Some(path), Some(version), None) = (request_line.next(), request_line.next(), request_line.next(), request_line.next()) else {
+        return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Invalid request line"));
+    };
+
+    // HTTP/1.0 connections are closed unless asked otherwise
+    let mut keep_alive = match version {
+        "HTTP/1.1" => true,
+        "HTTP/1.0" => false,
+        _ => return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Unsupported HTTP version"))
+    };
+
+    let mut content_length = 0;
+    let mut authorization = None;
+
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Invalid header"));
+        };
+
+        let value = value.trim();
+
+        if name.eq_ignore_ascii_case("Content-Length") {
+            match value.parse() {
+                Ok(length) if length > MAX_REQUEST_SIZE => return ParseResult::Invalid(Response::error(STATUS_PAYLOAD_TOO_LARGE, "Request too large")),
+                Ok(length) => content_length = length,
+                Err(_) => return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Invalid Content-Length"))
+            }
+        }
+        else if name.eq_ignore_ascii_case("Connection") {
+            if value.eq_ignore_ascii_case("close") {
+                keep_alive = false;
+            }
+            else if value.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
+            }
+        }
+        else if name.eq_ignore_ascii_case("Authorization") {
+            authorization = Some(String::from(value));
+        }
+        else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            return ParseResult::Invalid(Response::error(STATUS_BAD_REQUEST, "Chunked requests are not supported"));
+        }
+    }
+
+    if (end + 4).checked_add(content_length).is_none_or(|length| length > MAX_REQUEST_SIZE) {
+        return ParseResult::Invalid(Response::error(STATUS_PAYLOAD_TOO_LARGE, "Request too large"));
+    }
+
+    ParseResult::Complete(RequestHead {
+        method: String::from(method),
+        path: String::from(path),
+        content_length,
+        authorization,
+        keep_alive,
+        length: end + 4,
+    })
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        _ => "Unknown"
+    }
+}
+
+fn write_response(output: &mut Vec<u8>, response: &Response, keep_alive: bool) {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nServer: RetOS\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {}\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.body.len(),
+        match keep_alive {
+            true => "keep-alive",
+            false => "close"
+        }
+    );
+
+    if response.status == STATUS_UNAUTHORIZED {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+
+    head.push_str("\r\n");
+
+    output.extend_from_slice(head.as_bytes());
+    output.extend_from_slice(response.body.as_bytes());
+}
+
+/// Task serving the requests of a single connection, until it is closed by either side
+async fn run_connection(id: u64, connection: TcpConnection) {
+    // The connection is forgotten even when its task is killed
+    let _guard = SessionGuard(|| {
+        HTTP_SERVER.lock().connections.remove(&id);
+        debug!("Connection {} closed", id);
+    });
+
+    let mut received = Vec::new();
+    let mut pending_output = Vec::new();
+    let mut closing = false;
+    let mut last_activity = Clock::now();
+
+    loop {
+        Timer::after(CONNECTION_POLL_INTERVAL).await;
+
+        if !HTTP_SERVER.lock().connections.contains_key(&id) || Clock::elapsed(last_activity) > IDLE_TIMEOUT {
+            closing = true;
+        }
+
+        let Some(data) = connection.receive() else {
+            break;
+        };
+
+        if !data.is_empty() && !closing {
+            received.extend(data);
+            last_activity = Clock::now();
+        }
+
+        // Requests are answered in order, pipelined ones included
+        while !closing {
+            let head = match parse_head(&received) {
+                ParseResult::Complete(head) => head,
+                ParseResult::Incomplete => break,
+                ParseResult::Invalid(response) => {
+                    write_response(&mut pending_output, &response, false);
+                    closing = true;
+                    break;
+                }
+            };
+
+            let request_length = head.length + head.content_length;
+            if received.len() < request_length {
+                break;
+            }
+
+            let body = &received[head.length..request_length];
+
+            let authorized = HTTP_SERVER.lock().is_authorized(head.authorization.as_deref());
+
+            // The lock is not kept while the command runs
+            let response = match authorized {
+                true => handle_request(&head.method, &head.path, body).await,
+                false => Response::error(STATUS_UNAUTHORIZED, "Unauthorized")
+            };
+
+            debug!("{} {} {}", head.method, head.path, response.status);
+            HTTP_SERVER.lock().served_requests += 1;
+
+            write_response(&mut pending_output, &response, head.keep_alive);
+            received.drain(..request_length);
+
+            if !head.keep_alive {
+                closing = true;
+            }
+        }
+
+        let sent_everything = connection.send(&mut pending_output);
+
+        if closing && sent_everything {
+            break;
+        }
+    }
+
+    connection.close().await;
+}
+
+/// Task accepting the HTTP connections
+pub async fn http_server() {
+    loop {
+        Timer::after(ACCEPT_INTERVAL).await;
+        HTTP_SERVER.lock().poll();
+    }
+}
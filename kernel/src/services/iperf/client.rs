@@ -0,0 +1,125 @@
+use crate::devices::network::sockets::tcp::TcpStream;
+use crate::devices::network::sockets::udp::UdpSocket;
+use crate::println;
+use crate::services::iperf::stream::{DataSocket, DataStream, Role, Transfer, TransferOptions};
+use crate::services::iperf::{expect_state, new_cookie, read_json, send_json, send_state, stream_id, with_timeout, IperfError, TestParameters, TestResults, TestState, DATA_BUFFER_SIZE, DEFAULT_UDP_BITRATE, UDP_CONNECT_MESSAGE};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
+
+pub struct ClientOptions {
+    pub udp: bool,
+    /// Duration of the test, in seconds
+    pub time: u32,
+    /// Target bitrate of each stream, in bits per second
+    pub bitrate: Option<u64>,
+    /// Size of the blocks written, 0 for the default one of the protocol
+    pub block_size: usize,
+    pub parallel: u32,
+    /// The server sends and the client receives
+    pub reverse: bool,
+    pub interval: Option<Duration>,
+}
+
+/// Run a test against an iperf3 server, printing the bandwidth of each interval and a summary of both sides
+pub async fn run_client(server_endpoint: IpEndpoint, options: &ClientOptions) -> Result<(), IperfError> {
+    let parameters = TestParameters {
+        tcp: !options.udp,
+        udp: options.udp,
+        omit: 0,
+        time: options.time,
+        parallel: options.parallel,
+        reverse: options.reverse,
+        bidirectional: false,
+        len: options.block_size,
+        bandwidth: match options.udp {
+            true => Some(options.bitrate.unwrap_or(DEFAULT_UDP_BITRATE)),
+            false => options.bitrate
+        },
+        udp_counters_64bit: None,
+        client_version: Some(String::from("RetOS")),
+    };
+
+    parameters.validate()?;
+
+    println!("Connecting to host {}, port {}", server_endpoint.addr, server_endpoint.port);
+
+    let cookie = new_cookie();
+    let control = TcpStream::connect(server_endpoint).await?;
+    control.write_all(&cookie).await?;
+
+    // The server asks for the parameters, then for the data connections
+    expect_state(&control, TestState::ParamExchange).await?;
+    send_json(&control, &parameters).await?;
+    expect_state(&control, TestState::CreateStreams).await?;
+
+    let mut streams = Vec::new();
+
+    for index in 0..parameters.parallel as usize {
+        let socket = match parameters.udp {
+            true => {
+                let socket = UdpSocket::bind_with_buffer_size(0, DATA_BUFFER_SIZE)?;
+                socket.send_to(&UDP_CONNECT_MESSAGE, server_endpoint).await?;
+
+                let mut reply = [0u8; 4];
+                with_timeout(async { socket.recv_from(&mut reply).await.map_err(IperfError::from) }).await?;
+
+                DataSocket::Udp(Arc::new(socket), server_endpoint)
+            },
+            false => {
+                let stream = TcpStream::connect_with_buffer_sizes(server_endpoint, DATA_BUFFER_SIZE, DATA_BUFFER_SIZE).await?;
+                stream.write_all(&cookie).await?;
+
+                DataSocket::Tcp(stream)
+            }
+        };
+
+        let stream = DataStream::new(stream_id(index), socket);
+        stream.print_connected();
+        streams.push(stream);
+    }
+
+    expect_state(&control, TestState::TestStart).await?;
+    expect_state(&control, TestState::TestRunning).await?;
+
+    if options.reverse {
+        println!("Reverse mode, remote host {} is sending", server_endpoint.addr);
+    }
+
+    let mut transfer = Transfer::new(streams, TransferOptions {
+        role: match options.reverse {
+            true => Role::Receiver,
+            false => Role::Sender
+        },
+        udp: parameters.udp,
+        block_size: parameters.block_size(),
+        bitrate: parameters.bandwidth.filter(|bitrate| *bitrate != 0),
+        counters_64bit: false,
+        duration: Duration::from_secs(options.time as u64),
+        is_client: true,
+        interval: options.interval,
+    });
+
+    transfer.run(&control).await?;
+
+    // Both sides exchange their results, the client first
+    send_state(&control, TestState::TestEnd).await?;
+    expect_state(&control, TestState::ExchangeResults).await?;
+    send_json(&control, &transfer.results()).await?;
+    let server_results: TestResults = with_timeout(read_json(&control)).await?;
+    expect_state(&control, TestState::DisplayResults).await?;
+
+    transfer.print_summary(&server_results);
+
+    send_state(&control, TestState::IperfDone).await?;
+
+    transfer.close().await;
+    control.close().await;
+
+    println!();
+    println!("iperf Done.");
+
+    Ok(())
+}
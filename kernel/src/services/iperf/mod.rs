@@ -0,0 +1,343 @@
+pub mod client;
+pub mod server;
+mod stream;
+
+use crate::clock::Timer;
+use crate::devices::network::sockets::tcp::TcpStream;
+use crate::devices::network::sockets::SocketError;
+use crate::random::KernelRng;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use core::future::Future;
+use core::pin::pin;
+use futures_util::future::{select, Either};
+use rand_core::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use smoltcp::time::Duration;
+use thiserror::Error;
+
+/// Identifies the test on its control and TCP data connections, 36 characters and a NUL
+const COOKIE_SIZE: usize = 37;
+const COOKIE_CHARACTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+pub const DEFAULT_TCP_BLOCK_SIZE: usize = 128 * 1024;
+/// Fits an Ethernet frame, along with the IPv4 and UDP headers
+pub const DEFAULT_UDP_BLOCK_SIZE: usize = 1460;
+/// The UDP tests are rate limited, 1 Mbit/s unless told otherwise
+pub const DEFAULT_UDP_BITRATE: u64 = 1024 * 1024;
+/// Largest payload of a UDP datagram carried by IPv4
+const MAX_UDP_BLOCK_SIZE: usize = 65507;
+/// Largest TCP block, the limit of iperf3 as well
+const MAX_TCP_BLOCK_SIZE: usize = 1024 * 1024;
+const MAX_STREAMS: u32 = 128;
+
+/// Buffers of the data sockets, large enough to keep the link busy between two polls
+const DATA_BUFFER_SIZE: usize = 64 * 1024;
+const MAX_JSON_SIZE: usize = 64 * 1024;
+
+/// Time given to the peer to answer, outside of the test itself
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sent by the client on each UDP stream, and answered by the server
+const UDP_CONNECT_MESSAGE: [u8; 4] = *b"9876";
+const UDP_CONNECT_REPLY: [u8; 4] = *b"6789";
+
+#[derive(Debug, Error)]
+pub enum IperfError {
+    #[error("{0}")]
+    Socket(#[from] SocketError),
+
+    #[error("timed out waiting for the peer")]
+    Timeout,
+
+    #[error("the server is busy running a test")]
+    AccessDenied,
+
+    #[error("the server reported an error")]
+    ServerError,
+
+    #[error("the peer ended the test")]
+    Terminated,
+
+    #[error("unexpected state {0}")]
+    UnexpectedState(i8),
+
+    #[error("invalid JSON message")]
+    Json,
+
+    #[error("unsupported parameters: {0}")]
+    UnsupportedParameters(&'static str),
+}
+
+/// States of a test, sent as a single byte over the control connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+enum TestState {
+    TestStart = 1,
+    TestRunning = 2,
+    TestEnd = 4,
+    ParamExchange = 9,
+    CreateStreams = 10,
+    ServerTerminate = 11,
+    ClientTerminate = 12,
+    ExchangeResults = 13,
+    DisplayResults = 14,
+    IperfDone = 16,
+    AccessDenied = -1,
+    ServerError = -2,
+}
+
+impl TestState {
+    fn from_byte(byte: u8) -> Result<Self, IperfError> {
+        let state = match byte as i8 {
+            1 => TestState::TestStart,
+            2 => TestState::TestRunning,
+            4 => TestState::TestEnd,
+            9 => TestState::ParamExchange,
+            10 => TestState::CreateStreams,
+            11 => TestState::ServerTerminate,
+            12 => TestState::ClientTerminate,
+            13 => TestState::ExchangeResults,
+            14 => TestState::DisplayResults,
+            16 => TestState::IperfDone,
+            -1 => TestState::AccessDenied,
+            -2 => TestState::ServerError,
+            state => return Err(IperfError::UnexpectedState(state))
+        };
+
+        Ok(state)
+    }
+
+    /// Error corresponding to this state, when another one was expected
+    fn unexpected(self) -> IperfError {
+        match self {
+            TestState::AccessDenied => IperfError::AccessDenied,
+            TestState::ServerError => IperfError::ServerError,
+            TestState::ServerTerminate | TestState::ClientTerminate => IperfError::Terminated,
+            state => IperfError::UnexpectedState(state as i8)
+        }
+    }
+}
+
+/// Parameters of a test, sent by the client. The unknown ones are ignored.
+#[derive(Debug, Serialize, Deserialize)]
+struct TestParameters {
+    #[serde(default, skip_serializing_if = "is_false")]
+    tcp: bool,
+
+    #[serde(default, skip_serializing_if = "is_false")]
+    udp: bool,
+
+    #[serde(default)]
+    omit: u32,
+
+    /// Duration of the test, in seconds
+    #[serde(default)]
+    time: u32,
+
+    #[serde(default = "default_parallel")]
+    parallel: u32,
+
+    #[serde(default, skip_serializing_if = "is_false")]
+    reverse: bool,
+
+    #[serde(default, skip_serializing_if = "is_false")]
+    bidirectional: bool,
+
+    /// Size of the blocks written, 0 for the default one of the protocol
+    #[serde(default)]
+    len: usize,
+
+    /// Target bitrate of each stream, in bits per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bandwidth: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    udp_counters_64bit: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_version: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn default_parallel() -> u32 {
+    1
+}
+
+/// Results of one side of a test, exchanged at its end
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TestResults {
+    #[serde(default)]
+    cpu_util_total: f64,
+
+    #[serde(default)]
+    cpu_util_user: f64,
+
+    #[serde(default)]
+    cpu_util_system: f64,
+
+    #[serde(default)]
+    sender_has_retransmits: i32,
+
+    #[serde(default)]
+    streams: Vec<StreamResults>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct StreamResults {
+    id: u32,
+    bytes: u64,
+    /// -1 when unknown
+    retransmits: i64,
+    /// In seconds
+    jitter: f64,
+    /// UDP datagrams lost
+    errors: i64,
+    /// UDP datagrams sent, or the highest sequence number received
+    packets: i64,
+    #[serde(default)]
+    start_time: f64,
+    #[serde(default)]
+    end_time: f64,
+}
+
+impl TestParameters {
+    /// Size of the blocks written, the default one of the protocol unless given
+    fn block_size(&self) -> usize {
+        match (self.len, self.udp) {
+            (0, true) => DEFAULT_UDP_BLOCK_SIZE,
+            (0, false) => DEFAULT_TCP_BLOCK_SIZE,
+            (len, _) => len
+        }
+    }
+
+    fn validate(&self) -> Result<(), IperfError> {
+        if self.bidirectional {
+            return Err(IperfError::UnsupportedParameters("bidirectional tests"));
+        }
+
+        if self.parallel == 0 || self.parallel > MAX_STREAMS {
+            return Err(IperfError::UnsupportedParameters("number of parallel streams"));
+        }
+
+        if self.udp && (self.block_size() < stream::UDP_HEADER_SIZE_64BIT || self.block_size() > MAX_UDP_BLOCK_SIZE) {
+            return Err(IperfError::UnsupportedParameters("UDP datagram size"));
+        }
+
+        if !self.udp && self.block_size() > MAX_TCP_BLOCK_SIZE {
+            return Err(IperfError::UnsupportedParameters("TCP block size"));
+        }
+
+        Ok(())
+    }
+}
+
+fn new_cookie() -> [u8; COOKIE_SIZE] {
+    let mut cookie = [0u8; COOKIE_SIZE];
+    KernelRng.fill_bytes(&mut cookie);
+
+    for byte in cookie.iter_mut() {
+        *byte = COOKIE_CHARACTERS[*byte as usize % COOKIE_CHARACTERS.len()];
+    }
+
+    cookie[COOKIE_SIZE - 1] = 0;
+    cookie
+}
+
+/// Stream identifiers as iperf3 numbers them, 2 is skipped
+fn stream_id(index: usize) -> u32 {
+    match index {
+        0 => 1,
+        index => index as u32 + 2
+    }
+}
+
+/// Fail with a timeout if the peer does not answer in time
+async fn with_timeout<T>(future: impl Future<Output = Result<T, IperfError>>) -> Result<T, IperfError> {
+    match select(pin!(future), Timer::after(CONTROL_TIMEOUT)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(IperfError::Timeout)
+    }
+}
+
+async fn send_state(control: &TcpStream, state: TestState) -> Result<(), IperfError> {
+    control.write_all(&[state as i8 as u8]).await?;
+    Ok(())
+}
+
+async fn read_state(control: &TcpStream) -> Result<TestState, IperfError> {
+    let mut state = [0u8; 1];
+    control.read_exact(&mut state).await?;
+    TestState::from_byte(state[0])
+}
+
+async fn expect_state(control: &TcpStream, expected: TestState) -> Result<(), IperfError> {
+    match with_timeout(read_state(control)).await? {
+        state if state == expected => Ok(()),
+        state => Err(state.unexpected())
+    }
+}
+
+/// JSON messages are preceded by their length, as a 32 bits big endian integer
+async fn send_json<T: Serialize>(control: &TcpStream, value: &T) -> Result<(), IperfError> {
+    let json = serde_json::to_vec(value).map_err(|_| IperfError::Json)?;
+
+    let mut length = [0u8; 4];
+    NetworkEndian::write_u32(&mut length, json.len() as u32);
+
+    control.write_all(&length).await?;
+    control.write_all(&json).await?;
+
+    Ok(())
+}
+
+async fn read_json<T: DeserializeOwned>(control: &TcpStream) -> Result<T, IperfError> {
+    let mut length = [0u8; 4];
+    control.read_exact(&mut length).await?;
+
+    let length = NetworkEndian::read_u32(&length) as usize;
+    if length > MAX_JSON_SIZE {
+        return Err(IperfError::Json);
+    }
+
+    let mut json = vec![0u8; length];
+    control.read_exact(&mut json).await?;
+
+    serde_json::from_slice(&json).map_err(|_| IperfError::Json)
+}
+
+/// Amount of bytes with a binary unit, the way iperf3 prints it (e.g. "11.2 MBytes")
+fn format_bytes(bytes: u64) -> String {
+    format_value(bytes as f64, 1024.0, &["Bytes", "KBytes", "MBytes", "GBytes", "TBytes"])
+}
+
+/// Bitrate with a decimal unit, the way iperf3 prints it (e.g. "9.40 Mbits/sec")
+fn format_bitrate(bits_per_second: f64) -> String {
+    format_value(bits_per_second, 1000.0, &["bits/sec", "Kbits/sec", "Mbits/sec", "Gbits/sec", "Tbits/sec"])
+}
+
+/// Three significant digits, with the largest unit keeping the value above 1
+fn format_value(mut value: f64, base: f64, units: &[&str]) -> String {
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+
+    if value < 9.995 {
+        format!("{:.2} {}", value, units[unit])
+    }
+    else if value < 99.95 {
+        format!("{:.1} {}", value, units[unit])
+    }
+    else {
+        format!("{:.0} {}", value, units[unit])
+    }
+}
@@ -0,0 +1,164 @@
+use crate::devices::network::sockets::tcp::{TcpListener, TcpStream};
+use crate::devices::network::sockets::udp::UdpSocket;
+use crate::devices::network::sockets::SocketError;
+use crate::println;
+use crate::services::iperf::stream::{DataSocket, DataStream, Role, Transfer, TransferOptions};
+use crate::services::iperf::{expect_state, read_json, send_json, send_state, stream_id, with_timeout, IperfError, TestParameters, TestResults, TestState, COOKIE_SIZE, DATA_BUFFER_SIZE, DEFAULT_UDP_BITRATE, UDP_CONNECT_MESSAGE, UDP_CONNECT_REPLY};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use goolog::debug;
+use smoltcp::time::Duration;
+
+const GOOLOG_TARGET: &str = "IPERF";
+
+/// Connections which can be opening at the same time, the data streams of a client are opened back to back
+const LISTEN_BACKLOG: usize = 4;
+
+pub struct ServerOptions {
+    pub port: u16,
+    /// Serve a single test, then stop
+    pub one_off: bool,
+    pub interval: Option<Duration>,
+}
+
+/// Serve the tests of iperf3 clients, one at a time
+pub async fn run_server(options: &ServerOptions) -> Result<(), IperfError> {
+    let mut listener = TcpListener::with_buffer_sizes(options.port, DATA_BUFFER_SIZE, DATA_BUFFER_SIZE)?;
+    listener.set_backlog(LISTEN_BACKLOG);
+
+    loop {
+        println!("-----------------------------------------------------------");
+        println!("Server listening on {}", options.port);
+        println!("-----------------------------------------------------------");
+
+        let control = listener.accept().await;
+
+        match control.remote_endpoint() {
+            Some(remote_endpoint) => println!("Accepted connection from {}, port {}", remote_endpoint.addr, remote_endpoint.port),
+            None => println!("Accepted connection on {}", control.interface_name())
+        }
+
+        if let Err(error) = serve_test(&mut listener, &control, options).await {
+            println!("iperf3: error - {}", error);
+
+            // Best effort, the client may be gone already
+            if !matches!(error, IperfError::Socket(_)) {
+                let _ = send_state(&control, TestState::ServerError).await;
+            }
+        }
+
+        control.close().await;
+
+        if options.one_off {
+            return Ok(());
+        }
+    }
+}
+
+async fn serve_test(listener: &mut TcpListener, control: &TcpStream, options: &ServerOptions) -> Result<(), IperfError> {
+    let mut cookie = [0u8; COOKIE_SIZE];
+    with_timeout(async { control.read_exact(&mut cookie).await.map_err(IperfError::from) }).await?;
+
+    send_state(control, TestState::ParamExchange).await?;
+    let parameters: TestParameters = with_timeout(read_json(control)).await?;
+    debug!("Test parameters: {:?}", parameters);
+
+    parameters.validate()?;
+
+    // Bound before the client is told to connect, its first datagrams are not lost
+    let udp_socket = match parameters.udp {
+        true => Some(Arc::new(UdpSocket::bind_with_buffer_size(options.port, DATA_BUFFER_SIZE)?)),
+        false => None
+    };
+
+    send_state(control, TestState::CreateStreams).await?;
+
+    let mut streams = Vec::new();
+
+    for index in 0..parameters.parallel as usize {
+        let socket = match &udp_socket {
+            Some(udp_socket) => accept_udp_stream(udp_socket, &streams).await?,
+            None => accept_tcp_stream(listener, &cookie).await?
+        };
+
+        let stream = DataStream::new(stream_id(index), socket);
+        stream.print_connected();
+        streams.push(stream);
+    }
+
+    send_state(control, TestState::TestStart).await?;
+    send_state(control, TestState::TestRunning).await?;
+
+    let mut transfer = Transfer::new(streams, TransferOptions {
+        role: match parameters.reverse {
+            true => Role::Sender,
+            false => Role::Receiver
+        },
+        udp: parameters.udp,
+        block_size: parameters.block_size(),
+        bitrate: match parameters.udp {
+            true => Some(parameters.bandwidth.unwrap_or(DEFAULT_UDP_BITRATE)),
+            false => parameters.bandwidth
+        }
+        .filter(|bitrate| *bitrate != 0),
+        counters_64bit: parameters.udp_counters_64bit.is_some_and(|counters_64bit| counters_64bit != 0),
+        duration: Duration::from_secs((parameters.omit + parameters.time) as u64),
+        is_client: false,
+        interval: options.interval,
+    });
+
+    transfer.run(control).await?;
+
+    // Both sides exchange their results, the client first
+    send_state(control, TestState::ExchangeResults).await?;
+    let client_results: TestResults = with_timeout(read_json(control)).await?;
+    send_json(control, &transfer.results()).await?;
+    send_state(control, TestState::DisplayResults).await?;
+
+    transfer.print_summary(&client_results);
+
+    expect_state(control, TestState::IperfDone).await?;
+
+    transfer.close().await;
+
+    Ok(())
+}
+
+/// Next data connection of the test, the other clients are told the server is busy
+async fn accept_tcp_stream(listener: &mut TcpListener, cookie: &[u8; COOKIE_SIZE]) -> Result<DataSocket, IperfError> {
+    loop {
+        let stream = with_timeout(async { Ok::<_, IperfError>(listener.accept().await) }).await?;
+
+        let mut stream_cookie = [0u8; COOKIE_SIZE];
+        with_timeout(async { stream.read_exact(&mut stream_cookie).await.map_err(IperfError::from) }).await?;
+
+        if stream_cookie == *cookie {
+            return Ok(DataSocket::Tcp(stream));
+        }
+
+        let _ = send_state(&stream, TestState::AccessDenied).await;
+        stream.close().await;
+    }
+}
+
+/// Next UDP stream of the test, opened by a connection message from a new endpoint of the client
+async fn accept_udp_stream(socket: &Arc<UdpSocket>, streams: &[DataStream]) -> Result<DataSocket, IperfError> {
+    let mut message = [0u8; 64];
+
+    loop {
+        let (size, sender) = match with_timeout(async { socket.recv_from(&mut message).await.map_err(IperfError::from) }).await {
+            Ok(received) => received,
+            // Late datagrams of a previous test
+            Err(IperfError::Socket(SocketError::PacketTooLarge)) => continue,
+            Err(error) => return Err(error)
+        };
+
+        if size != UDP_CONNECT_MESSAGE.len() || streams.iter().any(|stream| stream.is_udp_peer(socket, sender)) {
+            continue;
+        }
+
+        socket.send_to(&UDP_CONNECT_REPLY, sender).await?;
+
+        return Ok(DataSocket::Udp(socket.clone(), sender));
+    }
+}
@@ -0,0 +1,557 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::sockets::tcp::TcpStream;
+use crate::devices::network::sockets::udp::UdpSocket;
+use crate::devices::network::sockets::SocketError;
+use crate::println;
+use crate::services::iperf::{format_bitrate, format_bytes, IperfError, StreamResults, TestResults, TestState, DATA_BUFFER_SIZE};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use futures_util::future::join_all;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::IpEndpoint;
+
+/// UDP datagrams start with the time they were sent (seconds and microseconds) and their sequence number
+pub const UDP_HEADER_SIZE: usize = 12;
+pub const UDP_HEADER_SIZE_64BIT: usize = 16;
+
+/// A rate limited sender is woken up this often to send its next blocks
+const PACING_INTERVAL: Duration = Duration::from_millis(1);
+/// The server gives up on a client which did not end its test this long after its duration
+const END_TIMEOUT: Duration = Duration::from_secs(10);
+const SEPARATOR: &str = "- - - - - - - - - - - - - - - - - - - - - - - - -";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Sender,
+    Receiver,
+}
+
+pub enum DataSocket {
+    Tcp(TcpStream),
+    /// The socket of a server is shared by its UDP streams, which are told apart by their remote endpoint
+    Udp(Arc<UdpSocket>, IpEndpoint),
+}
+
+/// Traffic of a stream, since the start of the test
+#[derive(Debug, Default, Clone, Copy)]
+struct Counters {
+    bytes: u64,
+    /// UDP datagrams sent, or the highest sequence number received
+    packets: u64,
+    /// UDP datagrams lost
+    lost: u64,
+}
+
+impl Counters {
+    fn since(&self, start: &Counters) -> Counters {
+        Counters {
+            bytes: self.bytes - start.bytes,
+            packets: self.packets - start.packets,
+            lost: self.lost.saturating_sub(start.lost),
+        }
+    }
+
+    fn add(&mut self, other: &Counters) {
+        self.bytes += other.bytes;
+        self.packets += other.packets;
+        self.lost += other.lost;
+    }
+}
+
+impl From<&StreamResults> for Counters {
+    fn from(results: &StreamResults) -> Self {
+        Counters {
+            bytes: results.bytes,
+            packets: results.packets.max(0) as u64,
+            lost: results.errors.max(0) as u64,
+        }
+    }
+}
+
+pub struct DataStream {
+    id: u32,
+    socket: DataSocket,
+    total: Counters,
+    /// Counters at the start of the current interval
+    interval_start: Counters,
+    /// Next UDP sequence number expected, they start at 1
+    next_packet: u64,
+    /// Interarrival jitter of the UDP datagrams (RFC 1889), in seconds
+    jitter: f64,
+    previous_transit: Option<f64>,
+}
+
+impl DataStream {
+    pub fn new(id: u32, socket: DataSocket) -> Self {
+        Self {
+            id,
+            socket,
+            total: Counters::default(),
+            interval_start: Counters::default(),
+            next_packet: 1,
+            jitter: 0.0,
+            previous_transit: None,
+        }
+    }
+
+    pub fn print_connected(&self) {
+        match &self.socket {
+            DataSocket::Tcp(stream) => match (stream.local_endpoint(), stream.remote_endpoint()) {
+                (Some(local_endpoint), Some(remote_endpoint)) => println!("[{:>3}] local {} connected to {}", self.id, local_endpoint, remote_endpoint),
+                _ => println!("[{:>3}] connected on {}", self.id, stream.interface_name())
+            },
+            DataSocket::Udp(socket, remote_endpoint) => println!("[{:>3}] local port {} connected to {}", self.id, socket.local_port(), remote_endpoint)
+        }
+    }
+
+    /// Whether the datagrams received by the socket from this endpoint belong to the stream
+    pub fn is_udp_peer(&self, socket: &Arc<UdpSocket>, endpoint: IpEndpoint) -> bool {
+        match &self.socket {
+            DataSocket::Udp(stream_socket, remote_endpoint) => Arc::ptr_eq(stream_socket, socket) && *remote_endpoint == endpoint,
+            DataSocket::Tcp(_) => false
+        }
+    }
+
+    fn poll_send(&mut self, context: &mut Context, block: &mut [u8], counters_64bit: bool, now: Instant) -> Poll<Result<(), IperfError>> {
+        match &self.socket {
+            DataSocket::Tcp(stream) => {
+                let size = ready!(stream.poll_write(context, block))?;
+                self.total.bytes += size as u64;
+            },
+            DataSocket::Udp(socket, remote_endpoint) => {
+                let packet = self.total.packets + 1;
+
+                NetworkEndian::write_u32(&mut block[0..4], now.secs() as u32);
+                NetworkEndian::write_u32(&mut block[4..8], now.micros() as u32);
+
+                match counters_64bit {
+                    true => NetworkEndian::write_u64(&mut block[8..16], packet),
+                    false => NetworkEndian::write_u32(&mut block[8..12], packet as u32)
+                }
+
+                ready!(socket.poll_send_to(context, block, *remote_endpoint))?;
+
+                self.total.packets = packet;
+                self.total.bytes += block.len() as u64;
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn receive_datagram(&mut self, datagram: &[u8], counters_64bit: bool, now: Instant) {
+        let header_size = match counters_64bit {
+            true => UDP_HEADER_SIZE_64BIT,
+            false => UDP_HEADER_SIZE
+        };
+
+        if datagram.len() < header_size {
+            return;
+        }
+
+        let sent_at = NetworkEndian::read_u32(&datagram[0..4]) as f64 + NetworkEndian::read_u32(&datagram[4..8]) as f64 / 1_000_000.0;
+        let packet = match counters_64bit {
+            true => NetworkEndian::read_u64(&datagram[8..16]),
+            false => NetworkEndian::read_u32(&datagram[8..12]) as u64
+        };
+
+        self.total.bytes += datagram.len() as u64;
+
+        if packet >= self.next_packet {
+            // The skipped datagrams are lost, unless they arrive later out of order
+            self.total.lost += packet - self.next_packet;
+            self.total.packets = packet;
+            self.next_packet = packet + 1;
+        }
+        else {
+            self.total.lost = self.total.lost.saturating_sub(1);
+        }
+
+        // Only the variations of the transit time matter, the clocks of both sides need not be synchronized
+        let transit = now.total_micros() as f64 / 1_000_000.0 - sent_at;
+
+        if let Some(previous_transit) = self.previous_transit {
+            let mut difference = transit - previous_transit;
+            if difference < 0.0 {
+                difference = -difference;
+            }
+
+            self.jitter += (difference - self.jitter) / 16.0;
+        }
+
+        self.previous_transit = Some(transit);
+    }
+
+    fn results(&self, role: Role, duration: f64) -> StreamResults {
+        let (jitter, errors) = match role {
+            Role::Sender => (0.0, 0),
+            Role::Receiver => (self.jitter, self.total.lost as i64)
+        };
+
+        StreamResults {
+            id: self.id,
+            bytes: self.total.bytes,
+            retransmits: -1,
+            jitter,
+            errors,
+            packets: self.total.packets as i64,
+            start_time: 0.0,
+            end_time: duration,
+        }
+    }
+}
+
+/// Columns of the reports, depending on the protocol and on the side
+#[derive(Debug, Clone, Copy)]
+enum Columns {
+    Tcp,
+    UdpSender,
+    UdpReceiver,
+}
+
+impl Columns {
+    fn header(&self) -> String {
+        let header = "[ ID] Interval           Transfer     Bitrate";
+
+        match self {
+            Columns::Tcp => String::from(header),
+            Columns::UdpSender => format!("{}         Total Datagrams", header),
+            Columns::UdpReceiver => format!("{}         Jitter    Lost/Total Datagrams", header)
+        }
+    }
+
+    fn line(&self, label: &str, start: f64, end: f64, counters: &Counters, jitter: f64) -> String {
+        let bitrate = match end - start {
+            duration if duration > 0.0 => counters.bytes as f64 * 8.0 / duration,
+            _ => 0.0
+        };
+
+        let line = format!("[{:>3}] {:>6.2}-{:<6.2} sec  {:>11}  {:>15}", label, start, end, format_bytes(counters.bytes), format_bitrate(bitrate));
+
+        match self {
+            Columns::Tcp => line,
+            Columns::UdpSender => format!("{}  {:>14}", line, counters.packets),
+            Columns::UdpReceiver => {
+                let lost_percent = match counters.packets {
+                    0 => 0.0,
+                    packets => counters.lost as f64 * 100.0 / packets as f64
+                };
+
+                format!("{}  {:>7.3} ms  {}/{} ({:.2}%)", line, jitter * 1000.0, counters.lost, counters.packets, lost_percent)
+            }
+        }
+    }
+}
+
+pub struct TransferOptions {
+    pub role: Role,
+    pub udp: bool,
+    pub block_size: usize,
+    /// Target bitrate of each stream, in bits per second
+    pub bitrate: Option<u64>,
+    pub counters_64bit: bool,
+    /// Duration of the test, 0 if the client ends it otherwise
+    pub duration: Duration,
+    /// The client ends the test once its duration elapsed, the server waits for it
+    pub is_client: bool,
+    /// Time between the reports, none for a summary only
+    pub interval: Option<Duration>,
+}
+
+enum Event {
+    Control(TestState),
+    Timer,
+}
+
+/// Data streams of a test, along with what they sent or received
+pub struct Transfer {
+    streams: Vec<DataStream>,
+    /// Distinct UDP sockets of the streams
+    udp_sockets: Vec<Arc<UdpSocket>>,
+    options: TransferOptions,
+    started_at: Instant,
+    ended_at: Instant,
+}
+
+impl Transfer {
+    pub fn new(streams: Vec<DataStream>, options: TransferOptions) -> Self {
+        let mut udp_sockets: Vec<Arc<UdpSocket>> = Vec::new();
+
+        for stream in &streams {
+            if let DataSocket::Udp(socket, _) = &stream.socket {
+                if !udp_sockets.iter().any(|udp_socket| Arc::ptr_eq(udp_socket, socket)) {
+                    udp_sockets.push(socket.clone());
+                }
+            }
+        }
+
+        Self {
+            streams,
+            udp_sockets,
+            options,
+            started_at: Clock::now(),
+            ended_at: Clock::now(),
+        }
+    }
+
+    fn columns(&self) -> Columns {
+        match (self.options.udp, self.options.role) {
+            (false, _) => Columns::Tcp,
+            (true, Role::Sender) => Columns::UdpSender,
+            (true, Role::Receiver) => Columns::UdpReceiver
+        }
+    }
+
+    /// Send or receive until the end of the test, reporting the bandwidth at each interval
+    pub async fn run(&mut self, control: &TcpStream) -> Result<(), IperfError> {
+        let mut buffer: Vec<u8> = match self.options.role {
+            // Same filling as iperf3 without a random payload
+            Role::Sender => (0..self.options.block_size).map(|index| b'0' + (index % 10) as u8).collect(),
+            // Large enough for any datagram
+            Role::Receiver => vec![0u8; DATA_BUFFER_SIZE.max(self.options.block_size)]
+        };
+
+        if self.options.interval.is_some() {
+            println!("{}", self.columns().header());
+        }
+
+        self.started_at = Clock::now();
+        let mut interval_started_at = self.started_at;
+
+        let end = match self.options.is_client {
+            true => self.started_at + self.options.duration,
+            false => self.started_at + self.options.duration + END_TIMEOUT
+        };
+
+        loop {
+            let now = Clock::now();
+            let next_report = self.options.interval.map(|interval| interval_started_at + interval);
+
+            let mut deadline = end;
+            if let Some(next_report) = next_report {
+                deadline = deadline.min(next_report);
+            }
+            if self.options.role == Role::Sender && self.options.bitrate.is_some() {
+                deadline = deadline.min(now + PACING_INTERVAL);
+            }
+
+            let mut timer = Timer::at(deadline);
+            let event = poll_fn(|context| self.poll_transfer(context, control, &mut buffer, &mut timer)).await?;
+
+            match event {
+                Event::Control(TestState::TestEnd) if !self.options.is_client => break,
+                Event::Control(state) => return Err(state.unexpected()),
+                Event::Timer => {}
+            }
+
+            let now = Clock::now();
+
+            if next_report.is_some_and(|next_report| now >= next_report) {
+                self.report_interval(interval_started_at, now);
+                interval_started_at = now;
+            }
+
+            if now >= end {
+                match self.options.is_client {
+                    true => break,
+                    // The duration of a test ended by a number of bytes is unknown
+                    false if self.options.duration != Duration::ZERO => return Err(IperfError::Timeout),
+                    false => {}
+                }
+            }
+        }
+
+        self.ended_at = Clock::now();
+
+        // The last interval is reported as well, unless it just started
+        if self.options.interval.is_some() && self.ended_at - interval_started_at >= PACING_INTERVAL {
+            self.report_interval(interval_started_at, self.ended_at);
+        }
+
+        Ok(())
+    }
+
+    fn poll_transfer(&mut self, context: &mut Context, control: &TcpStream, buffer: &mut [u8], timer: &mut Timer) -> Poll<Result<Event, IperfError>> {
+        let mut state = [0u8; 1];
+
+        match control.poll_read(context, &mut state) {
+            Poll::Ready(Ok(0)) => return Poll::Ready(Err(IperfError::Socket(SocketError::Closed))),
+            Poll::Ready(Ok(_)) => return Poll::Ready(TestState::from_byte(state[0]).map(Event::Control)),
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error.into())),
+            Poll::Pending => {}
+        }
+
+        let transferred = match self.options.role {
+            Role::Sender => self.poll_send(context, buffer),
+            Role::Receiver => self.poll_receive(context, buffer)
+        };
+
+        if let Err(error) = transferred {
+            return Poll::Ready(Err(error));
+        }
+
+        match Pin::new(timer).poll(context) {
+            Poll::Ready(()) => Poll::Ready(Ok(Event::Timer)),
+            Poll::Pending => Poll::Pending
+        }
+    }
+
+    /// Send on every stream until their sockets are full, or until they are ahead of the bitrate
+    fn poll_send(&mut self, context: &mut Context, block: &mut [u8]) -> Result<(), IperfError> {
+        let now = Clock::now();
+        let elapsed_micros = (now - self.started_at).total_micros() as u128;
+
+        for stream in self.streams.iter_mut() {
+            loop {
+                if let Some(bitrate) = self.options.bitrate {
+                    let budget = (bitrate as u128 * elapsed_micros / 8_000_000) as u64;
+                    if stream.total.bytes >= budget {
+                        break;
+                    }
+                }
+
+                match stream.poll_send(context, block, self.options.counters_64bit, now) {
+                    Poll::Ready(result) => result?,
+                    Poll::Pending => break
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read everything received on every stream
+    fn poll_receive(&mut self, context: &mut Context, buffer: &mut [u8]) -> Result<(), IperfError> {
+        let now = Clock::now();
+
+        for stream in self.streams.iter_mut() {
+            if let DataSocket::Tcp(tcp_stream) = &stream.socket {
+                while let Poll::Ready(received) = tcp_stream.poll_read(context, buffer) {
+                    match received? {
+                        // Closed by the sender, the test ends over the control connection
+                        0 => break,
+                        size => stream.total.bytes += size as u64
+                    }
+                }
+            }
+        }
+
+        for socket in &self.udp_sockets {
+            loop {
+                let (size, sender) = match socket.poll_recv_from(context, buffer) {
+                    Poll::Ready(Ok(received)) => received,
+                    // Not a datagram of the test
+                    Poll::Ready(Err(SocketError::PacketTooLarge)) => continue,
+                    Poll::Ready(Err(error)) => return Err(error.into()),
+                    Poll::Pending => break
+                };
+
+                // A late connection message, or another sender
+                if let Some(stream) = self.streams.iter_mut().find(|stream| stream.is_udp_peer(socket, sender)) {
+                    stream.receive_datagram(&buffer[..size], self.options.counters_64bit, now);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn report_interval(&mut self, start: Instant, end: Instant) {
+        let columns = self.columns();
+        let start_seconds = seconds_between(self.started_at, start);
+        let end_seconds = seconds_between(self.started_at, end);
+
+        let mut sum = Counters::default();
+        let mut jitter_sum = 0.0;
+
+        for stream in self.streams.iter_mut() {
+            let counters = stream.total.since(&stream.interval_start);
+            println!("{}", columns.line(&format!("{}", stream.id), start_seconds, end_seconds, &counters, stream.jitter));
+
+            sum.add(&counters);
+            jitter_sum += stream.jitter;
+            stream.interval_start = stream.total;
+        }
+
+        if self.streams.len() > 1 {
+            println!("{}", columns.line("SUM", start_seconds, end_seconds, &sum, jitter_sum / self.streams.len() as f64));
+        }
+    }
+
+    /// Results of this side, sent to the peer
+    pub fn results(&self) -> TestResults {
+        let duration = seconds_between(self.started_at, self.ended_at);
+
+        TestResults {
+            streams: self.streams.iter().map(|stream| stream.results(self.options.role, duration)).collect(),
+            ..TestResults::default()
+        }
+    }
+
+    /// Print what was sent and what was received, according to both sides
+    pub fn print_summary(&self, peer_results: &TestResults) {
+        let local_results = self.results();
+
+        let (sent, received) = match self.options.role {
+            Role::Sender => (&local_results, peer_results),
+            Role::Receiver => (peer_results, &local_results)
+        };
+
+        // Both lines show the losses with UDP, none for the sender
+        let columns = match self.options.udp {
+            true => Columns::UdpReceiver,
+            false => Columns::Tcp
+        };
+
+        println!("{}", SEPARATOR);
+        println!("{}", columns.header());
+
+        let mut sent_sum = (Counters::default(), 0.0);
+        let mut received_sum = (Counters::default(), 0.0, 0.0);
+
+        for sent_stream in &sent.streams {
+            let counters = Counters::from(sent_stream);
+            println!("{}  sender", columns.line(&format!("{}", sent_stream.id), 0.0, sent_stream.end_time, &counters, 0.0));
+
+            sent_sum.0.add(&counters);
+            sent_sum.1 = sent_stream.end_time.max(sent_sum.1);
+
+            if let Some(received_stream) = received.streams.iter().find(|stream| stream.id == sent_stream.id) {
+                let counters = Counters::from(received_stream);
+                println!("{}  receiver", columns.line(&format!("{}", received_stream.id), 0.0, received_stream.end_time, &counters, received_stream.jitter));
+
+                received_sum.0.add(&counters);
+                received_sum.1 = received_stream.end_time.max(received_sum.1);
+                received_sum.2 += received_stream.jitter;
+            }
+        }
+
+        if sent.streams.len() > 1 {
+            println!("{}  sender", columns.line("SUM", 0.0, sent_sum.1, &sent_sum.0, 0.0));
+            println!("{}  receiver", columns.line("SUM", 0.0, received_sum.1, &received_sum.0, received_sum.2 / received.streams.len().max(1) as f64));
+        }
+    }
+
+    /// Close the TCP data connections once the remote side is done with them
+    pub async fn close(self) {
+        let tcp_streams = self.streams
+            .into_iter()
+            .filter_map(|stream| match stream.socket {
+                DataSocket::Tcp(tcp_stream) => Some(tcp_stream.close()),
+                DataSocket::Udp(..) => None
+            });
+
+        join_all(tcp_streams).await;
+    }
+}
+
+fn seconds_between(start: Instant, end: Instant) -> f64 {
+    (end - start).total_micros() as f64 / 1_000_000.0
+}
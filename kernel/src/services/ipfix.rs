@@ -0,0 +1,332 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::flow::{FlowRecord, FLOW_CACHE, MAX_FLOWS};
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::ServiceSocket;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use goolog::{debug, trace, warn};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "IPFIX";
+
+pub const IPFIX_DEFAULT_PORT: u16 = 4739;
+
+/// Local port used to send the IPFIX messages
+const EXPORTER_PORT: u16 = 49152;
+
+const IPFIX_VERSION: u16 = 10;
+const TEMPLATE_SET_ID: u16 = 2;
+const IPV4_TEMPLATE_ID: u16 = 256;
+const IPV6_TEMPLATE_ID: u16 = 257;
+
+/// Keeps the messages under the ethernet MTU
+const MAX_MESSAGE_SIZE: usize = 1400;
+const MESSAGE_HEADER_SIZE: usize = 16;
+const SET_HEADER_SIZE: usize = 4;
+
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Templates are periodically resent, since UDP does not guarantee their delivery (RFC 7011, 8.4)
+const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Information Elements (RFC 7012)
+const IE_OCTET_DELTA_COUNT: u16 = 1;
+const IE_PACKET_DELTA_COUNT: u16 = 2;
+const IE_PROTOCOL_IDENTIFIER: u16 = 4;
+const IE_SOURCE_TRANSPORT_PORT: u16 = 7;
+const IE_SOURCE_IPV4_ADDRESS: u16 = 8;
+const IE_INGRESS_INTERFACE: u16 = 10;
+const IE_DESTINATION_TRANSPORT_PORT: u16 = 11;
+const IE_DESTINATION_IPV4_ADDRESS: u16 = 12;
+const IE_FLOW_END_SYS_UP_TIME: u16 = 21;
+const IE_FLOW_START_SYS_UP_TIME: u16 = 22;
+const IE_SOURCE_IPV6_ADDRESS: u16 = 27;
+const IE_DESTINATION_IPV6_ADDRESS: u16 = 28;
+
+const IPV4_TEMPLATE: [(u16, u16); 10] = [
+    (IE_SOURCE_IPV4_ADDRESS, 4),
+    (IE_DESTINATION_IPV4_ADDRESS, 4),
+    (IE_PROTOCOL_IDENTIFIER, 1),
+    (IE_SOURCE_TRANSPORT_PORT, 2),
+    (IE_DESTINATION_TRANSPORT_PORT, 2),
+    (IE_INGRESS_INTERFACE, 4),
+    (IE_OCTET_DELTA_COUNT, 8),
+    (IE_PACKET_DELTA_COUNT, 8),
+    (IE_FLOW_START_SYS_UP_TIME, 4),
+    (IE_FLOW_END_SYS_UP_TIME, 4),
+];
+
+const IPV6_TEMPLATE: [(u16, u16); 10] = [
+    (IE_SOURCE_IPV6_ADDRESS, 16),
+    (IE_DESTINATION_IPV6_ADDRESS, 16),
+    (IE_PROTOCOL_IDENTIFIER, 1),
+    (IE_SOURCE_TRANSPORT_PORT, 2),
+    (IE_DESTINATION_TRANSPORT_PORT, 2),
+    (IE_INGRESS_INTERFACE, 4),
+    (IE_OCTET_DELTA_COUNT, 8),
+    (IE_PACKET_DELTA_COUNT, 8),
+    (IE_FLOW_START_SYS_UP_TIME, 4),
+    (IE_FLOW_END_SYS_UP_TIME, 4),
+];
+
+pub static IPFIX_EXPORTER: Mutex<IpfixExporter> = Mutex::new(IpfixExporter::new());
+
+pub struct IpfixExporter {
+    pub collector: Option<IpEndpoint>,
+    pub observation_domain: u32,
+    /// Number of data records sent so far
+    pub sequence_number: u32,
+    pub messages_sent: u64,
+    last_template_at: Option<Instant>,
+    socket: Option<(String, ServiceSocket)>,
+    /// Flows the socket had no room for, sent again at the next export
+    unsent_flows: Vec<FlowRecord>,
+}
+
+impl IpfixExporter {
+    pub const fn new() -> Self {
+        Self {
+            collector: None,
+            observation_domain: 0,
+            sequence_number: 0,
+            messages_sent: 0,
+            last_template_at: None,
+            socket: None,
+            unsent_flows: Vec::new(),
+        }
+    }
+
+    pub fn set_collector(&mut self, collector: Option<IpEndpoint>) {
+        self.collector = collector;
+        // The new collector has never seen our templates
+        self.last_template_at = None;
+        self.close_socket();
+    }
+
+    fn close_socket(&mut self) {
+        if let Some((_, socket)) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    /// Encode the given flows, after the ones left unsent, into IPFIX messages and send them to the collector
+    fn export(&mut self, expired_flows: Vec<FlowRecord>) {
+        let Some(collector) = self.collector else {
+            self.unsent_flows.clear();
+            return;
+        };
+
+        let mut flows = core::mem::take(&mut self.unsent_flows);
+        flows.extend(expired_flows);
+
+        // The oldest flows are dropped while the collector stays unreachable
+        if flows.len() > MAX_FLOWS {
+            warn!("{} flows dropped, not sent to {}", flows.len() - MAX_FLOWS, collector);
+            flows.drain(..flows.len() - MAX_FLOWS);
+        }
+
+        if flows.is_empty() {
+            return;
+        }
+
+        let now = Clock::now();
+        let send_templates = self.last_template_at
+            .map(|last_template_at| now - last_template_at >= TEMPLATE_REFRESH_INTERVAL)
+            .unwrap_or(true);
+
+        let (ipv4_flows, ipv6_flows): (Vec<&FlowRecord>, Vec<&FlowRecord>) = flows
+            .iter()
+            .partition(|flow| matches!(flow.key.source, IpAddress::Ipv4(_)));
+
+        let mut messages = Vec::new();
+
+        if send_templates {
+            let mut message = self.new_message(now);
+            encode_template_set(&mut message);
+            messages.push((message, Vec::new()));
+            self.last_template_at = Some(now);
+        }
+
+        for (template_id, flows, record_size) in [(IPV4_TEMPLATE_ID, ipv4_flows, template_record_size(&IPV4_TEMPLATE)), (IPV6_TEMPLATE_ID, ipv6_flows, template_record_size(&IPV6_TEMPLATE))] {
+            let records_per_message = (MAX_MESSAGE_SIZE - MESSAGE_HEADER_SIZE - SET_HEADER_SIZE) / record_size;
+
+            for chunk in flows.chunks(records_per_message) {
+                let mut message = self.new_message(now);
+                encode_data_set(&mut message, template_id, chunk);
+                messages.push((message, chunk.to_vec()));
+            }
+        }
+
+        let mut sent_messages = 0;
+
+        while let Some((message, records)) = messages.get_mut(sent_messages) {
+            let length = message.len() as u16;
+            NetworkEndian::write_u16(&mut message[2..4], length);
+            // Data records sent before this message, the lost ones excluded (RFC 7011, 3.1)
+            NetworkEndian::write_u32(&mut message[8..12], self.sequence_number);
+
+            if !self.send(collector, message) {
+                debug!("IPFIX messages to {} delayed, the socket being full or the collector unreachable", collector);
+                // Templates will have to be sent again
+                self.last_template_at = None;
+                break;
+            }
+
+            self.sequence_number = self.sequence_number.wrapping_add(records.len() as u32);
+            self.messages_sent += 1;
+            sent_messages += 1;
+        }
+
+        // Sent again at the next export
+        self.unsent_flows = messages[sent_messages..]
+            .iter()
+            .flat_map(|(_, records)| records.iter().map(|flow| **flow))
+            .collect();
+
+        let exported_flows = flows.len() - self.unsent_flows.len();
+        debug!("Exported {} flows to {}", exported_flows, collector);
+    }
+
+    fn new_message(&self, now: Instant) -> Vec<u8> {
+        let mut message = vec![0u8; MESSAGE_HEADER_SIZE];
+
+        // Seconds since the UNIX epoch, or since boot until the clock is synchronized
+        let export_time = Clock::unix_millis(now).map(|unix_millis| unix_millis / 1000).unwrap_or(now.secs());
+
+        NetworkEndian::write_u16(&mut message[0..2], IPFIX_VERSION);
+        // Length and sequence number are written when the message is sent
+        NetworkEndian::write_u32(&mut message[4..8], export_time as u32);
+        NetworkEndian::write_u32(&mut message[12..16], self.observation_domain);
+
+        message
+    }
+
+    fn send(&mut self, collector: IpEndpoint, message: &[u8]) -> bool {
+        let network_manager = NETWORK_MANAGER.lock();
+
+        let Some(interface_name) = network_manager.find_route_interface(&collector.addr) else {
+            return false;
+        };
+
+        let device = network_manager.interfaces.get(&interface_name).unwrap().clone();
+        drop(network_manager);
+
+        let socket_set = device.lock().sockets.clone();
+
+        // The route to the collector changed, or its interface was deleted and created again
+        if self.socket.as_ref().map(|(socket_interface, socket)| socket_interface != &interface_name || !socket.is_in(&socket_set)).unwrap_or(false) {
+            self.close_socket();
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match &self.socket {
+            Some((_, socket)) => socket.handle,
+            None => {
+                trace!("Opening exporter socket on {}", interface_name);
+
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY], vec![0; 64]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 8], vec![0; 8 * MAX_MESSAGE_SIZE]);
+                let mut socket = Socket::new(rx_buffer, tx_buffer);
+                socket.bind(EXPORTER_PORT).unwrap();
+
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, socket);
+                let handle = socket.handle;
+                self.socket = Some((interface_name, socket));
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+        socket.send_slice(message, collector).is_ok()
+    }
+}
+
+impl Default for IpfixExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn template_record_size(template: &[(u16, u16)]) -> usize {
+    template.iter().map(|(_, length)| *length as usize).sum()
+}
+
+fn encode_template_set(message: &mut Vec<u8>) {
+    let start = message.len();
+    push_u16(message, TEMPLATE_SET_ID);
+    push_u16(message, 0);
+
+    for (template_id, template) in [(IPV4_TEMPLATE_ID, &IPV4_TEMPLATE), (IPV6_TEMPLATE_ID, &IPV6_TEMPLATE)] {
+        push_u16(message, template_id);
+        push_u16(message, template.len() as u16);
+
+        for (element_id, length) in template {
+            push_u16(message, *element_id);
+            push_u16(message, *length);
+        }
+    }
+
+    let length = (message.len() - start) as u16;
+    NetworkEndian::write_u16(&mut message[start + 2..start + 4], length);
+}
+
+fn encode_data_set(message: &mut Vec<u8>, template_id: u16, flows: &[&FlowRecord]) {
+    let start = message.len();
+    push_u16(message, template_id);
+    push_u16(message, 0);
+
+    for flow in flows {
+        match (flow.key.source, flow.key.destination) {
+            (IpAddress::Ipv4(source), IpAddress::Ipv4(destination)) => {
+                message.extend_from_slice(&source.octets());
+                message.extend_from_slice(&destination.octets());
+            },
+            (IpAddress::Ipv6(source), IpAddress::Ipv6(destination)) => {
+                message.extend_from_slice(&source.octets());
+                message.extend_from_slice(&destination.octets());
+            },
+            _ => unreachable!("Flow with mixed address families")
+        }
+
+        message.push(flow.key.protocol);
+        push_u16(message, flow.key.source_port);
+        push_u16(message, flow.key.destination_port);
+        push_u32(message, flow.key.ingress_interface);
+        push_u64(message, flow.bytes);
+        push_u64(message, flow.packets);
+        push_u32(message, flow.first_seen.total_millis() as u32);
+        push_u32(message, flow.last_seen.total_millis() as u32);
+    }
+
+    let length = (message.len() - start) as u16;
+    NetworkEndian::write_u16(&mut message[start + 2..start + 4], length);
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Task periodically exporting the expired flows of the flow cache
+pub async fn export_flows() {
+    loop {
+        Timer::after(EXPORT_INTERVAL).await;
+
+        let expired_flows = interrupts::without_interrupts(|| FLOW_CACHE.lock().expire(Clock::now()));
+
+        IPFIX_EXPORTER.lock().export(expired_flows);
+    }
+}
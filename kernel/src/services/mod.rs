@@ -0,0 +1,14 @@
+pub mod ipfix;
+pub mod snmp;
+pub mod syslog;
+pub mod sntp;
+pub mod slaac;
+pub mod radvd;
+pub mod dhcpv6;
+pub mod tunnel;
+pub mod vxlan;
+pub mod telnet;
+pub mod ssh;
+pub mod tcp;
+pub mod http;
+pub mod iperf;
\ No newline at end of file
@@ -0,0 +1,412 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::multicast::{IPV6_ALL_NODES, IPV6_ALL_ROUTERS};
+use crate::devices::network::slaac::{is_link_local, NDISC_HOP_LIMIT, SLAAC_PREFIX_LENGTH};
+use crate::devices::network::sockets::ServiceSocket;
+use crate::random::KernelRng;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use goolog::{debug, info, warn};
+use rand_core::RngCore;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::raw::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Icmpv6Message, Icmpv6Packet, Icmpv6Repr, IpCidr, IpProtocol, IpVersion, Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr, NdiscOption, NdiscOptionRepr, NdiscPrefixInfoFlags, NdiscPrefixInformation, NdiscRepr, NdiscRouterFlags};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "RADVD";
+
+const TICK: Duration = Duration::from_millis(100);
+
+/// Interval between the unsolicited advertisements, picked at random between these bounds (RFC 4861, 6.2.1)
+const MIN_RTR_ADV_INTERVAL: u64 = 198;
+const MAX_RTR_ADV_INTERVAL: u64 = 600;
+/// The first advertisements are sent faster, so that the hosts configure themselves quickly (RFC 4861, 10)
+const MAX_INITIAL_RTR_ADVERTISEMENTS: u64 = 3;
+const MAX_INITIAL_RTR_ADVERT_INTERVAL: Duration = Duration::from_secs(16);
+/// Delay of the advertisements answering solicitations
+const MAX_RA_DELAY_TIME: u64 = 500;
+const MIN_DELAY_BETWEEN_RAS: Duration = Duration::from_secs(3);
+
+pub const DEFAULT_ROUTER_LIFETIME: Duration = Duration::from_secs(1800);
+/// Largest router lifetime allowed (RFC 4861, 6.2.1)
+pub const MAX_ROUTER_LIFETIME: Duration = Duration::from_secs(9000);
+/// Lifetime of the DNS servers, at least the largest interval between two advertisements (RFC 8106, 5.1)
+const RDNSS_LIFETIME: u32 = 2 * MAX_RTR_ADV_INTERVAL as u32;
+
+/// Hop limit the hosts should use, advertised in every message
+const CUR_HOP_LIMIT: u8 = 64;
+/// Recursive DNS Server option (RFC 8106)
+const NDISC_OPTION_RDNSS: u8 = 25;
+const RA_HEADER_SIZE: usize = 16;
+const RDNSS_HEADER_SIZE: usize = 8;
+
+const SOCKET_PACKETS: usize = 4;
+const PACKET_SIZE: usize = 1280;
+
+pub static RADVD: Mutex<Radvd> = Mutex::new(Radvd::new());
+
+/// Router advertisement daemon, advertising prefixes and options to the hosts of the links (RFC 4861)
+pub struct Radvd {
+    pub interfaces: BTreeMap<String, AdvertisedInterface>,
+}
+
+/// Advertisement configuration of an interface, kept when it is stopped
+pub struct AdvertisedInterface {
+    pub enabled: bool,
+    pub prefixes: Vec<AdvertisedPrefix>,
+    /// Addresses are available with DHCPv6
+    pub managed: bool,
+    /// Other configuration, such as DNS servers, is available with DHCPv6
+    pub other: bool,
+    pub rdnss: Vec<Ipv6Address>,
+    pub mtu: Option<u32>,
+    /// A null lifetime means the router is not a default router
+    pub router_lifetime: Duration,
+    pub advertisements_sent: u64,
+    pub solicitations_received: u64,
+    socket: Option<ServiceSocket>,
+    next_advertisement_at: Instant,
+    last_advertisement_at: Option<Instant>,
+    /// The hosts are told the router is leaving, with a null router lifetime
+    final_advertisement_sent: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdvertisedPrefix {
+    pub cidr: Ipv6Cidr,
+    pub valid_lifetime: Duration,
+    pub preferred_lifetime: Duration,
+}
+
+impl Radvd {
+    pub const fn new() -> Self {
+        Self {
+            interfaces: BTreeMap::new(),
+        }
+    }
+
+    /// Configuration of an interface, created on first use
+    pub fn interface(&mut self, name: &str) -> &mut AdvertisedInterface {
+        self.interfaces.entry(String::from(name)).or_default()
+    }
+
+    /// Drop the configuration of an interface and close its socket
+    pub fn forget(&mut self, name: &str) {
+        if let Some(socket) = self.interfaces.remove(name).and_then(|advertised| advertised.socket) {
+            socket.close();
+        }
+    }
+
+    fn poll(&mut self) {
+        let now = Clock::now();
+
+        for (name, advertised) in self.interfaces.iter_mut() {
+            // Nothing left to send nor to close
+            if !advertised.enabled && advertised.socket.is_none() {
+                continue;
+            }
+
+            let Some(device) = NETWORK_MANAGER.lock().interfaces.get(name).cloned() else {
+                continue;
+            };
+
+            // The network interrupts lock the device as well
+            interrupts::without_interrupts(|| {
+                let mut device = device.lock();
+                advertised.poll(name, &mut device, now);
+            });
+        }
+    }
+}
+
+impl Default for Radvd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdvertisedInterface {
+    pub fn start(&mut self) {
+        self.enabled = true;
+        self.advertisements_sent = 0;
+        self.final_advertisement_sent = false;
+        self.next_advertisement_at = Clock::now();
+    }
+
+    pub fn stop(&mut self) {
+        self.enabled = false;
+    }
+
+    /// Advertise a configuration change right away
+    pub fn advertise_now(&mut self) {
+        self.next_advertisement_at = Clock::now();
+    }
+
+    fn poll(&mut self, name: &str, device: &mut NetworkDevice<'static>, now: Instant) {
+        let socket_set = device.sockets.clone();
+
+        // The interface was deleted and created again, its new socket set lacks the socket
+        if let Some(socket) = self.socket.take_if(|socket| !socket.is_in(&socket_set)) {
+            socket.close();
+
+            if !self.enabled {
+                return;
+            }
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match &self.socket {
+            Some(socket) => socket.handle,
+            None => {
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, Socket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer));
+                let handle = socket.handle;
+
+                // The solicitations are sent to the all-routers group
+                if device.interface.join_multicast_group(IPV6_ALL_ROUTERS).is_err() {
+                    warn!("{}: cannot join the all-routers group, the solicitations will not be answered", name);
+                }
+
+                device.update_multicast_filter();
+
+                info!("{}: advertising", name);
+                self.socket = Some(socket);
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        if !self.enabled {
+            // Closed once the final advertisement left
+            if self.final_advertisement_sent && socket.send_queue() == 0 {
+                drop(sockets);
+
+                if let Some(socket) = self.socket.take() {
+                    socket.close();
+                }
+
+                let _ = device.interface.leave_multicast_group(IPV6_ALL_ROUTERS);
+                device.update_multicast_filter();
+
+                info!("{}: stopped advertising", name);
+                return;
+            }
+        }
+
+        let mut solicited = false;
+
+        while let Ok(packet) = socket.recv() {
+            if is_router_solicitation(packet) {
+                self.solicitations_received += 1;
+                solicited = true;
+            }
+        }
+
+        if solicited && self.enabled {
+            // Answered after a random delay, without flooding the link (RFC 4861, 6.2.6)
+            let delay = Duration::from_millis(KernelRng.next_u64() % MAX_RA_DELAY_TIME);
+            let earliest = match self.last_advertisement_at {
+                Some(last_advertisement_at) => (last_advertisement_at + MIN_DELAY_BETWEEN_RAS).max(now + delay),
+                None => now + delay
+            };
+
+            self.next_advertisement_at = self.next_advertisement_at.min(earliest);
+        }
+
+        let is_final = !self.enabled && !self.final_advertisement_sent;
+
+        if now < self.next_advertisement_at && !is_final {
+            return;
+        }
+
+        let link_local = device.interface
+            .ip_addrs()
+            .iter()
+            .find_map(|cidr| match cidr {
+                IpCidr::Ipv6(cidr) if is_link_local(cidr.address()) => Some(cidr.address()),
+                _ => None
+            });
+
+        // The advertisements must come from a link-local address (RFC 4861, 4.2).
+        // Nothing to tell the hosts of a link which is down.
+        let (Some(link_local), true) = (link_local, device.network_controller.enabled) else {
+            self.final_advertisement_sent |= is_final;
+            self.next_advertisement_at = now + MIN_DELAY_BETWEEN_RAS;
+            return;
+        };
+
+        let Some(hardware_addr) = device.mac() else {
+            self.next_advertisement_at = now + MIN_DELAY_BETWEEN_RAS;
+            return;
+        };
+
+        let router_lifetime = if is_final { Duration::ZERO } else { self.router_lifetime };
+        let packet = self.advertisement(link_local, hardware_addr, router_lifetime);
+
+        if socket.send_slice(&packet).is_err() {
+            debug!("{}: advertisement dropped, the socket is full", name);
+            self.next_advertisement_at = now + MIN_DELAY_BETWEEN_RAS;
+            return;
+        }
+
+        if is_final {
+            self.final_advertisement_sent = true;
+            return;
+        }
+
+        self.advertisements_sent += 1;
+        self.last_advertisement_at = Some(now);
+
+        let interval = Duration::from_secs(MIN_RTR_ADV_INTERVAL + KernelRng.next_u64() % (MAX_RTR_ADV_INTERVAL - MIN_RTR_ADV_INTERVAL));
+
+        self.next_advertisement_at = match self.advertisements_sent <= MAX_INITIAL_RTR_ADVERTISEMENTS {
+            true => now + interval.min(MAX_INITIAL_RTR_ADVERT_INTERVAL),
+            false => now + interval
+        };
+    }
+
+    /// Router advertisement and its IPv6 header, to the all-nodes group.
+    /// Built by hand, smoltcp only emitting a single prefix and no DNS server.
+    fn advertisement(&self, src_addr: Ipv6Address, hardware_addr: EthernetAddress, router_lifetime: Duration) -> Vec<u8> {
+        let mut options = vec![NdiscOptionRepr::SourceLinkLayerAddr(hardware_addr.into())];
+
+        if let Some(mtu) = self.mtu {
+            options.push(NdiscOptionRepr::Mtu(mtu));
+        }
+
+        for prefix in &self.prefixes {
+            // The hosts only build addresses from /64 prefixes
+            let flags = match prefix.cidr.prefix_len() == SLAAC_PREFIX_LENGTH {
+                true => NdiscPrefixInfoFlags::ON_LINK | NdiscPrefixInfoFlags::ADDRCONF,
+                false => NdiscPrefixInfoFlags::ON_LINK
+            };
+
+            options.push(NdiscOptionRepr::PrefixInformation(NdiscPrefixInformation {
+                prefix_len: prefix.cidr.prefix_len(),
+                flags,
+                valid_lifetime: prefix.valid_lifetime,
+                preferred_lifetime: prefix.preferred_lifetime,
+                prefix: prefix.cidr.address(),
+            }));
+        }
+
+        let rdnss_len = match self.rdnss.is_empty() {
+            true => 0,
+            false => RDNSS_HEADER_SIZE + 16 * self.rdnss.len()
+        };
+
+        let options_len: usize = options.iter().map(|option| option.buffer_len()).sum();
+        let icmp_len = RA_HEADER_SIZE + options_len + rdnss_len;
+
+        let ip_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: IPV6_ALL_NODES,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_len,
+            hop_limit: NDISC_HOP_LIMIT,
+        };
+
+        let mut buffer = vec![0u8; ip_repr.buffer_len() + icmp_len];
+        let mut ip_packet = Ipv6Packet::new_unchecked(&mut buffer);
+        ip_repr.emit(&mut ip_packet);
+
+        let mut icmp_packet = Icmpv6Packet::new_unchecked(ip_packet.payload_mut());
+        icmp_packet.set_msg_type(Icmpv6Message::RouterAdvert);
+        icmp_packet.set_msg_code(0);
+        icmp_packet.set_current_hop_limit(CUR_HOP_LIMIT);
+        icmp_packet.set_router_flags(self.router_flags());
+        icmp_packet.set_router_lifetime(router_lifetime);
+        icmp_packet.set_reachable_time(Duration::ZERO);
+        icmp_packet.set_retrans_time(Duration::ZERO);
+
+        let payload = icmp_packet.payload_mut();
+        let mut offset = 0;
+
+        for option in &options {
+            let option_len = option.buffer_len();
+            option.emit(&mut NdiscOption::new_unchecked(&mut payload[offset..offset + option_len]));
+            offset += option_len;
+        }
+
+        if !self.rdnss.is_empty() {
+            let rdnss = &mut payload[offset..offset + rdnss_len];
+            rdnss[0] = NDISC_OPTION_RDNSS;
+            // In units of 8 octets
+            rdnss[1] = (rdnss_len / 8) as u8;
+            NetworkEndian::write_u32(&mut rdnss[4..8], RDNSS_LIFETIME);
+
+            for (index, server) in self.rdnss.iter().enumerate() {
+                let start = RDNSS_HEADER_SIZE + 16 * index;
+                rdnss[start..start + 16].copy_from_slice(&server.octets());
+            }
+        }
+
+        icmp_packet.fill_checksum(&src_addr, &IPV6_ALL_NODES);
+
+        buffer
+    }
+
+    fn router_flags(&self) -> NdiscRouterFlags {
+        let mut flags = NdiscRouterFlags::empty();
+        flags.set(NdiscRouterFlags::MANAGED, self.managed);
+        flags.set(NdiscRouterFlags::OTHER, self.other);
+        flags
+    }
+}
+
+impl Default for AdvertisedInterface {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prefixes: Vec::new(),
+            managed: false,
+            other: false,
+            rdnss: Vec::new(),
+            mtu: None,
+            router_lifetime: DEFAULT_ROUTER_LIFETIME,
+            advertisements_sent: 0,
+            solicitations_received: 0,
+            socket: None,
+            next_advertisement_at: Instant::ZERO,
+            last_advertisement_at: None,
+            final_advertisement_sent: false,
+        }
+    }
+}
+
+/// Router solicitation sent from the link
+fn is_router_solicitation(packet: &[u8]) -> bool {
+    let Ok(ip_packet) = Ipv6Packet::new_checked(packet) else {
+        return false;
+    };
+
+    if ip_packet.hop_limit() != NDISC_HOP_LIMIT {
+        return false;
+    }
+
+    let Ok(icmp_packet) = Icmpv6Packet::new_checked(ip_packet.payload()) else {
+        return false;
+    };
+
+    matches!(
+        Icmpv6Repr::parse(&ip_packet.src_addr(), &ip_packet.dst_addr(), &icmp_packet, &ChecksumCapabilities::default()),
+        Ok(Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit { .. }))
+    )
+}
+
+/// Task sending the router advertisements of the interfaces
+pub async fn advertise_routers() {
+    loop {
+        Timer::after(TICK).await;
+        RADVD.lock().poll();
+    }
+}
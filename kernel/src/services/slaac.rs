@@ -0,0 +1,393 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::multicast::{solicited_node, IPV6_ALL_ROUTERS};
+use crate::devices::network::slaac::{is_link_local, slaac_address, AddressOrigin, AddressState, AutoconfiguredAddress, NDISC_HOP_LIMIT, SLAAC_PREFIX_LENGTH};
+use crate::devices::network::sockets::ServiceSocket;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use goolog::{debug, info, warn};
+use smoltcp::iface::Route;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::raw::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, Icmpv6Packet, Icmpv6Repr, IpAddress, IpCidr, IpProtocol, IpVersion, Ipv6Address, Ipv6Cidr, Ipv6Packet, Ipv6Repr, NdiscPrefixInfoFlags, NdiscPrefixInformation, NdiscRepr};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "SLAAC";
+
+const TICK: Duration = Duration::from_millis(100);
+
+const IPV6_DEFAULT: Ipv6Cidr = Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0);
+
+/// Router solicitations sent when an interface comes up (RFC 4861, 10)
+const MAX_RTR_SOLICITATIONS: u8 = 3;
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+/// Time waited for another host to answer the neighbor solicitation of a tentative address (RFC 4862, 5.4)
+const DAD_DURATION: Duration = Duration::from_secs(1);
+/// Lifetime below which an advertisement cannot cut the one of an address (RFC 4862, 5.5.3)
+const TWO_HOURS: Duration = Duration::from_secs(2 * 60 * 60);
+const INFINITE_LIFETIME: Duration = Duration::from_secs(0xFFFF_FFFF);
+
+const SOCKET_PACKETS: usize = 4;
+/// Largest neighbor discovery message read, the IPv6 minimum MTU
+const PACKET_SIZE: usize = 1280;
+
+pub static SLAAC: Mutex<Slaac> = Mutex::new(Slaac::new());
+
+/// Stateless address autoconfiguration (RFC 4862) of the interfaces, from the router advertisements
+pub struct Slaac {
+    interfaces: BTreeMap<String, SlaacInterface>,
+}
+
+struct SlaacInterface {
+    /// Raw ICMPv6 socket receiving the neighbor discovery messages
+    socket: ServiceSocket,
+    was_enabled: bool,
+    solicitations_sent: u8,
+    next_solicitation_at: Instant,
+}
+
+impl Slaac {
+    pub const fn new() -> Self {
+        Self {
+            interfaces: BTreeMap::new(),
+        }
+    }
+
+    /// Drop the state of an interface and close its socket
+    pub fn forget(&mut self, name: &str) {
+        if let Some(state) = self.interfaces.remove(name) {
+            state.socket.close();
+        }
+    }
+
+    fn poll(&mut self) {
+        let devices: Vec<_> = NETWORK_MANAGER
+            .lock()
+            .interfaces
+            .iter()
+            .map(|(name, device)| (name.clone(), device.clone()))
+            .collect();
+
+        let now = Clock::now();
+
+        for (name, device) in devices {
+            // The network interrupts lock the device as well
+            interrupts::without_interrupts(|| {
+                let mut device = device.lock();
+                self.poll_interface(&name, &mut device, now);
+            });
+        }
+    }
+
+    fn poll_interface(&mut self, name: &str, device: &mut NetworkDevice<'static>, now: Instant) {
+        let socket_set = device.sockets.clone();
+
+        // The interface was deleted and created again, it starts over
+        if self.interfaces.get(name).is_some_and(|state| !state.socket.is_in(&socket_set)) {
+            self.forget(name);
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let state = self.interfaces.entry(String::from(name)).or_insert_with(|| {
+            let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+            let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * PACKET_SIZE]);
+
+            SlaacInterface {
+                socket: ServiceSocket::add(&socket_set, &mut sockets, Socket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer)),
+                was_enabled: false,
+                solicitations_sent: 0,
+                next_solicitation_at: now,
+            }
+        });
+
+        let enabled = device.network_controller.enabled;
+
+        // The routers are solicited again each time the interface comes up
+        if enabled && !state.was_enabled {
+            state.solicitations_sent = 0;
+            state.next_solicitation_at = now;
+        }
+
+        state.was_enabled = enabled;
+
+        // Without a link layer, the interface identifiers cannot be built from a MAC address
+        let Some(hardware_addr) = device.mac() else {
+            return;
+        };
+
+        let socket = sockets.get_mut::<Socket>(state.socket.handle);
+
+        let mut received = Vec::new();
+        let mut buffer = [0u8; PACKET_SIZE];
+
+        while let Ok(length) = socket.recv_slice(&mut buffer) {
+            received.push(buffer[..length].to_vec());
+        }
+
+        let mut outbox = Vec::new();
+
+        for packet in received {
+            let Ok(ip_packet) = Ipv6Packet::new_checked(&packet[..]) else {
+                continue;
+            };
+
+            if ip_packet.hop_limit() != NDISC_HOP_LIMIT {
+                continue;
+            }
+
+            let (src_addr, dst_addr) = (ip_packet.src_addr(), ip_packet.dst_addr());
+
+            let Ok(icmp_packet) = Icmpv6Packet::new_checked(ip_packet.payload()) else {
+                continue;
+            };
+
+            let Ok(Icmpv6Repr::Ndisc(repr)) = Icmpv6Repr::parse(&src_addr, &dst_addr, &icmp_packet, &ChecksumCapabilities::default()) else {
+                continue;
+            };
+
+            match repr {
+                // The routers advertise from their link-local address (RFC 4861, 6.1.2)
+                NdiscRepr::RouterAdvert { router_lifetime, prefix_info, .. } if is_link_local(src_addr) => {
+                    state.solicitations_sent = MAX_RTR_SOLICITATIONS;
+
+                    update_default_route(name, device, src_addr, router_lifetime, now);
+
+                    if let Some(prefix_info) = prefix_info {
+                        if let Some(solicitation) = update_prefix(name, device, hardware_addr, prefix_info, now) {
+                            outbox.push(solicitation);
+                        }
+                    }
+                },
+                NdiscRepr::NeighborAdvert { target_addr, .. } => detect_duplicate(name, device, target_addr),
+                _ => {}
+            }
+        }
+
+        update_addresses(name, device, now);
+
+        let link_local = device.autoconfigured_addresses
+            .iter()
+            .find(|address| address.origin == AddressOrigin::LinkLocal && address.state == AddressState::Preferred)
+            .map(|address| address.cidr.address());
+
+        if let Some(link_local) = link_local.filter(|_| enabled) {
+            if state.solicitations_sent < MAX_RTR_SOLICITATIONS && now >= state.next_solicitation_at {
+                debug!("{}: soliciting the routers", name);
+
+                let solicitation = NdiscRepr::RouterSolicit {
+                    lladdr: Some(hardware_addr.into()),
+                };
+
+                outbox.push(ndisc_packet(link_local, IPV6_ALL_ROUTERS, solicitation));
+                state.solicitations_sent += 1;
+                state.next_solicitation_at = now + RTR_SOLICITATION_INTERVAL;
+            }
+        }
+
+        let socket = sockets.get_mut::<Socket>(state.socket.handle);
+
+        for packet in outbox {
+            // Sent again by the next solicitation or advertisement
+            let _ = socket.send_slice(&packet);
+        }
+    }
+}
+
+impl Default for Slaac {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add, refresh or withdraw the default route through an advertising router
+fn update_default_route(name: &str, device: &mut NetworkDevice<'static>, router: Ipv6Address, router_lifetime: Duration, now: Instant) {
+    let via_router = IpAddress::Ipv6(router);
+    let mut is_known = false;
+
+    device.interface
+        .routes_mut()
+        .update(|routes| {
+            routes.retain(|route| {
+                let is_router_route = route.cidr == IpCidr::Ipv6(IPV6_DEFAULT) && route.via_router == via_router;
+                is_known |= is_router_route;
+                !is_router_route
+            });
+
+            // A null lifetime means the router is not a default router anymore
+            if router_lifetime > Duration::ZERO {
+                let route = Route {
+                    cidr: IpCidr::Ipv6(IPV6_DEFAULT),
+                    via_router,
+                    preferred_until: None,
+                    expires_at: Some(now + router_lifetime),
+                };
+
+                if routes.push(route).is_err() {
+                    warn!("{}: no room left for the default route through {}", name, router);
+                }
+            }
+        });
+
+    match (is_known, router_lifetime > Duration::ZERO) {
+        (false, true) => info!("{}: default route through {}", name, router),
+        (true, false) => info!("{}: {} is not a default router anymore", name, router),
+        _ => debug!("{}: default route through {} refreshed", name, router)
+    }
+}
+
+/// Autoconfigure an address from an advertised prefix, or refresh its lifetimes (RFC 4862, 5.5.3).
+/// Returns the neighbor solicitation of the duplicate address detection of a new address.
+fn update_prefix(name: &str, device: &mut NetworkDevice<'static>, hardware_addr: EthernetAddress, prefix_info: NdiscPrefixInformation, now: Instant) -> Option<Vec<u8>> {
+    let NdiscPrefixInformation { prefix_len, flags, valid_lifetime, preferred_lifetime, prefix } = prefix_info;
+
+    if !flags.contains(NdiscPrefixInfoFlags::ADDRCONF) || is_link_local(prefix) || prefix_len != SLAAC_PREFIX_LENGTH || preferred_lifetime > valid_lifetime {
+        return None;
+    }
+
+    let address = slaac_address(prefix, hardware_addr.0);
+    let preferred_until = lifetime_end(now, preferred_lifetime);
+
+    let Some(existing) = device.autoconfigured_addresses.iter_mut().find(|existing| existing.cidr.address() == address) else {
+        if valid_lifetime == Duration::ZERO {
+            return None;
+        }
+
+        debug!("{}: probing {}", name, address);
+
+        device.autoconfigured_addresses.push(AutoconfiguredAddress {
+            cidr: Ipv6Cidr::new(address, SLAAC_PREFIX_LENGTH),
+            origin: AddressOrigin::Slaac,
+            state: AddressState::Tentative,
+            tentative_until: Some(now + DAD_DURATION),
+            preferred_until,
+            valid_until: lifetime_end(now, valid_lifetime),
+        });
+
+        // Sent from the unspecified address, the tentative one not being usable yet (RFC 4862, 5.4.2)
+        let solicitation = NdiscRepr::NeighborSolicit {
+            target_addr: address,
+            lladdr: None,
+        };
+
+        return Some(ndisc_packet(Ipv6Address::UNSPECIFIED, solicited_node(address), solicitation));
+    };
+
+    let remaining_lifetime = existing.valid_until.map(|valid_until| match valid_until > now {
+        true => valid_until - now,
+        false => Duration::ZERO
+    });
+
+    // An unauthenticated advertisement cannot cut the valid lifetime below two hours
+    if valid_lifetime > TWO_HOURS || remaining_lifetime.is_some_and(|remaining_lifetime| valid_lifetime > remaining_lifetime) {
+        existing.valid_until = lifetime_end(now, valid_lifetime);
+    }
+    else if remaining_lifetime.is_none_or(|remaining_lifetime| remaining_lifetime > TWO_HOURS) {
+        existing.valid_until = Some(now + TWO_HOURS);
+    }
+
+    existing.preferred_until = preferred_until;
+
+    if existing.state == AddressState::Deprecated && preferred_until.is_none_or(|preferred_until| preferred_until > now) {
+        info!("{}: {} is preferred again", name, address);
+        existing.state = AddressState::Preferred;
+    }
+
+    None
+}
+
+/// A neighbor advertisement for a tentative address means another host uses it
+fn detect_duplicate(name: &str, device: &mut NetworkDevice<'static>, target_addr: Ipv6Address) {
+    for address in device.autoconfigured_addresses.iter_mut() {
+        if address.state == AddressState::Tentative && address.cidr.address() == target_addr {
+            warn!("{}: {} is already used on the link, it will not be configured", name, target_addr);
+            address.state = AddressState::Duplicate;
+        }
+    }
+}
+
+/// Configure the addresses past their duplicate address detection, then deprecate and remove the ones past their lifetimes
+fn update_addresses(name: &str, device: &mut NetworkDevice<'static>, now: Instant) {
+    let addresses = core::mem::take(&mut device.autoconfigured_addresses);
+    let mut kept = Vec::with_capacity(addresses.len());
+
+    for mut address in addresses {
+        let is_configured = matches!(address.state, AddressState::Preferred | AddressState::Deprecated);
+
+        if address.valid_until.is_some_and(|valid_until| valid_until <= now) {
+            if is_configured {
+                device.remove_ip_address(IpCidr::Ipv6(address.cidr));
+            }
+
+            info!("{}: {} expired", name, address.cidr);
+            continue;
+        }
+
+        match address.state {
+            AddressState::Tentative if address.tentative_until.is_some_and(|tentative_until| tentative_until <= now) => {
+                if !device.add_ip_address(IpCidr::Ipv6(address.cidr)) {
+                    warn!("{}: no room left for {}", name, address.cidr);
+                    continue;
+                }
+
+                info!("{}: {} autoconfigured", name, address.cidr);
+                address.tentative_until = None;
+                address.state = AddressState::Preferred;
+            },
+            AddressState::Preferred if address.preferred_until.is_some_and(|preferred_until| preferred_until <= now) => {
+                info!("{}: {} deprecated", name, address.cidr);
+                address.state = AddressState::Deprecated;
+            },
+            _ => {}
+        }
+
+        kept.push(address);
+    }
+
+    device.autoconfigured_addresses = kept;
+
+    device.interface
+        .routes_mut()
+        .update(|routes| routes.retain(|route| route.expires_at.is_none_or(|expires_at| expires_at > now)));
+}
+
+/// Neighbor discovery message and its IPv6 header, for the raw socket
+fn ndisc_packet(src_addr: Ipv6Address, dst_addr: Ipv6Address, repr: NdiscRepr) -> Vec<u8> {
+    let icmp_repr = Icmpv6Repr::Ndisc(repr);
+
+    let ip_repr = Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: NDISC_HOP_LIMIT,
+    };
+
+    let mut buffer = vec![0u8; ip_repr.buffer_len() + icmp_repr.buffer_len()];
+    let mut ip_packet = Ipv6Packet::new_unchecked(&mut buffer);
+    ip_repr.emit(&mut ip_packet);
+    icmp_repr.emit(&src_addr, &dst_addr, &mut Icmpv6Packet::new_unchecked(ip_packet.payload_mut()), &ChecksumCapabilities::default());
+
+    buffer
+}
+
+/// `None` for an infinite lifetime
+fn lifetime_end(now: Instant, lifetime: Duration) -> Option<Instant> {
+    match lifetime {
+        INFINITE_LIFETIME => None,
+        lifetime => Some(now + lifetime)
+    }
+}
+
+/// Task autoconfiguring the IPv6 addresses and default routes of the interfaces
+pub async fn autoconfigure_addresses() {
+    loop {
+        Timer::after(TICK).await;
+        SLAAC.lock().poll();
+    }
+}
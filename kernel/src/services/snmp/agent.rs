@@ -0,0 +1,340 @@
+use crate::clock::Timer;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::{close_stale_sockets, ServiceSocket};
+use crate::services::snmp::ber::{encode_constructed, encode_integer, encode_oid, encode_tlv, encode_value, BerError, BerReader, BerValue, Oid, TAG_OCTET_STRING, TAG_SEQUENCE};
+use crate::services::snmp::mib::Mib;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use goolog::{debug, trace};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket, UdpMetadata};
+use smoltcp::time::Duration;
+use spin::{Lazy, Mutex};
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "SNMP";
+
+pub const SNMP_PORT: u16 = 161;
+
+const SNMP_VERSION_2C: i64 = 1;
+
+// PDU tags (RFC 3416)
+const PDU_GET_REQUEST: u8 = 0xA0;
+const PDU_GET_NEXT_REQUEST: u8 = 0xA1;
+const PDU_RESPONSE: u8 = 0xA2;
+const PDU_SET_REQUEST: u8 = 0xA3;
+const PDU_GET_BULK_REQUEST: u8 = 0xA5;
+
+// Error status
+const ERROR_NO_ERROR: i64 = 0;
+const ERROR_NOT_WRITABLE: i64 = 17;
+
+const MAX_MESSAGE_SIZE: usize = 1472;
+/// Margin kept for the message header when filling GETBULK responses
+const MESSAGE_HEADER_MARGIN: usize = 64;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub static SNMP_AGENT: Lazy<Mutex<SnmpAgent>> = Lazy::new(|| Mutex::new(SnmpAgent::new()));
+
+pub struct SnmpAgent {
+    /// Read-only community strings
+    pub communities: Vec<String>,
+    pub sys_description: String,
+    pub sys_contact: String,
+    pub sys_name: String,
+    pub sys_location: String,
+    pub requests: u64,
+    pub bad_communities: u64,
+    pub parse_errors: u64,
+    sockets: BTreeMap<String, ServiceSocket>,
+}
+
+struct Request {
+    pdu_type: u8,
+    request_id: i64,
+    /// Error status, or non-repeaters for GETBULK
+    field_1: i64,
+    /// Error index, or max-repetitions for GETBULK
+    field_2: i64,
+    varbinds: Vec<(Oid, BerValue)>,
+}
+
+impl SnmpAgent {
+    pub fn new() -> Self {
+        Self {
+            communities: vec![String::from("public")],
+            sys_description: String::from("RetOS 0.1.0 - A Router Network Operating System"),
+            sys_contact: String::new(),
+            sys_name: String::from("RetOS"),
+            sys_location: String::new(),
+            requests: 0,
+            bad_communities: 0,
+            parse_errors: 0,
+            sockets: BTreeMap::new(),
+        }
+    }
+
+    /// Answer the pending requests of every interface
+    fn poll(&mut self) {
+        let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+        let mut mib = None;
+
+        close_stale_sockets(&mut self.sockets, &socket_sets);
+
+        for (interface_name, socket_set) in socket_sets {
+            // The set is not kept locked while the requests are handled, the MIB snapshot locking the interfaces
+            let (handle, requests) = interrupts::without_interrupts(|| {
+                let mut sockets = socket_set.lock();
+
+                let handle = self.sockets
+                    .entry(interface_name)
+                    .or_insert_with(|| {
+                        let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 4 * MAX_MESSAGE_SIZE]);
+                        let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 4 * MAX_MESSAGE_SIZE]);
+                        let mut socket = Socket::new(rx_buffer, tx_buffer);
+                        socket.bind(SNMP_PORT).unwrap();
+                        ServiceSocket::add(&socket_set, &mut sockets, socket)
+                    })
+                    .handle;
+
+                let socket = sockets.get_mut::<Socket>(handle);
+                let mut requests = Vec::new();
+
+                // As many requests as there is room for their responses
+                let room = (socket.payload_send_capacity() - socket.send_queue()) / MAX_MESSAGE_SIZE;
+
+                while socket.can_recv() && requests.len() < room {
+                    match socket.recv() {
+                        Ok((payload, metadata)) => requests.push((payload.to_vec(), metadata)),
+                        Err(_) => break
+                    }
+                }
+
+                (handle, requests)
+            });
+
+            if requests.is_empty() {
+                continue;
+            }
+
+            // The MIB snapshot is only built when needed, and once per poll
+            let mib = mib.get_or_insert_with(|| Mib::snapshot(self));
+            let mut responses = Vec::new();
+
+            for (message, metadata) in requests {
+                trace!("Request from {}", metadata.endpoint);
+
+                match self.handle_message(&message, mib) {
+                    Ok(Some(response)) => {
+                        let mut response_metadata = UdpMetadata::from(metadata.endpoint);
+                        response_metadata.local_address = metadata.local_address;
+                        responses.push((response, response_metadata));
+                    },
+                    Ok(None) => {},
+                    Err(error) => {
+                        self.parse_errors += 1;
+                        debug!("Malformed request from {}: {}", metadata.endpoint, error);
+                    }
+                }
+            }
+
+            interrupts::without_interrupts(|| {
+                let mut sockets = socket_set.lock();
+                let socket = sockets.get_mut::<Socket>(handle);
+
+                for (response, metadata) in responses {
+                    if socket.send_slice(&response, metadata).is_err() {
+                        debug!("Could not send response to {}", metadata.endpoint);
+                    }
+                }
+            });
+        }
+    }
+
+    fn handle_message(&mut self, message: &[u8], mib: &Mib) -> Result<Option<Vec<u8>>, BerError> {
+        let mut reader = BerReader::new(message);
+        let mut message = reader.read_sequence()?;
+
+        let version = message.read_integer()?;
+        let community = message.read_octet_string()?;
+
+        // Only SNMPv2c is supported
+        if version != SNMP_VERSION_2C {
+            return Ok(None);
+        }
+
+        if !self.communities.iter().any(|allowed| allowed.as_bytes() == community) {
+            self.bad_communities += 1;
+            return Ok(None);
+        }
+
+        self.requests += 1;
+
+        let request = parse_pdu(&mut message)?;
+
+        let (error_status, error_index, varbinds) = match request.pdu_type {
+            PDU_GET_REQUEST => {
+                let varbinds = request.varbinds
+                    .into_iter()
+                    .map(|(oid, _)| {
+                        let value = mib.get(&oid);
+                        (oid, value)
+                    })
+                    .collect();
+
+                (ERROR_NO_ERROR, 0, varbinds)
+            },
+            PDU_GET_NEXT_REQUEST => {
+                let varbinds = request.varbinds
+                    .into_iter()
+                    .map(|(oid, _)| get_next_or_end(mib, oid))
+                    .collect();
+
+                (ERROR_NO_ERROR, 0, varbinds)
+            },
+            PDU_GET_BULK_REQUEST => {
+                let varbinds = get_bulk(mib, request.varbinds, request.field_1, request.field_2);
+                (ERROR_NO_ERROR, 0, varbinds)
+            },
+            PDU_SET_REQUEST => {
+                // Communities are read-only
+                (ERROR_NOT_WRITABLE, 1, request.varbinds)
+            },
+            _ => return Ok(None)
+        };
+
+        Ok(Some(encode_response(community, request.request_id, error_status, error_index, &varbinds)))
+    }
+}
+
+impl Default for SnmpAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_pdu(message: &mut BerReader) -> Result<Request, BerError> {
+    let pdu_type = message.peek_tag()?;
+    let mut pdu = message.read_constructed(pdu_type)?;
+
+    let request_id = pdu.read_integer()?;
+    let field_1 = pdu.read_integer()?;
+    let field_2 = pdu.read_integer()?;
+
+    let mut varbind_list = pdu.read_sequence()?;
+    let mut varbinds = Vec::new();
+
+    while !varbind_list.is_empty() {
+        let mut varbind = varbind_list.read_sequence()?;
+        let oid = varbind.read_oid()?;
+        let value = varbind.read_value()?;
+        varbinds.push((oid, value));
+    }
+
+    Ok(Request {
+        pdu_type,
+        request_id,
+        field_1,
+        field_2,
+        varbinds,
+    })
+}
+
+fn get_next_or_end(mib: &Mib, oid: Oid) -> (Oid, BerValue) {
+    match mib.get_next(&oid) {
+        Some(next) => next,
+        None => (oid, BerValue::EndOfMibView)
+    }
+}
+
+/// GETBULK processing (RFC 3416, 4.2.3)
+fn get_bulk(mib: &Mib, requested: Vec<(Oid, BerValue)>, non_repeaters: i64, max_repetitions: i64) -> Vec<(Oid, BerValue)> {
+    let non_repeaters = (non_repeaters.max(0) as usize).min(requested.len());
+    let max_repetitions = max_repetitions.max(0) as usize;
+
+    let mut varbinds = Vec::new();
+    let mut size = 0;
+
+    let mut requested = requested.into_iter().map(|(oid, _)| oid);
+
+    for oid in requested.by_ref().take(non_repeaters) {
+        let varbind = get_next_or_end(mib, oid);
+        size += varbind_size(&varbind);
+        varbinds.push(varbind);
+    }
+
+    let mut repeaters: Vec<Oid> = requested.collect();
+
+    'repetitions: for _ in 0..max_repetitions {
+        let mut all_ended = true;
+
+        for oid in repeaters.iter_mut() {
+            let varbind = get_next_or_end(mib, oid.clone());
+
+            // Responses are truncated rather than rejected as too big
+            size += varbind_size(&varbind);
+            if size > MAX_MESSAGE_SIZE - MESSAGE_HEADER_MARGIN {
+                break 'repetitions;
+            }
+
+            if varbind.1 != BerValue::EndOfMibView {
+                all_ended = false;
+            }
+
+            *oid = varbind.0.clone();
+            varbinds.push(varbind);
+        }
+
+        if all_ended {
+            break;
+        }
+    }
+
+    varbinds
+}
+
+fn varbind_size(varbind: &(Oid, BerValue)) -> usize {
+    let mut buffer = Vec::new();
+    encode_varbind(&mut buffer, varbind);
+    buffer.len()
+}
+
+fn encode_varbind(buffer: &mut Vec<u8>, (oid, value): &(Oid, BerValue)) {
+    encode_constructed(buffer, TAG_SEQUENCE, |varbind| {
+        encode_oid(varbind, oid);
+        encode_value(varbind, value);
+    });
+}
+
+fn encode_response(community: &[u8], request_id: i64, error_status: i64, error_index: i64, varbinds: &[(Oid, BerValue)]) -> Vec<u8> {
+    let mut response = Vec::new();
+
+    encode_constructed(&mut response, TAG_SEQUENCE, |message| {
+        encode_integer(message, SNMP_VERSION_2C);
+        encode_tlv(message, TAG_OCTET_STRING, community);
+
+        encode_constructed(message, PDU_RESPONSE, |pdu| {
+            encode_integer(pdu, request_id);
+            encode_integer(pdu, error_status);
+            encode_integer(pdu, error_index);
+
+            encode_constructed(pdu, TAG_SEQUENCE, |varbind_list| {
+                for varbind in varbinds {
+                    encode_varbind(varbind_list, varbind);
+                }
+            });
+        });
+    });
+
+    response
+}
+
+/// Task answering the SNMP requests received on every interface
+pub async fn snmp_agent() {
+    loop {
+        Timer::after(POLL_INTERVAL).await;
+        SNMP_AGENT.lock().poll();
+    }
+}
@@ -0,0 +1,356 @@
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use thiserror::Error;
+
+// Universal tags
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OBJECT_IDENTIFIER: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+// SNMP application tags (RFC 2578)
+pub const TAG_IP_ADDRESS: u8 = 0x40;
+pub const TAG_COUNTER32: u8 = 0x41;
+pub const TAG_GAUGE32: u8 = 0x42;
+pub const TAG_TIME_TICKS: u8 = 0x43;
+pub const TAG_OPAQUE: u8 = 0x44;
+pub const TAG_COUNTER64: u8 = 0x46;
+
+// SNMPv2 exceptions (RFC 3416)
+pub const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+pub const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+pub const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+#[derive(Error, Debug)]
+pub enum BerError {
+    #[error("unexpected end of data")]
+    Truncated,
+
+    #[error("expected tag 0x{expected:02X}, found 0x{found:02X}")]
+    UnexpectedTag { expected: u8, found: u8 },
+
+    #[error("invalid length")]
+    InvalidLength,
+
+    #[error("invalid value")]
+    InvalidValue,
+}
+
+/// Object identifier, ordered lexicographically like the MIB tree
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Oid(pub Vec<u32>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BerValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    ObjectIdentifier(Oid),
+    IpAddress([u8; 4]),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl Oid {
+    pub fn new(arcs: &[u32]) -> Self {
+        Oid(arcs.to_vec())
+    }
+
+    /// Create a child OID by appending the given arcs
+    pub fn with(&self, arcs: &[u32]) -> Self {
+        let mut oid = self.0.clone();
+        oid.extend_from_slice(arcs);
+        Oid(oid)
+    }
+
+    pub fn starts_with(&self, prefix: &Oid) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+}
+
+impl Display for Oid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (index, arc) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", arc)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads TLVs sequentially from a BER encoded buffer
+pub struct BerReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    pub fn peek_tag(&self) -> Result<u8, BerError> {
+        self.data.get(self.position).copied().ok_or(BerError::Truncated)
+    }
+
+    /// Read the next TLV, returning its tag and its content
+    pub fn read_tlv(&mut self) -> Result<(u8, &'a [u8]), BerError> {
+        let tag = self.read_byte()?;
+        let first_length_byte = self.read_byte()?;
+
+        let length = match first_length_byte {
+            0x00..=0x7F => first_length_byte as usize,
+            0x81..=0x84 => {
+                let mut length = 0usize;
+                for _ in 0..(first_length_byte & 0x7F) {
+                    length = (length << 8) | self.read_byte()? as usize;
+                }
+                length
+            },
+            _ => return Err(BerError::InvalidLength)
+        };
+
+        let end = self.position.checked_add(length).ok_or(BerError::InvalidLength)?;
+        let content = self.data.get(self.position..end).ok_or(BerError::Truncated)?;
+        self.position = end;
+
+        Ok((tag, content))
+    }
+
+    pub fn read_expected(&mut self, expected: u8) -> Result<&'a [u8], BerError> {
+        let (tag, content) = self.read_tlv()?;
+
+        if tag != expected {
+            return Err(BerError::UnexpectedTag { expected, found: tag });
+        }
+
+        Ok(content)
+    }
+
+    pub fn read_sequence(&mut self) -> Result<BerReader<'a>, BerError> {
+        self.read_constructed(TAG_SEQUENCE)
+    }
+
+    pub fn read_constructed(&mut self, tag: u8) -> Result<BerReader<'a>, BerError> {
+        Ok(BerReader::new(self.read_expected(tag)?))
+    }
+
+    pub fn read_integer(&mut self) -> Result<i64, BerError> {
+        decode_integer(self.read_expected(TAG_INTEGER)?)
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8], BerError> {
+        self.read_expected(TAG_OCTET_STRING)
+    }
+
+    pub fn read_oid(&mut self) -> Result<Oid, BerError> {
+        decode_oid(self.read_expected(TAG_OBJECT_IDENTIFIER)?)
+    }
+
+    pub fn read_value(&mut self) -> Result<BerValue, BerError> {
+        let (tag, content) = self.read_tlv()?;
+
+        let value = match tag {
+            TAG_INTEGER => BerValue::Integer(decode_integer(content)?),
+            TAG_OCTET_STRING | TAG_OPAQUE => BerValue::OctetString(content.to_vec()),
+            TAG_NULL => BerValue::Null,
+            TAG_OBJECT_IDENTIFIER => BerValue::ObjectIdentifier(decode_oid(content)?),
+            TAG_IP_ADDRESS => BerValue::IpAddress(content.try_into().map_err(|_| BerError::InvalidValue)?),
+            TAG_COUNTER32 => BerValue::Counter32(decode_unsigned(content)? as u32),
+            TAG_GAUGE32 => BerValue::Gauge32(decode_unsigned(content)? as u32),
+            TAG_TIME_TICKS => BerValue::TimeTicks(decode_unsigned(content)? as u32),
+            TAG_COUNTER64 => BerValue::Counter64(decode_unsigned(content)?),
+            TAG_NO_SUCH_OBJECT => BerValue::NoSuchObject,
+            TAG_NO_SUCH_INSTANCE => BerValue::NoSuchInstance,
+            TAG_END_OF_MIB_VIEW => BerValue::EndOfMibView,
+            _ => return Err(BerError::InvalidValue)
+        };
+
+        Ok(value)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, BerError> {
+        let byte = *self.data.get(self.position).ok_or(BerError::Truncated)?;
+        self.position += 1;
+        Ok(byte)
+    }
+}
+
+fn decode_integer(content: &[u8]) -> Result<i64, BerError> {
+    if content.is_empty() || content.len() > 8 {
+        return Err(BerError::InvalidValue);
+    }
+
+    // Sign extension
+    let mut value: i64 = if content[0] & 0x80 != 0 { -1 } else { 0 };
+
+    for byte in content {
+        value = (value << 8) | *byte as i64;
+    }
+
+    Ok(value)
+}
+
+fn decode_unsigned(content: &[u8]) -> Result<u64, BerError> {
+    // A leading zero may be needed to keep the value positive
+    if content.is_empty() || content.len() > 9 {
+        return Err(BerError::InvalidValue);
+    }
+
+    let mut value: u64 = 0;
+
+    for byte in content {
+        value = (value << 8) | *byte as u64;
+    }
+
+    Ok(value)
+}
+
+fn decode_oid(content: &[u8]) -> Result<Oid, BerError> {
+    let mut subidentifiers = Vec::with_capacity(content.len());
+
+    let mut subidentifier: u32 = 0;
+    for byte in content {
+        subidentifier = subidentifier.checked_mul(128).ok_or(BerError::InvalidValue)? | (byte & 0x7F) as u32;
+
+        if byte & 0x80 == 0 {
+            subidentifiers.push(subidentifier);
+            subidentifier = 0;
+        }
+    }
+
+    // The last subidentifier is cut
+    if content.last().is_some_and(|byte| byte & 0x80 != 0) {
+        return Err(BerError::InvalidValue);
+    }
+
+    let Some((first, rest)) = subidentifiers.split_first() else {
+        return Err(BerError::InvalidValue);
+    };
+
+    // The first subidentifier holds the first two arcs, the second one being unbounded under 2 (X.690, 8.19.4)
+    let mut arcs = Vec::with_capacity(subidentifiers.len() + 1);
+    match *first {
+        0..=39 => arcs.extend([0, *first]),
+        40..=79 => arcs.extend([1, *first - 40]),
+        _ => arcs.extend([2, *first - 80])
+    }
+    arcs.extend_from_slice(rest);
+
+    Ok(Oid(arcs))
+}
+
+/// Encode a TLV with a definite length
+pub fn encode_tlv(buffer: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    buffer.push(tag);
+
+    let length = content.len();
+    match length {
+        0..=0x7F => buffer.push(length as u8),
+        0x80..=0xFF => buffer.extend_from_slice(&[0x81, length as u8]),
+        _ => buffer.extend_from_slice(&[0x82, (length >> 8) as u8, length as u8]),
+    }
+
+    buffer.extend_from_slice(content);
+}
+
+pub fn encode_integer(buffer: &mut Vec<u8>, value: i64) {
+    let bytes = value.to_be_bytes();
+
+    // Remove the redundant leading bytes while keeping the sign
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+
+    encode_tlv(buffer, TAG_INTEGER, &bytes[start..]);
+}
+
+fn encode_unsigned(buffer: &mut Vec<u8>, tag: u8, value: u64) {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(7);
+
+    // Keep the value positive
+    if bytes[start] & 0x80 != 0 {
+        let mut content = Vec::with_capacity(9 - start);
+        content.push(0);
+        content.extend_from_slice(&bytes[start..]);
+        encode_tlv(buffer, tag, &content);
+        return;
+    }
+
+    encode_tlv(buffer, tag, &bytes[start..]);
+}
+
+pub fn encode_oid(buffer: &mut Vec<u8>, oid: &Oid) {
+    let mut content = Vec::new();
+    let arcs = &oid.0;
+
+    // The first two arcs share the first subidentifier, which may take several bytes for the arcs under 2
+    let first = match arcs.len() {
+        0 => 0,
+        1 => arcs[0] * 40,
+        _ => arcs[0] * 40 + arcs[1],
+    };
+
+    for subidentifier in [first].iter().chain(arcs.iter().skip(2)) {
+        let mut encoded = [0u8; 5];
+        let mut index = encoded.len();
+        let mut value = *subidentifier;
+
+        loop {
+            index -= 1;
+            encoded[index] = (value & 0x7F) as u8 | if index == encoded.len() - 1 { 0 } else { 0x80 };
+            value >>= 7;
+
+            if value == 0 {
+                break;
+            }
+        }
+
+        content.extend_from_slice(&encoded[index..]);
+    }
+
+    encode_tlv(buffer, TAG_OBJECT_IDENTIFIER, &content);
+}
+
+pub fn encode_value(buffer: &mut Vec<u8>, value: &BerValue) {
+    match value {
+        BerValue::Integer(integer) => encode_integer(buffer, *integer),
+        BerValue::OctetString(bytes) => encode_tlv(buffer, TAG_OCTET_STRING, bytes),
+        BerValue::Null => encode_tlv(buffer, TAG_NULL, &[]),
+        BerValue::ObjectIdentifier(oid) => encode_oid(buffer, oid),
+        BerValue::IpAddress(address) => encode_tlv(buffer, TAG_IP_ADDRESS, address),
+        BerValue::Counter32(counter) => encode_unsigned(buffer, TAG_COUNTER32, *counter as u64),
+        BerValue::Gauge32(gauge) => encode_unsigned(buffer, TAG_GAUGE32, *gauge as u64),
+        BerValue::TimeTicks(ticks) => encode_unsigned(buffer, TAG_TIME_TICKS, *ticks as u64),
+        BerValue::Counter64(counter) => encode_unsigned(buffer, TAG_COUNTER64, *counter),
+        BerValue::NoSuchObject => encode_tlv(buffer, TAG_NO_SUCH_OBJECT, &[]),
+        BerValue::NoSuchInstance => encode_tlv(buffer, TAG_NO_SUCH_INSTANCE, &[]),
+        BerValue::EndOfMibView => encode_tlv(buffer, TAG_END_OF_MIB_VIEW, &[]),
+    }
+}
+
+/// Encode a constructed TLV whose content is written by the given closure
+pub fn encode_constructed<F: FnOnce(&mut Vec<u8>)>(buffer: &mut Vec<u8>, tag: u8, f: F) {
+    let mut content = Vec::new();
+    f(&mut content);
+    encode_tlv(buffer, tag, &content);
+}
@@ -0,0 +1,304 @@
+use crate::clock::Clock;
+use crate::devices::network::manager::{LOOPBACK_IF_INDEX, NETWORK_MANAGER};
+use crate::services::snmp::agent::SnmpAgent;
+use crate::services::snmp::ber::{BerValue, Oid};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Bound;
+use smoltcp::iface::Interface;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+
+// MIB-2 subtrees (RFC 1213, RFC 2863, RFC 2096)
+const SYSTEM: &[u32] = &[1, 3, 6, 1, 2, 1, 1];
+const INTERFACES: &[u32] = &[1, 3, 6, 1, 2, 1, 2];
+const IP_ROUTE_TABLE: &[u32] = &[1, 3, 6, 1, 2, 1, 4, 21, 1];
+const IP_CIDR_ROUTE_TABLE: &[u32] = &[1, 3, 6, 1, 2, 1, 4, 24, 4, 1];
+
+// ifType values (IANAifType-MIB)
+const IF_TYPE_ETHERNET_CSMACD: i64 = 6;
+const IF_TYPE_SOFTWARE_LOOPBACK: i64 = 24;
+const IF_TYPE_TUNNEL: i64 = 131;
+
+// ifAdminStatus and ifOperStatus values
+const IF_STATUS_UP: i64 = 1;
+const IF_STATUS_DOWN: i64 = 2;
+
+// ipRouteType and ipCidrRouteType values
+const ROUTE_TYPE_DIRECT: i64 = 3;
+const ROUTE_TYPE_INDIRECT: i64 = 4;
+
+// ipRouteProto and ipCidrRouteProto values
+const ROUTE_PROTO_LOCAL: i64 = 2;
+const ROUTE_PROTO_NETMGMT: i64 = 3;
+
+// ipCidrRouteStatus value
+const ROW_STATUS_ACTIVE: i64 = 1;
+
+/// Layers 2 (datalink) and 3 (internet)
+const SYS_SERVICES: i64 = 0b110;
+
+struct InterfaceEntry {
+    if_index: u32,
+    description: String,
+    if_type: i64,
+    mtu: usize,
+    speed: u32,
+    mac: Vec<u8>,
+    in_octets: u64,
+    in_packets: u64,
+    out_octets: u64,
+    out_packets: u64,
+    admin_up: bool,
+    oper_up: bool,
+}
+
+struct RouteEntry {
+    cidr: Ipv4Cidr,
+    next_hop: Ipv4Address,
+    if_index: u32,
+    direct: bool,
+}
+
+/// Snapshot of the objects exposed by the agent, sorted by OID
+pub struct Mib {
+    objects: BTreeMap<Oid, BerValue>,
+}
+
+impl Mib {
+    pub fn snapshot(agent: &SnmpAgent) -> Self {
+        let mut mib = Mib {
+            objects: BTreeMap::new(),
+        };
+
+        let (interfaces, routes) = collect_network_state();
+
+        mib.add_system_group(agent);
+        mib.add_interfaces_group(&interfaces);
+        mib.add_ip_route_table(&routes);
+        mib.add_ip_cidr_route_table(&routes);
+
+        mib
+    }
+
+    pub fn get(&self, oid: &Oid) -> BerValue {
+        if let Some(value) = self.objects.get(oid) {
+            return value.clone();
+        }
+
+        // The object exists if one of its instances exists
+        let object = Oid::new(&oid.0[..oid.0.len().saturating_sub(1)]);
+        let object_exists = self.objects
+            .range(&object..)
+            .next()
+            .map(|(next_oid, _)| next_oid.starts_with(&object))
+            .unwrap_or(false);
+
+        match object_exists {
+            true => BerValue::NoSuchInstance,
+            false => BerValue::NoSuchObject
+        }
+    }
+
+    pub fn get_next(&self, oid: &Oid) -> Option<(Oid, BerValue)> {
+        self.objects
+            .range((Bound::Excluded(oid), Bound::Unbounded))
+            .next()
+            .map(|(next_oid, value)| (next_oid.clone(), value.clone()))
+    }
+
+    fn insert(&mut self, oid: Oid, value: BerValue) {
+        self.objects.insert(oid, value);
+    }
+
+    fn add_system_group(&mut self, agent: &SnmpAgent) {
+        let system = Oid::new(SYSTEM);
+
+        self.insert(system.with(&[1, 0]), octet_string(&agent.sys_description));
+        self.insert(system.with(&[2, 0]), BerValue::ObjectIdentifier(Oid::new(&[0, 0])));
+        self.insert(system.with(&[3, 0]), BerValue::TimeTicks(sys_up_time()));
+        self.insert(system.with(&[4, 0]), octet_string(&agent.sys_contact));
+        self.insert(system.with(&[5, 0]), octet_string(&agent.sys_name));
+        self.insert(system.with(&[6, 0]), octet_string(&agent.sys_location));
+        self.insert(system.with(&[7, 0]), BerValue::Integer(SYS_SERVICES));
+    }
+
+    fn add_interfaces_group(&mut self, interfaces: &[InterfaceEntry]) {
+        let group = Oid::new(INTERFACES);
+        self.insert(group.with(&[1, 0]), BerValue::Integer(interfaces.len() as i64));
+
+        let if_entry = group.with(&[2, 1]);
+
+        for interface in interfaces {
+            let index = interface.if_index;
+
+            self.insert(if_entry.with(&[1, index]), BerValue::Integer(index as i64));
+            self.insert(if_entry.with(&[2, index]), octet_string(&interface.description));
+            self.insert(if_entry.with(&[3, index]), BerValue::Integer(interface.if_type));
+            self.insert(if_entry.with(&[4, index]), BerValue::Integer(interface.mtu as i64));
+            self.insert(if_entry.with(&[5, index]), BerValue::Gauge32(interface.speed));
+            self.insert(if_entry.with(&[6, index]), BerValue::OctetString(interface.mac.clone()));
+            self.insert(if_entry.with(&[7, index]), BerValue::Integer(if_status(interface.admin_up)));
+            self.insert(if_entry.with(&[8, index]), BerValue::Integer(if_status(interface.oper_up)));
+            self.insert(if_entry.with(&[9, index]), BerValue::TimeTicks(0));
+            self.insert(if_entry.with(&[10, index]), BerValue::Counter32(interface.in_octets as u32));
+            self.insert(if_entry.with(&[11, index]), BerValue::Counter32(interface.in_packets as u32));
+            self.insert(if_entry.with(&[16, index]), BerValue::Counter32(interface.out_octets as u32));
+            self.insert(if_entry.with(&[17, index]), BerValue::Counter32(interface.out_packets as u32));
+        }
+    }
+
+    fn add_ip_route_table(&mut self, routes: &[RouteEntry]) {
+        let table = Oid::new(IP_ROUTE_TABLE);
+
+        for route in routes {
+            let destination = route.cidr.network().address();
+            let index = destination.octets().map(|octet| octet as u32);
+
+            let (route_type, protocol) = route_type_and_protocol(route);
+
+            self.insert(table.with(&[1]).with(&index), BerValue::IpAddress(destination.octets()));
+            self.insert(table.with(&[2]).with(&index), BerValue::Integer(route.if_index as i64));
+            self.insert(table.with(&[7]).with(&index), BerValue::IpAddress(route.next_hop.octets()));
+            self.insert(table.with(&[8]).with(&index), BerValue::Integer(route_type));
+            self.insert(table.with(&[9]).with(&index), BerValue::Integer(protocol));
+            self.insert(table.with(&[11]).with(&index), BerValue::IpAddress(route.cidr.netmask().octets()));
+        }
+    }
+
+    fn add_ip_cidr_route_table(&mut self, routes: &[RouteEntry]) {
+        let table = Oid::new(IP_CIDR_ROUTE_TABLE);
+
+        for route in routes {
+            let destination = route.cidr.network().address();
+            let netmask = route.cidr.netmask();
+
+            // Index: ipCidrRouteDest, ipCidrRouteMask, ipCidrRouteTos, ipCidrRouteNextHop
+            let mut index = Vec::with_capacity(13);
+            index.extend(destination.octets().map(|octet| octet as u32));
+            index.extend(netmask.octets().map(|octet| octet as u32));
+            index.push(0);
+            index.extend(route.next_hop.octets().map(|octet| octet as u32));
+
+            let (route_type, protocol) = route_type_and_protocol(route);
+
+            self.insert(table.with(&[1]).with(&index), BerValue::IpAddress(destination.octets()));
+            self.insert(table.with(&[2]).with(&index), BerValue::IpAddress(netmask.octets()));
+            self.insert(table.with(&[3]).with(&index), BerValue::Integer(0));
+            self.insert(table.with(&[4]).with(&index), BerValue::IpAddress(route.next_hop.octets()));
+            self.insert(table.with(&[5]).with(&index), BerValue::Integer(route.if_index as i64));
+            self.insert(table.with(&[6]).with(&index), BerValue::Integer(route_type));
+            self.insert(table.with(&[7]).with(&index), BerValue::Integer(protocol));
+            self.insert(table.with(&[8]).with(&index), BerValue::Integer(0));
+            self.insert(table.with(&[16]).with(&index), BerValue::Integer(ROW_STATUS_ACTIVE));
+        }
+    }
+}
+
+/// Hundredths of seconds since boot
+pub fn sys_up_time() -> u32 {
+    (Clock::now().total_millis() / 10) as u32
+}
+
+/// ifAdminStatus or ifOperStatus value
+fn if_status(is_up: bool) -> i64 {
+    match is_up {
+        true => IF_STATUS_UP,
+        false => IF_STATUS_DOWN
+    }
+}
+
+fn octet_string(string: &str) -> BerValue {
+    BerValue::OctetString(string.as_bytes().to_vec())
+}
+
+fn route_type_and_protocol(route: &RouteEntry) -> (i64, i64) {
+    match route.direct {
+        true => (ROUTE_TYPE_DIRECT, ROUTE_PROTO_LOCAL),
+        false => (ROUTE_TYPE_INDIRECT, ROUTE_PROTO_NETMGMT)
+    }
+}
+
+fn collect_network_state() -> (Vec<InterfaceEntry>, Vec<RouteEntry>) {
+    let mut interfaces = Vec::new();
+    let mut routes = Vec::new();
+
+    let network_manager = NETWORK_MANAGER.lock();
+
+    interfaces.push(InterfaceEntry {
+        if_index: LOOPBACK_IF_INDEX,
+        description: String::from("lo"),
+        if_type: IF_TYPE_SOFTWARE_LOOPBACK,
+        mtu: 65535,
+        speed: 10_000_000,
+        mac: Vec::new(),
+        in_octets: 0,
+        in_packets: 0,
+        out_octets: 0,
+        out_packets: 0,
+        admin_up: true,
+        oper_up: true,
+    });
+    collect_connected_routes(&network_manager.loopback.interface, LOOPBACK_IF_INDEX, &mut routes);
+
+    for (name, device) in network_manager.interfaces.iter() {
+        let mut device = device.lock();
+        let controller = &device.network_controller;
+        let statistics = &controller.statistics;
+        let if_index = controller.if_index;
+
+        let nic_name = controller.driver.lock().nic_type().to_string();
+        let mac = device.mac();
+
+        interfaces.push(InterfaceEntry {
+            if_index,
+            description: format!("{} ({})", name, nic_name),
+            if_type: match mac {
+                Some(_) => IF_TYPE_ETHERNET_CSMACD,
+                None => IF_TYPE_TUNNEL
+            },
+            mtu: controller.mtu(),
+            speed: controller.link_status.speed.saturating_mul(1_000_000),
+            mac: mac.map(|mac| mac.as_bytes().to_vec()).unwrap_or_default(),
+            in_octets: statistics.rx_bytes(),
+            in_packets: statistics.rx_packets(),
+            out_octets: statistics.tx_bytes(),
+            out_packets: statistics.tx_packets(),
+            admin_up: controller.enabled,
+            oper_up: controller.enabled && controller.link_status.carrier,
+        });
+
+        collect_connected_routes(&device.interface, if_index, &mut routes);
+
+        device.interface
+            .routes_mut()
+            .update(|route_list| {
+                for route in route_list.iter() {
+                    if let (IpCidr::Ipv4(cidr), IpAddress::Ipv4(next_hop)) = (route.cidr, route.via_router) {
+                        routes.push(RouteEntry {
+                            cidr,
+                            next_hop,
+                            if_index,
+                            direct: false,
+                        });
+                    }
+                }
+            });
+    }
+
+    (interfaces, routes)
+}
+
+fn collect_connected_routes(interface: &Interface, if_index: u32, routes: &mut Vec<RouteEntry>) {
+    for address in interface.ip_addrs() {
+        if let IpCidr::Ipv4(cidr) = address {
+            routes.push(RouteEntry {
+                cidr: *cidr,
+                next_hop: Ipv4Address::UNSPECIFIED,
+                if_index,
+                direct: true,
+            });
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod agent;
+pub mod ber;
+pub mod mib;
\ No newline at end of file
@@ -0,0 +1,249 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::ServiceSocket;
+use alloc::string::String;
+use alloc::vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use goolog::{debug, info, warn};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint};
+use spin::Mutex;
+
+const GOOLOG_TARGET: &str = "SNTP";
+
+pub const NTP_PORT: u16 = 123;
+
+/// Local port used to query the server
+const CLIENT_PORT: u16 = 49154;
+
+const NTP_PACKET_SIZE: usize = 48;
+/// Seconds between 1900-01-01 (NTP era 0) and 1970-01-01 (unix epoch)
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+const LEAP_NOT_SYNCHRONIZED: u8 = 3;
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(64);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+const TICK: Duration = Duration::from_millis(50);
+
+pub static SNTP_CLIENT: Mutex<SntpClient> = Mutex::new(SntpClient::new());
+
+pub struct SntpClient {
+    pub server: Option<IpAddress>,
+    pub last_synchronization: Option<Instant>,
+    pub stratum: u8,
+    /// Round-trip delay of the last exchange
+    pub delay: Duration,
+    next_query_at: Instant,
+    pending: Option<PendingQuery>,
+    socket: Option<(String, ServiceSocket)>,
+}
+
+struct PendingQuery {
+    sent_at: Instant,
+    /// Transmit timestamp of the request, echoed as origin timestamp by the server
+    transmit_timestamp: u64,
+}
+
+impl SntpClient {
+    pub const fn new() -> Self {
+        Self {
+            server: None,
+            last_synchronization: None,
+            stratum: 0,
+            delay: Duration::ZERO,
+            next_query_at: Instant::ZERO,
+            pending: None,
+            socket: None,
+        }
+    }
+
+    pub fn set_server(&mut self, server: Option<IpAddress>) {
+        self.server = server;
+        self.pending = None;
+        self.next_query_at = Clock::now();
+        self.close_socket();
+    }
+
+    fn close_socket(&mut self) {
+        if let Some((_, socket)) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    fn poll(&mut self) {
+        let Some(server) = self.server else {
+            return;
+        };
+
+        let now = Clock::now();
+
+        match &self.pending {
+            None if now >= self.next_query_at => self.send_query(server, now),
+            None => {},
+            Some(pending) if now - pending.sent_at >= RESPONSE_TIMEOUT => {
+                debug!("No response from {}", server);
+                self.pending = None;
+                self.next_query_at = now + RETRY_INTERVAL;
+            },
+            Some(_) => self.receive_response(server, now)
+        }
+    }
+
+    fn send_query(&mut self, server: IpAddress, now: Instant) {
+        let network_manager = NETWORK_MANAGER.lock();
+
+        // The network is not up yet
+        let Some(interface_name) = network_manager.find_route_interface(&server) else {
+            self.next_query_at = now + RETRY_INTERVAL;
+            return;
+        };
+
+        let device = network_manager.interfaces.get(&interface_name).unwrap().clone();
+        drop(network_manager);
+
+        let socket_set = device.lock().sockets.clone();
+
+        // The route changed, or the interface was deleted and created again
+        if self.socket.as_ref().map(|(socket_interface, socket)| socket_interface != &interface_name || !socket.is_in(&socket_set)).unwrap_or(false) {
+            self.close_socket();
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match &self.socket {
+            Some((_, socket)) => socket.handle,
+            None => {
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 2 * NTP_PACKET_SIZE]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 2], vec![0; 2 * NTP_PACKET_SIZE]);
+                let mut socket = Socket::new(rx_buffer, tx_buffer);
+                socket.bind(CLIENT_PORT).unwrap();
+
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, socket);
+                let handle = socket.handle;
+                self.socket = Some((interface_name, socket));
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        // Drop any late response of a previous query
+        while socket.recv().is_ok() {}
+
+        // The server only echoes it, so the monotonic clock is enough to match the response
+        let transmit_timestamp = to_ntp_timestamp(now.total_millis());
+
+        let mut request = [0u8; NTP_PACKET_SIZE];
+        request[0] = (LEAP_NOT_SYNCHRONIZED << 6) | (VERSION << 3) | MODE_CLIENT;
+        NetworkEndian::write_u64(&mut request[40..48], transmit_timestamp);
+
+        match socket.send_slice(&request, IpEndpoint::new(server, NTP_PORT)) {
+            Ok(_) => {
+                self.pending = Some(PendingQuery {
+                    sent_at: now,
+                    transmit_timestamp,
+                });
+            },
+            Err(_) => self.next_query_at = now + RETRY_INTERVAL
+        }
+    }
+
+    fn receive_response(&mut self, server: IpAddress, now: Instant) {
+        let Some((interface_name, socket)) = &self.socket else {
+            return;
+        };
+
+        let Some(device) = NETWORK_MANAGER.lock().interfaces.get(interface_name).cloned() else {
+            return;
+        };
+
+        // The interface was deleted, the socket is opened again by the next query
+        let socket_set = device.lock().sockets.clone();
+
+        if !socket.is_in(&socket_set) {
+            return;
+        }
+
+        let mut sockets = socket_set.lock();
+        let socket = sockets.get_mut::<Socket>(socket.handle);
+
+        let mut response = [0u8; NTP_PACKET_SIZE];
+
+        while let Ok((length, metadata)) = socket.recv_slice(&mut response) {
+            if metadata.endpoint.addr != server || length < NTP_PACKET_SIZE {
+                continue;
+            }
+
+            let Some(pending) = self.pending.take_if(|pending| NetworkEndian::read_u64(&response[24..32]) == pending.transmit_timestamp) else {
+                continue;
+            };
+
+            let leap = response[0] >> 6;
+            let mode = response[0] & 0b111;
+            let stratum = response[1];
+
+            // Kiss-o'-Death or unsynchronized server
+            if mode != MODE_SERVER || stratum == 0 || leap == LEAP_NOT_SYNCHRONIZED {
+                warn!("Server {} is not synchronized", server);
+                self.next_query_at = now + POLL_INTERVAL;
+                return;
+            }
+
+            let receive_timestamp = from_ntp_timestamp(NetworkEndian::read_u64(&response[32..40]));
+            let transmit_timestamp = from_ntp_timestamp(NetworkEndian::read_u64(&response[40..48]));
+
+            // Round-trip delay, minus the processing time of the server (RFC 4330, 5)
+            let round_trip = (now - pending.sent_at).total_millis() as i64;
+            let delay = (round_trip - (transmit_timestamp - receive_timestamp)).max(0);
+
+            let was_synchronized = Clock::is_synchronized();
+            Clock::synchronize(now, transmit_timestamp + delay / 2);
+
+            self.stratum = stratum;
+            self.delay = Duration::from_millis(delay as u64);
+            self.last_synchronization = Some(now);
+            self.next_query_at = now + POLL_INTERVAL;
+
+            match was_synchronized {
+                false => info!("Clock synchronized with {} (stratum {})", server, stratum),
+                true => debug!("Clock synchronized with {} (delay {}ms)", server, delay)
+            }
+
+            return;
+        }
+    }
+}
+
+impl Default for SntpClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert unix milliseconds to a 64 bits NTP timestamp
+fn to_ntp_timestamp(unix_millis: i64) -> u64 {
+    let seconds = (unix_millis.div_euclid(1000) + NTP_UNIX_OFFSET) as u64;
+    let fraction = ((unix_millis.rem_euclid(1000) as u64) << 32) / 1000;
+    (seconds << 32) | fraction
+}
+
+/// Convert a 64 bits NTP timestamp to unix milliseconds
+fn from_ntp_timestamp(timestamp: u64) -> i64 {
+    let seconds = (timestamp >> 32) as i64 - NTP_UNIX_OFFSET;
+    let millis = ((timestamp & 0xFFFF_FFFF) * 1000) >> 32;
+    seconds * 1000 + millis as i64
+}
+
+/// Task keeping the clock synchronized with the configured server
+pub async fn synchronize_clock() {
+    loop {
+        Timer::after(TICK).await;
+        SNTP_CLIENT.lock().poll();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod server;
+mod session;
+mod transport;
+mod wire;
\ No newline at end of file
@@ -0,0 +1,350 @@
+use crate::clock::Timer;
+use crate::random::{KernelRng, SecureRng};
+use crate::services::ssh::session::SshSession;
+use crate::services::tcp::{SessionGuard, SessionInfo, TcpConnection, TcpListeners};
+use crate::task::executor::spawn_task;
+use crate::task::task::Task;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::SigningKey;
+use goolog::{debug, info};
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+use smoltcp::time::Duration;
+use spin::{Lazy, Mutex};
+
+const GOOLOG_TARGET: &str = "SSH";
+
+pub const SSH_PORT: u16 = 22;
+
+pub const HOST_KEY_ALGORITHM: &str = "ssh-ed25519";
+
+const MAX_SESSIONS: usize = 4;
+
+const RX_BUFFER_SIZE: usize = 8192;
+const TX_BUFFER_SIZE: usize = 16384;
+
+/// PBKDF2 iterations of the password hashes
+const PASSWORD_HASH_ITERATIONS: u32 = 100_000;
+/// Iterations computed between two yields to the executor, while a password is checked
+const PASSWORD_HASH_CHUNK: u32 = 2000;
+/// Wait before answering a wrong password, which slows down the guessing
+const FAILED_PASSWORD_DELAY: Duration = Duration::from_secs(2);
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub static SSH_SERVER: Lazy<Mutex<SshServer>> = Lazy::new(|| Mutex::new(SshServer::new()));
+
+pub struct SshServer {
+    pub port: Option<u16>,
+    pub sessions: BTreeMap<u64, SessionInfo>,
+    pub accepted_sessions: u64,
+    pub users: BTreeMap<String, SshUser>,
+    /// Generated when the server first starts, and kept until shutdown
+    host_key: Option<SigningKey>,
+    /// Set when the server first starts, which it cannot without RDRAND
+    rng: Option<SecureRng>,
+    listeners: TcpListeners,
+    next_session_id: u64,
+}
+
+#[derive(Default)]
+pub struct SshUser {
+    password: Option<PasswordHash>,
+    /// Authorized ed25519 public keys
+    pub keys: Vec<[u8; 32]>,
+}
+
+/// PBKDF2-HMAC-SHA256 of a password
+#[derive(Clone, Copy)]
+struct PasswordHash {
+    salt: [u8; 16],
+    hash: [u8; 32],
+}
+
+impl SshServer {
+    pub fn new() -> Self {
+        Self {
+            port: None,
+            sessions: BTreeMap::new(),
+            accepted_sessions: 0,
+            users: BTreeMap::new(),
+            host_key: None,
+            rng: None,
+            listeners: TcpListeners::new(RX_BUFFER_SIZE, TX_BUFFER_SIZE),
+            next_session_id: 0,
+        }
+    }
+
+    /// Start listening on the given port, or stop the server and close the sessions
+    pub fn set_port(&mut self, port: Option<u16>) -> Result<(), &'static str> {
+        if port.is_some() && self.rng.is_none() {
+            // The keys and nonces derived from the timestamp counter could be guessed
+            self.rng = Some(SecureRng::new().ok_or("No RDRAND instruction, the keys of the server cannot be generated securely")?);
+        }
+
+        self.port = port;
+        self.listeners.close();
+
+        match port {
+            Some(_) => {
+                if self.host_key.is_none() {
+                    self.host_key = Some(SigningKey::generate(&mut self.rng.unwrap()));
+                    info!("Host key generated: {}", self.host_key_fingerprint().unwrap());
+                }
+            },
+            None => self.sessions.clear()
+        }
+
+        Ok(())
+    }
+
+    pub fn host_key(&self) -> Option<&SigningKey> {
+        self.host_key.as_ref()
+    }
+
+    /// OpenSSH style fingerprint of the host key
+    pub fn host_key_fingerprint(&self) -> Option<String> {
+        let host_key = self.host_key.as_ref()?;
+        let digest = Sha256::digest(public_key_blob(&host_key.verifying_key().to_bytes()));
+
+        Some(format!("SHA256:{}", STANDARD_NO_PAD.encode(digest)))
+    }
+
+    pub fn set_password(&mut self, name: String, password: &str) {
+        // A salt only needs to be unique
+        let mut salt = [0u8; 16];
+        KernelRng.fill_bytes(&mut salt);
+
+        let hash = hash_password(&salt, password);
+
+        self.users.entry(name).or_default().password = Some(PasswordHash { salt, hash });
+    }
+
+    /// Authorize a public key, given as the base64 part of an OpenSSH "ssh-ed25519" line
+    pub fn add_key(&mut self, name: String, key: &str) -> Result<(), &'static str> {
+        let blob = STANDARD.decode(key).map_err(|_| "The key is not valid base64")?;
+        let public_key = parse_public_key_blob(&blob).ok_or("Only ssh-ed25519 keys are supported")?;
+
+        let user = self.users.entry(name).or_default();
+
+        if !user.keys.contains(&public_key) {
+            user.keys.push(public_key);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_key_authorized(&self, name: &str, public_key: &[u8; 32]) -> bool {
+        self.users
+            .get(name)
+            .map(|user| user.keys.contains(public_key))
+            .unwrap_or(false)
+    }
+
+    /// Listen on every interface, and hand the accepted connections to new sessions
+    fn poll(&mut self) {
+        let Some(port) = self.port else {
+            return;
+        };
+
+        // New connections are refused once the sessions are all taken
+        if self.sessions.len() >= MAX_SESSIONS {
+            if self.listeners.is_listening() {
+                debug!("Maximum number of sessions reached");
+                self.listeners.close();
+            }
+            return;
+        }
+
+        for connection in self.listeners.accept(port) {
+            let id = self.next_session_id;
+            self.next_session_id += 1;
+            self.accepted_sessions += 1;
+
+            let task_name = match connection.remote_endpoint {
+                Some(remote_endpoint) => {
+                    info!("Session {} opened from {} on {}", id, remote_endpoint, connection.interface_name);
+                    format!("SSH session {}", remote_endpoint)
+                },
+                None => {
+                    info!("Session {} opened on {}", id, connection.interface_name);
+                    String::from("SSH session")
+                }
+            };
+
+            self.sessions.insert(id, SessionInfo::new(&connection));
+
+            let host_key = self.host_key.clone().unwrap();
+            spawn_task(Task::new(task_name, run_session(id, connection, host_key, self.rng.unwrap())));
+        }
+    }
+}
+
+impl Default for SshServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SshUser {
+    pub fn has_password(&self) -> bool {
+        self.password.is_some()
+    }
+}
+
+/// PBKDF2-HMAC-SHA256 (RFC 8018, 5.2) of a single block, computed a few iterations at a time
+struct PasswordHasher {
+    prf: Hmac<Sha256>,
+    /// Output of the last iteration
+    block: [u8; 32],
+    hash: [u8; 32],
+    remaining_iterations: u32,
+}
+
+impl PasswordHasher {
+    fn new(salt: &[u8], password: &str) -> Self {
+        let prf = Hmac::<Sha256>::new_from_slice(password.as_bytes()).expect("HMAC takes keys of any length");
+
+        let mut block = [0u8; 32];
+        block.copy_from_slice(&prf.clone().chain_update(salt).chain_update(1u32.to_be_bytes()).finalize().into_bytes());
+
+        Self {
+            prf,
+            block,
+            hash: block,
+            remaining_iterations: PASSWORD_HASH_ITERATIONS - 1,
+        }
+    }
+
+    /// Compute up to the given number of iterations, returns the hash once they are all done
+    fn step(&mut self, iterations: u32) -> Option<[u8; 32]> {
+        let iterations = iterations.min(self.remaining_iterations);
+
+        for _ in 0..iterations {
+            let output = self.prf.clone().chain_update(self.block).finalize().into_bytes();
+            self.block.copy_from_slice(&output);
+
+            for (byte, output_byte) in self.hash.iter_mut().zip(self.block) {
+                *byte ^= output_byte;
+            }
+        }
+
+        self.remaining_iterations -= iterations;
+
+        match self.remaining_iterations {
+            0 => Some(self.hash),
+            _ => None
+        }
+    }
+}
+
+fn hash_password(salt: &[u8], password: &str) -> [u8; 32] {
+    PasswordHasher::new(salt, password).step(PASSWORD_HASH_ITERATIONS).unwrap()
+}
+
+/// Check the password of a user, the other tasks running between the chunks of the hash
+async fn check_password(name: &str, password: &str) -> bool {
+    let Some(PasswordHash { salt, hash }) = SSH_SERVER.lock().users.get(name).and_then(|user| user.password) else {
+        return false;
+    };
+
+    let mut hasher = PasswordHasher::new(&salt, password);
+
+    let candidate = loop {
+        if let Some(candidate) = hasher.step(PASSWORD_HASH_CHUNK) {
+            break candidate;
+        }
+
+        Timer::after(Duration::from_millis(1)).await;
+    };
+
+    // Constant time comparison
+    candidate.iter().zip(hash.iter()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// Public key in the SSH format (RFC 8709, 4)
+pub fn public_key_blob(public_key: &[u8; 32]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(4 + HOST_KEY_ALGORITHM.len() + 4 + 32);
+    blob.extend_from_slice(&(HOST_KEY_ALGORITHM.len() as u32).to_be_bytes());
+    blob.extend_from_slice(HOST_KEY_ALGORITHM.as_bytes());
+    blob.extend_from_slice(&32u32.to_be_bytes());
+    blob.extend_from_slice(public_key);
+    blob
+}
+
+pub fn parse_public_key_blob(blob: &[u8]) -> Option<[u8; 32]> {
+    let algorithm_length = HOST_KEY_ALGORITHM.len();
+
+    if blob.len() != 4 + algorithm_length + 4 + 32
+        || blob[..4] != (algorithm_length as u32).to_be_bytes()
+        || &blob[4..4 + algorithm_length] != HOST_KEY_ALGORITHM.as_bytes()
+        || blob[4 + algorithm_length..8 + algorithm_length] != 32u32.to_be_bytes() {
+        return None;
+    }
+
+    let mut public_key = [0u8; 32];
+    public_key.copy_from_slice(&blob[8 + algorithm_length..]);
+    Some(public_key)
+}
+
+/// Task serving a single connection, until it is closed by either side
+async fn run_session(id: u64, connection: TcpConnection, host_key: SigningKey, rng: SecureRng) {
+    // The session is forgotten even when its task is killed
+    let _guard = SessionGuard(|| {
+        SSH_SERVER.lock().sessions.remove(&id);
+        info!("Session {} closed", id);
+    });
+
+    let mut session = SshSession::new(host_key, rng);
+
+    loop {
+        Timer::after(SESSION_POLL_INTERVAL).await;
+
+        if !SSH_SERVER.lock().sessions.contains_key(&id) {
+            session.shutdown();
+        }
+
+        // The socket is not kept locked while the commands run
+        let Some(received) = connection.receive() else {
+            break;
+        };
+
+        session.receive(&received);
+
+        // The server is not kept locked while the password is checked
+        while let Some(attempt) = session.password_attempt.take() {
+            let authenticated = check_password(&attempt.user, &attempt.password).await;
+
+            if !authenticated {
+                Timer::after(FAILED_PASSWORD_DELAY).await;
+            }
+
+            session.finish_password_authentication(attempt, authenticated);
+        }
+
+        session.poll();
+
+        let sent_everything = connection.send(&mut session.transport.output);
+
+        if session.closing && sent_everything {
+            break;
+        }
+    }
+
+    connection.close().await;
+}
+
+/// Task accepting the SSH connections
+pub async fn ssh_server() {
+    loop {
+        Timer::after(ACCEPT_INTERVAL).await;
+        SSH_SERVER.lock().poll();
+    }
+}
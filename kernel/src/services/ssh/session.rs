@@ -0,0 +1,880 @@
+use crate::clock::Clock;
+use crate::printer::macros::{stop_capture, take_captured};
+use crate::random::SecureRng;
+use crate::services::ssh::server::{parse_public_key_blob, public_key_blob, HOST_KEY_ALGORITHM, SSH_SERVER};
+use crate::services::ssh::transport::{derive_key, PacketCipher, Transport, CIPHER_IV_LENGTH, CIPHER_KEY_LENGTH};
+use crate::services::ssh::wire::{negotiate, SshError, SshReader, SshWriter};
+use crate::task::executor::{cancel_task, is_task_running};
+use crate::task::task::TaskId;
+use crate::terminal::cli::spawn_command;
+use crate::terminal::remote::{RemoteSession, RemoteTerminal};
+use alloc::string::String;
+use alloc::vec::Vec;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use goolog::{debug, info, warn};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+use smoltcp::time::{Duration, Instant};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+const GOOLOG_TARGET: &str = "SSH";
+
+const SERVER_VERSION: &str = "SSH-2.0-RetOS_0.1";
+/// Maximum length of the lines sent before the client version
+const MAX_VERSION_LENGTH: usize = 8192;
+
+const LOGIN_GRACE_TIME: Duration = Duration::from_secs(120);
+const MAX_AUTHENTICATION_ATTEMPTS: u32 = 6;
+
+/// Window and packet sizes the server announces for its channel
+const LOCAL_WINDOW_SIZE: u32 = 64 * 1024;
+const LOCAL_MAX_PACKET_SIZE: u32 = 32 * 1024;
+/// Keeps the channel data packets well under the socket buffer
+const MAX_DATA_PACKET_SIZE: usize = 4096;
+
+const SERVER_CHANNEL_ID: u32 = 0;
+
+// Message numbers (RFC 4250, 4.1)
+const MSG_DISCONNECT: u8 = 1;
+const MSG_IGNORE: u8 = 2;
+const MSG_UNIMPLEMENTED: u8 = 3;
+const MSG_DEBUG: u8 = 4;
+const MSG_SERVICE_REQUEST: u8 = 5;
+const MSG_SERVICE_ACCEPT: u8 = 6;
+const MSG_KEXINIT: u8 = 20;
+const MSG_NEWKEYS: u8 = 21;
+const MSG_KEX_ECDH_INIT: u8 = 30;
+const MSG_KEX_ECDH_REPLY: u8 = 31;
+const MSG_USERAUTH_REQUEST: u8 = 50;
+const MSG_USERAUTH_FAILURE: u8 = 51;
+const MSG_USERAUTH_SUCCESS: u8 = 52;
+const MSG_USERAUTH_PK_OK: u8 = 60;
+const MSG_GLOBAL_REQUEST: u8 = 80;
+const MSG_REQUEST_FAILURE: u8 = 82;
+const MSG_CHANNEL_OPEN: u8 = 90;
+const MSG_CHANNEL_OPEN_CONFIRMATION: u8 = 91;
+const MSG_CHANNEL_OPEN_FAILURE: u8 = 92;
+const MSG_CHANNEL_WINDOW_ADJUST: u8 = 93;
+const MSG_CHANNEL_DATA: u8 = 94;
+const MSG_CHANNEL_EXTENDED_DATA: u8 = 95;
+const MSG_CHANNEL_EOF: u8 = 96;
+const MSG_CHANNEL_CLOSE: u8 = 97;
+const MSG_CHANNEL_REQUEST: u8 = 98;
+const MSG_CHANNEL_SUCCESS: u8 = 99;
+const MSG_CHANNEL_FAILURE: u8 = 100;
+
+// Disconnection reasons (RFC 4250, 4.2.2)
+const DISCONNECT_PROTOCOL_ERROR: u32 = 2;
+const DISCONNECT_KEY_EXCHANGE_FAILED: u32 = 3;
+const DISCONNECT_MAC_ERROR: u32 = 5;
+const DISCONNECT_SERVICE_NOT_AVAILABLE: u32 = 7;
+const DISCONNECT_PROTOCOL_VERSION_NOT_SUPPORTED: u32 = 8;
+const DISCONNECT_BY_APPLICATION: u32 = 11;
+const DISCONNECT_NO_MORE_AUTH_METHODS_AVAILABLE: u32 = 14;
+
+// Channel opening failures (RFC 4250, 4.3)
+const OPEN_ADMINISTRATIVELY_PROHIBITED: u32 = 1;
+const OPEN_UNKNOWN_CHANNEL_TYPE: u32 = 3;
+
+// Supported algorithms
+const KEX_ALGORITHMS: &[&str] = &["curve25519-sha256", "curve25519-sha256@libssh.org", STRICT_KEX_SERVER];
+const HOST_KEY_ALGORITHMS: &[&str] = &[HOST_KEY_ALGORITHM];
+const ENCRYPTION_ALGORITHMS: &[&str] = &["aes256-gcm@openssh.com"];
+/// Unused with AEAD ciphers, but the lists cannot be empty
+const MAC_ALGORITHMS: &[&str] = &["hmac-sha2-256"];
+const COMPRESSION_ALGORITHMS: &[&str] = &["none"];
+
+/// Strict key exchange, against prefix truncation attacks (CVE-2023-48795)
+const STRICT_KEX_SERVER: &str = "kex-strict-s-v00@openssh.com";
+const STRICT_KEX_CLIENT: &str = "kex-strict-c-v00@openssh.com";
+
+const AUTHENTICATION_METHODS: &[&str] = &["publickey", "password"];
+
+/// Key exchange in progress
+struct KeyExchange {
+    server_kexinit: Vec<u8>,
+    client_kexinit: Option<Vec<u8>>,
+    /// The client sent a wrong guess of its first key exchange packet, which must be ignored
+    ignore_next_packet: bool,
+    /// Cipher of the received packets, used once the client sends NEWKEYS
+    receive_cipher: Option<PacketCipher>,
+}
+
+/// The session channel, the only one a connection may open
+struct Channel {
+    remote_id: u32,
+    remote_window: u32,
+    remote_max_packet: u32,
+    local_window: u32,
+    pty: bool,
+    shell: Option<RemoteSession>,
+    /// Command run by an exec request, its output is captured
+    exec: Option<TaskId>,
+    pending_data: Vec<u8>,
+    /// Close the channel once the pending data is sent
+    close_when_sent: bool,
+    close_sent: bool,
+}
+
+/// The command of a closed channel is stopped
+impl Drop for Channel {
+    fn drop(&mut self) {
+        if let Some(task_id) = self.exec {
+            cancel_task(task_id);
+            stop_capture(task_id);
+        }
+    }
+}
+
+/// Password to check before the session goes on, the hash taking a while
+pub struct PasswordAttempt {
+    pub user: String,
+    pub password: String,
+}
+
+pub struct SshSession {
+    pub transport: Transport,
+    pub closing: bool,
+    /// The received packets are left in the transport until the password is checked
+    pub password_attempt: Option<PasswordAttempt>,
+    host_key: SigningKey,
+    rng: SecureRng,
+    started_at: Instant,
+    client_version: Option<Vec<u8>>,
+    key_exchange: Option<KeyExchange>,
+    session_id: Option<Vec<u8>>,
+    strict_kex: bool,
+    user_authentication_accepted: bool,
+    user: Option<String>,
+    failed_authentications: u32,
+    channel: Option<Channel>,
+}
+
+impl SshSession {
+    pub fn new(host_key: SigningKey, rng: SecureRng) -> Self {
+        let mut session = Self {
+            transport: Transport::new(),
+            closing: false,
+            password_attempt: None,
+            host_key,
+            rng,
+            started_at: Clock::now(),
+            client_version: None,
+            key_exchange: None,
+            session_id: None,
+            strict_kex: false,
+            user_authentication_accepted: false,
+            user: None,
+            failed_authentications: 0,
+            channel: None,
+        };
+
+        session.transport.output.extend_from_slice(SERVER_VERSION.as_bytes());
+        session.transport.output.extend_from_slice(b"\r\n");
+        session.start_key_exchange();
+
+        session
+    }
+
+    /// Close the connection, such as when the server stops
+    pub fn shutdown(&mut self) {
+        if !self.closing {
+            self.disconnect(DISCONNECT_BY_APPLICATION, "Server stopped");
+        }
+    }
+
+    pub fn receive(&mut self, data: &[u8]) {
+        if self.closing {
+            return;
+        }
+
+        self.transport.receive(data);
+
+        if let Err(error) = self.process_received() {
+            debug!("Session error: {}", error);
+
+            let reason = match error {
+                SshError::Authentication => DISCONNECT_MAC_ERROR,
+                SshError::NoCommonAlgorithm(_) | SshError::KeyExchange => DISCONNECT_KEY_EXCHANGE_FAILED,
+                _ => DISCONNECT_PROTOCOL_ERROR
+            };
+
+            self.disconnect(reason, "Protocol error");
+        }
+    }
+
+    /// Send the output of the channel, and enforce the login grace time
+    pub fn poll(&mut self) {
+        if self.closing {
+            return;
+        }
+
+        if self.user.is_none() && Clock::elapsed(self.started_at) > LOGIN_GRACE_TIME {
+            self.disconnect(DISCONNECT_BY_APPLICATION, "Authentication timeout");
+            return;
+        }
+
+        // Only the key exchange messages may be sent until NEWKEYS (RFC 4253, 7.1)
+        if self.key_exchange.is_some() {
+            return;
+        }
+
+        self.flush_channel();
+    }
+
+    fn process_received(&mut self) -> Result<(), SshError> {
+        if self.client_version.is_none() {
+            while let Some(line) = self.transport.next_line() {
+                // Other lines may precede the version (RFC 4253, 4.2)
+                if !line.starts_with(b"SSH-") {
+                    continue;
+                }
+
+                if !line.starts_with(b"SSH-2.0-") && !line.starts_with(b"SSH-1.99-") {
+                    self.disconnect(DISCONNECT_PROTOCOL_VERSION_NOT_SUPPORTED, "Only SSH-2.0 is supported");
+                    return Ok(());
+                }
+
+                self.client_version = Some(line);
+                break;
+            }
+
+            if self.client_version.is_none() {
+                if self.transport.received_length() > MAX_VERSION_LENGTH {
+                    return Err(SshError::Protocol("no version received"));
+                }
+                return Ok(());
+            }
+        }
+
+        while !self.closing && self.password_attempt.is_none() {
+            let Some(payload) = self.transport.next_packet()? else {
+                break;
+            };
+
+            self.handle_packet(&payload)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_packet(&mut self, payload: &[u8]) -> Result<(), SshError> {
+        let mut reader = SshReader::new(payload);
+        let message_type = reader.read_u8()?;
+
+        if let Some(key_exchange) = &mut self.key_exchange {
+            // Only the key exchange messages are allowed during the key exchange, and always with the strict one
+            let allowed = match message_type {
+                MSG_KEXINIT => key_exchange.client_kexinit.is_none(),
+                // A single ECDH exchange per key exchange, its keys being in use once NEWKEYS is sent
+                MSG_KEX_ECDH_INIT => key_exchange.client_kexinit.is_some() && key_exchange.receive_cipher.is_none(),
+                MSG_NEWKEYS => key_exchange.client_kexinit.is_some(),
+                MSG_DISCONNECT => true,
+                MSG_IGNORE | MSG_DEBUG | MSG_UNIMPLEMENTED => !self.strict_kex,
+                _ => false
+            };
+
+            if !allowed {
+                return Err(SshError::UnexpectedMessage(message_type));
+            }
+
+            if message_type == MSG_KEX_ECDH_INIT && key_exchange.ignore_next_packet {
+                key_exchange.ignore_next_packet = false;
+                return Ok(());
+            }
+        }
+        else if matches!(message_type, MSG_KEX_ECDH_INIT | MSG_NEWKEYS) {
+            // No key exchange in progress
+            return Err(SshError::UnexpectedMessage(message_type));
+        }
+
+        match message_type {
+            MSG_DISCONNECT => {
+                debug!("Disconnected by the client");
+                self.closing = true;
+            },
+            MSG_IGNORE | MSG_DEBUG | MSG_UNIMPLEMENTED => {},
+            MSG_KEXINIT => self.handle_kexinit(payload)?,
+            MSG_KEX_ECDH_INIT => self.handle_ecdh_init(&mut reader)?,
+            MSG_NEWKEYS => self.handle_newkeys()?,
+            _ if self.session_id.is_none() => return Err(SshError::UnexpectedMessage(message_type)),
+            MSG_SERVICE_REQUEST => self.handle_service_request(&mut reader)?,
+            MSG_USERAUTH_REQUEST if self.user_authentication_accepted => self.handle_userauth_request(&mut reader)?,
+            MSG_GLOBAL_REQUEST => {
+                let _name = reader.read_string()?;
+
+                if reader.read_bool()? {
+                    self.transport.send_packet(&[MSG_REQUEST_FAILURE]);
+                }
+            },
+            MSG_CHANNEL_OPEN..=MSG_CHANNEL_FAILURE if self.user.is_some() => self.handle_channel_message(message_type, &mut reader)?,
+            _ => {
+                let mut unimplemented = SshWriter::message(MSG_UNIMPLEMENTED);
+                unimplemented.write_u32(self.transport.last_receive_sequence());
+                self.transport.send_packet(&unimplemented.data);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, reason: u32, description: &str) {
+        let mut disconnect = SshWriter::message(MSG_DISCONNECT);
+        disconnect.write_u32(reason);
+        disconnect.write_string(description.as_bytes());
+        disconnect.write_string(b"");
+
+        self.transport.send_packet(&disconnect.data);
+        self.closing = true;
+    }
+
+    /* ---------- Key exchange (RFC 4253, 7 and RFC 8731) ---------- */
+
+    fn start_key_exchange(&mut self) {
+        let mut cookie = [0u8; 16];
+        self.rng.fill_bytes(&mut cookie);
+
+        let mut kexinit = SshWriter::message(MSG_KEXINIT);
+        kexinit.write_bytes(&cookie);
+        kexinit.write_name_list(KEX_ALGORITHMS);
+        kexinit.write_name_list(HOST_KEY_ALGORITHMS);
+        kexinit.write_name_list(ENCRYPTION_ALGORITHMS);
+        kexinit.write_name_list(ENCRYPTION_ALGORITHMS);
+        kexinit.write_name_list(MAC_ALGORITHMS);
+        kexinit.write_name_list(MAC_ALGORITHMS);
+        kexinit.write_name_list(COMPRESSION_ALGORITHMS);
+        kexinit.write_name_list(COMPRESSION_ALGORITHMS);
+        kexinit.write_name_list(&[]);
+        kexinit.write_name_list(&[]);
+        kexinit.write_bool(false);
+        kexinit.write_u32(0);
+
+        self.transport.send_packet(&kexinit.data);
+
+        self.key_exchange = Some(KeyExchange {
+            server_kexinit: kexinit.data,
+            client_kexinit: None,
+            ignore_next_packet: false,
+            receive_cipher: None,
+        });
+    }
+
+    fn handle_kexinit(&mut self, payload: &[u8]) -> Result<(), SshError> {
+        let first_key_exchange = self.session_id.is_none();
+
+        // Key re-exchange started by the client
+        if self.key_exchange.is_none() {
+            self.start_key_exchange();
+        }
+
+        let mut reader = SshReader::new(&payload[1..]);
+        let _cookie = reader.read_bytes(16)?;
+
+        let kex_algorithms = reader.read_name_list()?;
+        let host_key_algorithms = reader.read_name_list()?;
+        let encryption_client_to_server = reader.read_name_list()?;
+        let encryption_server_to_client = reader.read_name_list()?;
+        let _mac_client_to_server = reader.read_name_list()?;
+        let _mac_server_to_client = reader.read_name_list()?;
+        let compression_client_to_server = reader.read_name_list()?;
+        let compression_server_to_client = reader.read_name_list()?;
+        let _languages_client_to_server = reader.read_name_list()?;
+        let _languages_server_to_client = reader.read_name_list()?;
+        let first_kex_packet_follows = reader.read_bool()?;
+
+        let kex_algorithm = negotiate(&kex_algorithms, &KEX_ALGORITHMS[..2], "key exchange")?;
+        let host_key_algorithm = negotiate(&host_key_algorithms, HOST_KEY_ALGORITHMS, "host key")?;
+        negotiate(&encryption_client_to_server, ENCRYPTION_ALGORITHMS, "encryption")?;
+        negotiate(&encryption_server_to_client, ENCRYPTION_ALGORITHMS, "encryption")?;
+        negotiate(&compression_client_to_server, COMPRESSION_ALGORITHMS, "compression")?;
+        negotiate(&compression_server_to_client, COMPRESSION_ALGORITHMS, "compression")?;
+
+        if first_key_exchange && kex_algorithms.contains(&STRICT_KEX_CLIENT) {
+            // The KEXINIT must be the very first packet of the client
+            if self.transport.last_receive_sequence() != 0 {
+                return Err(SshError::Protocol("strict key exchange violation"));
+            }
+
+            self.strict_kex = true;
+        }
+
+        let key_exchange = self.key_exchange.as_mut().unwrap();
+        key_exchange.client_kexinit = Some(payload.to_vec());
+
+        // The guess is wrong if the preferred algorithms of the client were not chosen (RFC 4253, 7)
+        key_exchange.ignore_next_packet = first_kex_packet_follows
+            && (kex_algorithms.first() != Some(&kex_algorithm.as_str()) || host_key_algorithms.first() != Some(&host_key_algorithm.as_str()));
+
+        Ok(())
+    }
+
+    fn handle_ecdh_init(&mut self, reader: &mut SshReader) -> Result<(), SshError> {
+        let client_public_key: [u8; 32] = reader.read_string()?.try_into().map_err(|_| SshError::KeyExchange)?;
+
+        let secret = EphemeralSecret::random_from_rng(self.rng);
+        let server_public_key = PublicKey::from(&secret);
+        let shared_secret = secret.diffie_hellman(&PublicKey::from(client_public_key));
+
+        if !shared_secret.was_contributory() {
+            return Err(SshError::KeyExchange);
+        }
+
+        let mut shared_secret_mpint = SshWriter::new();
+        shared_secret_mpint.write_mpint(shared_secret.as_bytes());
+
+        let host_key_blob = public_key_blob(&self.host_key.verifying_key().to_bytes());
+
+        let key_exchange = self.key_exchange.as_mut().unwrap();
+
+        let mut exchange_data = SshWriter::new();
+        exchange_data.write_string(self.client_version.as_ref().unwrap());
+        exchange_data.write_string(SERVER_VERSION.as_bytes());
+        exchange_data.write_string(key_exchange.client_kexinit.as_ref().unwrap());
+        exchange_data.write_string(&key_exchange.server_kexinit);
+        exchange_data.write_string(&host_key_blob);
+        exchange_data.write_string(&client_public_key);
+        exchange_data.write_string(server_public_key.as_bytes());
+        exchange_data.write_bytes(&shared_secret_mpint.data);
+
+        let exchange_hash = Sha256::digest(&exchange_data.data).to_vec();
+
+        // The session identifier is the hash of the first key exchange
+        let session_id = self.session_id.get_or_insert_with(|| exchange_hash.clone()).clone();
+
+        let signature = self.host_key.sign(&exchange_hash);
+
+        let mut signature_blob = SshWriter::new();
+        signature_blob.write_string(HOST_KEY_ALGORITHM.as_bytes());
+        signature_blob.write_string(&signature.to_bytes());
+
+        let mut reply = SshWriter::message(MSG_KEX_ECDH_REPLY);
+        reply.write_string(&host_key_blob);
+        reply.write_string(server_public_key.as_bytes());
+        reply.write_string(&signature_blob.data);
+
+        let derive = |letter: u8, length: usize| derive_key(&shared_secret_mpint.data, &exchange_hash, letter, &session_id, length);
+
+        key_exchange.receive_cipher = Some(PacketCipher::new(&derive(b'C', CIPHER_KEY_LENGTH), &derive(b'A', CIPHER_IV_LENGTH)));
+        let send_cipher = PacketCipher::new(&derive(b'D', CIPHER_KEY_LENGTH), &derive(b'B', CIPHER_IV_LENGTH));
+
+        self.transport.send_packet(&reply.data);
+        self.transport.send_packet(&[MSG_NEWKEYS]);
+
+        // The packets following NEWKEYS use the new keys
+        self.transport.set_send_cipher(send_cipher);
+        if self.strict_kex {
+            self.transport.reset_send_sequence();
+        }
+
+        Ok(())
+    }
+
+    fn handle_newkeys(&mut self) -> Result<(), SshError> {
+        let key_exchange = self.key_exchange.take().unwrap();
+
+        let Some(receive_cipher) = key_exchange.receive_cipher else {
+            return Err(SshError::UnexpectedMessage(MSG_NEWKEYS));
+        };
+
+        self.transport.set_receive_cipher(receive_cipher);
+        if self.strict_kex {
+            self.transport.reset_receive_sequence();
+        }
+
+        Ok(())
+    }
+
+    /* ---------- Authentication (RFC 4252) ---------- */
+
+    fn handle_service_request(&mut self, reader: &mut SshReader) -> Result<(), SshError> {
+        let service = reader.read_string()?;
+
+        if service != b"ssh-userauth" {
+            self.disconnect(DISCONNECT_SERVICE_NOT_AVAILABLE, "Service not available");
+            return Ok(());
+        }
+
+        self.user_authentication_accepted = true;
+
+        let mut accept = SshWriter::message(MSG_SERVICE_ACCEPT);
+        accept.write_string(service);
+        self.transport.send_packet(&accept.data);
+
+        Ok(())
+    }
+
+    fn handle_userauth_request(&mut self, reader: &mut SshReader) -> Result<(), SshError> {
+        // Requests following a success are ignored
+        if self.user.is_some() {
+            return Ok(());
+        }
+
+        let user = reader.read_utf8()?;
+        let service = reader.read_string()?;
+        let method = reader.read_utf8()?;
+
+        if service != b"ssh-connection" {
+            self.disconnect(DISCONNECT_SERVICE_NOT_AVAILABLE, "Service not available");
+            return Ok(());
+        }
+
+        let authenticated = match method {
+            "password" => {
+                let _change = reader.read_bool()?;
+                let password = reader.read_utf8()?;
+
+                // Answered by finish_password_authentication
+                self.password_attempt = Some(PasswordAttempt {
+                    user: String::from(user),
+                    password: String::from(password),
+                });
+                return Ok(());
+            },
+            "publickey" => {
+                let has_signature = reader.read_bool()?;
+                let algorithm = reader.read_string()?;
+                let blob = reader.read_string()?;
+
+                let public_key = match algorithm == HOST_KEY_ALGORITHM.as_bytes() {
+                    true => parse_public_key_blob(blob),
+                    false => None
+                };
+
+                let authorized = public_key
+                    .map(|public_key| SSH_SERVER.lock().is_key_authorized(user, &public_key))
+                    .unwrap_or(false);
+
+                match (authorized, has_signature) {
+                    (false, _) => false,
+                    // The client asks whether the key is acceptable, before signing
+                    (true, false) => {
+                        let mut ok = SshWriter::message(MSG_USERAUTH_PK_OK);
+                        ok.write_string(algorithm);
+                        ok.write_string(blob);
+                        self.transport.send_packet(&ok.data);
+                        return Ok(());
+                    },
+                    (true, true) => {
+                        let signature = reader.read_string()?;
+                        self.verify_userauth_signature(user, algorithm, blob, &public_key.unwrap(), signature)
+                    }
+                }
+            },
+            // Lists the available methods
+            "none" => {
+                self.send_userauth_failure();
+                return Ok(());
+            },
+            _ => false
+        };
+
+        self.finish_authentication(user, method, authenticated);
+
+        Ok(())
+    }
+
+    /// Answer the password attempt once checked, and handle the packets received meanwhile
+    pub fn finish_password_authentication(&mut self, attempt: PasswordAttempt, authenticated: bool) {
+        self.finish_authentication(&attempt.user, "password", authenticated);
+        self.receive(&[]);
+    }
+
+    fn finish_authentication(&mut self, user: &str, method: &str, authenticated: bool) {
+        if authenticated {
+            info!("User {} authenticated ({})", user, method);
+            self.user = Some(String::from(user));
+            self.transport.send_packet(&[MSG_USERAUTH_SUCCESS]);
+            return;
+        }
+
+        warn!("Authentication of user {} failed ({})", user, method);
+
+        self.failed_authentications += 1;
+        if self.failed_authentications >= MAX_AUTHENTICATION_ATTEMPTS {
+            self.disconnect(DISCONNECT_NO_MORE_AUTH_METHODS_AVAILABLE, "Too many authentication failures");
+            return;
+        }
+
+        self.send_userauth_failure();
+    }
+
+    fn send_userauth_failure(&mut self) {
+        let mut failure = SshWriter::message(MSG_USERAUTH_FAILURE);
+        failure.write_name_list(AUTHENTICATION_METHODS);
+        failure.write_bool(false);
+        self.transport.send_packet(&failure.data);
+    }
+
+    /// Check the signature of a publickey request (RFC 4252, 7)
+    fn verify_userauth_signature(&self, user: &str, algorithm: &[u8], blob: &[u8], public_key: &[u8; 32], signature: &[u8]) -> bool {
+        let mut signature_reader = SshReader::new(signature);
+
+        let signature = match (signature_reader.read_string(), signature_reader.read_string()) {
+            (Ok(signature_algorithm), Ok(signature)) if signature_algorithm == HOST_KEY_ALGORITHM.as_bytes() => signature,
+            _ => return false
+        };
+
+        let (Ok(verifying_key), Ok(signature)) = (VerifyingKey::from_bytes(public_key), Signature::from_slice(signature)) else {
+            return false;
+        };
+
+        let mut signed_data = SshWriter::new();
+        signed_data.write_string(self.session_id.as_ref().unwrap());
+        signed_data.write_u8(MSG_USERAUTH_REQUEST);
+        signed_data.write_string(user.as_bytes());
+        signed_data.write_string(b"ssh-connection");
+        signed_data.write_string(b"publickey");
+        signed_data.write_bool(true);
+        signed_data.write_string(algorithm);
+        signed_data.write_string(blob);
+
+        verifying_key.verify(&signed_data.data, &signature).is_ok()
+    }
+
+    /* ---------- Connection (RFC 4254) ---------- */
+
+    fn handle_channel_message(&mut self, message_type: u8, reader: &mut SshReader) -> Result<(), SshError> {
+        if message_type == MSG_CHANNEL_OPEN {
+            return self.handle_channel_open(reader);
+        }
+
+        let recipient = reader.read_u32()?;
+
+        let Some(channel) = &mut self.channel else {
+            return Err(SshError::Protocol("unknown channel"));
+        };
+
+        if recipient != SERVER_CHANNEL_ID {
+            return Err(SshError::Protocol("unknown channel"));
+        }
+
+        match message_type {
+            MSG_CHANNEL_WINDOW_ADJUST => {
+                let increment = reader.read_u32()?;
+                channel.remote_window = channel.remote_window.saturating_add(increment);
+            },
+            MSG_CHANNEL_DATA | MSG_CHANNEL_EXTENDED_DATA => {
+                if message_type == MSG_CHANNEL_EXTENDED_DATA {
+                    let _data_type = reader.read_u32()?;
+                }
+
+                let data = reader.read_string()?;
+                channel.local_window = channel.local_window.saturating_sub(data.len() as u32);
+
+                if message_type == MSG_CHANNEL_DATA {
+                    if let Some(shell) = &mut channel.shell {
+                        for &byte in data {
+                            shell.receive(byte);
+                        }
+                    }
+                    // Ctrl-C, sent as is with a terminal
+                    else if let Some(task_id) = channel.exec {
+                        if data.contains(&0x03) {
+                            cancel_task(task_id);
+                        }
+                    }
+                }
+
+                // Reopen the window once half of it is consumed
+                if channel.local_window < LOCAL_WINDOW_SIZE / 2 {
+                    let mut adjust = SshWriter::message(MSG_CHANNEL_WINDOW_ADJUST);
+                    adjust.write_u32(channel.remote_id);
+                    adjust.write_u32(LOCAL_WINDOW_SIZE - channel.local_window);
+                    channel.local_window = LOCAL_WINDOW_SIZE;
+
+                    self.transport.send_packet(&adjust.data);
+                }
+            },
+            MSG_CHANNEL_EOF => channel.close_when_sent = true,
+            MSG_CHANNEL_CLOSE => {
+                if !channel.close_sent {
+                    let mut close = SshWriter::message(MSG_CHANNEL_CLOSE);
+                    close.write_u32(channel.remote_id);
+                    self.transport.send_packet(&close.data);
+                }
+
+                // A connection only carries one session
+                self.channel = None;
+                self.disconnect(DISCONNECT_BY_APPLICATION, "Session closed");
+            },
+            MSG_CHANNEL_REQUEST => self.handle_channel_request(reader)?,
+            // Answers to requests the server never sends
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn handle_channel_open(&mut self, reader: &mut SshReader) -> Result<(), SshError> {
+        let channel_type = reader.read_string()?;
+        let sender = reader.read_u32()?;
+        let initial_window = reader.read_u32()?;
+        let max_packet = reader.read_u32()?;
+
+        let failure_reason = match (channel_type == b"session", &self.channel) {
+            (false, _) => Some((OPEN_UNKNOWN_CHANNEL_TYPE, "Unknown channel type")),
+            (true, Some(_)) => Some((OPEN_ADMINISTRATIVELY_PROHIBITED, "Only one session per connection")),
+            // The channel data could never be sent
+            (true, None) if max_packet == 0 => Some((OPEN_ADMINISTRATIVELY_PROHIBITED, "Null maximum packet size")),
+            (true, None) => None
+        };
+
+        if let Some((reason, description)) = failure_reason {
+            let mut failure = SshWriter::message(MSG_CHANNEL_OPEN_FAILURE);
+            failure.write_u32(sender);
+            failure.write_u32(reason);
+            failure.write_string(description.as_bytes());
+            failure.write_string(b"");
+            self.transport.send_packet(&failure.data);
+            return Ok(());
+        }
+
+        self.channel = Some(Channel {
+            remote_id: sender,
+            remote_window: initial_window,
+            remote_max_packet: max_packet,
+            local_window: LOCAL_WINDOW_SIZE,
+            pty: false,
+            shell: None,
+            exec: None,
+            pending_data: Vec::new(),
+            close_when_sent: false,
+            close_sent: false,
+        });
+
+        let mut confirmation = SshWriter::message(MSG_CHANNEL_OPEN_CONFIRMATION);
+        confirmation.write_u32(sender);
+        confirmation.write_u32(SERVER_CHANNEL_ID);
+        confirmation.write_u32(LOCAL_WINDOW_SIZE);
+        confirmation.write_u32(LOCAL_MAX_PACKET_SIZE);
+        self.transport.send_packet(&confirmation.data);
+
+        Ok(())
+    }
+
+    fn handle_channel_request(&mut self, reader: &mut SshReader) -> Result<(), SshError> {
+        let request_type = reader.read_utf8()?;
+        let want_reply = reader.read_bool()?;
+
+        let channel = self.channel.as_mut().unwrap();
+        let started = channel.shell.is_some() || channel.exec.is_some() || channel.close_when_sent;
+
+        let success = match request_type {
+            "pty-req" => {
+                channel.pty = true;
+                true
+            },
+            "shell" if !started => {
+                let mut terminal = RemoteTerminal::new();
+                // Without a terminal, the client does not display the typed characters
+                terminal.echo = channel.pty;
+
+                channel.shell = Some(RemoteSession::new(terminal));
+                true
+            },
+            "exec" if !started => {
+                let command = reader.read_utf8()?;
+                info!("Executing \"{}\"", command);
+
+                channel.exec = Some(spawn_command(command, true));
+                true
+            },
+            "signal" => {
+                let signal = reader.read_utf8()?;
+
+                if signal == "INT" {
+                    match (&mut channel.shell, channel.exec) {
+                        (Some(shell), _) => shell.interrupt(),
+                        (None, Some(task_id)) => {
+                            cancel_task(task_id);
+                        },
+                        (None, None) => {}
+                    }
+                }
+
+                true
+            },
+            "window-change" => true,
+            _ => false
+        };
+
+        if want_reply {
+            let mut reply = SshWriter::message(match success {
+                true => MSG_CHANNEL_SUCCESS,
+                false => MSG_CHANNEL_FAILURE
+            });
+            reply.write_u32(channel.remote_id);
+            self.transport.send_packet(&reply.data);
+        }
+
+        Ok(())
+    }
+
+    /// Send the channel output as the window of the client allows
+    fn flush_channel(&mut self) {
+        let Some(channel) = &mut self.channel else {
+            return;
+        };
+
+        if let Some(task_id) = channel.exec {
+            let mut output = take_captured(task_id);
+
+            if !is_task_running(task_id) {
+                output.push_str(&stop_capture(task_id));
+
+                channel.exec = None;
+                channel.close_when_sent = true;
+            }
+
+            match channel.pty {
+                true => channel.pending_data.extend_from_slice(output.replace('\n', "\r\n").as_bytes()),
+                false => channel.pending_data.extend_from_slice(output.as_bytes())
+            }
+        }
+
+        if let Some(shell) = &mut channel.shell {
+            shell.poll();
+            channel.pending_data.extend(shell.take_output());
+
+            if shell.closing {
+                channel.close_when_sent = true;
+            }
+        }
+
+        while !channel.pending_data.is_empty() && channel.remote_window > 0 {
+            let length = channel.pending_data
+                .len()
+                .min(channel.remote_window as usize)
+                .min(channel.remote_max_packet as usize)
+                .min(MAX_DATA_PACKET_SIZE);
+
+            let mut data = SshWriter::message(MSG_CHANNEL_DATA);
+            data.write_u32(channel.remote_id);
+            data.write_string(&channel.pending_data[..length]);
+
+            channel.pending_data.drain(..length);
+            channel.remote_window -= length as u32;
+
+            self.transport.send_packet(&data.data);
+        }
+
+        if channel.close_when_sent && channel.pending_data.is_empty() && !channel.close_sent {
+            let mut exit_status = SshWriter::message(MSG_CHANNEL_REQUEST);
+            exit_status.write_u32(channel.remote_id);
+            exit_status.write_string(b"exit-status");
+            exit_status.write_bool(false);
+            exit_status.write_u32(0);
+            self.transport.send_packet(&exit_status.data);
+
+            let mut eof = SshWriter::message(MSG_CHANNEL_EOF);
+            eof.write_u32(channel.remote_id);
+            self.transport.send_packet(&eof.data);
+
+            let mut close = SshWriter::message(MSG_CHANNEL_CLOSE);
+            close.write_u32(channel.remote_id);
+            self.transport.send_packet(&close.data);
+
+            channel.close_sent = true;
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::services::ssh::wire::SshError;
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce, Tag};
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+/// Maximum packet length a server must accept (RFC 4253, 6.1), with some margin
+const MAX_PACKET_LENGTH: usize = 35000;
+const MIN_PADDING_LENGTH: usize = 4;
+const TAG_LENGTH: usize = 16;
+
+pub const CIPHER_KEY_LENGTH: usize = 32;
+pub const CIPHER_IV_LENGTH: usize = 12;
+
+/// aes256-gcm@openssh.com (RFC 5647), the packet length is sent in clear as associated data
+pub struct PacketCipher {
+    cipher: Aes256Gcm,
+    fixed: [u8; 4],
+    invocation_counter: u64,
+}
+
+impl PacketCipher {
+    pub fn new(key: &[u8], iv: &[u8]) -> Self {
+        let mut fixed = [0u8; 4];
+        fixed.copy_from_slice(&iv[..4]);
+
+        let mut invocation_counter = [0u8; 8];
+        invocation_counter.copy_from_slice(&iv[4..CIPHER_IV_LENGTH]);
+
+        Self {
+            cipher: Aes256Gcm::new_from_slice(&key[..CIPHER_KEY_LENGTH]).unwrap(),
+            fixed,
+            invocation_counter: u64::from_be_bytes(invocation_counter),
+        }
+    }
+
+    fn next_nonce(&mut self) -> [u8; CIPHER_IV_LENGTH] {
+        let mut nonce = [0u8; CIPHER_IV_LENGTH];
+        nonce[..4].copy_from_slice(&self.fixed);
+        nonce[4..].copy_from_slice(&self.invocation_counter.to_be_bytes());
+
+        self.invocation_counter = self.invocation_counter.wrapping_add(1);
+        nonce
+    }
+}
+
+/// Binary packet protocol (RFC 4253, 6)
+pub struct Transport {
+    received: Vec<u8>,
+    /// Bytes waiting to be sent on the socket
+    pub output: Vec<u8>,
+    receive_cipher: Option<PacketCipher>,
+    send_cipher: Option<PacketCipher>,
+    receive_sequence: u32,
+    send_sequence: u32,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self {
+            received: Vec::new(),
+            output: Vec::new(),
+            receive_cipher: None,
+            send_cipher: None,
+            receive_sequence: 0,
+            send_sequence: 0,
+        }
+    }
+
+    pub fn receive(&mut self, data: &[u8]) {
+        self.received.extend_from_slice(data);
+    }
+
+    pub fn received_length(&self) -> usize {
+        self.received.len()
+    }
+
+    /// Take a line of the received data, for the protocol version exchange
+    pub fn next_line(&mut self) -> Option<Vec<u8>> {
+        let end = self.received.iter().position(|byte| *byte == b'\n')?;
+        let mut line: Vec<u8> = self.received.drain(..=end).collect();
+
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        Some(line)
+    }
+
+    /// Sequence number of the last received packet
+    pub fn last_receive_sequence(&self) -> u32 {
+        self.receive_sequence.wrapping_sub(1)
+    }
+
+    pub fn set_receive_cipher(&mut self, cipher: PacketCipher) {
+        self.receive_cipher = Some(cipher);
+    }
+
+    pub fn set_send_cipher(&mut self, cipher: PacketCipher) {
+        self.send_cipher = Some(cipher);
+    }
+
+    /// Sequence numbers restart at every NEWKEYS with the strict key exchange
+    pub fn reset_receive_sequence(&mut self) {
+        self.receive_sequence = 0;
+    }
+
+    pub fn reset_send_sequence(&mut self) {
+        self.send_sequence = 0;
+    }
+
+    /// Payload of the next complete packet, if any
+    pub fn next_packet(&mut self) -> Result<Option<Vec<u8>>, SshError> {
+        if self.received.len() < 4 {
+            return Ok(None);
+        }
+
+        let packet_length = u32::from_be_bytes([self.received[0], self.received[1], self.received[2], self.received[3]]) as usize;
+
+        if packet_length > MAX_PACKET_LENGTH {
+            return Err(SshError::PacketTooLong(packet_length));
+        }
+
+        let tag_length = match self.receive_cipher {
+            Some(_) => TAG_LENGTH,
+            None => 0
+        };
+
+        if self.received.len() < 4 + packet_length + tag_length {
+            return Ok(None);
+        }
+
+        let mut packet: Vec<u8> = self.received.drain(..4 + packet_length + tag_length).collect();
+
+        if let Some(cipher) = &mut self.receive_cipher {
+            if packet_length % 16 != 0 {
+                return Err(SshError::Protocol("packet length is not a multiple of the block size"));
+            }
+
+            let (header, rest) = packet.split_at_mut(4);
+            let (body, tag) = rest.split_at_mut(packet_length);
+            let nonce = cipher.next_nonce();
+
+            cipher.cipher
+                .decrypt_in_place_detached(Nonce::from_slice(&nonce), header, body, Tag::from_slice(tag))
+                .map_err(|_| SshError::Authentication)?;
+        }
+
+        self.receive_sequence = self.receive_sequence.wrapping_add(1);
+
+        let padding_length = *packet.get(4).ok_or(SshError::Truncated)? as usize;
+
+        if packet_length < 1 + padding_length || padding_length < MIN_PADDING_LENGTH {
+            return Err(SshError::Protocol("invalid padding"));
+        }
+
+        let payload_end = 4 + packet_length - padding_length;
+        Ok(Some(packet[5..payload_end].to_vec()))
+    }
+
+    pub fn send_packet(&mut self, payload: &[u8]) {
+        let block_size = match self.send_cipher {
+            Some(_) => 16,
+            // The packet length is encrypted with the other ciphers, and counts in the blocks
+            None => 8
+        };
+
+        let aligned_length = match self.send_cipher {
+            Some(_) => 1 + payload.len(),
+            None => 4 + 1 + payload.len()
+        };
+
+        let mut padding_length = block_size - aligned_length % block_size;
+        if padding_length < MIN_PADDING_LENGTH {
+            padding_length += block_size;
+        }
+
+        let packet_length = 1 + payload.len() + padding_length;
+
+        let mut packet = Vec::with_capacity(4 + packet_length + TAG_LENGTH);
+        packet.extend_from_slice(&(packet_length as u32).to_be_bytes());
+        packet.push(padding_length as u8);
+        packet.extend_from_slice(payload);
+        packet.resize(4 + packet_length, 0);
+
+        if let Some(cipher) = &mut self.send_cipher {
+            let (header, body) = packet.split_at_mut(4);
+            let nonce = cipher.next_nonce();
+
+            let tag = cipher.cipher
+                .encrypt_in_place_detached(Nonce::from_slice(&nonce), header, body)
+                .unwrap();
+
+            packet.extend_from_slice(&tag);
+        }
+
+        self.send_sequence = self.send_sequence.wrapping_add(1);
+        self.output.extend_from_slice(&packet);
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Key derivation (RFC 4253, 7.2), the shared secret being already encoded as an mpint
+pub fn derive_key(shared_secret: &[u8], exchange_hash: &[u8], letter: u8, session_id: &[u8], length: usize) -> Vec<u8> {
+    let mut key = Sha256::new()
+        .chain_update(shared_secret)
+        .chain_update(exchange_hash)
+        .chain_update([letter])
+        .chain_update(session_id)
+        .finalize()
+        .to_vec();
+
+    while key.len() < length {
+        let extension = Sha256::new()
+            .chain_update(shared_secret)
+            .chain_update(exchange_hash)
+            .chain_update(&key)
+            .finalize();
+
+        key.extend_from_slice(&extension);
+    }
+
+    key.truncate(length);
+    key
+}
@@ -0,0 +1,160 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use thiserror::Error;
+
+/// SSH data types (RFC 4251, 5)
+#[derive(Error, Debug)]
+pub enum SshError {
+    #[error("truncated message")]
+    Truncated,
+
+    #[error("invalid string")]
+    InvalidString,
+
+    #[error("packet too long ({0} bytes)")]
+    PacketTooLong(usize),
+
+    #[error("message authentication failed")]
+    Authentication,
+
+    #[error("no common {0} algorithm")]
+    NoCommonAlgorithm(&'static str),
+
+    #[error("invalid key exchange")]
+    KeyExchange,
+
+    #[error("unexpected message {0}")]
+    UnexpectedMessage(u8),
+
+    #[error("protocol error: {0}")]
+    Protocol(&'static str),
+}
+
+pub struct SshReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SshReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], SshError> {
+        if self.data.len() < length {
+            return Err(SshError::Truncated);
+        }
+
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SshError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SshError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SshError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_string(&mut self) -> Result<&'a [u8], SshError> {
+        let length = self.read_u32()? as usize;
+        self.read_bytes(length)
+    }
+
+    pub fn read_utf8(&mut self) -> Result<&'a str, SshError> {
+        core::str::from_utf8(self.read_string()?).map_err(|_| SshError::InvalidString)
+    }
+
+    pub fn read_name_list(&mut self) -> Result<Vec<&'a str>, SshError> {
+        let names = self.read_utf8()?;
+
+        match names.is_empty() {
+            true => Ok(Vec::new()),
+            false => Ok(names.split(',').collect())
+        }
+    }
+}
+
+pub struct SshWriter {
+    pub data: Vec<u8>,
+}
+
+impl SshWriter {
+    /// Start a message of the given type
+    pub fn message(message_type: u8) -> Self {
+        let mut writer = Self::new();
+        writer.write_u8(message_type);
+        writer
+    }
+
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+        }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn write_string(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn write_name_list(&mut self, names: &[&str]) {
+        self.write_string(names.join(",").as_bytes());
+    }
+
+    /// Unsigned multiple precision integer, in two's complement
+    pub fn write_mpint(&mut self, bytes: &[u8]) {
+        let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+        let bytes = &bytes[start..];
+
+        match bytes.first() {
+            Some(byte) if byte & 0x80 != 0 => {
+                self.write_u32(bytes.len() as u32 + 1);
+                self.data.push(0);
+                self.data.extend_from_slice(bytes);
+            },
+            _ => self.write_string(bytes)
+        }
+    }
+}
+
+impl Default for SshWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// First algorithm of the client list that the server supports (RFC 4253, 7.1)
+pub fn negotiate(client: &[&str], server: &[&str], kind: &'static str) -> Result<String, SshError> {
+    client
+        .iter()
+        .find(|algorithm| server.contains(algorithm))
+        .map(|algorithm| String::from(*algorithm))
+        .ok_or(SshError::NoCommonAlgorithm(kind))
+}
@@ -0,0 +1,207 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::ServiceSocket;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use core::fmt::Arguments;
+use core::sync::atomic::{AtomicU8, Ordering};
+use crossbeam_queue::ArrayQueue;
+use goolog::log::Level;
+use no_std_clap_macros::EnumValuesArg;
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
+use spin::{Lazy, Mutex};
+use strum::{Display, EnumString, FromRepr, VariantNames};
+
+pub const SYSLOG_DEFAULT_PORT: u16 = 514;
+
+/// Local port used to send the syslog messages
+const FORWARDER_PORT: u16 = 49153;
+
+const HOSTNAME: &str = "RetOS";
+
+/// Messages kept while the network is not up, the oldest ones are dropped first
+const BUFFERED_MESSAGES: usize = 128;
+/// Keeps the messages under the ethernet MTU
+const MAX_MESSAGE_SIZE: usize = 1024;
+
+const FORWARD_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Formatted messages waiting to be forwarded.
+/// Lock-free, since logs may be written from interrupt handlers.
+static PENDING_MESSAGES: Lazy<ArrayQueue<String>> = Lazy::new(|| ArrayQueue::new(BUFFERED_MESSAGES));
+
+static FACILITY: AtomicU8 = AtomicU8::new(SyslogFacility::Local0 as u8);
+
+pub static SYSLOG_FORWARDER: Mutex<SyslogForwarder> = Mutex::new(SyslogForwarder::new());
+
+/// Syslog facilities (RFC 5424, 6.2.1)
+#[derive(Debug, Clone, Copy, Default, EnumValuesArg, VariantNames, EnumString, Display, FromRepr)]
+#[strum(serialize_all = "lowercase")]
+#[repr(u8)]
+pub enum SyslogFacility {
+    Kern = 0,
+    User = 1,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    #[default]
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+pub struct SyslogForwarder {
+    pub server: Option<IpEndpoint>,
+    pub messages_sent: u64,
+    socket: Option<(String, ServiceSocket)>,
+}
+
+impl SyslogForwarder {
+    pub const fn new() -> Self {
+        Self {
+            server: None,
+            messages_sent: 0,
+            socket: None,
+        }
+    }
+
+    pub fn set_server(&mut self, server: Option<IpEndpoint>) {
+        self.server = server;
+        self.close_socket();
+    }
+
+    fn close_socket(&mut self) {
+        if let Some((_, socket)) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    /// Send the pending messages, as long as the server is reachable
+    fn forward(&mut self) {
+        let Some(server) = self.server else {
+            return;
+        };
+
+        if PENDING_MESSAGES.is_empty() {
+            return;
+        }
+
+        let network_manager = NETWORK_MANAGER.lock();
+
+        // The network is not up yet, messages stay buffered
+        let Some(interface_name) = network_manager.find_route_interface(&server.addr) else {
+            return;
+        };
+
+        let device = network_manager.interfaces.get(&interface_name).unwrap().clone();
+        drop(network_manager);
+
+        let socket_set = device.lock().sockets.clone();
+
+        // The route changed, or the interface was deleted and created again
+        if self.socket.as_ref().map(|(socket_interface, socket)| socket_interface != &interface_name || !socket.is_in(&socket_set)).unwrap_or(false) {
+            self.close_socket();
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match &self.socket {
+            Some((_, socket)) => socket.handle,
+            None => {
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY], vec![0; 64]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; 16], vec![0; 16 * MAX_MESSAGE_SIZE]);
+                let mut socket = Socket::new(rx_buffer, tx_buffer);
+                socket.bind(FORWARDER_PORT).unwrap();
+
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, socket);
+                let handle = socket.handle;
+                self.socket = Some((interface_name, socket));
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        while socket.can_send() {
+            let Some(message) = PENDING_MESSAGES.pop() else {
+                break;
+            };
+
+            if socket.send_slice(message.as_bytes(), server).is_ok() {
+                self.messages_sent += 1;
+            }
+        }
+    }
+}
+
+impl Default for SyslogForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn facility() -> SyslogFacility {
+    SyslogFacility::from_repr(FACILITY.load(Ordering::Relaxed)).unwrap_or_default()
+}
+
+pub fn set_facility(facility: SyslogFacility) {
+    FACILITY.store(facility as u8, Ordering::Relaxed);
+}
+
+/// Severity of a log level (RFC 5424, 6.2.1)
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7
+    }
+}
+
+/// Format a log as a RFC 5424 message and queue it for forwarding
+pub fn queue_log(target: &str, level: Level, args: &Arguments) {
+    let priority = facility() as u8 * 8 + severity(level);
+
+    // APP-NAME cannot contain spaces
+    let app_name = target.replace(' ', "_");
+
+    // The timestamp is left out until the clock is synchronized
+    let timestamp = match Clock::utc_now() {
+        Some(date_time) => date_time.to_string(),
+        None => String::from("-")
+    };
+
+    let mut message = format!("<{}>1 {} {} {} - - - {}", priority, timestamp, HOSTNAME, app_name, args);
+    message.truncate(floor_char_boundary(&message, MAX_MESSAGE_SIZE));
+
+    // Drop the oldest message when the buffer is full
+    PENDING_MESSAGES.force_push(message);
+}
+
+fn floor_char_boundary(string: &str, index: usize) -> usize {
+    if index >= string.len() {
+        return string.len();
+    }
+
+    (0..=index).rev().find(|index| string.is_char_boundary(*index)).unwrap_or(0)
+}
+
+pub fn pending_messages() -> usize {
+    PENDING_MESSAGES.len()
+}
+
+/// Task forwarding the logs to the configured syslog server
+pub async fn forward_logs() {
+    loop {
+        Timer::after(FORWARD_INTERVAL).await;
+        SYSLOG_FORWARDER.lock().forward();
+    }
+}
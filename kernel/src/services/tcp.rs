@@ -0,0 +1,217 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::{close_stale_sockets, poll_interface, unregister_owner, ServiceSocket};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::tcp::{Socket, SocketBuffer, State};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::IpEndpoint;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Remote session of a TCP service, as shown to the user
+pub struct SessionInfo {
+    pub interface_name: String,
+    pub remote_endpoint: Option<IpEndpoint>,
+    pub started_at: Instant,
+}
+
+impl SessionInfo {
+    pub fn new(connection: &TcpConnection) -> Self {
+        Self {
+            interface_name: connection.interface_name.clone(),
+            remote_endpoint: connection.remote_endpoint,
+            started_at: Clock::now(),
+        }
+    }
+}
+
+/// Listening sockets of a TCP service, one per interface
+pub struct TcpListeners {
+    sockets: BTreeMap<String, ServiceSocket>,
+    rx_buffer_size: usize,
+    tx_buffer_size: usize,
+}
+
+/// Established connection, whose socket was handed over by a listener
+pub struct TcpConnection {
+    pub interface_name: String,
+    pub remote_endpoint: Option<IpEndpoint>,
+    sockets: Arc<Mutex<SocketSet<'static>>>,
+    handle: SocketHandle,
+    /// The socket was removed by close
+    closed: bool,
+}
+
+/// Runs the cleanup of a session when dropped, its task being killed included
+pub struct SessionGuard<F: FnMut()>(pub F);
+
+impl TcpListeners {
+    pub const fn new(rx_buffer_size: usize, tx_buffer_size: usize) -> Self {
+        Self {
+            sockets: BTreeMap::new(),
+            rx_buffer_size,
+            tx_buffer_size,
+        }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        !self.sockets.is_empty()
+    }
+
+    pub fn close(&mut self) {
+        while let Some((_, socket)) = self.sockets.pop_first() {
+            socket.close();
+        }
+    }
+
+    /// Listen on every interface, and return the connections established since the last call
+    pub fn accept(&mut self, port: u16) -> Vec<TcpConnection> {
+        let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+        let (rx_buffer_size, tx_buffer_size) = (self.rx_buffer_size, self.tx_buffer_size);
+        let mut connections = Vec::new();
+
+        close_stale_sockets(&mut self.sockets, &socket_sets);
+
+        for (interface_name, socket_set) in socket_sets {
+            let accepted = interrupts::without_interrupts(|| {
+                let mut sockets = socket_set.lock();
+
+                let handle = self.sockets
+                    .entry(interface_name.clone())
+                    .or_insert_with(|| {
+                        let rx_buffer = SocketBuffer::new(vec![0; rx_buffer_size]);
+                        let tx_buffer = SocketBuffer::new(vec![0; tx_buffer_size]);
+                        let mut socket = Socket::new(rx_buffer, tx_buffer);
+                        socket.listen(port).unwrap();
+                        ServiceSocket::add(&socket_set, &mut sockets, socket)
+                    })
+                    .handle;
+
+                let socket = sockets.get::<Socket>(handle);
+
+                // The connection is handed over once the handshake is complete
+                match socket.state() {
+                    State::Listen | State::SynReceived => None,
+                    _ => Some((handle, socket.remote_endpoint()))
+                }
+            });
+
+            // The listener now belongs to the connection, a new one is created at the next call
+            if let Some((handle, remote_endpoint)) = accepted {
+                self.sockets.remove(&interface_name);
+
+                connections.push(TcpConnection {
+                    interface_name,
+                    remote_endpoint,
+                    sockets: socket_set,
+                    handle,
+                    closed: false,
+                });
+            }
+        }
+
+        connections
+    }
+}
+
+impl TcpConnection {
+    /// Read the received data, or nothing once the remote side closed the connection
+    pub fn receive(&self) -> Option<Vec<u8>> {
+        interrupts::without_interrupts(|| {
+            let mut sockets = self.sockets.lock();
+            let socket = sockets.get_mut::<Socket>(self.handle);
+
+            let mut received = Vec::new();
+            while socket.can_recv() {
+                match socket.recv(|buffer| (buffer.len(), buffer.to_vec())) {
+                    Ok(data) if !data.is_empty() => received.extend(data),
+                    _ => break
+                }
+            }
+
+            match socket.is_active() && socket.may_recv() {
+                true => Some(received),
+                false => None
+            }
+        })
+    }
+
+    /// Send as much of the pending data as the socket accepts, returns whether everything was sent
+    pub fn send(&self, pending: &mut Vec<u8>) -> bool {
+        interrupts::without_interrupts(|| {
+            let mut sockets = self.sockets.lock();
+            let socket = sockets.get_mut::<Socket>(self.handle);
+
+            if socket.can_send() {
+                if let Ok(sent) = socket.send_slice(pending) {
+                    pending.drain(..sent);
+                }
+            }
+
+            pending.is_empty()
+        })
+    }
+
+    /// Close the connection, letting the sent data go, and drop the socket
+    pub async fn close(mut self) {
+        interrupts::without_interrupts(|| {
+            self.sockets.lock().get_mut::<Socket>(self.handle).close();
+        });
+
+        let close_deadline = Clock::now() + CLOSE_TIMEOUT;
+
+        loop {
+            let is_closed = interrupts::without_interrupts(|| {
+                let mut sockets = self.sockets.lock();
+                let state = sockets.get::<Socket>(self.handle).state();
+
+                if matches!(state, State::Closed | State::TimeWait) || Clock::now() >= close_deadline {
+                    sockets.remove(self.handle);
+                    unregister_owner(&self.sockets, self.handle);
+                    true
+                }
+                else {
+                    false
+                }
+            });
+
+            if is_closed {
+                self.closed = true;
+                break;
+            }
+
+            Timer::after(CLOSE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Drop for TcpConnection {
+    /// Reset the connection of a session killed before closing it, and drop the socket
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+
+        interrupts::without_interrupts(|| self.sockets.lock().get_mut::<Socket>(self.handle).abort());
+        poll_interface(&self.interface_name);
+
+        interrupts::without_interrupts(|| {
+            self.sockets.lock().remove(self.handle);
+            unregister_owner(&self.sockets, self.handle);
+        });
+    }
+}
+
+impl<F: FnMut()> Drop for SessionGuard<F> {
+    fn drop(&mut self) {
+        (self.0)();
+    }
+}
@@ -0,0 +1,258 @@
+use crate::clock::Timer;
+use crate::services::tcp::{SessionGuard, SessionInfo, TcpConnection, TcpListeners};
+use crate::task::executor::spawn_task;
+use crate::task::task::Task;
+use crate::terminal::remote::{RemoteSession, RemoteTerminal};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use goolog::{debug, info};
+use smoltcp::time::Duration;
+use spin::{Lazy, Mutex};
+
+const GOOLOG_TARGET: &str = "TELNET";
+
+pub const TELNET_PORT: u16 = 23;
+
+const MAX_SESSIONS: usize = 4;
+
+const RX_BUFFER_SIZE: usize = 1024;
+const TX_BUFFER_SIZE: usize = 8192;
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Commands (RFC 854)
+const SE: u8 = 240;
+const IP: u8 = 244;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+// Options
+const OPTION_ECHO: u8 = 1;
+const OPTION_SUPPRESS_GO_AHEAD: u8 = 3;
+
+pub static TELNET_SERVER: Lazy<Mutex<TelnetServer>> = Lazy::new(|| Mutex::new(TelnetServer::new()));
+
+pub struct TelnetServer {
+    pub port: Option<u16>,
+    pub sessions: BTreeMap<u64, SessionInfo>,
+    pub accepted_sessions: u64,
+    listeners: TcpListeners,
+    next_session_id: u64,
+}
+
+impl TelnetServer {
+    pub fn new() -> Self {
+        Self {
+            port: None,
+            sessions: BTreeMap::new(),
+            accepted_sessions: 0,
+            listeners: TcpListeners::new(RX_BUFFER_SIZE, TX_BUFFER_SIZE),
+            next_session_id: 0,
+        }
+    }
+
+    /// Start listening on the given port, or stop the server and close the sessions
+    pub fn set_port(&mut self, port: Option<u16>) {
+        self.port = port;
+        self.listeners.close();
+
+        if port.is_none() {
+            self.sessions.clear();
+        }
+    }
+
+    /// Listen on every interface, and hand the accepted connections to new sessions
+    fn poll(&mut self) {
+        let Some(port) = self.port else {
+            return;
+        };
+
+        // New connections are refused once the sessions are all taken
+        if self.sessions.len() >= MAX_SESSIONS {
+            if self.listeners.is_listening() {
+                debug!("Maximum number of sessions reached");
+                self.listeners.close();
+            }
+            return;
+        }
+
+        for connection in self.listeners.accept(port) {
+            let id = self.next_session_id;
+            self.next_session_id += 1;
+            self.accepted_sessions += 1;
+
+            let task_name = match connection.remote_endpoint {
+                Some(remote_endpoint) => {
+                    info!("Session {} opened from {} on {}", id, remote_endpoint, connection.interface_name);
+                    format!("Telnet session {}", remote_endpoint)
+                },
+                None => {
+                    info!("Session {} opened on {}", id, connection.interface_name);
+                    String::from("Telnet session")
+                }
+            };
+
+            self.sessions.insert(id, SessionInfo::new(&connection));
+
+            spawn_task(Task::new(task_name, run_session(id, connection)));
+        }
+    }
+}
+
+impl Default for TelnetServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputState {
+    Data,
+    Iac,
+    Negotiation(u8),
+    Subnegotiation,
+    SubnegotiationIac,
+}
+
+/// Telnet layer of a session, the data is handed to the CLI
+struct TelnetSession {
+    session: RemoteSession,
+    state: InputState,
+    suppress_go_ahead: bool,
+    remote_suppress_go_ahead: bool,
+}
+
+impl TelnetSession {
+    fn new() -> Self {
+        let mut terminal = RemoteTerminal::new();
+
+        // Character at a time mode, with the server echoing the input (RFC 857, RFC 858)
+        terminal.write_raw(&[IAC, WILL, OPTION_ECHO, IAC, WILL, OPTION_SUPPRESS_GO_AHEAD, IAC, DO, OPTION_SUPPRESS_GO_AHEAD]);
+
+        Self {
+            session: RemoteSession::new(terminal),
+            state: InputState::Data,
+            suppress_go_ahead: true,
+            remote_suppress_go_ahead: true,
+        }
+    }
+
+    fn send_command(&mut self, command: u8, option: u8) {
+        self.session.terminal().write_raw(&[IAC, command, option]);
+    }
+
+    fn receive(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (InputState::Data, IAC) => InputState::Iac,
+                (InputState::Data, _) => {
+                    self.session.receive(byte);
+                    InputState::Data
+                },
+                // Escaped 0xFF data byte, not printable
+                (InputState::Iac, IAC) => InputState::Data,
+                (InputState::Iac, WILL | WONT | DO | DONT) => InputState::Negotiation(byte),
+                (InputState::Iac, SB) => InputState::Subnegotiation,
+                (InputState::Iac, IP) => {
+                    self.session.interrupt();
+                    InputState::Data
+                },
+                // Other commands (NOP, AYT, GA...) are ignored
+                (InputState::Iac, _) => InputState::Data,
+                (InputState::Negotiation(command), option) => {
+                    self.negotiate(command, option);
+                    InputState::Data
+                },
+                (InputState::Subnegotiation, IAC) => InputState::SubnegotiationIac,
+                (InputState::Subnegotiation, _) => InputState::Subnegotiation,
+                (InputState::SubnegotiationIac, SE) => InputState::Data,
+                (InputState::SubnegotiationIac, _) => InputState::Subnegotiation,
+            };
+        }
+    }
+
+    /// Option negotiation (RFC 854), the offered options are considered enabled until refused
+    fn negotiate(&mut self, command: u8, option: u8) {
+        match (command, option) {
+            (DO, OPTION_ECHO) => if !self.session.terminal().echo {
+                self.session.terminal().echo = true;
+                self.send_command(WILL, option);
+            },
+            (DONT, OPTION_ECHO) => if self.session.terminal().echo {
+                self.session.terminal().echo = false;
+                self.send_command(WONT, option);
+            },
+            (DO, OPTION_SUPPRESS_GO_AHEAD) => if !self.suppress_go_ahead {
+                self.suppress_go_ahead = true;
+                self.send_command(WILL, option);
+            },
+            (DONT, OPTION_SUPPRESS_GO_AHEAD) => if self.suppress_go_ahead {
+                self.suppress_go_ahead = false;
+                self.send_command(WONT, option);
+            },
+            (WILL, OPTION_SUPPRESS_GO_AHEAD) => if !self.remote_suppress_go_ahead {
+                self.remote_suppress_go_ahead = true;
+                self.send_command(DO, option);
+            },
+            (WONT, OPTION_SUPPRESS_GO_AHEAD) => if self.remote_suppress_go_ahead {
+                self.remote_suppress_go_ahead = false;
+                self.send_command(DONT, option);
+            },
+            // Every other option is refused
+            (DO, _) => self.send_command(WONT, option),
+            (WILL, _) => self.send_command(DONT, option),
+            _ => {}
+        }
+    }
+}
+
+/// Task serving a single connection, until it is closed by either side
+async fn run_session(id: u64, connection: TcpConnection) {
+    // The session is forgotten even when its task is killed
+    let _guard = SessionGuard(|| {
+        TELNET_SERVER.lock().sessions.remove(&id);
+        info!("Session {} closed", id);
+    });
+
+    let mut telnet = TelnetSession::new();
+    let mut pending_output = telnet.session.take_output();
+
+    loop {
+        Timer::after(SESSION_POLL_INTERVAL).await;
+
+        if !TELNET_SERVER.lock().sessions.contains_key(&id) {
+            telnet.session.closing = true;
+        }
+
+        // The socket is not kept locked while the commands run
+        let Some(received) = connection.receive() else {
+            break;
+        };
+
+        telnet.receive(&received);
+        telnet.session.poll();
+        pending_output.extend(telnet.session.take_output());
+
+        let sent_everything = connection.send(&mut pending_output);
+
+        if telnet.session.closing && sent_everything {
+            break;
+        }
+    }
+
+    connection.close().await;
+}
+
+/// Task accepting the telnet connections
+pub async fn telnet_server() {
+    loop {
+        Timer::after(ACCEPT_INTERVAL).await;
+        TELNET_SERVER.lock().poll();
+    }
+}
\ No newline at end of file
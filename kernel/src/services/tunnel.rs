@@ -0,0 +1,212 @@
+use crate::clock::Timer;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::ServiceSocket;
+use crate::devices::network::tunnel::{decapsulate, encapsulate, TunnelDriver, TunnelMode, MAX_IP_PACKET_SIZE};
+use crate::services::radvd::RADVD;
+use crate::services::slaac::SLAAC;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use goolog::{debug, info};
+use smoltcp::socket::raw::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpVersion, Ipv4Address};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "TUNNEL";
+
+const TICK: Duration = Duration::from_millis(10);
+
+/// Room for a reassembled packet, or for several ones of the usual sizes
+const SOCKET_PACKETS: usize = 16;
+const SOCKET_BUFFER_SIZE: usize = MAX_IP_PACKET_SIZE;
+
+/// Tunnel interfaces. Locked before the network manager.
+pub static TUNNELS: Mutex<Tunnels> = Mutex::new(Tunnels::new());
+
+pub struct Tunnels {
+    pub tunnels: BTreeMap<String, Tunnel>,
+}
+
+/// Point-to-point tunnel, its packets encapsulated in IPv4 packets sent to the remote end
+pub struct Tunnel {
+    pub mode: TunnelMode,
+    pub local: Ipv4Address,
+    pub remote: Ipv4Address,
+    pub driver: Arc<Mutex<TunnelDriver>>,
+    /// Raw socket on the interface reaching the remote end
+    socket: Option<(String, ServiceSocket)>,
+}
+
+impl Tunnels {
+    pub const fn new() -> Self {
+        Self {
+            tunnels: BTreeMap::new(),
+        }
+    }
+
+    /// Create the interface of a tunnel
+    pub fn add(&mut self, name: &str, mode: TunnelMode, local: Ipv4Address, remote: Ipv4Address) {
+        let driver = Arc::new(Mutex::new(TunnelDriver::new(mode)));
+        let device = NETWORK_MANAGER.lock().register_virtual_device(name, driver.clone());
+
+        interrupts::without_interrupts(|| {
+            device.lock().network_controller.set_mtu(mode.default_mtu());
+        });
+
+        self.tunnels.insert(String::from(name), Tunnel {
+            mode,
+            local,
+            remote,
+            driver,
+            socket: None,
+        });
+    }
+
+    /// Remove the interface of a tunnel
+    pub fn delete(&mut self, name: &str) {
+        let Some(mut tunnel) = self.tunnels.remove(name) else {
+            return;
+        };
+
+        tunnel.close_socket();
+        NETWORK_MANAGER.lock().unregister_virtual_device(name);
+
+        // An interface created again under the same name starts over
+        SLAAC.lock().forget(name);
+        RADVD.lock().forget(name);
+    }
+
+    fn poll(&mut self) {
+        for (name, tunnel) in self.tunnels.iter_mut() {
+            tunnel.poll(name);
+        }
+    }
+}
+
+impl Default for Tunnels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tunnel {
+    fn close_socket(&mut self) {
+        if let Some((_, socket)) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    /// Encapsulate the packets sent on the tunnel, and hand the ones received from the remote end to it
+    fn poll(&mut self, name: &str) {
+        let network_manager = NETWORK_MANAGER.lock();
+
+        let Some(device) = network_manager.interfaces.get(name).cloned() else {
+            return;
+        };
+
+        // The remote end is reached through another interface, a route through the tunnel itself would loop
+        let underlay = network_manager
+            .find_route_interface(&IpAddress::Ipv4(self.remote))
+            .filter(|interface_name| interface_name != name)
+            .and_then(|interface_name| network_manager.interfaces.get(&interface_name).map(|device| (interface_name, device.lock().sockets.clone())));
+
+        drop(network_manager);
+
+        // The route to the remote end changed, or the underlying interface was deleted and created again
+        let is_stale = match (&self.socket, &underlay) {
+            (Some((socket_interface, socket)), Some((interface_name, socket_set))) => socket_interface != interface_name || !socket.is_in(socket_set),
+            (Some(_), None) => true,
+            (None, _) => false
+        };
+
+        if is_stale {
+            self.close_socket();
+        }
+
+        let outgoing: Vec<Vec<u8>> = interrupts::without_interrupts(|| self.driver.lock().tx_queue.drain(..).collect());
+
+        let Some((underlay_name, socket_set)) = underlay else {
+            if !outgoing.is_empty() {
+                debug!("{}: {} unreachable, {} packets dropped", name, self.remote, outgoing.len());
+                interrupts::without_interrupts(|| self.driver.lock().statistics.tx_errors += outgoing.len() as u64);
+            }
+
+            return;
+        };
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match &self.socket {
+            Some((_, socket)) => socket.handle,
+            None => {
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_BUFFER_SIZE]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_BUFFER_SIZE]);
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, Socket::new(IpVersion::Ipv4, self.mode.protocol(), rx_buffer, tx_buffer));
+                let handle = socket.handle;
+
+                info!("{}: reaching {} through {}", name, self.remote, underlay_name);
+                self.socket = Some((underlay_name, socket));
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        let mut tx_errors = 0;
+        let mut tx_dropped = 0;
+
+        for packet in outgoing {
+            match encapsulate(self.mode, self.local, self.remote, &packet) {
+                // IPv6 over IPIP
+                None => tx_errors += 1,
+                Some(packet) => {
+                    if socket.send_slice(&packet).is_err() {
+                        tx_dropped += 1;
+                    }
+                }
+            }
+        }
+
+        // Every raw socket of the protocol gets the packet, the ones of the other tunnels are skipped
+        let mut incoming = Vec::new();
+
+        while let Ok(packet) = socket.recv() {
+            if let Some(packet) = decapsulate(self.mode, self.local, self.remote, packet) {
+                incoming.push(packet);
+            }
+        }
+
+        drop(sockets);
+
+        interrupts::without_interrupts(|| {
+            let mut driver = self.driver.lock();
+            driver.statistics.tx_errors += tx_errors;
+            driver.statistics.tx_dropped += tx_dropped;
+
+            for packet in incoming {
+                driver.queue_rx(packet);
+            }
+        });
+
+        // smoltcp takes the received packets one at a time
+        interrupts::without_interrupts(|| {
+            let mut device = device.lock();
+
+            while !self.driver.lock().rx_queue.is_empty() {
+                device.network_controller.process_interrupt();
+                device.poll();
+            }
+        });
+    }
+}
+
+pub async fn forward_tunnels() {
+    loop {
+        Timer::after(TICK).await;
+        TUNNELS.lock().poll();
+    }
+}
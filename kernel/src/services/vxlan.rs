@@ -0,0 +1,335 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::ServiceSocket;
+use crate::devices::network::tunnel::MAX_IP_PACKET_SIZE;
+use crate::devices::network::vxlan::{decapsulate, default_mtu, encapsulate, VxlanDriver, VXLAN_PORT};
+use crate::services::radvd::RADVD;
+use crate::services::slaac::SLAAC;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use goolog::{debug, info, warn};
+use smoltcp::socket::udp::{PacketBuffer, PacketMetadata, Socket};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpEndpoint};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "VXLAN";
+
+const TICK: Duration = Duration::from_millis(10);
+
+/// Learned addresses are forgotten when their hosts stay silent (IEEE 802.1D default)
+pub const FDB_AGEING_TIME: Duration = Duration::from_secs(300);
+/// Learned addresses kept by each interface, the other ones being flooded
+const MAX_FDB_ENTRIES: usize = 1024;
+
+/// Room for a reassembled datagram, or for several ones of the usual sizes
+const SOCKET_PACKETS: usize = 32;
+const SOCKET_BUFFER_SIZE: usize = MAX_IP_PACKET_SIZE;
+
+/// VXLAN interfaces. Locked before the network manager.
+pub static VXLANS: Mutex<Vxlans> = Mutex::new(Vxlans::new());
+
+pub struct Vxlans {
+    pub interfaces: BTreeMap<String, Vxlan>,
+    /// Socket bound to the VXLAN port, by underlying interface, shared by the VNIs
+    sockets: BTreeMap<String, ServiceSocket>,
+}
+
+/// Virtual Ethernet segment, its frames carried in UDP datagrams between the VTEPs (RFC 7348)
+pub struct Vxlan {
+    pub vni: u32,
+    /// Interface reaching the remote VTEPs
+    pub underlay: String,
+    /// Multicast group the broadcast, multicast and unknown unicast frames are flooded to
+    pub group: Option<IpAddress>,
+    /// VTEPs the flooded frames are replicated to
+    pub remotes: Vec<IpAddress>,
+    /// VTEP behind each learned MAC address
+    pub fdb: BTreeMap<EthernetAddress, FdbEntry>,
+    pub driver: Arc<Mutex<VxlanDriver>>,
+}
+
+pub struct FdbEntry {
+    pub vtep: IpAddress,
+    pub updated_at: Instant,
+}
+
+impl Vxlans {
+    pub const fn new() -> Self {
+        Self {
+            interfaces: BTreeMap::new(),
+            sockets: BTreeMap::new(),
+        }
+    }
+
+    /// Create a VXLAN interface
+    pub fn add(&mut self, name: &str, vni: u32, underlay: &str, group: Option<IpAddress>) {
+        let driver = Arc::new(Mutex::new(VxlanDriver::new()));
+        let device = NETWORK_MANAGER.lock().register_virtual_device(name, driver.clone());
+
+        interrupts::without_interrupts(|| {
+            device.lock().network_controller.set_mtu(default_mtu());
+        });
+
+        if let Some(group) = group {
+            join_group(underlay, group);
+        }
+
+        self.interfaces.insert(String::from(name), Vxlan {
+            vni,
+            underlay: String::from(underlay),
+            group,
+            remotes: Vec::new(),
+            fdb: BTreeMap::new(),
+            driver,
+        });
+    }
+
+    /// Remove a VXLAN interface
+    pub fn delete(&mut self, name: &str) {
+        let Some(vxlan) = self.interfaces.remove(name) else {
+            return;
+        };
+
+        NETWORK_MANAGER.lock().unregister_virtual_device(name);
+
+        // An interface created again under the same name starts over
+        SLAAC.lock().forget(name);
+        RADVD.lock().forget(name);
+
+        // The group may still be used by another VNI
+        if let Some(group) = vxlan.group {
+            if !self.interfaces.values().any(|other| other.underlay == vxlan.underlay && other.group == Some(group)) {
+                leave_group(&vxlan.underlay, group);
+            }
+        }
+    }
+
+    fn poll(&mut self) {
+        let now = Clock::now();
+
+        let underlays: BTreeSet<String> = self.interfaces
+            .values()
+            .map(|vxlan| vxlan.underlay.clone())
+            .collect();
+
+        let unused: Vec<String> = self.sockets
+            .keys()
+            .filter(|underlay| !underlays.contains(*underlay))
+            .cloned()
+            .collect();
+
+        for underlay in unused {
+            self.close_socket(&underlay);
+        }
+
+        for underlay in underlays {
+            self.forward(&underlay, now);
+        }
+
+        for (name, vxlan) in self.interfaces.iter_mut() {
+            vxlan.fdb.retain(|_, entry| now < entry.updated_at + FDB_AGEING_TIME);
+            vxlan.deliver(name);
+        }
+    }
+
+    fn close_socket(&mut self, underlay: &str) {
+        if let Some(socket) = self.sockets.remove(underlay) {
+            socket.close();
+        }
+    }
+
+    /// Exchange the frames of the VXLAN interfaces over an underlying interface
+    fn forward(&mut self, underlay: &str, now: Instant) {
+        let Some(device) = NETWORK_MANAGER.lock().interfaces.get(underlay).cloned() else {
+            self.close_socket(underlay);
+            return;
+        };
+
+        let socket_set = device.lock().sockets.clone();
+
+        // The underlying interface was deleted and created again
+        if self.sockets.get(underlay).is_some_and(|socket| !socket.is_in(&socket_set)) {
+            self.close_socket(underlay);
+        }
+
+        let mut sockets = socket_set.lock();
+
+        let handle = match self.sockets.get(underlay) {
+            Some(socket) => socket.handle,
+            None => {
+                let rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_BUFFER_SIZE]);
+                let tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_BUFFER_SIZE]);
+                let mut socket = Socket::new(rx_buffer, tx_buffer);
+
+                if socket.bind(VXLAN_PORT).is_err() {
+                    warn!("{}: cannot bind the VXLAN port", underlay);
+                    return;
+                }
+
+                let socket = ServiceSocket::add(&socket_set, &mut sockets, socket);
+                let handle = socket.handle;
+
+                info!("{}: listening on UDP port {}", underlay, VXLAN_PORT);
+                self.sockets.insert(String::from(underlay), socket);
+                handle
+            }
+        };
+
+        let socket = sockets.get_mut::<Socket>(handle);
+
+        while let Ok((packet, metadata)) = socket.recv() {
+            let Some((vni, frame)) = decapsulate(packet) else {
+                continue;
+            };
+
+            match self.interfaces.values_mut().find(|vxlan| vxlan.underlay == underlay && vxlan.vni == vni) {
+                Some(vxlan) => vxlan.receive(frame, metadata.endpoint.addr, now),
+                None => debug!("{}: frame of unknown VNI {} from {}", underlay, vni, metadata.endpoint.addr)
+            }
+        }
+
+        for vxlan in self.interfaces.values_mut().filter(|vxlan| vxlan.underlay == underlay) {
+            let outgoing: Vec<Vec<u8>> = interrupts::without_interrupts(|| vxlan.driver.lock().tx_queue.drain(..).collect());
+            let mut tx_dropped = 0;
+
+            for frame in outgoing {
+                let packet = encapsulate(vxlan.vni, &frame);
+
+                for vtep in vxlan.destinations(&frame) {
+                    if socket.send_slice(&packet, IpEndpoint::new(vtep, VXLAN_PORT)).is_err() {
+                        tx_dropped += 1;
+                    }
+                }
+            }
+
+            if tx_dropped > 0 {
+                interrupts::without_interrupts(|| vxlan.driver.lock().statistics.tx_dropped += tx_dropped);
+            }
+        }
+    }
+}
+
+impl Default for Vxlans {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vxlan {
+    /// Remote VTEPs of a frame: the one it was learned behind, or every one for the frames flooded
+    fn destinations(&self, frame: &[u8]) -> Vec<IpAddress> {
+        let destination = EthernetAddress::from_bytes(&frame[..6]);
+
+        if let Some(entry) = self.fdb.get(&destination).filter(|_| destination.is_unicast()) {
+            return vec![entry.vtep];
+        }
+
+        self.group.iter().chain(self.remotes.iter()).copied().collect()
+    }
+
+    /// Learn the VTEP behind the source of a frame, and queue it for smoltcp
+    fn receive(&mut self, frame: &[u8], vtep: IpAddress, now: Instant) {
+        let source = EthernetAddress::from_bytes(&frame[6..12]);
+
+        // A frame of ours, flooded back by the group
+        let is_own = interrupts::without_interrupts(|| {
+            let mut driver = self.driver.lock();
+
+            if source.0 == driver.mac {
+                return true;
+            }
+
+            driver.queue_rx(frame.to_vec());
+            false
+        });
+
+        if is_own || !source.is_unicast() {
+            return;
+        }
+
+        match self.fdb.get_mut(&source) {
+            Some(entry) => {
+                if entry.vtep != vtep {
+                    debug!("{} moved from {} to {}", source, entry.vtep, vtep);
+                    entry.vtep = vtep;
+                }
+
+                entry.updated_at = now;
+            },
+            None if self.fdb.len() < MAX_FDB_ENTRIES => {
+                self.fdb.insert(source, FdbEntry {
+                    vtep,
+                    updated_at: now,
+                });
+            },
+            None => {}
+        }
+    }
+
+    /// Hand the decapsulated frames to smoltcp, one at a time
+    fn deliver(&mut self, name: &str) {
+        if interrupts::without_interrupts(|| self.driver.lock().rx_queue.is_empty()) {
+            return;
+        }
+
+        let Some(device) = NETWORK_MANAGER.lock().interfaces.get(name).cloned() else {
+            return;
+        };
+
+        interrupts::without_interrupts(|| {
+            let mut device = device.lock();
+
+            while !self.driver.lock().rx_queue.is_empty() {
+                device.network_controller.process_interrupt();
+                device.poll();
+            }
+        });
+    }
+}
+
+/// Receive the frames flooded to a group on the underlying interface
+fn join_group(underlay: &str, group: IpAddress) {
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(underlay).cloned() else {
+        return;
+    };
+
+    interrupts::without_interrupts(|| {
+        let mut device = device.lock();
+
+        if device.interface.join_multicast_group(group).is_err() {
+            warn!("{}: cannot join {}, the flooded frames will not be received", underlay, group);
+        }
+
+        device.update_multicast_filter();
+    });
+}
+
+fn leave_group(underlay: &str, group: IpAddress) {
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(underlay).cloned() else {
+        return;
+    };
+
+    interrupts::without_interrupts(|| {
+        let mut device = device.lock();
+
+        // Joined with ip maddr as well
+        if device.multicast_groups.contains(&group) {
+            return;
+        }
+
+        let _ = device.interface.leave_multicast_group(group);
+        device.update_multicast_filter();
+    });
+}
+
+pub async fn forward_vxlans() {
+    loop {
+        Timer::after(TICK).await;
+        VXLANS.lock().poll();
+    }
+}
@@ -1,19 +1,102 @@
 use crate::task::task::{Task, TaskId, TaskWaker};
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 use alloc::{collections::BTreeMap, sync::Arc};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::{Context, Poll, Waker};
 use crossbeam_queue::ArrayQueue;
 use spin::{Lazy, Mutex, RwLock};
 use x86_64::instructions::interrupts;
 use x86_64::instructions::interrupts::enable_and_hlt;
 
+/// Value of CURRENT_TASK when no task is being polled
+const NO_TASK: u64 = u64::MAX;
+
 pub static WAKER_CACHE: Mutex<BTreeMap<TaskId, Waker>> = Mutex::new(BTreeMap::new());
 pub static TASKS: RwLock<BTreeMap<TaskId, Task>> = RwLock::new(BTreeMap::new());
 pub static NEW_TASKS: Mutex<Vec<Task>> = Mutex::new(Vec::new());
 pub static TASK_QUEUE: Lazy<Mutex<Arc<ArrayQueue<TaskId>>>> = Lazy::new(|| Mutex::new(Arc::new(ArrayQueue::new(100))));
 
-pub fn spawn_task(task: Task) {
+/// Tasks to drop instead of polling them
+static CANCELLED_TASKS: Mutex<BTreeSet<TaskId>> = Mutex::new(BTreeSet::new());
+/// Wakers of the futures waiting for a task to end
+static TASK_WAITERS: Mutex<BTreeMap<TaskId, Vec<Waker>>> = Mutex::new(BTreeMap::new());
+static CURRENT_TASK: AtomicU64 = AtomicU64::new(NO_TASK);
+
+pub fn spawn_task(task: Task) -> TaskId {
+    let task_id = task.id;
     NEW_TASKS.lock().push(task);
+    task_id
+}
+
+/// Task being polled, if any
+pub fn current_task() -> Option<TaskId> {
+    match CURRENT_TASK.load(Ordering::Relaxed) {
+        NO_TASK => None,
+        id => Some(TaskId(id))
+    }
+}
+
+pub fn is_task_running(task_id: TaskId) -> bool {
+    TASKS.read().contains_key(&task_id) || NEW_TASKS.lock().iter().any(|task| task.id == task_id)
+}
+
+/// Drop a task the next time the executor runs, returns whether it exists
+pub fn cancel_task(task_id: TaskId) -> bool {
+    if !is_task_running(task_id) {
+        return false;
+    }
+
+    CANCELLED_TASKS.lock().insert(task_id);
+
+    // The executor only looks at the queued tasks
+    let _ = TASK_QUEUE.lock().push(task_id);
+
+    true
+}
+
+/// Future completing once the given task ended, or was cancelled
+pub fn wait_task(task_id: TaskId) -> TaskCompletion {
+    TaskCompletion { task_id }
+}
+
+pub struct TaskCompletion {
+    task_id: TaskId,
+}
+
+impl Future for TaskCompletion {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if !is_task_running(self.task_id) {
+            return Poll::Ready(());
+        }
+
+        let mut task_waiters = TASK_WAITERS.lock();
+        let waiters = task_waiters.entry(self.task_id).or_default();
+
+        if !waiters.iter().any(|waiter| waiter.will_wake(context.waker())) {
+            waiters.push(context.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+fn end_task(task_id: TaskId) -> Option<Task> {
+    let task = TASKS.write().remove(&task_id);
+    WAKER_CACHE.lock().remove(&task_id);
+    CANCELLED_TASKS.lock().remove(&task_id);
+
+    if let Some(waiters) = TASK_WAITERS.lock().remove(&task_id) {
+        for waiter in waiters {
+            waiter.wake();
+        }
+    }
+
+    task
 }
 
 fn run_ready_tasks() {
@@ -29,11 +112,26 @@ fn run_ready_tasks() {
         }
     }
 
-    let task_queue = TASK_QUEUE.lock();
+    // Not kept locked, as the tasks may cancel each other
+    let task_queue = TASK_QUEUE.lock().clone();
+
     while let Some(task_id) = task_queue.pop() {
-        let mut waker_cache = WAKER_CACHE.lock();
+        // Dropping the future runs its destructors, which free what it holds
+        if CANCELLED_TASKS.lock().contains(&task_id) {
+            // The task may have been cancelled before reaching the executor
+            let new_task = {
+                let mut new_tasks = NEW_TASKS.lock();
+                new_tasks.iter().position(|task| task.id == task_id).map(|index| new_tasks.remove(index))
+            };
+
+            drop(new_task);
+            drop(end_task(task_id));
+            continue;
+        }
 
         let task_state = {
+            let mut waker_cache = WAKER_CACHE.lock();
+
             let tasks = TASKS.read();
             let task = match tasks.get(&task_id) {
                 Some(task) => task,
@@ -43,18 +141,22 @@ fn run_ready_tasks() {
 
             let waker = waker_cache
                 .entry(task_id)
-                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
+                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()))
+                .clone();
+
+            drop(waker_cache);
+
+            let mut context = Context::from_waker(&waker);
 
-            let mut context = Context::from_waker(waker);
+            CURRENT_TASK.store(task_id.0, Ordering::Relaxed);
+            let task_state = task.poll(&mut context);
+            CURRENT_TASK.store(NO_TASK, Ordering::Relaxed);
 
-            task.poll(&mut context)
+            task_state
         };
 
         match task_state {
-            Poll::Ready(()) => {
-                TASKS.write().remove(&task_id);
-                waker_cache.remove(&task_id);
-            }
+            Poll::Ready(()) => drop(end_task(task_id)),
             Poll::Pending => {}
         }
     }
@@ -76,4 +178,4 @@ fn sleep_if_idle() {
     else {
         interrupts::enable();
     }
-}
\ No newline at end of file
+}
@@ -1,16 +1,18 @@
 use crate::printer::buffer::WRITER;
-use crate::println;
-use crate::terminal::args::CliArgs;
-use crate::terminal::cli::{handle_command, set_max_verbosity, Cli};
+use crate::{print, println};
+use crate::task::executor::{cancel_task, wait_task};
+use crate::task::task::TaskId;
+use crate::terminal::cli::{parse_background, spawn_command, Cli};
+use crate::terminal::input::{close_input, open_input, remove_input, send_input};
 use alloc::format;
+use alloc::string::String;
 use core::pin::Pin;
 use core::task::{Context, Poll};
 use crossbeam_queue::ArrayQueue;
+use futures_util::future::{select, Either};
 use futures_util::task::AtomicWaker;
 use futures_util::{Stream, StreamExt};
 use goolog::set_target;
-use no_std_clap_core::error::ParseError;
-use no_std_clap_core::parser::Parser;
 use pc_keyboard::{DecodedKey, KeyCode};
 use spin::Once;
 use yansi::Paint;
@@ -18,6 +20,10 @@ use yansi::Paint;
 static SCANCODE_QUEUE: Once<ArrayQueue<DecodedKey>> = Once::new();
 static WAKER: AtomicWaker = AtomicWaker::new();
 
+/// Decoded from Ctrl-C by the keyboard, and sent as is by the serial terminals
+pub const CTRL_C: char = '\u{3}';
+pub const CTRL_D: char = '\u{4}';
+
 pub struct ScancodeStream {
     _private: (),
 }
@@ -101,24 +107,17 @@ pub async fn handle_keyboard() {
                 KeyCode::ArrowRight => cli.move_cursor_right(),
                 _ => {}
             },
+            DecodedKey::Unicode(CTRL_C) => cli.cancel_line(),
             DecodedKey::Unicode(char) => {
                 if let Some(command) = cli.handle_scancode(char as u8) {
-                    match CliArgs::parse_str(&command) {
-                        Ok(cli_args) => {
-                            set_max_verbosity(cli_args.verbose);
-                            handle_command(cli_args.command);
-                        },
-                        Err(parse_error) => {
-                            match parse_error {
-                                ParseError::EmptyInput => {}
-                                ParseError::Help(help) => println!("{}", help),
-                                ParseError::MissingArgument(argument) => println!("{} {}", "Missing required argument:".red(), argument),
-                                ParseError::InvalidValue(value) => println!("{} {}", "Invalid value:".red(), value),
-                                ParseError::UnknownArgument(argument) => println!("{} {}", "Unknown argument:".red(), argument),
-                                ParseError::UnknownSubcommand => println!("{} {}", "Unknown command:".red(), command),
-                                ParseError::InvalidFormat(format) => println!("{} {}", "Invalid format:".red(), format),
-                                ParseError::UnknownEnumVariant(value, possible_values) => println!("{} {}, possible values are: {}", "Invalid value:".red(), value, possible_values)
-                            }
+                    let (command, background) = parse_background(&command);
+
+                    if !command.is_empty() {
+                        let task_id = spawn_command(command, false);
+
+                        match background {
+                            true => println!("[{}] {}", task_id.0, command),
+                            false => wait_foreground(task_id, &mut scancodes).await
                         }
                     }
 
@@ -127,4 +126,44 @@ pub async fn handle_keyboard() {
             },
         }
     }
+}
+
+/// Wait for the foreground command to end, giving it the typed lines. Ctrl-C cancels it, Ctrl-D ends its input
+async fn wait_foreground(task_id: TaskId, scancodes: &mut ScancodeStream) {
+    let mut line = String::new();
+
+    open_input(task_id);
+
+    loop {
+        match select(wait_task(task_id), scancodes.next()).await {
+            Either::Left(_) | Either::Right((None, _)) => break,
+            Either::Right((Some(key), _)) => match key {
+                DecodedKey::Unicode(CTRL_C) => {
+                    cancel_task(task_id);
+                    println!("^C");
+
+                    // The task is only dropped by the executor
+                    wait_task(task_id).await;
+                    break;
+                },
+                DecodedKey::Unicode(CTRL_D) => close_input(task_id),
+                DecodedKey::Unicode('\n') => {
+                    println!();
+                    send_input(task_id, core::mem::take(&mut line));
+                },
+                DecodedKey::Unicode('\u{8}') | DecodedKey::RawKey(KeyCode::Backspace) => {
+                    if line.pop().is_some() {
+                        print!("\x1b[D");
+                    }
+                },
+                DecodedKey::Unicode(char) if !char.is_control() => {
+                    line.push(char);
+                    print!("{}", char);
+                },
+                _ => {}
+            }
+        }
+    }
+
+    remove_input(task_id);
 }
\ No newline at end of file
@@ -1,7 +1,21 @@
+use crate::terminal::commands::arping::ArpingCommand;
 use crate::terminal::commands::echo::EchoCommand;
+use crate::terminal::commands::flow::FlowCommand;
+use crate::terminal::commands::http::HttpCommand;
+use crate::terminal::commands::iperf::IperfCommand;
 use crate::terminal::commands::ip::ip::IpCommand;
 use crate::terminal::commands::keyboard::KeyboardLayout;
+use crate::terminal::commands::logging::LoggingCommand;
+use crate::terminal::commands::ntp::NtpCommand;
+use crate::terminal::commands::nc::NcCommand;
 use crate::terminal::commands::ping::PingCommand;
+use crate::terminal::commands::radvd::RadvdCommand;
+use crate::terminal::commands::dhcpv6::Dhcpv6Command;
+use crate::terminal::commands::snmp::SnmpCommand;
+use crate::terminal::commands::ss::SsCommand;
+use crate::terminal::commands::ssh::SshCommand;
+use crate::terminal::commands::telnet::TelnetCommand;
+use crate::terminal::commands::traceroute::TracerouteCommand;
 use no_std_clap_core::arg::arg_info::ArgInfo;
 use no_std_clap_macros::{Parser, Subcommand};
 
@@ -29,12 +43,21 @@ pub enum Commands {
     /// List current processes
     Ps,
 
+    /// Stop a process
+    Kill {
+        /// ID of the process, as listed by ps
+        id: u64,
+    },
+
     /// Print details about system resources usage
     Top,
 
     /// Print for how much time the system is running
     Uptime,
 
+    /// Print the current UTC date and time
+    Date,
+
     /// Sleeps the system
     Sleep {
         /// Seconds amount to sleep the system
@@ -59,7 +82,59 @@ pub enum Commands {
     /// Ping an IP address
     Ping(PingCommand),
 
+    /// Resolve an IPv4 address to its MAC address with ARP requests
+    Arping(ArpingCommand),
+
+    /// Trace the route to an IP address
+    Traceroute(TracerouteCommand),
+
+    /// Read and write data over TCP or UDP
+    Nc(NcCommand),
+
+    /// List the sockets of every interface, the loopback included
+    Ss(SsCommand),
+
+    /// Measure the bandwidth with the iperf3 protocol
+    #[command(subcommand)]
+    Iperf(IperfCommand),
+
     /// Network commands
     #[command(subcommand)]
-    Ip(IpCommand)
+    Ip(IpCommand),
+
+    /// Flow accounting and IPFIX export
+    #[command(subcommand)]
+    Flow(FlowCommand),
+
+    /// SNMP agent configuration
+    #[command(subcommand)]
+    Snmp(SnmpCommand),
+
+    /// Remote syslog forwarding
+    #[command(subcommand)]
+    Logging(LoggingCommand),
+
+    /// Clock synchronization with an NTP server
+    #[command(subcommand)]
+    Ntp(NtpCommand),
+
+    /// IPv6 router advertisements to the hosts of the links
+    #[command(subcommand)]
+    Radvd(RadvdCommand),
+
+    /// IPv6 prefix delegation with DHCPv6
+    #[command(subcommand)]
+    Dhcpv6(Dhcpv6Command),
+
+    /// Remote access to the CLI over telnet
+    #[command(subcommand)]
+    Telnet(TelnetCommand),
+
+    /// Remote access to the CLI over SSH
+    #[command(subcommand)]
+    Ssh(SshCommand),
+
+    /// HTTP/JSON management API
+    #[command(subcommand)]
+    Http(HttpCommand)
 }
\ No newline at end of file
@@ -1,20 +1,45 @@
 use crate::{print, println};
 use crate::printer::buffer::{Writer, BORDER_PADDING};
+use crate::printer::macros::start_capture;
+use crate::services::iperf::client::ClientOptions;
+use crate::services::iperf::server::ServerOptions;
+use crate::task::executor::spawn_task;
+use crate::task::task::{Task, TaskId};
 use crate::terminal::args::{CliArgs, Commands};
+use crate::terminal::commands::arping::{arping, ArpingCommand};
 use crate::terminal::commands::clear::clear;
+use crate::terminal::commands::date::date;
 use crate::terminal::commands::echo::{echo, EchoCommand};
-use crate::terminal::commands::ip::address::{ip_address_add, ip_address_delete, IpAddressAddCommand, IpAddressCommand, IpAddressDeleteCommand};
-use crate::terminal::commands::ip::interface::{ip_interface_show, IpInterfaceCommand};
+use crate::terminal::commands::flow::{flow_collector, flow_show, flow_stop, flow_timeout, FlowCollectorCommand, FlowCommand, FlowTimeoutCommand};
+use crate::terminal::commands::http::{http_show, http_start, http_stop, http_token, HttpCommand, HttpStartCommand};
+use crate::terminal::commands::iperf::{iperf_client, iperf_server, IperfClientCommand, IperfCommand, IperfServerCommand};
+use crate::terminal::commands::ip::address::{ip_address_add, ip_address_delete, ip_address_show, IpAddressAddCommand, IpAddressCommand, IpAddressDeleteCommand};
+use crate::terminal::commands::ip::interface::{ip_interface_rate, ip_interface_show, IpInterfaceCommand, IpInterfaceShowCommand};
 use crate::terminal::commands::ip::ip::IpCommand;
+use crate::terminal::commands::ip::link::{ip_link_set, IpLinkCommand, IpLinkSetCommand};
+use crate::terminal::commands::ip::maddr::{ip_maddr_add, ip_maddr_delete, ip_maddr_show, IpMaddrAddCommand, IpMaddrCommand, IpMaddrDeleteCommand};
 use crate::terminal::commands::ip::route::{ip_route_add, ip_route_delete, ip_route_show, IpRouteAddCommand, IpRouteCommand, IpRouteDeleteCommand};
+use crate::terminal::commands::ip::tunnel::{ip_tunnel_add, ip_tunnel_delete, ip_tunnel_show, IpTunnelAddCommand, IpTunnelCommand, IpTunnelDeleteCommand};
+use crate::terminal::commands::ip::vxlan::{ip_vxlan_add, ip_vxlan_delete, ip_vxlan_fdb, ip_vxlan_remote_add, ip_vxlan_remote_delete, ip_vxlan_show, IpVxlanAddCommand, IpVxlanCommand, IpVxlanRemoteCommand};
 use crate::terminal::commands::keyboard::change_layout;
+use crate::terminal::commands::kill::kill;
+use crate::terminal::commands::logging::{logging_facility, logging_host, logging_show, logging_stop, LoggingCommand, LoggingHostCommand};
 use crate::terminal::commands::lspci::lspci;
-use crate::terminal::commands::ping::{ping, PingCommand};
+use crate::terminal::commands::nc::{nc, NcCommand};
+use crate::terminal::commands::ntp::{ntp_server, ntp_show, ntp_stop, NtpCommand};
+use crate::terminal::commands::dhcpv6::{dhcpv6_lan_add, dhcpv6_lan_delete, dhcpv6_show, dhcpv6_start, dhcpv6_stop, Dhcpv6Command, Dhcpv6LanCommand, Dhcpv6StartCommand};
+use crate::terminal::commands::radvd::{radvd_flags, radvd_lifetime, radvd_mtu, radvd_prefix_add, radvd_prefix_delete, radvd_rdnss_add, radvd_rdnss_delete, radvd_show, radvd_start, radvd_stop, RadvdCommand, RadvdFlagsCommand, RadvdPrefixAddCommand, RadvdPrefixCommand, RadvdRdnssCommand};
+use crate::terminal::commands::ping::{ping, PingCommand, PingOptions};
 use crate::terminal::commands::ps::ps;
 use crate::terminal::commands::scanpci::scanpci;
 use crate::terminal::commands::shutdown::shutdown;
+use crate::terminal::commands::snmp::{snmp_community_add, snmp_community_delete, snmp_show, SnmpCommand, SnmpCommunityCommand};
 use crate::terminal::commands::sleep::cli_sleep;
+use crate::terminal::commands::ss::{ss, SsCommand};
+use crate::terminal::commands::ssh::{ssh_show, ssh_start, ssh_stop, ssh_user_add, ssh_user_delete, ssh_user_key, SshCommand, SshStartCommand, SshUserCommand};
+use crate::terminal::commands::telnet::{telnet_show, telnet_start, telnet_stop, TelnetCommand, TelnetStartCommand};
 use crate::terminal::commands::top::top;
+use crate::terminal::commands::traceroute::{traceroute, TracerouteCommand};
 use crate::terminal::commands::uptime::uptime;
 use crate::terminal::custom_arguments::verbosity::verbosity_to_level_filter;
 use alloc::string::String;
@@ -22,59 +47,103 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::Write;
 use goolog::log::set_max_level;
+use no_std_clap_core::error::ParseError;
 use no_std_clap_core::parser::Parser;
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
 use spin::RwLock;
 
-pub struct Cli {
+/// Display a CLI draws its prompt and edited line on
+pub trait CliTerminal {
+    fn write_str(&mut self, string: &str);
+
+    fn newline(&mut self);
+
+    /// Redraw the prompt and the line, with the cursor at the given index
+    fn redraw_line(&mut self, prompt: &str, prompt_length: usize, line: &str, cursor_index: usize, show_cursor: bool);
+}
+
+/// The framebuffer console
+impl CliTerminal for Arc<RwLock<Writer>> {
+    fn write_str(&mut self, string: &str) {
+        self.write().write_str(string).unwrap();
+    }
+
+    fn newline(&mut self) {
+        self.write().newline();
+    }
+
+    fn redraw_line(&mut self, prompt: &str, prompt_length: usize, line: &str, cursor_index: usize, show_cursor: bool) {
+        let mut writer = self.write();
+
+        writer.clear_line();
+        writer.write_str(prompt).unwrap();
+
+        // Write the full line
+        writer.write_str(line).unwrap();
+
+        // Now compute cursor_x, cursor_y
+        writer.cursor_x = BORDER_PADDING + (prompt_length * Writer::column_width()) + (cursor_index * Writer::column_width());
+        writer.cursor_y = writer.y;
+
+        writer.show_cursor = show_cursor;
+        writer.draw_cursor();
+    }
+}
+
+pub struct Cli<T: CliTerminal = Arc<RwLock<Writer>>> {
     prompt: String,
     prompt_length: usize,
     line: String,
     cursor_index: usize,
-    writer: Arc<RwLock<Writer>>,
+    terminal: T,
     history: Vec<String>,
     history_index: Option<usize>,
 }
 
-impl Cli {
-    pub fn new(prompt: String, prompt_length: usize, writer: Arc<RwLock<Writer>>) -> Self {
-        writer.write().write_str(&prompt).unwrap();
+impl<T: CliTerminal> Cli<T> {
+    pub fn new(prompt: String, prompt_length: usize, mut terminal: T) -> Self {
+        terminal.write_str(&prompt);
 
         Self {
             prompt,
             prompt_length,
             line: String::new(),
-            writer,
+            terminal,
             cursor_index: 0,
             history: Vec::new(),
             history_index: None,
         }
     }
 
+    pub fn terminal(&mut self) -> &mut T {
+        &mut self.terminal
+    }
+
     pub fn reset_line(&mut self) {
         self.print_current_line(false, true);
     }
 
-    fn print_current_line(&mut self, line_from_history: bool, show_cursor: bool) {
-        let mut writer = self.writer.write();
+    /// Drop the line being edited, and start a new one
+    pub fn cancel_line(&mut self) {
+        self.terminal.write_str("^C");
+        self.terminal.newline();
 
-        writer.clear_line();
-        writer.write_str(&self.prompt).unwrap();
+        self.line.clear();
+        self.cursor_index = 0;
+        self.history_index = None;
 
+        self.print_current_line(false, true);
+    }
+
+    fn print_current_line(&mut self, line_from_history: bool, show_cursor: bool) {
         let line_length = self.line.len();
 
         if self.cursor_index > line_length || (line_from_history && self.cursor_index == 0) {
             self.cursor_index = line_length;
         }
 
-        // Write the full line
-        writer.write_str(&self.line).unwrap();
-
-        // Now compute cursor_x, cursor_y
-        writer.cursor_x = BORDER_PADDING + (self.prompt_length * Writer::column_width()) + (self.cursor_index * Writer::column_width());
-        writer.cursor_y = writer.y;
-
-        writer.show_cursor = show_cursor;
-        writer.draw_cursor();
+        self.terminal.redraw_line(&self.prompt, self.prompt_length, &self.line, self.cursor_index, show_cursor);
     }
 
     pub fn handle_scancode(&mut self, scancode: u8) -> Option<String> {
@@ -83,13 +152,13 @@ impl Cli {
                 true => {
                     self.line.clear();
                     self.print_current_line(false, false);
-                    self.writer.write().newline();
+                    self.terminal.newline();
                     self.history_index = None;
                     self.cursor_index = 0;
                 }
                 false => {
                     self.print_current_line(false, false);
-                    self.writer.write().newline();
+                    self.terminal.newline();
 
                     self.history_index = None;
                     self.cursor_index = 0;
@@ -121,6 +190,8 @@ impl Cli {
                     self.line.remove(self.cursor_index);
                 }
             },
+            // Other control characters
+            0x0..=0x1F => return None,
             _ => {
                 //println!("{:X}", scancode);
                 //println!("{}", self.cursor_index);
@@ -188,7 +259,7 @@ impl Cli {
     }
 }
 
-pub fn handle_command(command: Commands) {
+pub async fn handle_command(command: Commands) {
     let result = match command {
         Commands::Help => {
             let help = CliArgs::get_help();
@@ -198,29 +269,89 @@ pub fn handle_command(command: Commands) {
         Commands::Echo(EchoCommand { text }) => echo(&text),
         Commands::Clear => clear(),
         Commands::Ps => ps(),
+        Commands::Kill { id } => kill(id),
         Commands::Keyboard { layout } => change_layout(layout),
         Commands::Lspci => lspci(),
         Commands::Scanpci => scanpci(),
         Commands::Top => top(),
         Commands::Uptime => uptime(),
-        Commands::Sleep { seconds, .. } => cli_sleep(seconds),
+        Commands::Date => date(),
+        Commands::Sleep { seconds, .. } => cli_sleep(seconds).await,
         Commands::Shutdown => shutdown(),
-        Commands::Ping (PingCommand { ip_address, count, timeout }) => ping(ip_address.0, count, timeout),
+        Commands::Ping (PingCommand { ip_address, count, timeout, size, interval, interface, ttl, flood, no_fragment }) => {
+            let options = PingOptions {
+                count,
+                timeout: Duration::from_secs(timeout),
+                size,
+                interval: interval.0,
+                source: interface,
+                ttl,
+                flood,
+                no_fragment,
+            };
+
+            ping(ip_address.0, &options).await.map(|_| ())
+        },
+        Commands::Arping(ArpingCommand { ip_address, interface, count, interval, dad }) => arping(ip_address.0, interface.as_ref().map(|interface| interface.0.as_str()), count, interval.0, dad).await,
+        Commands::Traceroute(TracerouteCommand { ip_address, protocol, max_hops, probes, timeout }) => traceroute(ip_address.0, protocol, max_hops, probes, timeout).await,
+        Commands::Nc(NcCommand { host, port, listen, local_port, udp }) => nc(host.map(|host| host.0), port, listen, local_port, udp).await,
+        Commands::Ss(SsCommand { tcp, udp, listening }) => ss(tcp, udp, listening),
+        Commands::Iperf(subcommand) => match subcommand {
+            IperfCommand::Client(IperfClientCommand { host, port, udp, time, bitrate, length, parallel, reverse, interval }) => {
+                let options = ClientOptions {
+                    udp,
+                    time,
+                    bitrate: bitrate.map(|bitrate| bitrate.0),
+                    block_size: length.unwrap_or(0),
+                    parallel,
+                    reverse,
+                    interval: Some(interval.0).filter(|interval| *interval != Duration::ZERO),
+                };
+
+                iperf_client(IpEndpoint::new(host.0, port), options).await
+            },
+            IperfCommand::Server(IperfServerCommand { port, one_off, interval }) => {
+                let options = ServerOptions {
+                    port,
+                    one_off,
+                    interval: Some(interval.0).filter(|interval| *interval != Duration::ZERO),
+                };
+
+                iperf_server(options).await
+            }
+        },
         Commands::Ip(subcommand) => {
             match subcommand {
                 IpCommand::Interface(subcommand) | IpCommand::I(subcommand) => match subcommand {
-                    None => ip_interface_show(),
+                    None => ip_interface_show(false),
+                    Some(subcommand) => match subcommand {
+                        IpInterfaceCommand::Show(IpInterfaceShowCommand { statistics }) => ip_interface_show(statistics),
+                        IpInterfaceCommand::Rate => ip_interface_rate().await,
+                    }
+                },
+                IpCommand::Link(subcommand) | IpCommand::L(subcommand) => match subcommand {
+                    None => ip_interface_show(false),
                     Some(subcommand) => match subcommand {
-                        IpInterfaceCommand::Show => ip_interface_show(),
+                        IpLinkCommand::Show => ip_interface_show(false),
+                        IpLinkCommand::Set(IpLinkSetCommand { interface_name, setting, value }) => ip_link_set(&interface_name.0, setting, value.as_deref()),
                     }
                 },
                 IpCommand::Address(subcommand) | IpCommand::A(subcommand) => match subcommand {
-                    None => Ok(()),
+                    None => ip_address_show(),
                     Some(subcommand) => match subcommand {
-                        IpAddressCommand::Add(IpAddressAddCommand { address, interface_name }) => ip_address_add(address.0, &interface_name.0),
+                        IpAddressCommand::Show => ip_address_show(),
+                        IpAddressCommand::Add(IpAddressAddCommand { address, interface_name, dad }) => ip_address_add(address.0, &interface_name.0, dad).await,
                         IpAddressCommand::Delete(IpAddressDeleteCommand { address, interface_name }) => ip_address_delete(address.0, &interface_name.0),
                     }
                 },
+                IpCommand::Maddr(subcommand) | IpCommand::M(subcommand) => match subcommand {
+                    None => ip_maddr_show(),
+                    Some(subcommand) => match subcommand {
+                        IpMaddrCommand::Show => ip_maddr_show(),
+                        IpMaddrCommand::Add(IpMaddrAddCommand { group, interface_name }) => ip_maddr_add(group.0, &interface_name.0),
+                        IpMaddrCommand::Delete(IpMaddrDeleteCommand { group, interface_name }) => ip_maddr_delete(group.0, &interface_name.0),
+                    }
+                },
                 IpCommand::Route(subcommand) | IpCommand::R(subcommand) => match subcommand {
                     None => ip_route_show(),
                     Some(subcommand) => match subcommand {
@@ -228,8 +359,99 @@ pub fn handle_command(command: Commands) {
                         IpRouteCommand::Add (IpRouteAddCommand { address, interface_name, gateway }) => ip_route_add(address.0, &interface_name.0, gateway.0),
                         IpRouteCommand::Delete(IpRouteDeleteCommand { address, interface_name }) => ip_route_delete(address.0, &interface_name.0)
                     }
+                },
+                IpCommand::Tunnel(subcommand) | IpCommand::T(subcommand) => match subcommand {
+                    None => ip_tunnel_show(),
+                    Some(subcommand) => match subcommand {
+                        IpTunnelCommand::Show => ip_tunnel_show(),
+                        IpTunnelCommand::Add(IpTunnelAddCommand { name, mode, local, remote }) => ip_tunnel_add(&name, mode, local.0, remote.0),
+                        IpTunnelCommand::Delete(IpTunnelDeleteCommand { interface_name }) => ip_tunnel_delete(&interface_name.0),
+                    }
+                },
+                IpCommand::Vxlan(subcommand) | IpCommand::V(subcommand) => match subcommand {
+                    None => ip_vxlan_show(),
+                    Some(subcommand) => match subcommand {
+                        IpVxlanCommand::Show => ip_vxlan_show(),
+                        IpVxlanCommand::Add(IpVxlanAddCommand { name, vni, interface_name, group }) => ip_vxlan_add(&name, vni, &interface_name.0, group.map(|group| group.0)),
+                        IpVxlanCommand::Delete { interface_name } => ip_vxlan_delete(&interface_name.0),
+                        IpVxlanCommand::Remote(subcommand) => match subcommand {
+                            IpVxlanRemoteCommand::Add { interface_name, address } => ip_vxlan_remote_add(&interface_name.0, address.0),
+                            IpVxlanRemoteCommand::Delete { interface_name, address } => ip_vxlan_remote_delete(&interface_name.0, address.0),
+                        },
+                        IpVxlanCommand::Fdb => ip_vxlan_fdb(),
+                    }
                 }
             }
+        },
+        Commands::Flow(subcommand) => match subcommand {
+            FlowCommand::Show => flow_show(),
+            FlowCommand::Collector(FlowCollectorCommand { address, port }) => flow_collector(address.0, port),
+            FlowCommand::Stop => flow_stop(),
+            FlowCommand::Timeout(FlowTimeoutCommand { active, inactive }) => flow_timeout(active, inactive),
+        },
+        Commands::Snmp(subcommand) => match subcommand {
+            SnmpCommand::Show => snmp_show(),
+            SnmpCommand::Community(subcommand) => match subcommand {
+                SnmpCommunityCommand::Add { community } => snmp_community_add(community),
+                SnmpCommunityCommand::Delete { community } => snmp_community_delete(community),
+            }
+        },
+        Commands::Logging(subcommand) => match subcommand {
+            LoggingCommand::Show => logging_show(),
+            LoggingCommand::Host(LoggingHostCommand { address, port }) => logging_host(address.0, port),
+            LoggingCommand::Stop => logging_stop(),
+            LoggingCommand::Facility { facility } => logging_facility(facility),
+        },
+        Commands::Ntp(subcommand) => match subcommand {
+            NtpCommand::Show => ntp_show(),
+            NtpCommand::Server { address } => ntp_server(address.0),
+            NtpCommand::Stop => ntp_stop(),
+        },
+        Commands::Radvd(subcommand) => match subcommand {
+            RadvdCommand::Show => radvd_show(),
+            RadvdCommand::Start { interface_name } => radvd_start(&interface_name.0),
+            RadvdCommand::Stop { interface_name } => radvd_stop(&interface_name.0),
+            RadvdCommand::Prefix(subcommand) => match subcommand {
+                RadvdPrefixCommand::Add(RadvdPrefixAddCommand { interface_name, prefix, valid, preferred }) => radvd_prefix_add(&interface_name.0, prefix.0, valid, preferred),
+                RadvdPrefixCommand::Delete { interface_name, prefix } => radvd_prefix_delete(&interface_name.0, prefix.0),
+            },
+            RadvdCommand::Flags(RadvdFlagsCommand { interface_name, managed, other }) => radvd_flags(&interface_name.0, managed, other),
+            RadvdCommand::Rdnss(subcommand) => match subcommand {
+                RadvdRdnssCommand::Add { interface_name, address } => radvd_rdnss_add(&interface_name.0, address.0),
+                RadvdRdnssCommand::Delete { interface_name, address } => radvd_rdnss_delete(&interface_name.0, address.0),
+            },
+            RadvdCommand::Mtu { interface_name, mtu } => radvd_mtu(&interface_name.0, mtu),
+            RadvdCommand::Lifetime { interface_name, seconds } => radvd_lifetime(&interface_name.0, seconds),
+        },
+        Commands::Dhcpv6(subcommand) => match subcommand {
+            Dhcpv6Command::Show => dhcpv6_show(),
+            Dhcpv6Command::Start(Dhcpv6StartCommand { interface_name, length }) => dhcpv6_start(&interface_name.0, length),
+            Dhcpv6Command::Stop => dhcpv6_stop(),
+            Dhcpv6Command::Lan(subcommand) => match subcommand {
+                Dhcpv6LanCommand::Add { interface_name } => dhcpv6_lan_add(&interface_name.0),
+                Dhcpv6LanCommand::Delete { interface_name } => dhcpv6_lan_delete(&interface_name.0),
+            },
+        },
+        Commands::Telnet(subcommand) => match subcommand {
+            TelnetCommand::Show => telnet_show(),
+            TelnetCommand::Start(TelnetStartCommand { port }) => telnet_start(port),
+            TelnetCommand::Stop => telnet_stop(),
+        },
+        Commands::Ssh(subcommand) => match subcommand {
+            SshCommand::Show => ssh_show(),
+            SshCommand::Start(SshStartCommand { port }) => ssh_start(port),
+            SshCommand::Stop => ssh_stop(),
+            SshCommand::User(subcommand) => match subcommand {
+                SshUserCommand::Add { name, password } => ssh_user_add(name, password),
+                SshUserCommand::Key { name, key } => ssh_user_key(name, key),
+                SshUserCommand::Delete { name } => ssh_user_delete(name),
+            }
+        },
+        Commands::Http(subcommand) => match subcommand {
+            HttpCommand::Show => http_show(),
+            HttpCommand::Start(HttpStartCommand { port }) => http_start(port),
+            HttpCommand::Stop => http_stop(),
+            HttpCommand::Token { token } => http_token(token),
         }
     };
 
@@ -239,6 +461,52 @@ pub fn handle_command(command: Commands) {
     }
 }
 
+/// Parse and run a command line, printing the parsing errors
+pub async fn run_command_line(command: &str) {
+    use yansi::Paint;
+
+    match CliArgs::parse_str(command) {
+        Ok(cli_args) => {
+            set_max_verbosity(cli_args.verbose);
+            handle_command(cli_args.command).await;
+        },
+        Err(parse_error) => {
+            match parse_error {
+                ParseError::EmptyInput => {}
+                ParseError::Help(help) => println!("{}", help),
+                ParseError::MissingArgument(argument) => println!("{} {}", "Missing required argument:".red(), argument),
+                ParseError::InvalidValue(value) => println!("{} {}", "Invalid value:".red(), value),
+                ParseError::UnknownArgument(argument) => println!("{} {}", "Unknown argument:".red(), argument),
+                ParseError::UnknownSubcommand => println!("{} {}", "Unknown command:".red(), command),
+                ParseError::InvalidFormat(format) => println!("{} {}", "Invalid format:".red(), format),
+                ParseError::UnknownEnumVariant(value, possible_values) => println!("{} {}, possible values are: {}", "Invalid value:".red(), value, possible_values)
+            }
+        }
+    }
+}
+
+/// Split the trailing "&" of the command lines run in the background
+pub fn parse_background(command: &str) -> (&str, bool) {
+    match command.trim_end().strip_suffix('&') {
+        Some(command) => (command.trim_end(), true),
+        None => (command, false)
+    }
+}
+
+/// Run a command line in its own task, which can be cancelled, capturing its output if asked
+pub fn spawn_command(command: &str, captured: bool) -> TaskId {
+    let command = String::from(command);
+    let task = Task::new(command.clone(), async move {
+        run_command_line(&command).await;
+    });
+
+    if captured {
+        start_capture(task.id);
+    }
+
+    spawn_task(task)
+}
+
 pub fn set_max_verbosity(verbosity: usize) {
     let level_filter = verbosity_to_level_filter(verbosity);
     set_max_level(level_filter)
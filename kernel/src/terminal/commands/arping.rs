@@ -0,0 +1,127 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::arp::{device_identity, request_frame, send_frame, ArpListener};
+use crate::devices::network::interface::format_mac;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::println;
+use crate::terminal::custom_arguments::duration::SecondsArg;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::format;
+use alloc::string::String;
+use core::pin::pin;
+use futures_util::future::{select, Either};
+use goolog::trace;
+use no_std_clap_macros::Args;
+use smoltcp::time::Duration;
+use smoltcp::wire::{ArpOperation, IpAddress, IpCidr, Ipv4Address};
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "ARPING";
+
+#[derive(Args)]
+pub struct ArpingCommand {
+    /// IPv4 address to resolve
+    pub ip_address: IpAddressArg,
+
+    /// Interface to send the requests from. Defaults to the one holding the route to the address
+    #[arg(short = 'I', long)]
+    pub interface: Option<NetworkInterfaceArg>,
+
+    /// Requests count. Defaults to: 4
+    #[arg(short, long, default_value = "4")]
+    pub count: u16,
+
+    /// Interval between the requests, in seconds (e.g. 0.2). Defaults to: 1
+    #[arg(short, long, default_value = "1")]
+    pub interval: SecondsArg,
+
+    /// Duplicate address detection, the requests are sent from 0.0.0.0 and any answer means the address is in use
+    #[arg(short = 'D', long)]
+    pub dad: bool,
+}
+
+pub async fn arping(remote_addr: IpAddress, interface_name: Option<&str>, count: u16, interval: Duration, dad: bool) -> Result<(), CliError> {
+    trace!("ARPING");
+
+    let IpAddress::Ipv4(target_addr) = remote_addr else {
+        return Err(CliError::Message(String::from("ARP only resolves IPv4 addresses")));
+    };
+
+    let interface_name = match interface_name {
+        Some(interface_name) => String::from(interface_name),
+        None => NETWORK_MANAGER
+            .lock()
+            .find_route_interface(&remote_addr)
+            .ok_or_else(|| CliError::Message(format!("No interface found to reach {}", remote_addr)))?
+    };
+
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(&interface_name).cloned() else {
+        return Err(CliError::Message(format!("Interface \"{}\" does not use ARP", interface_name)));
+    };
+
+    let Some((if_index, hardware_addr)) = device_identity(&device) else {
+        return Err(CliError::Message(format!("Interface \"{}\" does not use ARP", interface_name)));
+    };
+
+    let source_addr = match dad {
+        true => Ipv4Address::UNSPECIFIED,
+        false => {
+            let source_addr = interrupts::without_interrupts(|| {
+                device.lock().interface.ip_addrs().iter().find_map(|cidr| match cidr {
+                    IpCidr::Ipv4(cidr) => Some(cidr.address()),
+                    IpCidr::Ipv6(_) => None
+                })
+            });
+
+            source_addr.ok_or_else(|| CliError::Message(format!("Interface \"{}\" has no IPv4 address, use -D to probe from 0.0.0.0", interface_name)))?
+        }
+    };
+
+    println!("ARPING {} from {} {}", target_addr, source_addr, interface_name);
+
+    let listener = ArpListener::new(if_index, target_addr, hardware_addr);
+    let mut sent = 0;
+    let mut received = 0;
+
+    'requests: for _ in 0..count {
+        let sent_at = Clock::now();
+        send_frame(&device, &request_frame(hardware_addr, source_addr, target_addr));
+        sent += 1;
+
+        // The replies are reported until the next request
+        let deadline = sent_at + interval;
+
+        while let Either::Left((packet, _)) = select(pin!(listener.receive()), Timer::at(deadline)).await {
+            // Only the probes of other hosts and the replies tell the address is in use
+            if !dad && packet.operation != ArpOperation::Reply {
+                continue;
+            }
+
+            received += 1;
+
+            let kind = match packet.is_broadcast {
+                true => "Broadcast",
+                false => "Unicast"
+            };
+
+            println!("{} reply from {} [{}]  {}ms", kind, target_addr, format_mac(packet.source_hardware_addr.as_bytes()), (packet.received_at - sent_at).total_millis());
+
+            if dad {
+                break 'requests;
+            }
+        }
+    }
+
+    println!("Sent {} probes ({} broadcast(s))", sent, sent);
+    println!("Received {} response(s)", received);
+
+    if dad {
+        match received {
+            0 => println!("No other host uses {}", target_addr),
+            _ => println!("Address {} is already in use", target_addr)
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,19 @@
 use goolog::trace;
+use crate::print;
 use crate::printer::buffer::WRITER;
+use crate::printer::macros::is_output_captured;
 use crate::terminal::error::CliError;
 
 const GOOLOG_TARGET: &str = "CLEAR";
 
 pub fn clear() -> Result<(), CliError> {
     trace!("Clear");
-    WRITER.write().clear();
+
+    match is_output_captured() {
+        // Remote terminals are cleared with ANSI escape sequences
+        true => print!("\x1b[2J\x1b[H"),
+        false => WRITER.write().clear()
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,19 @@
+use crate::clock::Clock;
+use crate::println;
+use crate::terminal::error::CliError;
+use alloc::string::String;
+use goolog::trace;
+
+const GOOLOG_TARGET: &str = "DATE";
+
+pub fn date() -> Result<(), CliError> {
+    trace!("DATE");
+
+    match Clock::utc_now() {
+        Some(date_time) => {
+            println!("{}", date_time);
+            Ok(())
+        },
+        None => Err(CliError::Message(String::from("Clock not synchronized, configure an NTP server with \"ntp server <ip>\"")))
+    }
+}
@@ -0,0 +1,193 @@
+use crate::clock::Clock;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::slaac::SLAAC_PREFIX_LENGTH;
+use crate::printer::macros::Output;
+use crate::println;
+use crate::services::dhcpv6::{Dhcpv6State, DHCPV6_CLIENT};
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::time::Instant;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "DHCPV6";
+
+#[derive(Subcommand)]
+pub enum Dhcpv6Command {
+    /// Show the delegated prefix and its subnets
+    Show,
+
+    /// Request a delegated prefix on the WAN interface
+    Start(Dhcpv6StartCommand),
+
+    /// Release the delegated prefix
+    Stop,
+
+    /// Interact with the interfaces getting a /64 of the delegated prefix
+    #[command(subcommand)]
+    Lan(Dhcpv6LanCommand),
+}
+
+#[derive(Args)]
+pub struct Dhcpv6StartCommand {
+    /// WAN interface, facing the delegating router
+    pub interface_name: NetworkInterfaceArg,
+
+    /// Prefix length asked to the server, 0 leaving the choice to it. Defaults to: 0
+    #[arg(short, long, default_value = "0")]
+    pub length: u8,
+}
+
+#[derive(Subcommand)]
+pub enum Dhcpv6LanCommand {
+    /// Assign a /64 of the delegated prefix to an interface, in the order they are added
+    Add {
+        /// LAN interface
+        interface_name: NetworkInterfaceArg,
+    },
+
+    /// Remove the subnet of an interface
+    Delete {
+        /// LAN interface
+        interface_name: NetworkInterfaceArg,
+    },
+}
+
+pub fn dhcpv6_show() -> Result<(), CliError> {
+    trace!("DHCPV6 SHOW");
+
+    let client = DHCPV6_CLIENT.lock();
+
+    println!("State: {}", client.state);
+
+    if let Some(wan) = &client.wan {
+        println!("WAN: {}", wan);
+    }
+
+    if let Some(lease) = &client.lease {
+        println!("Delegated prefix: {} from {}", lease.prefix, lease.server);
+        println!("Valid until: {}, preferred until: {}", format_instant(lease.valid_until, "forever"), format_instant(lease.preferred_until, "forever"));
+        println!("Renew at: {}, rebind at: {}", format_instant(lease.renew_at, "never"), format_instant(lease.rebind_at, "never"));
+    }
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("Subnet")]
+    ];
+
+    for name in client.lan.iter() {
+        let subnet = client.assignments
+            .iter()
+            .find(|(assigned, _)| assigned == name)
+            .map(|(_, subnet)| subnet.to_string())
+            .unwrap_or_default();
+
+        table.push([name.clone(), subnet]);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+pub fn dhcpv6_start(interface_name: &str, length: u8) -> Result<(), CliError> {
+    trace!("DHCPV6 START");
+
+    check_interface(interface_name)?;
+
+    if length > SLAAC_PREFIX_LENGTH {
+        return Err(CliError::Message(format!("The delegated prefix must hold /{} subnets", SLAAC_PREFIX_LENGTH)));
+    }
+
+    let mut client = DHCPV6_CLIENT.lock();
+
+    if client.state != Dhcpv6State::Stopped {
+        return Err(CliError::Message(String::from("Already requesting a prefix, stop first")));
+    }
+
+    if client.lease.is_some() {
+        return Err(CliError::Message(String::from("The previous prefix is being released, try again")));
+    }
+
+    if client.lan.iter().any(|lan| lan == interface_name) {
+        return Err(CliError::Message(format!("{} is a LAN interface", interface_name)));
+    }
+
+    info!("Requesting a delegated prefix on {}", interface_name);
+    client.start(interface_name, length);
+
+    Ok(())
+}
+
+pub fn dhcpv6_stop() -> Result<(), CliError> {
+    trace!("DHCPV6 STOP");
+
+    let mut client = DHCPV6_CLIENT.lock();
+
+    if client.state == Dhcpv6State::Stopped {
+        return Err(CliError::Message(String::from("Not requesting a prefix")));
+    }
+
+    info!("Stopping prefix delegation");
+    client.stop();
+
+    Ok(())
+}
+
+pub fn dhcpv6_lan_add(interface_name: &str) -> Result<(), CliError> {
+    trace!("DHCPV6 LAN ADD");
+
+    check_interface(interface_name)?;
+
+    let mut client = DHCPV6_CLIENT.lock();
+
+    if client.wan.as_deref() == Some(interface_name) && client.state != Dhcpv6State::Stopped {
+        return Err(CliError::Message(format!("{} is the WAN interface", interface_name)));
+    }
+
+    if client.lan.iter().any(|lan| lan == interface_name) {
+        return Err(CliError::Message(format!("{} is already a LAN interface", interface_name)));
+    }
+
+    info!("Assigning a subnet to {}", interface_name);
+    client.add_lan(interface_name);
+
+    Ok(())
+}
+
+pub fn dhcpv6_lan_delete(interface_name: &str) -> Result<(), CliError> {
+    trace!("DHCPV6 LAN DELETE");
+
+    let mut client = DHCPV6_CLIENT.lock();
+
+    if !client.lan.iter().any(|lan| lan == interface_name) {
+        return Err(CliError::Message(format!("{} is not a LAN interface", interface_name)));
+    }
+
+    info!("Removing the subnet of {}", interface_name);
+    client.remove_lan(interface_name);
+
+    Ok(())
+}
+
+/// Only the physical interfaces can face a router or hosts
+fn check_interface(interface_name: &str) -> Result<(), CliError> {
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(interface_name).cloned() else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    if interrupts::without_interrupts(|| device.lock().mac().is_none()) {
+        return Err(CliError::Message(format!("{} has no link layer", interface_name)));
+    }
+
+    Ok(())
+}
+
+fn format_instant(instant: Option<Instant>, none: &str) -> String {
+    match instant {
+        None => String::from(none),
+        Some(instant) => Clock::format_instant(instant)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::clock::Clock;
+use crate::devices::network::flow::FLOW_CACHE;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::printer::macros::Output;
+use crate::println;
+use crate::services::ipfix::IPFIX_EXPORTER;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpEndpoint, IpProtocol};
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "FLOW";
+
+#[derive(Subcommand)]
+pub enum FlowCommand {
+    /// Show the active flows
+    Show,
+
+    /// Export expired flows as IPFIX to a collector
+    Collector(FlowCollectorCommand),
+
+    /// Stop exporting flows
+    Stop,
+
+    /// Set the flow cache timeouts
+    Timeout(FlowTimeoutCommand),
+}
+
+#[derive(Args)]
+pub struct FlowCollectorCommand {
+    /// IP address of the collector
+    pub address: IpAddressArg,
+
+    /// UDP port of the collector. Defaults to: 4739
+    #[arg(default_value = "4739")]
+    pub port: u16,
+}
+
+#[derive(Args)]
+pub struct FlowTimeoutCommand {
+    /// Seconds after which a long-lasting flow is exported
+    pub active: u64,
+
+    /// Seconds without packets after which a flow is exported
+    pub inactive: u64,
+}
+
+pub fn flow_show() -> Result<(), CliError> {
+    trace!("FLOW SHOW");
+
+    let mut interface_names = BTreeMap::new();
+
+    for (name, device) in NETWORK_MANAGER.lock().interfaces.iter() {
+        interface_names.insert(device.lock().network_controller.if_index, name.clone());
+    }
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("Source"), String::from("Destination"), String::from("Protocol"), String::from("Packets"), String::from("Bytes"), String::from("Age")]
+    ];
+
+    let now = Clock::now();
+
+    let untracked_packets = interrupts::without_interrupts(|| {
+        let flow_cache = FLOW_CACHE.lock();
+
+        for record in flow_cache.flows.values() {
+            let key = &record.key;
+
+            table.push([
+                interface_names.get(&key.ingress_interface).cloned().unwrap_or_else(|| key.ingress_interface.to_string()),
+                format_endpoint(key.source, key.source_port),
+                format_endpoint(key.destination, key.destination_port),
+                IpProtocol::from(key.protocol).to_string(),
+                record.packets.to_string(),
+                record.bytes.to_string(),
+                format!("{}s", (now - record.first_seen).secs()),
+            ]);
+        }
+
+        flow_cache.untracked_packets
+    });
+
+    text_tables::render(&mut Output, table).unwrap();
+    drop(writer);
+
+    if untracked_packets > 0 {
+        println!("{} packets untracked, the flow cache is full", untracked_packets);
+    }
+
+    let exporter = IPFIX_EXPORTER.lock();
+    match exporter.collector {
+        None => println!("Export: disabled"),
+        Some(collector) => println!("Export: {} ({} messages sent)", collector, exporter.messages_sent)
+    }
+
+    Ok(())
+}
+
+pub fn flow_collector(address: IpAddress, port: u16) -> Result<(), CliError> {
+    trace!("FLOW COLLECTOR");
+
+    if !address.is_unicast() {
+        return Err(CliError::Message(String::from("The collector address must be unicast")));
+    }
+
+    info!("Exporting flows to {}:{}", address, port);
+    IPFIX_EXPORTER.lock().set_collector(Some(IpEndpoint::new(address, port)));
+
+    Ok(())
+}
+
+pub fn flow_stop() -> Result<(), CliError> {
+    trace!("FLOW STOP");
+
+    info!("Stopping flow export");
+    IPFIX_EXPORTER.lock().set_collector(None);
+
+    Ok(())
+}
+
+pub fn flow_timeout(active: u64, inactive: u64) -> Result<(), CliError> {
+    trace!("FLOW TIMEOUT");
+
+    if active == 0 || inactive == 0 {
+        return Err(CliError::Message(String::from("Timeouts must be greater than 0")));
+    }
+
+    interrupts::without_interrupts(|| {
+        let mut flow_cache = FLOW_CACHE.lock();
+        flow_cache.active_timeout = Duration::from_secs(active);
+        flow_cache.inactive_timeout = Duration::from_secs(inactive);
+    });
+
+    Ok(())
+}
+
+fn format_endpoint(address: IpAddress, port: u16) -> String {
+    match address {
+        IpAddress::Ipv4(_) => format!("{}:{}", address, port),
+        IpAddress::Ipv6(_) => format!("[{}]:{}", address, port),
+    }
+}
@@ -0,0 +1,97 @@
+use crate::clock::Clock;
+use crate::println;
+use crate::services::http::server::HTTP_SERVER;
+use crate::terminal::error::CliError;
+use alloc::string::String;
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+
+const GOOLOG_TARGET: &str = "HTTP";
+
+#[derive(Subcommand)]
+pub enum HttpCommand {
+    /// Show the HTTP API server state and its connections
+    Show,
+
+    /// Start the HTTP API server, once its token is set
+    Start(HttpStartCommand),
+
+    /// Stop the HTTP API server and close its connections
+    Stop,
+
+    /// Set the bearer token the API requests must carry
+    Token {
+        /// Token to expect in the "Authorization: Bearer" header
+        token: String,
+    },
+}
+
+#[derive(Args)]
+pub struct HttpStartCommand {
+    /// TCP port to listen on. Defaults to: 80
+    #[arg(default_value = "80")]
+    pub port: u16,
+}
+
+pub fn http_show() -> Result<(), CliError> {
+    trace!("HTTP SHOW");
+
+    let server = HTTP_SERVER.lock();
+
+    match server.port {
+        None => println!("HTTP server: disabled"),
+        Some(port) => println!("HTTP server: listening on port {} ({} requests served)", port, server.served_requests)
+    }
+
+    match server.token {
+        None => println!("Authentication: none"),
+        Some(_) => println!("Authentication: bearer token")
+    }
+
+    for (id, connection) in server.connections.iter() {
+        match connection.remote_endpoint {
+            Some(remote_endpoint) => println!("{}: {} on {}, since {}", id, remote_endpoint, connection.interface_name, Clock::format_instant(connection.started_at)),
+            None => println!("{}: on {}, since {}", id, connection.interface_name, Clock::format_instant(connection.started_at))
+        }
+    }
+
+    Ok(())
+}
+
+pub fn http_start(port: u16) -> Result<(), CliError> {
+    trace!("HTTP START");
+
+    let mut server = HTTP_SERVER.lock();
+
+    // The API changes the addresses and the routes
+    if server.token.is_none() {
+        return Err(CliError::Message(String::from("Set a token with http token first")));
+    }
+
+    info!("Starting HTTP server on port {}", port);
+    server.set_port(Some(port));
+
+    Ok(())
+}
+
+pub fn http_stop() -> Result<(), CliError> {
+    trace!("HTTP STOP");
+
+    info!("Stopping HTTP server");
+    HTTP_SERVER.lock().set_port(None);
+
+    Ok(())
+}
+
+pub fn http_token(token: String) -> Result<(), CliError> {
+    trace!("HTTP TOKEN");
+
+    if token.is_empty() {
+        return Err(CliError::Message(String::from("The token cannot be empty")));
+    }
+
+    HTTP_SERVER.lock().token = Some(token);
+    info!("HTTP API token set");
+
+    Ok(())
+}
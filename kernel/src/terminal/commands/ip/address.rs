@@ -1,16 +1,28 @@
+use crate::clock::Clock;
+use crate::devices::network::arp::{announce_address, probe_address};
+use crate::devices::network::interface::format_mac;
 use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::slaac::AddressState;
+use crate::printer::macros::Output;
 use crate::terminal::custom_arguments::ip_address::IpCidrArg;
 use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
 use crate::terminal::error::CliError;
-use alloc::format;
-use goolog::{debug, info, trace};
-use no_std_clap_macros::{Args, Subcommand};
-use smoltcp::wire::{IpCidr};
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use goolog::{debug, info, trace, warn};
+use no_std_clap_macros::{Args, EnumValuesArg, Subcommand};
+use smoltcp::time::Instant;
+use smoltcp::wire::{IpAddress, IpCidr};
+use strum::{EnumString, VariantNames};
+use x86_64::instructions::interrupts;
 
 const GOOLOG_TARGET: &str = "IP ADDRESS";
 
 #[derive(Subcommand)]
 pub enum IpAddressCommand {
+    /// Show the IP addresses of the interfaces, with their state and lifetimes
+    Show,
+
     /// Add an IP address to an interface
     Add(IpAddressAddCommand),
 
@@ -18,6 +30,19 @@ pub enum IpAddressCommand {
     Delete(IpAddressDeleteCommand),
 }
 
+/// Duplicate address detection, with ARP probes (RFC 5227)
+#[derive(Default, EnumValuesArg, VariantNames, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum DadMode {
+    /// The address is added without probing
+    #[default]
+    Off,
+    /// The address is refused if another host uses it
+    Refuse,
+    /// The address is added, and flagged as duplicate if another host uses it
+    Flag,
+}
+
 #[derive(Args)]
 pub struct IpAddressAddCommand {
     /// IP address to add to the interface
@@ -25,6 +50,10 @@ pub struct IpAddressAddCommand {
 
     /// Interface to add the address to
     pub interface_name: NetworkInterfaceArg,
+
+    /// Duplicate address detection of the IPv4 addresses: off, refuse or flag. Defaults to: off
+    #[arg(short, long, default_value = "off")]
+    pub dad: DadMode,
 }
 
 #[derive(Args)]
@@ -36,27 +65,168 @@ pub struct IpAddressDeleteCommand {
     pub interface_name: NetworkInterfaceArg,
 }
 
-pub fn ip_address_add(ip_address: IpCidr, interface_name: &str) -> Result<(), CliError> {
-    trace!("IP ADDRESS ADD");
+pub fn ip_address_show() -> Result<(), CliError> {
+    trace!("IP ADDRESS SHOW");
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("Address"), String::from("Origin"), String::from("State"), String::from("Valid until"), String::from("Preferred until")]
+    ];
 
     trace!("Locking NETWORK_INTERFACES mutex...");
-    let mut network_manager = NETWORK_MANAGER.lock();
+    let network_manager = NETWORK_MANAGER.lock();
 
-    trace!("Retrieving network interface \"{}\"", interface_name);
-    let device = network_manager.interfaces.get_mut(interface_name).unwrap();
-    let mut locked_device = device.lock();
-    let iface = &mut locked_device.interface;
+    for cidr in network_manager.loopback.interface.ip_addrs() {
+        table.push([String::from("lo"), cidr.to_string(), String::from("static"), AddressState::Preferred.to_string(), format_lifetime(None), format_lifetime(None)]);
+    }
 
-    info!("Adding IP address");
-    iface.update_ip_addrs(|addrs| {
-        addrs.push(ip_address).unwrap();
-    });
+    for (name, device) in network_manager.interfaces.iter() {
+        let device = device.lock();
+
+        for cidr in device.interface.ip_addrs() {
+            let autoconfigured = device.autoconfigured_addresses
+                .iter()
+                .find(|address| IpCidr::Ipv6(address.cidr) == *cidr);
+
+            let row = match autoconfigured {
+                Some(address) => [address.origin.to_string(), address.state.to_string(), format_lifetime(address.valid_until), format_lifetime(address.preferred_until)],
+                None => {
+                    let state = match cidr.address() {
+                        IpAddress::Ipv4(address) if device.duplicate_addresses.contains(&address) => AddressState::Duplicate,
+                        _ => AddressState::Preferred
+                    };
+
+                    [String::from("static"), state.to_string(), format_lifetime(None), format_lifetime(None)]
+                }
+            };
+
+            let [origin, state, valid_until, preferred_until] = row;
+            table.push([name.clone(), cidr.to_string(), origin, state, valid_until, preferred_until]);
+        }
+
+        // Not given to smoltcp yet, or never
+        for address in &device.autoconfigured_addresses {
+            if matches!(address.state, AddressState::Tentative | AddressState::Duplicate) {
+                table.push([name.clone(), address.cidr.to_string(), address.origin.to_string(), address.state.to_string(), format_lifetime(address.valid_until), format_lifetime(address.preferred_until)]);
+            }
+        }
+    }
 
     trace!("NETWORK_INTERFACES mutex freed");
 
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+fn format_lifetime(until: Option<Instant>) -> String {
+    match until {
+        None => String::from("forever"),
+        Some(instant) => Clock::format_instant(instant)
+    }
+}
+
+pub async fn ip_address_add(ip_address: IpCidr, interface_name: &str, dad: DadMode) -> Result<(), CliError> {
+    trace!("IP ADDRESS ADD");
+
+    // Refused before being probed
+    if has_address(interface_name, ip_address.address())? {
+        return Err(CliError::Message(format!("Address {} already exists on \"{}\"", ip_address.address(), interface_name)));
+    }
+
+    // Device announcing the address once added
+    let (is_duplicate, announcing_device) = match (ip_address, dad) {
+        (_, DadMode::Off) | (IpCidr::Ipv6(_), _) => (false, None),
+        (IpCidr::Ipv4(cidr), dad) => {
+            // The loopback and the tunnels have no neighbors to ask
+            let device = NETWORK_MANAGER.lock()
+                .interfaces
+                .get(interface_name)
+                .filter(|device| interrupts::without_interrupts(|| device.lock().mac().is_some()))
+                .cloned();
+
+            match device {
+                None => (false, None),
+                Some(device) => {
+                    info!("Probing {} on {}", cidr.address(), interface_name);
+
+                    match probe_address(&device, cidr.address()).await {
+                        None => (false, Some(device)),
+                        Some(hardware_addr) if matches!(dad, DadMode::Refuse) => {
+                            return Err(CliError::Message(format!("Address {} is already used by {}", cidr.address(), format_mac(hardware_addr.as_bytes()))));
+                        },
+                        Some(hardware_addr) => {
+                            warn!("Address {} is already used by {}, flagged as duplicate", cidr.address(), format_mac(hardware_addr.as_bytes()));
+                            (true, None)
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    {
+        trace!("Locking NETWORK_INTERFACES mutex...");
+        let mut network_manager = NETWORK_MANAGER.lock();
+
+        trace!("Retrieving network interface \"{}\"", interface_name);
+        let Some(device) = network_manager.interfaces.get_mut(interface_name) else {
+            return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+        };
+        let mut locked_device = device.lock();
+        let iface = &mut locked_device.interface;
+
+        info!("Adding IP address");
+        let mut is_full = false;
+        let mut exists = false;
+
+        iface.update_ip_addrs(|addrs| {
+            // Added while probing
+            exists = addrs.iter().any(|cidr| cidr.address() == ip_address.address());
+
+            if !exists {
+                is_full = addrs.push(ip_address).is_err();
+            }
+        });
+
+        trace!("NETWORK_INTERFACES mutex freed");
+
+        if exists {
+            return Err(CliError::Message(format!("Address {} already exists on \"{}\"", ip_address.address(), interface_name)));
+        }
+
+        if is_full {
+            return Err(CliError::Message(format!("Interface \"{}\" cannot have more addresses", interface_name)));
+        }
+
+        if let (IpCidr::Ipv4(cidr), true) = (ip_address, is_duplicate) {
+            locked_device.duplicate_addresses.push(cidr.address());
+        }
+
+        // Solicited-node group of the IPv6 addresses
+        locked_device.update_multicast_filter();
+    }
+
+    // Only once the address is actually taken
+    if let (IpCidr::Ipv4(cidr), Some(device)) = (ip_address, announcing_device) {
+        announce_address(&device, cidr.address());
+    }
+
     Ok(())
 }
 
+/// Whether an interface already has the given address, whatever its prefix
+fn has_address(interface_name: &str, address: IpAddress) -> Result<bool, CliError> {
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(interface_name).cloned() else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    let has_address = interrupts::without_interrupts(|| {
+        device.lock().interface.ip_addrs().iter().any(|cidr| cidr.address() == address)
+    });
+
+    Ok(has_address)
+}
+
 pub fn ip_address_delete(ip_address: IpCidr, interface_name: &str) -> Result<(), CliError> {
     trace!("IP ADDRESS DELETE");
 
@@ -65,7 +235,9 @@ pub fn ip_address_delete(ip_address: IpCidr, interface_name: &str) -> Result<(),
     let mut network_manager = NETWORK_MANAGER.lock();
 
     trace!("Retrieving network interface \"{}\"", interface_name);
-    let device = network_manager.interfaces.get_mut(interface_name).unwrap();
+    let Some(device) = network_manager.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
     let mut locked_device = device.lock();
     let iface = &mut locked_device.interface;
 
@@ -86,6 +258,13 @@ pub fn ip_address_delete(ip_address: IpCidr, interface_name: &str) -> Result<(),
             })
         });
 
+    match ip_address {
+        IpCidr::Ipv4(cidr) => locked_device.duplicate_addresses.retain(|duplicate| *duplicate != cidr.address()),
+        IpCidr::Ipv6(cidr) => locked_device.autoconfigured_addresses.retain(|address| address.cidr != cidr)
+    }
+
+    locked_device.update_multicast_filter();
+
     trace!("NETWORK_INTERFACES mutex freed");
 
     if was_address_found {
@@ -1,58 +1,249 @@
-use crate::printer::buffer::WRITER;
+use crate::clock::{Clock, Timer};
+use crate::printer::macros::Output;
+use crate::println;
+use crate::terminal::commands::clear::clear;
 use crate::terminal::error::CliError;
+use alloc::format;
 use alloc::string::{String, ToString};
-use alloc::{vec};
+use alloc::vec;
+use alloc::vec::Vec;
 use goolog::trace;
-use no_std_clap_macros::Subcommand;
+use no_std_clap_macros::{Args, Subcommand};
 use smoltcp::iface::Interface;
-use smoltcp::wire::IpCidr;
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpCidr, Ipv4Address};
+use x86_64::instructions::interrupts;
+use crate::devices::network::driver::LinkStatus;
 use crate::devices::network::interface::format_mac;
 use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::statistics::InterfaceCounters;
 
 const GOOLOG_TARGET: &str = "IP INTERFACE";
 
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// MTU of the smoltcp loopback device
+const LOOPBACK_MTU: usize = 65535;
+
 #[derive(Subcommand)]
 pub enum IpInterfaceCommand {
     /// Show network interfaces
-    Show
+    Show(IpInterfaceShowCommand),
+
+    /// Show the traffic rate of each interface, refreshed every second
+    Rate
 }
 
-pub fn ip_interface_show() -> Result<(), CliError> {
+#[derive(Args)]
+pub struct IpInterfaceShowCommand {
+    /// Show the traffic and error counters
+    #[arg(short, long)]
+    pub statistics: bool,
+}
+
+pub fn ip_interface_show(statistics: bool) -> Result<(), CliError> {
     trace!("IP INTERFACE SHOW");
 
     let mut table = vec![
-        [String::from("Interface"), String::from("NIC"), String::from("MAC address"), String::from("IPv4 addresses"), String::from("IPv6 addresses")],
+        [String::from("Interface"), String::from("NIC"), String::from("State"), String::from("Link"), String::from("MTU"), String::from("MAC address"), String::from("IPv4 addresses"), String::from("IPv6 addresses")],
     ];
 
-    let network_manager = NETWORK_MANAGER.lock();
+    let interfaces = interfaces();
 
-    table.push(row_from_interface(String::from("lo"), String::from("Loopback"), &network_manager.loopback.interface));
-    
-    for (name, device) in network_manager.interfaces.iter() {
-        let device = device.lock();
-        let driver = device.network_controller.driver.lock();
-        let nic_name = driver.nic_type().to_string();
-        table.push(row_from_interface(name.clone(), nic_name, &device.interface))
+    for interface in &interfaces {
+        let mut ips_v4 = vec![];
+        let mut ips_v6 = vec![];
+
+        for ip in &interface.addresses {
+            match ip {
+                IpCidr::Ipv4(ipv4) if interface.duplicate_addresses.contains(&ipv4.address()) => ips_v4.push(format!("{} (duplicate)", ipv4)),
+                IpCidr::Ipv4(ipv4) => ips_v4.push(ipv4.to_string()),
+                IpCidr::Ipv6(ipv6) => ips_v6.push(ipv6.to_string()),
+            }
+        }
+
+        let state = match interface.enabled {
+            true => "up",
+            false => "down"
+        };
+
+        let link = interface.link_status.map(|link_status| link_status.to_string()).unwrap_or_default();
+
+        table.push([interface.name.clone(), interface.nic_name.clone(), String::from(state), link, interface.mtu.to_string(), interface.mac.clone(), ips_v4.join(", "), ips_v6.join(", ")]);
     }
 
-    let mut writer = WRITER.write();
-    text_tables::render(&mut *writer, table).unwrap();
+    text_tables::render(&mut Output, table).unwrap();
+
+    if statistics {
+        print_counters(&interfaces);
+    }
 
     Ok(())
 }
 
-fn row_from_interface(interface_name: String, nic_name: String, interface: &Interface) -> [String; 5] {
-    let mac = interface.hardware_addr();
+/// Counters of the hardware interfaces, the loopback has none
+fn print_counters(interfaces: &[InterfaceInfo]) {
+    let mut rx_table = vec![
+        [String::from("Interface"), String::from("RX bytes"), String::from("Packets"), String::from("Errors"), String::from("Dropped"), String::from("Missed"), String::from("Multicast"), String::from("CRC errors")],
+    ];
+
+    let mut tx_table = vec![
+        [String::from("Interface"), String::from("TX bytes"), String::from("Packets"), String::from("Errors"), String::from("Dropped"), String::from("Collisions")],
+    ];
+
+    for interface in interfaces {
+        let Some(counters) = &interface.counters else {
+            continue;
+        };
+
+        rx_table.push([
+            interface.name.clone(),
+            counters.rx_bytes.to_string(),
+            counters.rx_packets.to_string(),
+            counters.rx_errors.to_string(),
+            counters.rx_dropped.to_string(),
+            counters.rx_missed.to_string(),
+            counters.rx_multicast.to_string(),
+            counters.rx_crc_errors.to_string(),
+        ]);
+
+        tx_table.push([
+            interface.name.clone(),
+            counters.tx_bytes.to_string(),
+            counters.tx_packets.to_string(),
+            counters.tx_errors.to_string(),
+            counters.tx_dropped.to_string(),
+            counters.collisions.to_string(),
+        ]);
+    }
+
+    println!();
+    text_tables::render(&mut Output, rx_table).unwrap();
+    println!();
+    text_tables::render(&mut Output, tx_table).unwrap();
+}
+
+pub async fn ip_interface_rate() -> Result<(), CliError> {
+    trace!("IP INTERFACE RATE");
+
+    let mut previous = (Clock::now(), interfaces());
+
+    loop {
+        Timer::after(RATE_INTERVAL).await;
+
+        let current = (Clock::now(), interfaces());
+        let seconds = (current.0 - previous.0).total_millis().max(1) as f64 / 1000.0;
 
-    let mut ips_v4 = vec![];
-    let mut ips_v6 = vec![];
+        let mut table = vec![
+            [String::from("Interface"), String::from("RX packets/s"), String::from("RX bits/s"), String::from("TX packets/s"), String::from("TX bits/s")],
+        ];
 
-    for ip in interface.ip_addrs() {
-        match ip {
-            IpCidr::Ipv4(ipv4) => ips_v4.push(ipv4.to_string()),
-            IpCidr::Ipv6(ipv6) => ips_v6.push(ipv6.to_string()),
+        for interface in &current.1 {
+            let Some(counters) = &interface.counters else {
+                continue;
+            };
+
+            // Interfaces which just appeared start from zero
+            let before = previous.1
+                .iter()
+                .find(|before| before.name == interface.name)
+                .and_then(|before| before.counters)
+                .unwrap_or_default();
+
+            let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / seconds;
+
+            table.push([
+                interface.name.clone(),
+                format!("{:.0}", rate(counters.rx_packets, before.rx_packets)),
+                format_bitrate(rate(counters.rx_bytes, before.rx_bytes) * 8.0),
+                format!("{:.0}", rate(counters.tx_packets, before.tx_packets)),
+                format_bitrate(rate(counters.tx_bytes, before.tx_bytes) * 8.0),
+            ]);
         }
+
+        clear()?;
+        println!("Every {}s, Ctrl-C to stop", RATE_INTERVAL.secs());
+        text_tables::render(&mut Output, table).unwrap();
+
+        previous = current;
+    }
+}
+
+/// Bitrate with a decimal unit (e.g. "9.40 Mbit/s")
+fn format_bitrate(mut bits_per_second: f64) -> String {
+    let units = ["bit/s", "Kbit/s", "Mbit/s", "Gbit/s"];
+    let mut unit = 0;
+
+    while bits_per_second >= 1000.0 && unit < units.len() - 1 {
+        bits_per_second /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{:.2} {}", bits_per_second, units[unit])
+}
+
+pub struct InterfaceInfo {
+    pub name: String,
+    pub nic_name: String,
+    pub mac: String,
+    pub addresses: Vec<IpCidr>,
+    /// Addresses another host answered for when they were added
+    pub duplicate_addresses: Vec<Ipv4Address>,
+    /// Traffic and error counters, the loopback has none
+    pub counters: Option<InterfaceCounters>,
+    /// Administrative state
+    pub enabled: bool,
+    pub mtu: usize,
+    /// State of the physical link, the loopback has none
+    pub link_status: Option<LinkStatus>,
+}
+
+/// Every interface, the loopback first
+pub fn interfaces() -> Vec<InterfaceInfo> {
+    let network_manager = NETWORK_MANAGER.lock();
+
+    let mut interfaces = vec![
+        info_from_interface(String::from("lo"), String::from("Loopback"), format_mac(network_manager.loopback.interface.hardware_addr().as_bytes()), &network_manager.loopback.interface)
+    ];
+
+    for (name, device) in network_manager.interfaces.iter() {
+        // The driver is locked by the network interrupts as well
+        let info = interrupts::without_interrupts(|| {
+            let device = device.lock();
+            let nic_name = device.network_controller.driver.lock().nic_type().to_string();
+            let mac = device.mac().map(|mac| format_mac(mac.as_bytes())).unwrap_or_default();
+            let mut info = info_from_interface(name.clone(), nic_name, mac, &device.interface);
+            info.duplicate_addresses = device.duplicate_addresses.clone();
+            info.counters = Some(device.network_controller.counters());
+            info.enabled = device.network_controller.enabled;
+            info.mtu = device.network_controller.mtu();
+            info.link_status = Some(device.network_controller.link_status);
+
+            // Extra unicast addresses accepted by the NIC
+            for mac in &device.network_controller.secondary_macs {
+                info.mac = format!("{}, {}", info.mac, format_mac(mac));
+            }
+
+            info
+        });
+
+        interfaces.push(info)
     }
 
-    [interface_name, nic_name, format_mac(mac.as_bytes()), ips_v4.join(", "), ips_v6.join(", ")]
+    interfaces
+}
+
+fn info_from_interface(name: String, nic_name: String, mac: String, interface: &Interface) -> InterfaceInfo {
+    InterfaceInfo {
+        name,
+        nic_name,
+        mac,
+        addresses: interface.ip_addrs().to_vec(),
+        duplicate_addresses: Vec::new(),
+        counters: None,
+        enabled: true,
+        // Overridden for the NICs
+        mtu: LOOPBACK_MTU,
+        link_status: None,
+    }
 }
\ No newline at end of file
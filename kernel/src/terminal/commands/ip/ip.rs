@@ -1,6 +1,10 @@
 use crate::terminal::commands::ip::address::IpAddressCommand;
 use crate::terminal::commands::ip::interface::IpInterfaceCommand;
+use crate::terminal::commands::ip::link::IpLinkCommand;
+use crate::terminal::commands::ip::maddr::IpMaddrCommand;
 use crate::terminal::commands::ip::route::IpRouteCommand;
+use crate::terminal::commands::ip::tunnel::IpTunnelCommand;
+use crate::terminal::commands::ip::vxlan::IpVxlanCommand;
 use no_std_clap_macros::Subcommand;
 
 #[derive(Subcommand)]
@@ -13,6 +17,14 @@ pub enum IpCommand {
     #[command(subcommand)]
     I(Option<IpInterfaceCommand>),
 
+    /// Bring network interfaces up or down
+    #[command(subcommand)]
+    Link(Option<IpLinkCommand>),
+
+    /// Bring network interfaces up or down
+    #[command(subcommand)]
+    L(Option<IpLinkCommand>),
+
     /// Interact with network addresses
     #[command(subcommand)]
     Address(Option<IpAddressCommand>),
@@ -21,6 +33,14 @@ pub enum IpCommand {
     #[command(subcommand)]
     A(Option<IpAddressCommand>),
 
+    /// Join and leave multicast groups
+    #[command(subcommand)]
+    Maddr(Option<IpMaddrCommand>),
+
+    /// Join and leave multicast groups
+    #[command(subcommand)]
+    M(Option<IpMaddrCommand>),
+
     /// Interact with network routes
     #[command(subcommand)]
     Route(Option<IpRouteCommand>),
@@ -28,4 +48,20 @@ pub enum IpCommand {
     /// Interact with network routes
     #[command(subcommand)]
     R(Option<IpRouteCommand>),
+
+    /// Interact with tunnel interfaces
+    #[command(subcommand)]
+    Tunnel(Option<IpTunnelCommand>),
+
+    /// Interact with tunnel interfaces
+    #[command(subcommand)]
+    T(Option<IpTunnelCommand>),
+
+    /// Interact with VXLAN interfaces
+    #[command(subcommand)]
+    Vxlan(Option<IpVxlanCommand>),
+
+    /// Interact with VXLAN interfaces
+    #[command(subcommand)]
+    V(Option<IpVxlanCommand>),
 }
\ No newline at end of file
@@ -0,0 +1,194 @@
+use crate::devices::network::arp::announce_address;
+use crate::devices::network::controller::MIN_MTU;
+use crate::devices::network::interface::format_mac;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, EnumValuesArg, Subcommand};
+use smoltcp::wire::{EthernetAddress, IpCidr};
+use strum::{EnumString, VariantNames};
+
+const GOOLOG_TARGET: &str = "IP LINK";
+
+#[derive(Subcommand)]
+pub enum IpLinkCommand {
+    /// Show the state of the network interfaces
+    Show,
+
+    /// Change a setting of a network interface
+    Set(IpLinkSetCommand),
+}
+
+/// Setting of an interface
+#[derive(Default, EnumValuesArg, VariantNames, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LinkSetting {
+    /// Bring the interface up
+    #[default]
+    Up,
+    /// Bring the interface down
+    Down,
+    /// Change the MTU, in bytes
+    Mtu,
+    /// Change the MAC address
+    Address,
+    /// Accept the frames sent to another MAC address as well
+    Secondary,
+    /// Stop accepting the frames sent to a secondary MAC address
+    Nosecondary,
+}
+
+#[derive(Args)]
+pub struct IpLinkSetCommand {
+    /// Interface to change
+    pub interface_name: NetworkInterfaceArg,
+
+    /// Setting to change: up, down, mtu, address, secondary or nosecondary
+    pub setting: LinkSetting,
+
+    /// Value of the setting, a size for mtu and a MAC address for the others
+    pub value: Option<String>,
+}
+
+pub fn ip_link_set(interface_name: &str, setting: LinkSetting, value: Option<&str>) -> Result<(), CliError> {
+    trace!("IP LINK SET");
+
+    if interface_name == "lo" {
+        return Err(CliError::Message(format!("Interface \"{}\" cannot be changed", interface_name)));
+    }
+
+    trace!("Locking NETWORK_INTERFACES mutex...");
+    let network_manager = NETWORK_MANAGER.lock();
+
+    let Some(device_arc) = network_manager.interfaces.get(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    let mut device = device_arc.lock();
+
+    match setting {
+        LinkSetting::Up | LinkSetting::Down => {
+            let enabled = matches!(setting, LinkSetting::Up);
+
+            device.set_enabled(enabled);
+
+            info!("Interface {} is {}", interface_name, if enabled { "up" } else { "down" });
+        },
+        LinkSetting::Mtu => {
+            let Some(value) = value else {
+                return Err(CliError::Message(String::from("An MTU is required")));
+            };
+
+            let Ok(mtu) = value.parse::<usize>() else {
+                return Err(CliError::Message(format!("Invalid MTU \"{}\"", value)));
+            };
+
+            let (nic_name, max_mtu) = {
+                let driver = device.network_controller.driver.lock();
+                (driver.nic_type(), driver.max_mtu())
+            };
+
+            if mtu < MIN_MTU || mtu > max_mtu {
+                return Err(CliError::Message(format!("MTU {} not supported by the {} of {}, from {} to {} bytes", mtu, nic_name, interface_name, MIN_MTU, max_mtu)));
+            }
+
+            device.network_controller.set_mtu(mtu);
+
+            info!("MTU of {} set to {}", interface_name, mtu);
+        },
+        LinkSetting::Address => {
+            let mac = parse_mac(value)?;
+
+            if device.mac().is_none() {
+                return Err(CliError::Message(format!("{} has no MAC address", interface_name)));
+            }
+
+            device.set_mac(mac);
+            info!("MAC address of {} set to {}", interface_name, format_mac(&mac.0));
+
+            let ipv4_addresses: Vec<_> = device.interface
+                .ip_addrs()
+                .iter()
+                .filter_map(|cidr| match cidr {
+                    IpCidr::Ipv4(cidr) => Some(cidr.address()),
+                    _ => None
+                })
+                .collect();
+
+            let enabled = device.network_controller.enabled;
+            let device_arc = device_arc.clone();
+            drop(device);
+            drop(network_manager);
+
+            // The neighbors update their caches with a gratuitous ARP
+            if enabled {
+                for address in ipv4_addresses {
+                    announce_address(&device_arc, address);
+                }
+            }
+
+            return Ok(());
+        },
+        LinkSetting::Secondary => {
+            let mac = parse_mac(value)?;
+            let (nic_name, max_secondary_macs) = {
+                let driver = device.network_controller.driver.lock();
+                (driver.nic_type(), driver.max_secondary_macs())
+            };
+
+            if max_secondary_macs == 0 {
+                return Err(CliError::Message(format!("The {} of {} has no extra receive address", nic_name, interface_name)));
+            }
+
+            let mut secondary_macs = device.network_controller.secondary_macs.clone();
+
+            if secondary_macs.contains(&mac.0) {
+                return Err(CliError::Message(format!("{} already accepts {}", interface_name, format_mac(&mac.0))));
+            }
+
+            if secondary_macs.len() >= max_secondary_macs {
+                return Err(CliError::Message(format!("{} cannot accept more than {} secondary MAC addresses", interface_name, max_secondary_macs)));
+            }
+
+            secondary_macs.push(mac.0);
+            device.network_controller.set_secondary_macs(secondary_macs);
+
+            info!("{} accepts {}", interface_name, format_mac(&mac.0));
+        },
+        LinkSetting::Nosecondary => {
+            let mac = parse_mac(value)?;
+            let mut secondary_macs = device.network_controller.secondary_macs.clone();
+
+            let Some(index) = secondary_macs.iter().position(|secondary_mac| *secondary_mac == mac.0) else {
+                return Err(CliError::Message(format!("{} is not a secondary MAC address of {}", format_mac(&mac.0), interface_name)));
+            };
+
+            secondary_macs.remove(index);
+            device.network_controller.set_secondary_macs(secondary_macs);
+
+            info!("{} no longer accepts {}", interface_name, format_mac(&mac.0));
+        }
+    }
+
+    trace!("NETWORK_INTERFACES mutex freed");
+
+    Ok(())
+}
+
+/// Unicast MAC address given as the value of a setting
+fn parse_mac(value: Option<&str>) -> Result<EthernetAddress, CliError> {
+    let Some(value) = value else {
+        return Err(CliError::Message(String::from("A MAC address is required")));
+    };
+
+    match EthernetAddress::from_str(value) {
+        Ok(mac) if mac.is_unicast() && mac != EthernetAddress([0; 6]) => Ok(mac),
+        Ok(_) => Err(CliError::Message(format!("{} is not a unicast MAC address", value))),
+        Err(_) => Err(CliError::Message(format!("Invalid MAC address \"{}\"", value)))
+    }
+}
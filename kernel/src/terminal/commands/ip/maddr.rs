@@ -0,0 +1,134 @@
+use crate::devices::network::interface::format_mac;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::multicast::multicast_mac;
+use crate::printer::macros::Output;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::iface::MulticastError;
+use smoltcp::wire::IpAddress;
+
+const GOOLOG_TARGET: &str = "IP MADDR";
+
+#[derive(Subcommand)]
+pub enum IpMaddrCommand {
+    /// Show the multicast groups of the network interfaces
+    Show,
+
+    /// Join a multicast group on an interface
+    Add(IpMaddrAddCommand),
+
+    /// Leave a multicast group on an interface
+    Delete(IpMaddrDeleteCommand),
+}
+
+#[derive(Args)]
+pub struct IpMaddrAddCommand {
+    /// Multicast group to join, IPv4 or IPv6
+    pub group: IpAddressArg,
+
+    /// Interface to join the group on
+    pub interface_name: NetworkInterfaceArg,
+}
+
+#[derive(Args)]
+pub struct IpMaddrDeleteCommand {
+    /// Multicast group to leave
+    pub group: IpAddressArg,
+
+    /// Interface to leave the group on
+    pub interface_name: NetworkInterfaceArg,
+}
+
+pub fn ip_maddr_show() -> Result<(), CliError> {
+    trace!("IP MADDR SHOW");
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("Group"), String::from("MAC"), String::from("Origin")]
+    ];
+
+    trace!("Locking NETWORK_INTERFACES mutex...");
+    let network_manager = NETWORK_MANAGER.lock();
+
+    for (name, device) in network_manager.interfaces.iter() {
+        let device = device.lock();
+
+        for group in device.all_multicast_groups() {
+            // The groups not joined with ip maddr are the ones of the addresses
+            let origin = match device.multicast_groups.contains(&group) {
+                true => "static",
+                false => "kernel"
+            };
+
+            table.push([name.clone(), group.to_string(), format_mac(&multicast_mac(group)), String::from(origin)]);
+        }
+    }
+
+    trace!("NETWORK_INTERFACES mutex freed");
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+pub fn ip_maddr_add(group: IpAddress, interface_name: &str) -> Result<(), CliError> {
+    trace!("IP MADDR ADD");
+
+    if !group.is_multicast() {
+        return Err(CliError::Message(format!("{} is not a multicast address", group)));
+    }
+
+    trace!("Locking NETWORK_INTERFACES mutex...");
+    let network_manager = NETWORK_MANAGER.lock();
+
+    let Some(device) = network_manager.interfaces.get(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    let mut device = device.lock();
+
+    if device.multicast_groups.contains(&group) {
+        return Err(CliError::Message(format!("Group {} already joined on {}", group, interface_name)));
+    }
+
+    match device.join_multicast_group(group) {
+        Ok(()) => info!("Joined {} on {}", group, interface_name),
+        Err(MulticastError::GroupTableFull) => return Err(CliError::Message(format!("Interface \"{}\" cannot join more groups", interface_name))),
+        Err(MulticastError::Unaddressable) => return Err(CliError::Message(format!("Group {} cannot be joined", group)))
+    }
+
+    trace!("NETWORK_INTERFACES mutex freed");
+
+    Ok(())
+}
+
+pub fn ip_maddr_delete(group: IpAddress, interface_name: &str) -> Result<(), CliError> {
+    trace!("IP MADDR DELETE");
+
+    trace!("Locking NETWORK_INTERFACES mutex...");
+    let network_manager = NETWORK_MANAGER.lock();
+
+    let Some(device) = network_manager.interfaces.get(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    let mut device = device.lock();
+
+    // The groups of the addresses are left along with them
+    if !device.multicast_groups.contains(&group) {
+        return Err(CliError::Message(format!("Group {} not joined on {}", group, interface_name)));
+    }
+
+    match device.leave_multicast_group(group) {
+        Ok(()) => info!("Left {} on {}", group, interface_name),
+        Err(_) => return Err(CliError::Message(format!("Group {} cannot be left", group)))
+    }
+
+    trace!("NETWORK_INTERFACES mutex freed");
+
+    Ok(())
+}
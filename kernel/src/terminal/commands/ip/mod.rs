@@ -1,4 +1,8 @@
 pub mod ip;
 pub mod interface;
+pub mod link;
 pub mod address;
-pub mod route;
\ No newline at end of file
+pub mod maddr;
+pub mod route;
+pub mod tunnel;
+pub mod vxlan;
\ No newline at end of file
@@ -1,9 +1,11 @@
-use crate::printer::buffer::WRITER;
+use crate::clock::Clock;
+use crate::printer::macros::Output;
 use crate::terminal::error::CliError;
 use crate::devices::network::manager::NETWORK_MANAGER;
 use crate::terminal::custom_arguments::ip_address::{IpAddressArg, IpCidrArg};
 use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use alloc::{format, vec};
 use goolog::{debug, info, trace};
 use no_std_clap_macros::{Args, Subcommand};
@@ -53,6 +55,29 @@ pub fn ip_route_show() -> Result<(), CliError> {
         [String::from("Interface"), String::from("IP"), String::from("Gateway"), String::from("Expires at"), String::from("Preferred until")]
     ];
 
+    for (name, route) in routes() {
+        let expires_at = match route.expires_at {
+            None => String::new(),
+            Some(instant) => Clock::format_instant(instant)
+        };
+
+        let preferred_until = match route.preferred_until {
+            None => String::new(),
+            Some(instant) => Clock::format_instant(instant)
+        };
+
+        table.push([name, route.cidr.to_string(), route.via_router.to_string(), expires_at, preferred_until]);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+/// Routes of every interface, along with the interface name
+pub fn routes() -> Vec<(String, Route)> {
+    let mut routes = Vec::new();
+
     trace!("Locking NETWORK_INTERFACES mutex...");
     let mut network_manager = NETWORK_MANAGER.lock();
 
@@ -63,26 +88,13 @@ pub fn ip_route_show() -> Result<(), CliError> {
             .routes_mut()
             .update(|route_list| {
                 for route in route_list.iter() {
-                    let expires_at = match route.expires_at {
-                        None => String::new(),
-                        Some(instant) => instant.to_string()
-                    };
-
-                    let preferred_until = match route.preferred_until {
-                        None => String::new(),
-                        Some(instant) => instant.to_string()
-                    };
-
-                    table.push([name.to_string(), route.cidr.to_string(), route.via_router.to_string(), expires_at, preferred_until]);
+                    routes.push((name.to_string(), *route));
                 }
             });
     }
     trace!("NETWORK_INTERFACES mutex freed");
 
-    let mut writer = WRITER.write();
-    text_tables::render(&mut *writer, table).unwrap();
-
-    Ok(())
+    routes
 }
 
 pub fn ip_route_add(ip_address: IpCidr, interface_name: &str, gateway: IpAddress) -> Result<(), CliError> {
@@ -92,26 +104,34 @@ pub fn ip_route_add(ip_address: IpCidr, interface_name: &str, gateway: IpAddress
     let mut network_manager = NETWORK_MANAGER.lock();
 
     trace!("Retrieving network interface \"{}\"", interface_name);
-    let device = network_manager.interfaces.get_mut(interface_name).unwrap();
+    let Some(device) = network_manager.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
     let mut locked_device = device.lock();
     let iface = &mut locked_device.interface;
 
     info!("Adding IP route");
+    let mut is_full = false;
+
     iface
         .routes_mut()
         .update(|routes| {
-        routes
+            is_full = routes
                 .push(Route {
                     cidr: ip_address,
                     via_router: gateway,
                     preferred_until: None,
                     expires_at: None,
                 })
-                .unwrap();
+                .is_err();
         });
 
     trace!("NETWORK_INTERFACES mutex freed");
 
+    if is_full {
+        return Err(CliError::Message(format!("Interface \"{}\" cannot have more routes", interface_name)));
+    }
+
     Ok(())
 }
 
@@ -122,7 +142,9 @@ pub fn ip_route_delete(ip_address: IpCidr, interface_name: &str) -> Result<(), C
     let mut network_manager = NETWORK_MANAGER.lock();
 
     trace!("Retrieving network interface \"{}\"", interface_name);
-    let device = network_manager.interfaces.get_mut(interface_name).unwrap();
+    let Some(device) = network_manager.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
     let mut locked_device = device.lock();
     let iface = &mut locked_device.interface;
 
@@ -0,0 +1,113 @@
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::tunnel::TunnelMode;
+use crate::printer::macros::Output;
+use crate::services::tunnel::TUNNELS;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::string::{String, ToString};
+use alloc::{format, vec};
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::wire::{IpAddress, Ipv4Address};
+
+const GOOLOG_TARGET: &str = "IP TUNNEL";
+
+#[derive(Subcommand)]
+pub enum IpTunnelCommand {
+    /// Show the tunnel interfaces
+    Show,
+
+    /// Create a tunnel interface
+    Add(IpTunnelAddCommand),
+
+    /// Delete a tunnel interface
+    Delete(IpTunnelDeleteCommand),
+}
+
+#[derive(Args)]
+pub struct IpTunnelAddCommand {
+    /// Name of the new interface, such as gre1
+    pub name: String,
+
+    /// Encapsulation: gre or ipip
+    pub mode: TunnelMode,
+
+    /// IPv4 address of this end, one of the addresses of the interface reaching the remote end
+    pub local: IpAddressArg,
+
+    /// IPv4 address of the remote end
+    pub remote: IpAddressArg,
+}
+
+#[derive(Args)]
+pub struct IpTunnelDeleteCommand {
+    /// Tunnel interface to delete
+    pub interface_name: NetworkInterfaceArg,
+}
+
+pub fn ip_tunnel_show() -> Result<(), CliError> {
+    trace!("IP TUNNEL SHOW");
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("Mode"), String::from("Local"), String::from("Remote")]
+    ];
+
+    for (name, tunnel) in TUNNELS.lock().tunnels.iter() {
+        table.push([name.clone(), tunnel.mode.to_string(), tunnel.local.to_string(), tunnel.remote.to_string()]);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+pub fn ip_tunnel_add(name: &str, mode: TunnelMode, local: IpAddress, remote: IpAddress) -> Result<(), CliError> {
+    trace!("IP TUNNEL ADD");
+
+    if name.is_empty() || name == "lo" || NETWORK_MANAGER.lock().interfaces.contains_key(name) {
+        return Err(CliError::Message(format!("Interface \"{}\" already exists", name)));
+    }
+
+    let local = ipv4_endpoint(local)?;
+    let remote = ipv4_endpoint(remote)?;
+
+    if local == remote {
+        return Err(CliError::Message(String::from("The local and remote addresses must differ")));
+    }
+
+    let mut tunnels = TUNNELS.lock();
+
+    // The packets would be handed to both tunnels
+    if tunnels.tunnels.values().any(|tunnel| tunnel.mode == mode && tunnel.local == local && tunnel.remote == remote) {
+        return Err(CliError::Message(format!("A {} tunnel from {} to {} already exists", mode, local, remote)));
+    }
+
+    info!("Creating {} tunnel {} from {} to {}", mode, name, local, remote);
+    tunnels.add(name, mode, local, remote);
+
+    Ok(())
+}
+
+pub fn ip_tunnel_delete(interface_name: &str) -> Result<(), CliError> {
+    trace!("IP TUNNEL DELETE");
+
+    let mut tunnels = TUNNELS.lock();
+
+    if !tunnels.tunnels.contains_key(interface_name) {
+        return Err(CliError::Message(format!("{} is not a tunnel", interface_name)));
+    }
+
+    info!("Deleting tunnel {}", interface_name);
+    tunnels.delete(interface_name);
+
+    Ok(())
+}
+
+/// The tunnels are carried over IPv4
+fn ipv4_endpoint(address: IpAddress) -> Result<Ipv4Address, CliError> {
+    match address {
+        IpAddress::Ipv4(address) if !address.is_multicast() && !address.is_broadcast() && !address.is_unspecified() => Ok(address),
+        _ => Err(CliError::Message(format!("{} is not a unicast IPv4 address", address)))
+    }
+}
@@ -0,0 +1,208 @@
+use crate::clock::Clock;
+use crate::devices::network::interface::format_mac;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::vxlan::MAX_VNI;
+use crate::printer::macros::Output;
+use crate::services::vxlan::{FDB_AGEING_TIME, VXLANS};
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::wire::IpAddress;
+
+const GOOLOG_TARGET: &str = "IP VXLAN";
+
+#[derive(Subcommand)]
+pub enum IpVxlanCommand {
+    /// Show the VXLAN interfaces
+    Show,
+
+    /// Create a VXLAN interface
+    Add(IpVxlanAddCommand),
+
+    /// Delete a VXLAN interface
+    Delete {
+        /// VXLAN interface to delete
+        interface_name: NetworkInterfaceArg,
+    },
+
+    /// Interact with the VTEPs the flooded frames are replicated to
+    #[command(subcommand)]
+    Remote(IpVxlanRemoteCommand),
+
+    /// Show the MAC addresses learned behind the remote VTEPs
+    Fdb,
+}
+
+#[derive(Args)]
+pub struct IpVxlanAddCommand {
+    /// Name of the new interface, such as vxlan0
+    pub name: String,
+
+    /// VXLAN network identifier, from 0 to 16777215
+    pub vni: u32,
+
+    /// Interface reaching the remote VTEPs
+    pub interface_name: NetworkInterfaceArg,
+
+    /// Multicast group the broadcast, multicast and unknown unicast frames are flooded to
+    #[arg(short, long)]
+    pub group: Option<IpAddressArg>,
+}
+
+#[derive(Subcommand)]
+pub enum IpVxlanRemoteCommand {
+    /// Replicate the flooded frames to a VTEP
+    Add {
+        /// VXLAN interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// Address of the remote VTEP
+        address: IpAddressArg,
+    },
+
+    /// Stop replicating the flooded frames to a VTEP
+    Delete {
+        /// VXLAN interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// Address of the remote VTEP
+        address: IpAddressArg,
+    },
+}
+
+pub fn ip_vxlan_show() -> Result<(), CliError> {
+    trace!("IP VXLAN SHOW");
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("VNI"), String::from("Underlay"), String::from("Group"), String::from("Remotes"), String::from("Learned")]
+    ];
+
+    for (name, vxlan) in VXLANS.lock().interfaces.iter() {
+        let remotes: Vec<_> = vxlan.remotes.iter().map(|remote| remote.to_string()).collect();
+
+        table.push([
+            name.clone(),
+            vxlan.vni.to_string(),
+            vxlan.underlay.clone(),
+            vxlan.group.map(|group| group.to_string()).unwrap_or_default(),
+            remotes.join(", "),
+            vxlan.fdb.len().to_string(),
+        ]);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+pub fn ip_vxlan_add(name: &str, vni: u32, interface_name: &str, group: Option<IpAddress>) -> Result<(), CliError> {
+    trace!("IP VXLAN ADD");
+
+    if name.is_empty() || name == "lo" || NETWORK_MANAGER.lock().interfaces.contains_key(name) {
+        return Err(CliError::Message(format!("Interface \"{}\" already exists", name)));
+    }
+
+    if vni > MAX_VNI {
+        return Err(CliError::Message(format!("The VNI cannot exceed {}", MAX_VNI)));
+    }
+
+    if interface_name == "lo" {
+        return Err(CliError::Message(String::from("The remote VTEPs cannot be reached through the loopback")));
+    }
+
+    if let Some(group) = group.filter(|group| !group.is_multicast()) {
+        return Err(CliError::Message(format!("{} is not a multicast group", group)));
+    }
+
+    let mut vxlans = VXLANS.lock();
+
+    // The frames of a VNI would be handed to both interfaces
+    if vxlans.interfaces.values().any(|vxlan| vxlan.underlay == interface_name && vxlan.vni == vni) {
+        return Err(CliError::Message(format!("VNI {} is already used on {}", vni, interface_name)));
+    }
+
+    info!("Creating VXLAN {} with VNI {} over {}", name, vni, interface_name);
+    vxlans.add(name, vni, interface_name, group);
+
+    Ok(())
+}
+
+pub fn ip_vxlan_delete(interface_name: &str) -> Result<(), CliError> {
+    trace!("IP VXLAN DELETE");
+
+    let mut vxlans = VXLANS.lock();
+
+    if !vxlans.interfaces.contains_key(interface_name) {
+        return Err(CliError::Message(format!("{} is not a VXLAN interface", interface_name)));
+    }
+
+    info!("Deleting VXLAN {}", interface_name);
+    vxlans.delete(interface_name);
+
+    Ok(())
+}
+
+pub fn ip_vxlan_remote_add(interface_name: &str, address: IpAddress) -> Result<(), CliError> {
+    trace!("IP VXLAN REMOTE ADD");
+
+    if !address.is_unicast() {
+        return Err(CliError::Message(format!("{} is not a unicast address", address)));
+    }
+
+    let mut vxlans = VXLANS.lock();
+
+    let Some(vxlan) = vxlans.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("{} is not a VXLAN interface", interface_name)));
+    };
+
+    if vxlan.remotes.contains(&address) {
+        return Err(CliError::Message(format!("VTEP {} already added to {}", address, interface_name)));
+    }
+
+    info!("Flooding the frames of {} to {}", interface_name, address);
+    vxlan.remotes.push(address);
+
+    Ok(())
+}
+
+pub fn ip_vxlan_remote_delete(interface_name: &str, address: IpAddress) -> Result<(), CliError> {
+    trace!("IP VXLAN REMOTE DELETE");
+
+    let mut vxlans = VXLANS.lock();
+
+    let Some(vxlan) = vxlans.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("{} is not a VXLAN interface", interface_name)));
+    };
+
+    let Some(index) = vxlan.remotes.iter().position(|remote| *remote == address) else {
+        return Err(CliError::Message(format!("VTEP {} not found on {}", address, interface_name)));
+    };
+
+    info!("Stopping flooding the frames of {} to {}", interface_name, address);
+    vxlan.remotes.remove(index);
+
+    Ok(())
+}
+
+pub fn ip_vxlan_fdb() -> Result<(), CliError> {
+    trace!("IP VXLAN FDB");
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("MAC"), String::from("VTEP"), String::from("Expires at")]
+    ];
+
+    for (name, vxlan) in VXLANS.lock().interfaces.iter() {
+        for (mac, entry) in vxlan.fdb.iter() {
+            table.push([name.clone(), format_mac(mac.as_bytes()), entry.vtep.to_string(), Clock::format_instant(entry.updated_at + FDB_AGEING_TIME)]);
+        }
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use crate::services::iperf::client::{run_client, ClientOptions};
+use crate::services::iperf::server::{run_server, ServerOptions};
+use crate::services::iperf::IperfError;
+use crate::terminal::custom_arguments::bitrate::BitrateArg;
+use crate::terminal::custom_arguments::duration::SecondsArg;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use alloc::format;
+use alloc::string::String;
+use goolog::trace;
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::wire::IpEndpoint;
+
+const GOOLOG_TARGET: &str = "IPERF";
+
+#[derive(Subcommand)]
+pub enum IperfCommand {
+    /// Measure the bandwidth to an iperf3 server
+    Client(IperfClientCommand),
+
+    /// Serve the tests of iperf3 clients, until stopped
+    Server(IperfServerCommand),
+}
+
+#[derive(Args)]
+pub struct IperfClientCommand {
+    /// Address of the server
+    pub host: IpAddressArg,
+
+    /// Port of the server. Defaults to: 5201
+    #[arg(short, long, default_value = "5201")]
+    pub port: u16,
+
+    /// Use UDP instead of TCP
+    #[arg(short, long)]
+    pub udp: bool,
+
+    /// Duration of the test, in seconds. Defaults to: 10
+    #[arg(short, long, default_value = "10")]
+    pub time: u32,
+
+    /// Target bitrate of each stream, in bits per second (e.g. 10M). Defaults to: 1M with UDP, unlimited with TCP
+    #[arg(short, long)]
+    pub bitrate: Option<BitrateArg>,
+
+    /// Length of the blocks written, in bytes. Defaults to: 131072 with TCP, 1460 with UDP
+    #[arg(short, long)]
+    pub length: Option<usize>,
+
+    /// Number of parallel streams. Defaults to: 1
+    #[arg(short = 'P', long, default_value = "1")]
+    pub parallel: u32,
+
+    /// Reverse mode, the server sends and the client receives
+    #[arg(short = 'R', long)]
+    pub reverse: bool,
+
+    /// Seconds between the bandwidth reports, 0 for the summary only. Defaults to: 1
+    #[arg(short, long, default_value = "1")]
+    pub interval: SecondsArg,
+}
+
+#[derive(Args)]
+pub struct IperfServerCommand {
+    /// Port to listen on. Defaults to: 5201
+    #[arg(short, long, default_value = "5201")]
+    pub port: u16,
+
+    /// Serve a single test, then stop
+    #[arg(short, long)]
+    pub one_off: bool,
+
+    /// Seconds between the bandwidth reports, 0 for the summary only. Defaults to: 1
+    #[arg(short, long, default_value = "1")]
+    pub interval: SecondsArg,
+}
+
+pub async fn iperf_client(server_endpoint: IpEndpoint, options: ClientOptions) -> Result<(), CliError> {
+    trace!("IPERF CLIENT");
+
+    if options.time == 0 {
+        return Err(CliError::Message(String::from("The duration of the test must be positive")));
+    }
+
+    run_client(server_endpoint, &options).await.map_err(iperf_error)
+}
+
+pub async fn iperf_server(options: ServerOptions) -> Result<(), CliError> {
+    trace!("IPERF SERVER");
+
+    if options.port == 0 {
+        return Err(CliError::Message(String::from("The port must be positive")));
+    }
+
+    run_server(&options).await.map_err(iperf_error)
+}
+
+fn iperf_error(error: IperfError) -> CliError {
+    CliError::Message(format!("iperf3: error - {}", error))
+}
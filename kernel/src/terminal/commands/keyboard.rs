@@ -41,7 +41,7 @@ pub fn change_layout(layout: KeyboardLayout) -> Result<(), CliError> {
     };
     
     debug!("Locking and setting KEYBOARD mutex...");
-    *KEYBOARD.write() = Keyboard::new(ScancodeSet1::new(), layout, HandleControl::Ignore);
+    *KEYBOARD.write() = Keyboard::new(ScancodeSet1::new(), layout, HandleControl::MapLettersToUnicode);
     debug!("KEYBOARD mutex set and freed");
 
     Ok(())
@@ -0,0 +1,16 @@
+use crate::task::executor::cancel_task;
+use crate::task::task::TaskId;
+use crate::terminal::error::CliError;
+use alloc::format;
+use goolog::trace;
+
+const GOOLOG_TARGET: &str = "KILL";
+
+pub fn kill(id: u64) -> Result<(), CliError> {
+    trace!("KILL");
+
+    match cancel_task(TaskId(id)) {
+        true => Ok(()),
+        false => Err(CliError::Message(format!("No task with ID {}", id)))
+    }
+}
@@ -0,0 +1,84 @@
+use crate::println;
+use crate::services::syslog::{facility, pending_messages, set_facility, SyslogFacility, SYSLOG_FORWARDER};
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use alloc::string::String;
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::wire::{IpAddress, IpEndpoint};
+
+const GOOLOG_TARGET: &str = "LOGGING";
+
+#[derive(Subcommand)]
+pub enum LoggingCommand {
+    /// Show the remote logging configuration
+    Show,
+
+    /// Forward the logs to a syslog server
+    Host(LoggingHostCommand),
+
+    /// Stop forwarding the logs
+    Stop,
+
+    /// Set the syslog facility of the forwarded logs
+    Facility {
+        /// Syslog facility
+        facility: SyslogFacility,
+    },
+}
+
+#[derive(Args)]
+pub struct LoggingHostCommand {
+    /// IP address of the syslog server
+    pub address: IpAddressArg,
+
+    /// UDP port of the syslog server. Defaults to: 514
+    #[arg(default_value = "514")]
+    pub port: u16,
+}
+
+pub fn logging_show() -> Result<(), CliError> {
+    trace!("LOGGING SHOW");
+
+    let forwarder = SYSLOG_FORWARDER.lock();
+
+    match forwarder.server {
+        None => println!("Remote logging: disabled"),
+        Some(server) => println!("Remote logging: {} ({} messages sent)", server, forwarder.messages_sent)
+    }
+
+    println!("Facility: {}", facility());
+    println!("Buffered messages: {}", pending_messages());
+
+    Ok(())
+}
+
+pub fn logging_host(address: IpAddress, port: u16) -> Result<(), CliError> {
+    trace!("LOGGING HOST");
+
+    if !address.is_unicast() {
+        return Err(CliError::Message(String::from("The syslog server address must be unicast")));
+    }
+
+    info!("Forwarding logs to {}:{}", address, port);
+    SYSLOG_FORWARDER.lock().set_server(Some(IpEndpoint::new(address, port)));
+
+    Ok(())
+}
+
+pub fn logging_stop() -> Result<(), CliError> {
+    trace!("LOGGING STOP");
+
+    info!("Stopping log forwarding");
+    SYSLOG_FORWARDER.lock().set_server(None);
+
+    Ok(())
+}
+
+pub fn logging_facility(syslog_facility: SyslogFacility) -> Result<(), CliError> {
+    trace!("LOGGING FACILITY");
+
+    set_facility(syslog_facility);
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use crate::devices::pci::PCI_DEVICES;
-use crate::printer::buffer::WRITER;
+use crate::printer::macros::Output;
 use crate::terminal::error::CliError;
 use alloc::string::{String, ToString};
 use alloc::{format, vec};
@@ -27,8 +27,7 @@ pub fn lspci() -> Result<(), CliError> {
         ])
     }
 
-    let mut writer = WRITER.write();
-    text_tables::render(&mut *writer, table).unwrap();
+    text_tables::render(&mut Output, table).unwrap();
 
     Ok(())
 }
\ No newline at end of file
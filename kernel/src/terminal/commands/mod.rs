@@ -1,6 +1,8 @@
 pub mod echo;
 pub mod shutdown;
 pub mod ps;
+pub mod ss;
+pub mod kill;
 pub mod clear;
 pub mod keyboard;
 pub mod uptime;
@@ -9,4 +11,18 @@ pub mod scanpci;
 pub mod top;
 pub mod ip;
 pub mod ping;
-pub mod sleep;
\ No newline at end of file
+pub mod arping;
+pub mod traceroute;
+pub mod nc;
+pub mod iperf;
+pub mod sleep;
+pub mod flow;
+pub mod snmp;
+pub mod logging;
+pub mod date;
+pub mod ntp;
+pub mod radvd;
+pub mod dhcpv6;
+pub mod telnet;
+pub mod ssh;
+pub mod http;
\ No newline at end of file
@@ -0,0 +1,194 @@
+use crate::devices::network::sockets::tcp::{TcpListener, TcpStream};
+use crate::devices::network::sockets::udp::UdpSocket;
+use crate::devices::network::sockets::SocketError;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use crate::terminal::input::read_line;
+use crate::{print, println};
+use alloc::format;
+use alloc::string::String;
+use core::pin::pin;
+use futures_util::future::{select, Either};
+use goolog::trace;
+use no_std_clap_macros::Args;
+use smoltcp::wire::{IpAddress, IpEndpoint};
+
+const GOOLOG_TARGET: &str = "NC";
+
+const BUFFER_SIZE: usize = 2048;
+
+#[derive(Args)]
+pub struct NcCommand {
+    /// Remote host to connect or send to
+    pub host: Option<IpAddressArg>,
+
+    /// Remote port, or the port to listen on with -l
+    pub port: Option<u16>,
+
+    /// Listen for an incoming connection, or for datagrams with -u
+    #[arg(short, long)]
+    pub listen: bool,
+
+    /// Port to listen on
+    #[arg(short = 'p', long)]
+    pub local_port: Option<u16>,
+
+    /// Use UDP instead of TCP
+    #[arg(short, long)]
+    pub udp: bool,
+}
+
+pub async fn nc(host: Option<IpAddress>, port: Option<u16>, listen: bool, local_port: Option<u16>, udp: bool) -> Result<(), CliError> {
+    trace!("NC");
+
+    match listen {
+        true => {
+            // "nc -l 1234" is accepted along with "nc -l -p 1234"
+            let Some(local_port) = local_port.or(port) else {
+                return Err(CliError::Message(String::from("A port to listen on is required")));
+            };
+
+            match udp {
+                true => nc_udp(UdpSocket::bind(local_port).map_err(socket_error)?, None).await,
+                false => nc_tcp_listen(local_port).await
+            }
+        },
+        false => {
+            let (Some(host), Some(port)) = (host, port) else {
+                return Err(CliError::Message(String::from("A host and a port are required")));
+            };
+
+            let remote_endpoint = IpEndpoint::new(host, port);
+
+            match udp {
+                true => nc_udp(UdpSocket::bind(local_port.unwrap_or(0)).map_err(socket_error)?, Some(remote_endpoint)).await,
+                false => nc_tcp_connect(remote_endpoint).await
+            }
+        }
+    }
+}
+
+fn socket_error(error: SocketError) -> CliError {
+    CliError::Message(format!("{}", error))
+}
+
+async fn nc_tcp_connect(remote_endpoint: IpEndpoint) -> Result<(), CliError> {
+    println!("Connecting to {}...", remote_endpoint);
+
+    let stream = TcpStream::connect(remote_endpoint).await.map_err(socket_error)?;
+
+    println!("Connected to {} from {}", remote_endpoint, stream.interface_name());
+
+    nc_tcp_session(stream).await
+}
+
+async fn nc_tcp_listen(local_port: u16) -> Result<(), CliError> {
+    let mut listener = TcpListener::bind(local_port).map_err(socket_error)?;
+
+    println!("Listening on port {}", local_port);
+
+    let stream = listener.accept().await;
+
+    // A single connection is served
+    drop(listener);
+
+    match stream.remote_endpoint() {
+        Some(remote_endpoint) => println!("Connection from {} on {}", remote_endpoint, stream.interface_name()),
+        None => println!("Connection on {}", stream.interface_name())
+    }
+
+    nc_tcp_session(stream).await
+}
+
+/// Send the typed lines and print the received data, until the remote side closes the connection
+async fn nc_tcp_session(stream: TcpStream) -> Result<(), CliError> {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let mut input_open = true;
+
+    loop {
+        let received = match input_open {
+            true => match select(pin!(stream.read(&mut buffer)), pin!(read_line())).await {
+                Either::Left((received, _)) => received,
+                Either::Right((Some(line), _)) => {
+                    stream.write_all(line.as_bytes()).await.map_err(socket_error)?;
+                    stream.write_all(b"\n").await.map_err(socket_error)?;
+                    continue;
+                },
+                // The end of the input closes the sending half of the connection
+                Either::Right((None, _)) => {
+                    stream.shutdown();
+                    input_open = false;
+                    continue;
+                }
+            },
+            false => stream.read(&mut buffer).await
+        };
+
+        match received.map_err(socket_error)? {
+            0 => break,
+            size => print!("{}", String::from_utf8_lossy(&buffer[..size]))
+        }
+    }
+
+    println!("Connection closed");
+
+    stream.close().await;
+
+    Ok(())
+}
+
+/// Send the typed lines to the remote endpoint, or to the last sender, and print the datagrams received from it
+async fn nc_udp(socket: UdpSocket, mut remote_endpoint: Option<IpEndpoint>) -> Result<(), CliError> {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let mut input_open = true;
+    let is_connected = remote_endpoint.is_some();
+
+    if remote_endpoint.is_none() {
+        println!("Listening on port {}", socket.local_port());
+    }
+
+    loop {
+        let received = match input_open {
+            true => match select(pin!(socket.recv_from(&mut buffer)), pin!(read_line())).await {
+                Either::Left((received, _)) => received,
+                Either::Right((Some(mut line), _)) => {
+                    match remote_endpoint {
+                        Some(remote_endpoint) => {
+                            line.push('\n');
+                            socket.send_to(line.as_bytes(), remote_endpoint).await.map_err(socket_error)?;
+                        },
+                        None => println!("Nothing received yet, nowhere to send to")
+                    }
+                    continue;
+                },
+                // Datagrams are still printed, until the command is stopped
+                Either::Right((None, _)) => {
+                    input_open = false;
+                    continue;
+                }
+            },
+            false => socket.recv_from(&mut buffer).await
+        };
+
+        let (size, sender) = match received {
+            Ok(received) => received,
+            Err(SocketError::PacketTooLarge) => {
+                println!("Datagram larger than {} bytes dropped", BUFFER_SIZE);
+                continue;
+            },
+            Err(error) => return Err(socket_error(error))
+        };
+
+        if remote_endpoint != Some(sender) {
+            // A client only talks to the endpoint it connected to
+            if is_connected {
+                continue;
+            }
+
+            println!("Receiving from {}", sender);
+            remote_endpoint = Some(sender);
+        }
+
+        print!("{}", String::from_utf8_lossy(&buffer[..size]));
+    }
+}
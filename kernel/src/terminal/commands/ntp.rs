@@ -0,0 +1,69 @@
+use crate::clock::Clock;
+use crate::println;
+use crate::services::sntp::SNTP_CLIENT;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use alloc::string::String;
+use goolog::{info, trace};
+use no_std_clap_macros::Subcommand;
+use smoltcp::wire::IpAddress;
+
+const GOOLOG_TARGET: &str = "NTP";
+
+#[derive(Subcommand)]
+pub enum NtpCommand {
+    /// Show the clock synchronization state
+    Show,
+
+    /// Synchronize the clock with an NTP server
+    Server {
+        /// IP address of the NTP server
+        address: IpAddressArg,
+    },
+
+    /// Stop synchronizing the clock
+    Stop,
+}
+
+pub fn ntp_show() -> Result<(), CliError> {
+    trace!("NTP SHOW");
+
+    let client = SNTP_CLIENT.lock();
+
+    match client.server {
+        None => println!("Server: none"),
+        Some(server) => println!("Server: {}", server)
+    }
+
+    match client.last_synchronization {
+        None => println!("Not synchronized"),
+        Some(instant) => {
+            println!("Last synchronization: {}", Clock::format_instant(instant));
+            println!("Stratum: {}, delay: {}ms", client.stratum, client.delay.total_millis());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn ntp_server(address: IpAddress) -> Result<(), CliError> {
+    trace!("NTP SERVER");
+
+    if !address.is_unicast() {
+        return Err(CliError::Message(String::from("The NTP server address must be unicast")));
+    }
+
+    info!("Synchronizing clock with {}", address);
+    SNTP_CLIENT.lock().set_server(Some(address));
+
+    Ok(())
+}
+
+pub fn ntp_stop() -> Result<(), CliError> {
+    trace!("NTP STOP");
+
+    info!("Stopping clock synchronization");
+    SNTP_CLIENT.lock().set_server(None);
+
+    Ok(())
+}
@@ -1,22 +1,38 @@
-use alloc::borrow::ToOwned;
-use crate::clock::{sleep, Clock};
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::OwnedSockets;
+use crate::terminal::custom_arguments::duration::SecondsArg;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::source::SourceArg;
 use crate::terminal::error::CliError;
-use crate::{println};
+use crate::{print, println};
 use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec;
+use alloc::vec::Vec;
 use byteorder::{ByteOrder, NetworkEndian};
-use goolog::{trace};
+use goolog::trace;
 use no_std_clap_macros::Args;
-use smoltcp::phy::Device;
-use smoltcp::socket::icmp::{Endpoint, PacketBuffer, PacketMetadata, Socket};
+use smoltcp::phy::{ChecksumCapabilities, Device};
+use smoltcp::socket::{icmp, raw};
 use smoltcp::time::{Duration, Instant};
-use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress};
-use crate::devices::network::manager::NETWORK_MANAGER;
-use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use smoltcp::wire::{Icmpv4Packet, Icmpv4Repr, Icmpv6Packet, Icmpv6Repr, IpAddress, IpProtocol, IpVersion, Ipv4Packet, Ipv4Repr, Ipv6Packet, Ipv6Repr};
+use spin::Mutex;
 
 const GOOLOG_TARGET: &str = "PING";
 
+const ICMP_HEADER_LENGTH: usize = 8;
+/// Largest payload of an echo request carried by a single IPv4 packet
+const MAX_PAYLOAD_SIZE: u16 = 65507;
+/// Requests in flight the sockets can hold
+const SOCKET_PACKETS: usize = 8;
+/// Flood mode sends at least this often, even without replies
+const FLOOD_INTERVAL: Duration = Duration::from_millis(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 #[derive(Args)]
 pub struct PingCommand {
     /// IP address to ping
@@ -28,238 +44,408 @@ pub struct PingCommand {
 
     /// Timeout
     #[arg(default_value = "2")]
-    pub timeout: u64
+    pub timeout: u64,
+
+    /// Payload size, in bytes. Defaults to: 56
+    #[arg(short, long, default_value = "56")]
+    pub size: u16,
+
+    /// Interval between the requests, in seconds (e.g. 0.2). Defaults to: 1
+    #[arg(short, long, default_value = "1")]
+    pub interval: SecondsArg,
+
+    /// Interface or source address to ping from
+    #[arg(short = 'I', long)]
+    pub interface: Option<SourceArg>,
+
+    /// Time to live (hop limit) of the requests. Defaults to: 64
+    #[arg(short, long, default_value = "64")]
+    pub ttl: u8,
+
+    /// Flood mode, a request is sent as soon as the previous one is answered
+    #[arg(short, long)]
+    pub flood: bool,
+
+    /// Refuse the requests larger than the MTU, instead of fragmenting them
+    #[arg(short = 'D', long)]
+    pub no_fragment: bool,
 }
 
-pub fn ping(remote_addr: IpAddress, count: u16, timeout: u64) -> Result<(), CliError> {
-    trace!("PING");
+pub struct PingOptions {
+    pub count: u16,
+    pub timeout: Duration,
+    pub size: u16,
+    pub interval: Duration,
+    pub source: Option<SourceArg>,
+    pub ttl: u8,
+    pub flood: bool,
+    pub no_fragment: bool,
+}
 
-    if remote_addr.is_unspecified() {
-        return Err(CliError::Message(String::from("The given address is not addressable")));
+impl Default for PingOptions {
+    /// Same defaults as the ping command
+    fn default() -> Self {
+        Self {
+            count: 4,
+            timeout: Duration::from_secs(2),
+            size: 56,
+            interval: Duration::from_secs(1),
+            source: None,
+            ttl: 64,
+            flood: false,
+            no_fragment: false,
+        }
     }
+}
 
-    if !remote_addr.is_unicast() {
-        return Err(CliError::Message(String::from("The given address is not unicast")));
+pub struct PingStatistics {
+    pub transmitted: u16,
+    pub received: u16,
+    /// Round-trip time of every reply, in milliseconds
+    pub round_trip_times: Vec<i64>,
+}
+
+impl PingStatistics {
+    /// Minimum, average, maximum and mean deviation of the round-trip times
+    pub fn summary(&self) -> Option<(i64, f64, i64, f64)> {
+        let min = *self.round_trip_times.iter().min()?;
+        let max = *self.round_trip_times.iter().max()?;
+
+        let count = self.round_trip_times.len() as f64;
+        let average = self.round_trip_times.iter().sum::<i64>() as f64 / count;
+        let square_average = self.round_trip_times.iter().map(|time| (time * time) as f64).sum::<f64>() / count;
+
+        Some((min, average, max, square_root(square_average - average * average)))
     }
+}
 
-    let mut manager = NETWORK_MANAGER.lock();
-
-    let mut iface_name = None;
-    let now = Clock::now();
-
-    for (device_name, device) in &manager.interfaces {
-        let mut device = device.lock();
-        let mut has_route = false;
-
-        device.interface
-            .routes_mut()
-            .update(|routes| {
-                has_route = routes
-                    .iter()
-                    .filter(|route| {
-                        if let Some(expires_at) = route.expires_at {
-                            if now > expires_at {
-                                return false;
-                            }
-                        }
-                        route.cidr.contains_addr(&remote_addr)
-                    })
-                    .max_by_key(|route| route.cidr.prefix_len())
-                    .is_some();
-            });
+pub async fn ping(remote_addr: IpAddress, options: &PingOptions) -> Result<PingStatistics, CliError> {
+    trace!("PING");
 
-        if has_route {
-            iface_name = Some(device_name.clone());
-        }
+    if options.size > MAX_PAYLOAD_SIZE {
+        return Err(CliError::Message(format!("The payload size cannot exceed {} bytes", MAX_PAYLOAD_SIZE)));
     }
 
-    if iface_name.is_none() {
-        return Err(CliError::Message(String::from("No interface found to ping from")));
+    if options.ttl == 0 {
+        return Err(CliError::Message(String::from("The TTL must be positive")));
     }
 
-    let local_device = manager.interfaces.get_mut(iface_name.as_ref().unwrap()).unwrap().clone();
-    drop(manager);
+    let (local_device, source_addr) = source_device(&remote_addr, options.source.as_ref())?;
 
     let device_caps = local_device.lock().network_controller.capabilities();
-    let local_sockets = local_device.lock().sockets.clone();
+    let mut local_sockets = OwnedSockets::new(local_device.lock().sockets.clone());
 
-    let icmp_rx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY], vec![0; 256]);
-    let icmp_tx_buffer = PacketBuffer::new(vec![PacketMetadata::EMPTY], vec![0; 256]);
-    let icmp_socket = Socket::new(icmp_rx_buffer, icmp_tx_buffer);
+    let ip_header_length = match remote_addr {
+        IpAddress::Ipv4(_) => 20,
+        IpAddress::Ipv6(_) => 40
+    };
+    let packet_length = ip_header_length + ICMP_HEADER_LENGTH + options.size as usize;
 
-    let icmp_handle = local_sockets.lock().add(icmp_socket);
+    // The larger requests are fragmented by the interface, unless asked otherwise
+    if options.no_fragment && packet_length > device_caps.ip_mtu() {
+        return Err(CliError::Message(format!("Message too long, the MTU is {}", device_caps.ip_mtu())));
+    }
+
+    // The requests are sent from a raw socket to choose their source and TTL, the ICMP socket gets the replies
+    let raw_rx_buffer = raw::PacketBuffer::new(vec![], vec![]);
+    let raw_tx_buffer = raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * packet_length]);
+    let raw_socket = match remote_addr {
+        IpAddress::Ipv4(_) => raw::Socket::new(IpVersion::Ipv4, IpProtocol::Icmp, raw_rx_buffer, raw_tx_buffer),
+        IpAddress::Ipv6(_) => raw::Socket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, raw_rx_buffer, raw_tx_buffer)
+    };
+
+    let icmp_rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY; SOCKET_PACKETS], vec![0; SOCKET_PACKETS * packet_length]);
+    let icmp_tx_buffer = icmp::PacketBuffer::new(vec![], vec![]);
+    let mut icmp_socket = icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer);
+
+    let ident = 0x22b;
+    icmp_socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+
+    let raw_handle = local_sockets.add(raw_socket);
+    let icmp_handle = local_sockets.add(icmp_socket);
+
+    let mut echo_payload = vec![0u8; options.size as usize];
+    for (index, byte) in echo_payload.iter_mut().enumerate() {
+        *byte = index as u8;
+    }
 
-    let mut send_at = Instant::from_millis(0);
+    let mut send_at = Clock::now();
     let mut seq_no = 0;
     let mut received = 0;
-    let mut echo_payload = [0xffu8; 40];
+    let mut round_trip_times = Vec::new();
     let mut waiting_queue = BTreeMap::new();
-    let ident = 0x22b;
 
-    let interval = Duration::from_secs(1);
-    let timeout = Duration::from_secs(timeout);
+    println!("PING {} from {}: {} data bytes", remote_addr, source_addr, options.size);
 
     loop {
         let timestamp = Clock::now();
-        let (can_send, can_receive) = {
-            let mut sockets = local_sockets.lock();
-            let socket = sockets.get_mut::<Socket>(icmp_handle);
-
-            if !socket.is_open() {
-                trace!("Binding socket");
-                socket.bind(Endpoint::Ident(ident)).unwrap();
-                send_at = timestamp;
-                trace!("Socket bound");
-            }
 
-            (socket.can_send(), socket.can_recv())
+        // In flood mode, the next request goes as soon as the previous one is answered
+        let can_send_next = match options.flood {
+            true => waiting_queue.is_empty() || send_at <= timestamp,
+            false => send_at <= timestamp
         };
 
-        if can_send && seq_no < count && send_at <= timestamp {
-            NetworkEndian::write_i64(&mut echo_payload, timestamp.total_millis());
-
-            match remote_addr {
-                IpAddress::Ipv4(_) => {
-                    trace!("Sending ICMP packet (IPv4)");
-
-                    let mut sockets = local_sockets.lock();
-                    let socket = sockets.get_mut::<Socket>(icmp_handle);
-
-                    let icmp_repr = Icmpv4Repr::EchoRequest {
-                        ident,
-                        seq_no,
-                        data: &echo_payload,
-                    };
-
-                    let icmp_payload = socket.send(icmp_repr.buffer_len(), remote_addr).unwrap();
-                    let mut icmp_packet = Icmpv4Packet::new_unchecked(icmp_payload);
+        if seq_no < options.count && can_send_next {
+            // The timestamp is kept in the payload when it fits, as the other implementations do
+            if echo_payload.len() >= 8 {
+                NetworkEndian::write_i64(&mut echo_payload, timestamp.total_millis());
+            }
 
-                    icmp_repr.emit(&mut icmp_packet, &device_caps.checksum);
+            let sent = local_sockets.with(raw_handle, |socket: &mut raw::Socket| {
+                match socket.send(packet_length) {
+                    Ok(packet) => {
+                        emit_request(packet, source_addr, remote_addr, options.ttl, ident, seq_no, &echo_payload, &device_caps.checksum);
+                        true
+                    },
+                    Err(_) => false
                 }
-                IpAddress::Ipv6(address) => {
-                    trace!("Sending ICMP packet (IPv6)");
-
-                    let mut sockets = local_sockets.lock();
-                    let socket = sockets.get_mut::<Socket>(icmp_handle);
+            });
 
-                    let icmp_repr = Icmpv6Repr::EchoRequest {
-                        ident,
-                        seq_no,
-                        data: &echo_payload,
-                    };
+            if sent {
+                trace!("ICMP packet sent");
 
-                    let icmp_payload = socket.send(icmp_repr.buffer_len(), remote_addr).unwrap();
-                    let mut icmp_packet = Icmpv6Packet::new_unchecked(icmp_payload);
+                waiting_queue.insert(seq_no, timestamp);
+                seq_no += 1;
 
-                    icmp_repr.emit(
-                        &local_device.lock().interface.get_source_address_ipv6(&address),
-                        &address,
-                        &mut icmp_packet,
-                        &device_caps.checksum,
-                    );
-                }
+                send_at = match options.flood {
+                    true => timestamp + FLOOD_INTERVAL,
+                    false => timestamp + options.interval
+                };
             }
-            trace!("ICMP packet sent");
-
-            waiting_queue.insert(seq_no, timestamp);
-            seq_no += 1;
-            send_at += interval;
         }
 
-        if can_receive {
-            let payload = {
-                let mut sockets = local_sockets.lock();
-                let socket = sockets.get_mut::<Socket>(icmp_handle);
-
-                let (payload, _) =socket.recv().unwrap();
-                payload.to_owned()
-            };
+        let reply = local_sockets.with(icmp_handle, |socket: &mut icmp::Socket| {
+            match socket.can_recv() {
+                true => socket.recv().ok().map(|(payload, address)| (payload.to_vec(), address)),
+                false => None
+            }
+        });
 
-            match remote_addr {
+        if let Some((payload, address)) = reply {
+            let reply_seq_no = match remote_addr {
                 IpAddress::Ipv4(_) => {
                     trace!("ICMP packet received (IPv4)");
 
-                    let icmp_packet = Icmpv4Packet::new_checked(&payload).unwrap();
-                    let icmp_repr = Icmpv4Repr::parse(&icmp_packet, &device_caps.checksum).unwrap();
-
-                    if let Icmpv4Repr::EchoReply { seq_no, data, .. } = icmp_repr {
-                        handle_reply(
-                            &mut waiting_queue,
-                            seq_no,
-                            data,
-                            remote_addr,
-                            timestamp,
-                            &mut received
-                        );
-                    }
+                    Icmpv4Packet::new_checked(&payload)
+                        .ok()
+                        .and_then(|icmp_packet| Icmpv4Repr::parse(&icmp_packet, &device_caps.checksum).ok())
+                        .and_then(|icmp_repr| match icmp_repr {
+                            Icmpv4Repr::EchoReply { seq_no, .. } => Some(seq_no),
+                            _ => None
+                        })
                 }
-                IpAddress::Ipv6(address) => {
+                IpAddress::Ipv6(remote_addr) => {
                     trace!("ICMP packet received (IPv6)");
 
-                    let icmp_packet = Icmpv6Packet::new_checked(&payload).unwrap();
-                    let icmp_repr = Icmpv6Repr::parse(
-                        &address,
-                        &local_device.lock().interface.get_source_address_ipv6(&address),
-                        &icmp_packet,
-                        &device_caps.checksum,
-                    )
-                        .unwrap();
-
-                    if let Icmpv6Repr::EchoReply { seq_no, data, .. } = icmp_repr {
-                        handle_reply(
-                            &mut waiting_queue,
-                            seq_no,
-                            data,
-                            remote_addr,
-                            timestamp,
-                            &mut received
-                        );
-                    }
+                    let IpAddress::Ipv6(source_addr) = source_addr else {
+                        unreachable!()
+                    };
+
+                    Icmpv6Packet::new_checked(&payload)
+                        .ok()
+                        .and_then(|icmp_packet| Icmpv6Repr::parse(&remote_addr, &source_addr, &icmp_packet, &device_caps.checksum).ok())
+                        .and_then(|icmp_repr| match icmp_repr {
+                            Icmpv6Repr::EchoReply { seq_no, .. } => Some(seq_no),
+                            _ => None
+                        })
+                }
+            };
+
+            if let Some(reply_seq_no) = reply_seq_no {
+                if address == remote_addr {
+                    handle_reply(
+                        &mut waiting_queue,
+                        reply_seq_no,
+                        payload.len() - ICMP_HEADER_LENGTH,
+                        remote_addr,
+                        Clock::now(),
+                        options.flood,
+                        &mut received,
+                        &mut round_trip_times
+                    );
                 }
             }
+
+            continue;
         }
 
         waiting_queue.retain(|seq, from| {
-            if timestamp - *from < timeout {
+            if timestamp - *from < options.timeout {
                 true
-            } else {
-                println!("From {remote_addr} icmp_seq={seq} timeout");
+            }
+            else {
+                match options.flood {
+                    true => print!("."),
+                    false => println!("From {remote_addr} icmp_seq={seq} timeout")
+                }
                 false
             }
         });
 
-        if seq_no >= count && waiting_queue.is_empty() {
+        if seq_no >= options.count && waiting_queue.is_empty() {
             break;
         }
 
-        trace!("Loop");
-        sleep(1);
+        Timer::after(POLL_INTERVAL).await;
     }
 
-    println!("--- {remote_addr} ping statistics ---");
-    println!(
-        "{} packets transmitted, {} received, {:.0}% packet loss",
-        seq_no,
+    drop(local_sockets);
+
+    if options.flood {
+        println!();
+    }
+
+    let statistics = PingStatistics {
+        transmitted: seq_no,
         received,
-        100.0 * (seq_no - received) as f64 / seq_no as f64
-    );
+        round_trip_times,
+    };
+
+    println!("--- {remote_addr} ping statistics ---");
+    // Nothing is sent with a null count
+    let packet_loss = match seq_no {
+        0 => 0.0,
+        _ => 100.0 * (seq_no - received) as f64 / seq_no as f64
+    };
+
+    println!("{} packets transmitted, {} received, {:.0}% packet loss", seq_no, received, packet_loss);
+
+    if let Some((min, average, max, mean_deviation)) = statistics.summary() {
+        println!("rtt min/avg/max/mdev = {}/{:.3}/{}/{:.3} ms", min, average, max, mean_deviation);
+    }
+
+    Ok(statistics)
+}
+
+/// Device of the interface holding the route to the given unicast address
+pub fn route_device(remote_addr: &IpAddress) -> Result<Arc<Mutex<NetworkDevice<'static>>>, CliError> {
+    if remote_addr.is_unspecified() {
+        return Err(CliError::Message(String::from("The given address is not addressable")));
+    }
+
+    if !remote_addr.is_unicast() {
+        return Err(CliError::Message(String::from("The given address is not unicast")));
+    }
+
+    let manager = NETWORK_MANAGER.lock();
 
-    Ok(())
+    let Some(iface_name) = manager.find_route_interface(remote_addr) else {
+        return Err(CliError::Message(String::from("No interface found to reach the given address")));
+    };
+
+    Ok(manager.interfaces.get(&iface_name).unwrap().clone())
 }
 
-fn handle_reply(waiting_queue: &mut BTreeMap<u16, Instant>, seq_no: u16, data: &[u8], remote_addr: IpAddress, timestamp: Instant, received: &mut u16) {
-    if waiting_queue.get(&seq_no).is_some() {
-        let packet_timestamp_ms = NetworkEndian::read_i64(data);
+/// Device to send from and source address, either given or chosen by the routes
+pub fn source_device(remote_addr: &IpAddress, source: Option<&SourceArg>) -> Result<(Arc<Mutex<NetworkDevice<'static>>>, IpAddress), CliError> {
+    let local_device = match source {
+        None => route_device(remote_addr)?,
+        Some(source) => {
+            if !remote_addr.is_unicast() {
+                return Err(CliError::Message(String::from("The given address is not unicast")));
+            }
+
+            let manager = NETWORK_MANAGER.lock();
+
+            let device = match source {
+                SourceArg::Interface(interface_name) => manager.interfaces.get(interface_name),
+                SourceArg::Address(address) => manager.interfaces
+                    .values()
+                    .find(|device| device.lock().interface.has_ip_addr(*address))
+            };
+
+            match device {
+                Some(device) => device.clone(),
+                None => return Err(CliError::Message(String::from("No interface found with the given source")))
+            }
+        }
+    };
+
+    let source_addr = match source {
+        Some(SourceArg::Address(address)) => *address,
+        _ => local_device
+            .lock()
+            .interface
+            .get_source_address(remote_addr)
+            .ok_or_else(|| CliError::Message(String::from("The interface has no address to send from")))?
+    };
+
+    if source_addr.version() != remote_addr.version() {
+        return Err(CliError::Message(String::from("The source and destination addresses are not of the same IP version")));
+    }
 
-        println!(
-            "{} bytes from {}: icmp_seq={}, time={}ms",
-            data.len(),
-            remote_addr,
-            seq_no,
-            timestamp.total_millis() - packet_timestamp_ms
-        );
+    Ok((local_device, source_addr))
+}
+
+/// Write an echo request and its IP header
+#[allow(clippy::too_many_arguments)]
+fn emit_request(packet: &mut [u8], source_addr: IpAddress, remote_addr: IpAddress, ttl: u8, ident: u16, seq_no: u16, data: &[u8], checksum: &ChecksumCapabilities) {
+    match (source_addr, remote_addr) {
+        (IpAddress::Ipv4(source_addr), IpAddress::Ipv4(remote_addr)) => {
+            let icmp_repr = Icmpv4Repr::EchoRequest { ident, seq_no, data };
+
+            let ip_repr = Ipv4Repr {
+                src_addr: source_addr,
+                dst_addr: remote_addr,
+                next_header: IpProtocol::Icmp,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: ttl,
+            };
+
+            let mut ip_packet = Ipv4Packet::new_unchecked(packet);
+            ip_repr.emit(&mut ip_packet, checksum);
+            icmp_repr.emit(&mut Icmpv4Packet::new_unchecked(ip_packet.payload_mut()), checksum);
+        },
+        (IpAddress::Ipv6(source_addr), IpAddress::Ipv6(remote_addr)) => {
+            let icmp_repr = Icmpv6Repr::EchoRequest { ident, seq_no, data };
+
+            let ip_repr = Ipv6Repr {
+                src_addr: source_addr,
+                dst_addr: remote_addr,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: ttl,
+            };
+
+            let mut ip_packet = Ipv6Packet::new_unchecked(packet);
+            ip_repr.emit(&mut ip_packet);
+            icmp_repr.emit(&source_addr, &remote_addr, &mut Icmpv6Packet::new_unchecked(ip_packet.payload_mut()), checksum);
+        },
+        _ => unreachable!()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_reply(waiting_queue: &mut BTreeMap<u16, Instant>, seq_no: u16, length: usize, remote_addr: IpAddress, timestamp: Instant, flood: bool, received: &mut u16, round_trip_times: &mut Vec<i64>) {
+    if let Some(sent_at) = waiting_queue.remove(&seq_no) {
+        let round_trip_time = (timestamp - sent_at).total_millis() as i64;
+
+        if !flood {
+            println!(
+                "{} bytes from {}: icmp_seq={}, time={}ms",
+                length,
+                remote_addr,
+                seq_no,
+                round_trip_time
+            );
+        }
 
-        waiting_queue.remove(&seq_no);
         *received += 1;
+        round_trip_times.push(round_trip_time);
     }
 }
+
+/// Newton's method, as the core library has no square root
+fn square_root(value: f64) -> f64 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+
+    let mut root = value;
+    for _ in 0..32 {
+        root = (root + value / root) / 2.0;
+    }
+
+    root
+}
@@ -0,0 +1,396 @@
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::slaac::{ipv6_network, is_link_local};
+use crate::printer::macros::Output;
+use crate::services::radvd::{AdvertisedPrefix, MAX_ROUTER_LIFETIME, RADVD};
+use crate::terminal::custom_arguments::ip_address::{IpAddressArg, IpCidrArg};
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use crate::terminal::error::CliError;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use goolog::{info, trace, warn};
+use no_std_clap_macros::{Args, Subcommand};
+use smoltcp::time::Duration;
+use smoltcp::wire::{IpAddress, IpCidr};
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "RADVD";
+
+/// Smallest MTU of an IPv6 link (RFC 8200, 5)
+const IPV6_MIN_MTU: u32 = 1280;
+
+#[derive(Subcommand)]
+pub enum RadvdCommand {
+    /// Show the router advertisement configuration of the interfaces
+    Show,
+
+    /// Send router advertisements on an interface
+    Start {
+        /// Interface to advertise on
+        interface_name: NetworkInterfaceArg,
+    },
+
+    /// Stop sending router advertisements, the hosts being told the router leaves
+    Stop {
+        /// Interface to stop advertising on
+        interface_name: NetworkInterfaceArg,
+    },
+
+    /// Interact with the advertised prefixes
+    #[command(subcommand)]
+    Prefix(RadvdPrefixCommand),
+
+    /// Set the DHCPv6 flags, the ones not given are cleared
+    Flags(RadvdFlagsCommand),
+
+    /// Interact with the advertised DNS servers
+    #[command(subcommand)]
+    Rdnss(RadvdRdnssCommand),
+
+    /// Set the advertised MTU, 0 to stop advertising it
+    Mtu {
+        /// Interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// MTU, in bytes
+        mtu: u32,
+    },
+
+    /// Set the router lifetime, 0 for a router which is not a default router
+    Lifetime {
+        /// Interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// Lifetime, in seconds
+        seconds: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RadvdPrefixCommand {
+    /// Advertise a prefix, the hosts building their addresses from the /64 ones
+    Add(RadvdPrefixAddCommand),
+
+    /// Stop advertising a prefix
+    Delete {
+        /// Interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// Prefix to remove
+        prefix: IpCidrArg,
+    },
+}
+
+#[derive(Args)]
+pub struct RadvdPrefixAddCommand {
+    /// Interface to change
+    pub interface_name: NetworkInterfaceArg,
+
+    /// IPv6 prefix to advertise
+    pub prefix: IpCidrArg,
+
+    /// Valid lifetime, in seconds. Defaults to: 2592000 (30 days)
+    #[arg(short = 'V', long, default_value = "2592000")]
+    pub valid: u64,
+
+    /// Preferred lifetime, in seconds. Defaults to: 604800 (7 days)
+    #[arg(short = 'P', long, default_value = "604800")]
+    pub preferred: u64,
+}
+
+#[derive(Args)]
+pub struct RadvdFlagsCommand {
+    /// Interface to change
+    pub interface_name: NetworkInterfaceArg,
+
+    /// Managed flag, the addresses are available with DHCPv6
+    #[arg(short, long)]
+    pub managed: bool,
+
+    /// Other flag, other configuration is available with DHCPv6
+    #[arg(short, long)]
+    pub other: bool,
+}
+
+#[derive(Subcommand)]
+pub enum RadvdRdnssCommand {
+    /// Advertise a DNS server
+    Add {
+        /// Interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// IPv6 address of the DNS server
+        address: IpAddressArg,
+    },
+
+    /// Stop advertising a DNS server
+    Delete {
+        /// Interface to change
+        interface_name: NetworkInterfaceArg,
+
+        /// IPv6 address of the DNS server
+        address: IpAddressArg,
+    },
+}
+
+pub fn radvd_show() -> Result<(), CliError> {
+    trace!("RADVD SHOW");
+
+    let radvd = RADVD.lock();
+
+    let mut table = vec![
+        [String::from("Interface"), String::from("State"), String::from("Prefixes"), String::from("Flags"), String::from("RDNSS"), String::from("MTU"), String::from("Lifetime"), String::from("Sent"), String::from("Solicited")]
+    ];
+
+    for (name, advertised) in radvd.interfaces.iter() {
+        let prefixes: Vec<_> = advertised.prefixes
+            .iter()
+            .map(|prefix| format!("{} ({}s/{}s)", prefix.cidr, prefix.valid_lifetime.secs(), prefix.preferred_lifetime.secs()))
+            .collect();
+
+        let mut flags = String::new();
+
+        if advertised.managed {
+            flags.push('M');
+        }
+
+        if advertised.other {
+            flags.push('O');
+        }
+
+        let rdnss: Vec<_> = advertised.rdnss.iter().map(|address| address.to_string()).collect();
+
+        table.push([
+            name.clone(),
+            String::from(if advertised.enabled { "advertising" } else { "stopped" }),
+            prefixes.join(", "),
+            flags,
+            rdnss.join(", "),
+            advertised.mtu.map(|mtu| mtu.to_string()).unwrap_or_default(),
+            format!("{}s", advertised.router_lifetime.secs()),
+            advertised.advertisements_sent.to_string(),
+            advertised.solicitations_received.to_string(),
+        ]);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
+
+pub fn radvd_start(interface_name: &str) -> Result<(), CliError> {
+    trace!("RADVD START");
+
+    check_interface(interface_name)?;
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    if advertised.enabled {
+        return Err(CliError::Message(format!("Already advertising on {}", interface_name)));
+    }
+
+    if advertised.prefixes.is_empty() {
+        warn!("No prefix advertised on {}, the hosts will only learn the default route", interface_name);
+    }
+
+    info!("Advertising on {}", interface_name);
+    advertised.start();
+
+    Ok(())
+}
+
+pub fn radvd_stop(interface_name: &str) -> Result<(), CliError> {
+    trace!("RADVD STOP");
+
+    let mut radvd = RADVD.lock();
+
+    match radvd.interfaces.get_mut(interface_name) {
+        Some(advertised) if advertised.enabled => {
+            info!("Stopping advertisements on {}", interface_name);
+            advertised.stop();
+            Ok(())
+        },
+        _ => Err(CliError::Message(format!("Not advertising on {}", interface_name)))
+    }
+}
+
+pub fn radvd_prefix_add(interface_name: &str, prefix: IpCidr, valid: u64, preferred: u64) -> Result<(), CliError> {
+    trace!("RADVD PREFIX ADD");
+
+    check_interface(interface_name)?;
+
+    let IpCidr::Ipv6(cidr) = prefix else {
+        return Err(CliError::Message(format!("{} is not an IPv6 prefix", prefix)));
+    };
+
+    if is_link_local(cidr.address()) {
+        return Err(CliError::Message(String::from("The link-local prefix cannot be advertised")));
+    }
+
+    if preferred > valid {
+        return Err(CliError::Message(String::from("The preferred lifetime cannot exceed the valid lifetime")));
+    }
+
+    // Lifetimes are 32 bits, the largest value meaning infinity
+    if valid > u32::MAX as u64 {
+        return Err(CliError::Message(format!("Lifetimes cannot exceed {} seconds", u32::MAX)));
+    }
+
+    // The host bits are cleared
+    let cidr = ipv6_network(cidr);
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    if advertised.prefixes.iter().any(|prefix| prefix.cidr == cidr) {
+        return Err(CliError::Message(format!("Prefix {} already advertised on {}", cidr, interface_name)));
+    }
+
+    info!("Advertising {} on {}", cidr, interface_name);
+
+    advertised.prefixes.push(AdvertisedPrefix {
+        cidr,
+        valid_lifetime: Duration::from_secs(valid),
+        preferred_lifetime: Duration::from_secs(preferred),
+    });
+
+    advertised.advertise_now();
+
+    Ok(())
+}
+
+pub fn radvd_prefix_delete(interface_name: &str, prefix: IpCidr) -> Result<(), CliError> {
+    trace!("RADVD PREFIX DELETE");
+
+    let IpCidr::Ipv6(cidr) = prefix else {
+        return Err(CliError::Message(format!("{} is not an IPv6 prefix", prefix)));
+    };
+
+    let cidr = ipv6_network(cidr);
+
+    let mut radvd = RADVD.lock();
+
+    let Some(advertised) = radvd.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("Prefix {} not advertised on {}", cidr, interface_name)));
+    };
+
+    let Some(index) = advertised.prefixes.iter().position(|prefix| prefix.cidr == cidr) else {
+        return Err(CliError::Message(format!("Prefix {} not advertised on {}", cidr, interface_name)));
+    };
+
+    info!("Stopping advertising {} on {}", cidr, interface_name);
+    advertised.prefixes.remove(index);
+
+    Ok(())
+}
+
+pub fn radvd_flags(interface_name: &str, managed: bool, other: bool) -> Result<(), CliError> {
+    trace!("RADVD FLAGS");
+
+    check_interface(interface_name)?;
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    advertised.managed = managed;
+    advertised.other = other;
+    advertised.advertise_now();
+
+    Ok(())
+}
+
+pub fn radvd_rdnss_add(interface_name: &str, address: IpAddress) -> Result<(), CliError> {
+    trace!("RADVD RDNSS ADD");
+
+    check_interface(interface_name)?;
+
+    let IpAddress::Ipv6(address) = address else {
+        return Err(CliError::Message(format!("{} is not an IPv6 address", address)));
+    };
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    if advertised.rdnss.contains(&address) {
+        return Err(CliError::Message(format!("DNS server {} already advertised on {}", address, interface_name)));
+    }
+
+    info!("Advertising DNS server {} on {}", address, interface_name);
+    advertised.rdnss.push(address);
+    advertised.advertise_now();
+
+    Ok(())
+}
+
+pub fn radvd_rdnss_delete(interface_name: &str, address: IpAddress) -> Result<(), CliError> {
+    trace!("RADVD RDNSS DELETE");
+
+    let mut radvd = RADVD.lock();
+
+    let Some(advertised) = radvd.interfaces.get_mut(interface_name) else {
+        return Err(CliError::Message(format!("DNS server {} not advertised on {}", address, interface_name)));
+    };
+
+    let server_count = advertised.rdnss.len();
+    advertised.rdnss.retain(|server| IpAddress::Ipv6(*server) != address);
+
+    if advertised.rdnss.len() == server_count {
+        return Err(CliError::Message(format!("DNS server {} not advertised on {}", address, interface_name)));
+    }
+
+    info!("Stopping advertising DNS server {} on {}", address, interface_name);
+
+    Ok(())
+}
+
+pub fn radvd_mtu(interface_name: &str, mtu: u32) -> Result<(), CliError> {
+    trace!("RADVD MTU");
+
+    check_interface(interface_name)?;
+
+    if mtu != 0 && mtu < IPV6_MIN_MTU {
+        return Err(CliError::Message(format!("IPv6 links have an MTU of at least {} bytes", IPV6_MIN_MTU)));
+    }
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    advertised.mtu = (mtu != 0).then_some(mtu);
+    advertised.advertise_now();
+
+    Ok(())
+}
+
+pub fn radvd_lifetime(interface_name: &str, seconds: u64) -> Result<(), CliError> {
+    trace!("RADVD LIFETIME");
+
+    check_interface(interface_name)?;
+
+    let lifetime = Duration::from_secs(seconds);
+
+    if lifetime > MAX_ROUTER_LIFETIME {
+        return Err(CliError::Message(format!("The router lifetime cannot exceed {} seconds", MAX_ROUTER_LIFETIME.secs())));
+    }
+
+    let mut radvd = RADVD.lock();
+    let advertised = radvd.interface(interface_name);
+
+    advertised.router_lifetime = lifetime;
+    advertised.advertise_now();
+
+    Ok(())
+}
+
+/// Only the physical interfaces have hosts to advertise to
+fn check_interface(interface_name: &str) -> Result<(), CliError> {
+    let Some(device) = NETWORK_MANAGER.lock().interfaces.get(interface_name).cloned() else {
+        return Err(CliError::Message(format!("Interface \"{}\" not found", interface_name)));
+    };
+
+    if interrupts::without_interrupts(|| device.lock().mac().is_none()) {
+        return Err(CliError::Message(format!("{} has no link layer", interface_name)));
+    }
+
+    Ok(())
+}
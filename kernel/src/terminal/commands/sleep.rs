@@ -1,13 +1,14 @@
-use crate::clock::{sleep};
+use crate::clock::Timer;
 use crate::terminal::error::CliError;
 use goolog::{trace};
+use smoltcp::time::Duration;
 
 const GOOLOG_TARGET: &str = "SLEEP";
 
-pub fn cli_sleep(seconds: u64) -> Result<(), CliError> {
+pub async fn cli_sleep(seconds: u64) -> Result<(), CliError> {
     trace!("SLEEP");
     
-    sleep(seconds);
+    Timer::after(Duration::from_secs(seconds)).await;
     
     Ok(())
 }
\ No newline at end of file
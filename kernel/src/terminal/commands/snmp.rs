@@ -0,0 +1,78 @@
+use crate::println;
+use crate::services::snmp::agent::{SNMP_AGENT, SNMP_PORT};
+use crate::terminal::error::CliError;
+use alloc::format;
+use alloc::string::String;
+use goolog::{info, trace};
+use no_std_clap_macros::Subcommand;
+
+const GOOLOG_TARGET: &str = "SNMP";
+
+#[derive(Subcommand)]
+pub enum SnmpCommand {
+    /// Show the SNMP agent configuration and counters
+    Show,
+
+    /// Interact with the community strings
+    #[command(subcommand)]
+    Community(SnmpCommunityCommand),
+}
+
+#[derive(Subcommand)]
+pub enum SnmpCommunityCommand {
+    /// Allow a read-only community string
+    Add {
+        /// Community string
+        community: String,
+    },
+
+    /// Remove a community string
+    Delete {
+        /// Community string
+        community: String,
+    },
+}
+
+pub fn snmp_show() -> Result<(), CliError> {
+    trace!("SNMP SHOW");
+
+    let agent = SNMP_AGENT.lock();
+
+    println!("SNMPv2c agent listening on UDP port {}", SNMP_PORT);
+    println!("Communities: {}", agent.communities.join(", "));
+    println!("Requests: {}, bad communities: {}, parse errors: {}", agent.requests, agent.bad_communities, agent.parse_errors);
+
+    Ok(())
+}
+
+pub fn snmp_community_add(community: String) -> Result<(), CliError> {
+    trace!("SNMP COMMUNITY ADD");
+
+    let mut agent = SNMP_AGENT.lock();
+
+    if agent.communities.contains(&community) {
+        return Err(CliError::Message(format!("Community \"{}\" already exists", community)));
+    }
+
+    info!("Adding community");
+    agent.communities.push(community);
+
+    Ok(())
+}
+
+pub fn snmp_community_delete(community: String) -> Result<(), CliError> {
+    trace!("SNMP COMMUNITY DELETE");
+
+    let mut agent = SNMP_AGENT.lock();
+    let community_count = agent.communities.len();
+
+    agent.communities.retain(|allowed| allowed != &community);
+
+    if agent.communities.len() == community_count {
+        return Err(CliError::Message(format!("Community \"{}\" not found", community)));
+    }
+
+    info!("Deleting community");
+
+    Ok(())
+}
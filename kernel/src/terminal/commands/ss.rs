@@ -0,0 +1,150 @@
+use crate::devices::network::manager::NETWORK_MANAGER;
+use crate::devices::network::sockets::socket_owner;
+use crate::printer::macros::Output;
+use crate::task::executor::TASKS;
+use crate::terminal::error::CliError;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use goolog::trace;
+use no_std_clap_macros::Args;
+use smoltcp::socket::tcp::State as TcpState;
+use smoltcp::socket::Socket;
+use x86_64::instructions::interrupts;
+
+const GOOLOG_TARGET: &str = "SS";
+
+#[derive(Args)]
+pub struct SsCommand {
+    /// Show the TCP sockets
+    #[arg(short, long)]
+    pub tcp: bool,
+
+    /// Show the UDP sockets
+    #[arg(short, long)]
+    pub udp: bool,
+
+    /// Show only the listening sockets, TCP listeners and bound UDP sockets
+    #[arg(short, long)]
+    pub listening: bool,
+}
+
+pub fn ss(tcp: bool, udp: bool, listening: bool) -> Result<(), CliError> {
+    trace!("SS");
+
+    // Every socket type unless some are asked for
+    let all_types = !tcp && !udp;
+
+    let task_names: BTreeMap<_, _> = TASKS
+        .read()
+        .iter()
+        .map(|(task_id, task)| (*task_id, task.name.clone()))
+        .collect();
+
+    let socket_sets = NETWORK_MANAGER.lock().socket_sets();
+
+    let mut table = vec![
+        [String::from("Netid"), String::from("State"), String::from("Recv-Q"), String::from("Send-Q"), String::from("Local"), String::from("Peer"), String::from("Interface"), String::from("Task")]
+    ];
+
+    for (interface_name, socket_set) in socket_sets {
+        let rows: Vec<_> = interrupts::without_interrupts(|| {
+            let sockets = socket_set.lock();
+            let mut rows = Vec::new();
+
+            for (handle, socket) in sockets.iter() {
+                let row = match socket {
+                    Socket::Tcp(socket) if tcp || all_types => {
+                        let is_listening = socket.state() == TcpState::Listen;
+
+                        if listening && !is_listening {
+                            continue;
+                        }
+
+                        let local = match is_listening {
+                            true => socket.listen_endpoint().to_string(),
+                            false => socket.local_endpoint().map(|endpoint| endpoint.to_string()).unwrap_or_else(|| String::from("*"))
+                        };
+
+                        [
+                            String::from("tcp"),
+                            socket.state().to_string(),
+                            socket.recv_queue().to_string(),
+                            socket.send_queue().to_string(),
+                            local,
+                            socket.remote_endpoint().map(|endpoint| endpoint.to_string()).unwrap_or_else(|| String::from("*:*")),
+                        ]
+                    },
+                    // Not connected to a peer, the bound sockets are listening
+                    Socket::Udp(socket) if udp || all_types => {
+                        if listening && !socket.is_open() {
+                            continue;
+                        }
+
+                        [
+                            String::from("udp"),
+                            String::from(if socket.is_open() { "UNCONN" } else { "CLOSED" }),
+                            socket.recv_queue().to_string(),
+                            socket.send_queue().to_string(),
+                            socket.endpoint().to_string(),
+                            String::from("*:*"),
+                        ]
+                    },
+                    Socket::Icmp(socket) if all_types && !listening => [
+                        String::from("icmp"),
+                        String::from(if socket.is_open() { "UNCONN" } else { "CLOSED" }),
+                        socket.recv_queue().to_string(),
+                        socket.send_queue().to_string(),
+                        String::from("*"),
+                        String::from("*"),
+                    ],
+                    Socket::Raw(socket) if all_types && !listening => [
+                        String::from("raw"),
+                        String::from("UNCONN"),
+                        socket.recv_queue().to_string(),
+                        socket.send_queue().to_string(),
+                        format!("{}:{}", socket.ip_version(), socket.ip_protocol()),
+                        String::from("*"),
+                    ],
+                    Socket::Dhcpv4(_) if all_types && !listening => [
+                        String::from("dhcp"),
+                        String::from("UNCONN"),
+                        String::from("0"),
+                        String::from("0"),
+                        String::from("*:68"),
+                        String::from("*:67"),
+                    ],
+                    Socket::Dns(_) if all_types && !listening => [
+                        String::from("dns"),
+                        String::from("UNCONN"),
+                        String::from("0"),
+                        String::from("0"),
+                        String::from("*"),
+                        String::from("*:53"),
+                    ],
+                    _ => continue
+                };
+
+                let task = match socket_owner(&socket_set, handle) {
+                    Some(task_id) => match task_names.get(&task_id) {
+                        Some(name) => format!("{}: {}", task_id.0, name),
+                        None => task_id.0.to_string()
+                    },
+                    None => String::from("kernel")
+                };
+
+                let [netid, state, recv_queue, send_queue, local, peer] = row;
+                rows.push([netid, state, recv_queue, send_queue, local, peer, interface_name.clone(), task]);
+            }
+
+            rows
+        });
+
+        table.extend(rows);
+    }
+
+    text_tables::render(&mut Output, table).unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use crate::clock::Clock;
+use crate::println;
+use crate::services::ssh::server::SSH_SERVER;
+use crate::terminal::error::CliError;
+use alloc::format;
+use alloc::string::String;
+use goolog::{info, trace, warn};
+use no_std_clap_macros::{Args, Subcommand};
+
+const GOOLOG_TARGET: &str = "SSH";
+
+#[derive(Subcommand)]
+pub enum SshCommand {
+    /// Show the SSH server state, its users and its sessions
+    Show,
+
+    /// Start the SSH server
+    Start(SshStartCommand),
+
+    /// Stop the SSH server and close its sessions
+    Stop,
+
+    /// Manage the users allowed to log in
+    #[command(subcommand)]
+    User(SshUserCommand),
+}
+
+#[derive(Args)]
+pub struct SshStartCommand {
+    /// TCP port to listen on. Defaults to: 22
+    #[arg(default_value = "22")]
+    pub port: u16,
+}
+
+#[derive(Subcommand)]
+pub enum SshUserCommand {
+    /// Add a user, or change its password
+    Add {
+        /// User name
+        name: String,
+        /// Password of the user
+        password: String,
+    },
+
+    /// Authorize a public key for a user
+    Key {
+        /// User name
+        name: String,
+        /// Base64 part of an "ssh-ed25519" public key
+        key: String,
+    },
+
+    /// Delete a user
+    Delete {
+        /// User name
+        name: String,
+    },
+}
+
+pub fn ssh_show() -> Result<(), CliError> {
+    trace!("SSH SHOW");
+
+    let server = SSH_SERVER.lock();
+
+    match server.port {
+        None => println!("SSH server: disabled"),
+        Some(port) => println!("SSH server: listening on port {} ({} sessions accepted)", port, server.accepted_sessions)
+    }
+
+    if let Some(fingerprint) = server.host_key_fingerprint() {
+        println!("Host key: ssh-ed25519 {}", fingerprint);
+    }
+
+    println!("Users:");
+    for (name, user) in server.users.iter() {
+        println!("\t{} (password: {}, keys: {})", name, if user.has_password() { "yes" } else { "no" }, user.keys.len());
+    }
+
+    println!("Sessions:");
+    for (id, session) in server.sessions.iter() {
+        match session.remote_endpoint {
+            Some(remote_endpoint) => println!("\t{}: {} on {}, since {}", id, remote_endpoint, session.interface_name, Clock::format_instant(session.started_at)),
+            None => println!("\t{}: on {}, since {}", id, session.interface_name, Clock::format_instant(session.started_at))
+        }
+    }
+
+    Ok(())
+}
+
+pub fn ssh_start(port: u16) -> Result<(), CliError> {
+    trace!("SSH START");
+
+    let mut server = SSH_SERVER.lock();
+
+    if server.users.is_empty() {
+        warn!("No user is configured, add one with \"ssh user add\" to log in");
+    }
+
+    info!("Starting SSH server on port {}", port);
+
+    server
+        .set_port(Some(port))
+        .map_err(|error| CliError::Message(String::from(error)))
+}
+
+pub fn ssh_stop() -> Result<(), CliError> {
+    trace!("SSH STOP");
+
+    info!("Stopping SSH server");
+    SSH_SERVER
+        .lock()
+        .set_port(None)
+        .map_err(|error| CliError::Message(String::from(error)))
+}
+
+pub fn ssh_user_add(name: String, password: String) -> Result<(), CliError> {
+    trace!("SSH USER ADD");
+
+    if password.is_empty() {
+        return Err(CliError::Message(String::from("The password cannot be empty")));
+    }
+
+    SSH_SERVER.lock().set_password(name, &password);
+
+    Ok(())
+}
+
+pub fn ssh_user_key(name: String, key: String) -> Result<(), CliError> {
+    trace!("SSH USER KEY");
+
+    SSH_SERVER
+        .lock()
+        .add_key(name, &key)
+        .map_err(|error| CliError::Message(String::from(error)))
+}
+
+pub fn ssh_user_delete(name: String) -> Result<(), CliError> {
+    trace!("SSH USER DELETE");
+
+    match SSH_SERVER.lock().users.remove(&name) {
+        Some(_) => Ok(()),
+        None => Err(CliError::Message(format!("User \"{}\" does not exist", name)))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+use crate::clock::Clock;
+use crate::println;
+use crate::services::telnet::TELNET_SERVER;
+use crate::terminal::error::CliError;
+use goolog::{info, trace};
+use no_std_clap_macros::{Args, Subcommand};
+
+const GOOLOG_TARGET: &str = "TELNET";
+
+#[derive(Subcommand)]
+pub enum TelnetCommand {
+    /// Show the telnet server state and its sessions
+    Show,
+
+    /// Start the telnet server. Sessions are not authenticated
+    Start(TelnetStartCommand),
+
+    /// Stop the telnet server and close its sessions
+    Stop,
+}
+
+#[derive(Args)]
+pub struct TelnetStartCommand {
+    /// TCP port to listen on. Defaults to: 23
+    #[arg(default_value = "23")]
+    pub port: u16,
+}
+
+pub fn telnet_show() -> Result<(), CliError> {
+    trace!("TELNET SHOW");
+
+    let server = TELNET_SERVER.lock();
+
+    match server.port {
+        None => println!("Telnet server: disabled"),
+        Some(port) => println!("Telnet server: listening on port {} ({} sessions accepted)", port, server.accepted_sessions)
+    }
+
+    for (id, session) in server.sessions.iter() {
+        match session.remote_endpoint {
+            Some(remote_endpoint) => println!("{}: {} on {}, since {}", id, remote_endpoint, session.interface_name, Clock::format_instant(session.started_at)),
+            None => println!("{}: on {}, since {}", id, session.interface_name, Clock::format_instant(session.started_at))
+        }
+    }
+
+    Ok(())
+}
+
+pub fn telnet_start(port: u16) -> Result<(), CliError> {
+    trace!("TELNET START");
+
+    info!("Starting telnet server on port {}", port);
+    TELNET_SERVER.lock().set_port(Some(port));
+
+    Ok(())
+}
+
+pub fn telnet_stop() -> Result<(), CliError> {
+    trace!("TELNET STOP");
+
+    info!("Stopping telnet server");
+    TELNET_SERVER.lock().set_port(None);
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,375 @@
+use crate::clock::{Clock, Timer};
+use crate::devices::network::device::NetworkDevice;
+use crate::devices::network::sockets::OwnedSockets;
+use crate::terminal::commands::ping::route_device;
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::error::CliError;
+use crate::{print, println};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use byteorder::{ByteOrder, NetworkEndian};
+use goolog::trace;
+use no_std_clap_macros::{Args, EnumValuesArg};
+use smoltcp::iface::SocketHandle;
+use smoltcp::phy::{Device, DeviceCapabilities};
+use smoltcp::socket::{icmp, raw, udp};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{Icmpv4DstUnreachable, Icmpv4Message, Icmpv4Packet, Icmpv4Repr, Icmpv6DstUnreachable, Icmpv6Message, Icmpv6Packet, Icmpv6Repr, IpAddress, IpEndpoint, IpProtocol, IpVersion, Ipv4Packet, Ipv6Packet};
+use spin::Mutex;
+use strum::{EnumString, VariantNames};
+
+const GOOLOG_TARGET: &str = "TRACEROUTE";
+
+/// Destination port of the first UDP probe, incremented with every probe
+const BASE_PORT: u16 = 33434;
+const PROBE_PAYLOAD_SIZE: usize = 32;
+
+const IPV4_HEADER_MIN_LENGTH: usize = 20;
+const IPV6_HEADER_LENGTH: usize = 40;
+/// Bytes of the probe transport header quoted in the ICMP errors
+const QUOTED_TRANSPORT_LENGTH: usize = 8;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Default, EnumValuesArg, VariantNames, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TracerouteProtocol {
+    #[default]
+    Udp,
+    Icmp,
+}
+
+#[derive(Args)]
+pub struct TracerouteCommand {
+    /// IP address to trace the route to
+    pub ip_address: IpAddressArg,
+
+    /// Probe protocol. Defaults to: udp
+    #[arg(default_value = "udp")]
+    pub protocol: TracerouteProtocol,
+
+    /// Maximum number of hops. Defaults to: 30
+    #[arg(default_value = "30")]
+    pub max_hops: u8,
+
+    /// Probes sent per hop. Defaults to: 3
+    #[arg(default_value = "3")]
+    pub probes: u8,
+
+    /// Timeout of each probe, in seconds. Defaults to: 2
+    #[arg(default_value = "2")]
+    pub timeout: u64,
+}
+
+/// ICMP message answering a probe
+enum ProbeReply {
+    /// Router whose hop limit expired
+    Hop(IpAddress),
+    /// The destination answered, the trace is complete
+    Reached(IpAddress),
+    /// The destination was reported unreachable, along with the traceroute style flag
+    Unreachable(IpAddress, String),
+}
+
+impl ProbeReply {
+    fn address(&self) -> IpAddress {
+        match self {
+            ProbeReply::Hop(address) | ProbeReply::Reached(address) | ProbeReply::Unreachable(address, _) => *address
+        }
+    }
+}
+
+pub async fn traceroute(remote_addr: IpAddress, protocol: TracerouteProtocol, max_hops: u8, probes: u8, timeout: u64) -> Result<(), CliError> {
+    trace!("TRACEROUTE");
+
+    if max_hops == 0 || probes == 0 {
+        return Err(CliError::Message(String::from("The maximum number of hops and the probe count must be positive")));
+    }
+
+    let local_device = route_device(&remote_addr)?;
+
+    let device_caps = local_device.lock().network_controller.capabilities();
+    let mut local_sockets = OwnedSockets::new(local_device.lock().sockets.clone());
+
+    // Identifies the probes, as the ICMP echo identifier or the UDP source port
+    let ident = 0x8000 | Clock::now().total_millis() as u16;
+
+    let (ip_version, icmp_protocol) = match remote_addr {
+        IpAddress::Ipv4(_) => (IpVersion::Ipv4, IpProtocol::Icmp),
+        IpAddress::Ipv6(_) => (IpVersion::Ipv6, IpProtocol::Icmpv6)
+    };
+
+    // Every ICMP message is received, the ICMP sockets only get the echo replies
+    let raw_rx_buffer = raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 8], vec![0; 4096]);
+    let raw_tx_buffer = raw::PacketBuffer::new(vec![], vec![]);
+    let raw_socket = raw::Socket::new(ip_version, icmp_protocol, raw_rx_buffer, raw_tx_buffer);
+
+    let raw_handle = local_sockets.add(raw_socket);
+
+    let probe_handle = match protocol {
+        TracerouteProtocol::Udp => {
+            let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 256]);
+            let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 256]);
+            let mut udp_socket = udp::Socket::new(udp_rx_buffer, udp_tx_buffer);
+            udp_socket.bind(ident).unwrap();
+            local_sockets.add(udp_socket)
+        },
+        TracerouteProtocol::Icmp => {
+            let icmp_rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
+            let icmp_tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 256]);
+            let mut icmp_socket = icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer);
+            icmp_socket.bind(icmp::Endpoint::Ident(ident)).unwrap();
+            local_sockets.add(icmp_socket)
+        }
+    };
+
+    let timeout = Duration::from_secs(timeout);
+    let mut sequence: u16 = 0;
+
+    println!("traceroute to {}, {} hops max, {} byte packets", remote_addr, max_hops, PROBE_PAYLOAD_SIZE);
+
+    for hop_limit in 1..=max_hops {
+        print!("{:>2} ", hop_limit);
+
+        let mut last_address = None;
+        let mut is_done = false;
+
+        for _ in 0..probes {
+            let sent_at = Clock::now();
+
+            let reply = match send_probe(&local_device, &local_sockets, probe_handle, &protocol, &device_caps, remote_addr, ident, sequence, hop_limit) {
+                true => wait_reply(&local_sockets, raw_handle, &protocol, remote_addr, ident, sequence, sent_at + timeout).await,
+                false => None
+            };
+
+            sequence = sequence.wrapping_add(1);
+
+            let Some((reply, received_at)) = reply else {
+                print!(" *");
+                continue;
+            };
+
+            let address = reply.address();
+
+            if last_address != Some(address) {
+                print!(" {}", address);
+                last_address = Some(address);
+            }
+
+            print!("  {} ms", (received_at - sent_at).total_millis());
+
+            match reply {
+                ProbeReply::Hop(_) => {},
+                ProbeReply::Reached(_) => is_done = true,
+                ProbeReply::Unreachable(_, flag) => {
+                    print!(" {}", flag);
+                    is_done = true;
+                }
+            }
+        }
+
+        println!();
+
+        if is_done {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queue a probe with the given hop limit, returns whether it could be sent
+#[allow(clippy::too_many_arguments)]
+fn send_probe(local_device: &Arc<Mutex<NetworkDevice>>, local_sockets: &OwnedSockets, probe_handle: SocketHandle, protocol: &TracerouteProtocol, device_caps: &DeviceCapabilities, remote_addr: IpAddress, ident: u16, sequence: u16, hop_limit: u8) -> bool {
+    let payload = [0u8; PROBE_PAYLOAD_SIZE];
+
+    // The hop limit is read when the packet leaves, the socket only holds a single probe
+    match protocol {
+        TracerouteProtocol::Udp => local_sockets.with(probe_handle, |socket: &mut udp::Socket| {
+            if socket.send_queue() > 0 {
+                return false;
+            }
+
+            socket.set_hop_limit(Some(hop_limit));
+
+            let endpoint = IpEndpoint::new(remote_addr, BASE_PORT.wrapping_add(sequence));
+            socket.send_slice(&payload, endpoint).is_ok()
+        }),
+        TracerouteProtocol::Icmp => {
+            let source_address = match remote_addr {
+                IpAddress::Ipv4(_) => None,
+                IpAddress::Ipv6(address) => Some(local_device.lock().interface.get_source_address_ipv6(&address))
+            };
+
+            local_sockets.with(probe_handle, |socket: &mut icmp::Socket| {
+                if socket.send_queue() > 0 {
+                    return false;
+                }
+
+                socket.set_hop_limit(Some(hop_limit));
+
+                match (remote_addr, source_address) {
+                    (IpAddress::Ipv6(address), Some(source_address)) => {
+                        let icmp_repr = Icmpv6Repr::EchoRequest {
+                            ident,
+                            seq_no: sequence,
+                            data: &payload,
+                        };
+
+                        let Ok(icmp_payload) = socket.send(icmp_repr.buffer_len(), remote_addr) else {
+                            return false;
+                        };
+
+                        icmp_repr.emit(&source_address, &address, &mut Icmpv6Packet::new_unchecked(icmp_payload), &device_caps.checksum);
+                    },
+                    _ => {
+                        let icmp_repr = Icmpv4Repr::EchoRequest {
+                            ident,
+                            seq_no: sequence,
+                            data: &payload,
+                        };
+
+                        let Ok(icmp_payload) = socket.send(icmp_repr.buffer_len(), remote_addr) else {
+                            return false;
+                        };
+
+                        icmp_repr.emit(&mut Icmpv4Packet::new_unchecked(icmp_payload), &device_caps.checksum);
+                    }
+                }
+
+                true
+            })
+        }
+    }
+}
+
+/// Wait for the ICMP message answering the given probe, until the deadline
+async fn wait_reply(local_sockets: &OwnedSockets, raw_handle: SocketHandle, protocol: &TracerouteProtocol, remote_addr: IpAddress, ident: u16, sequence: u16, deadline: Instant) -> Option<(ProbeReply, Instant)> {
+    while Clock::now() < deadline {
+        let packet = local_sockets.with(raw_handle, |socket: &mut raw::Socket| {
+            match socket.can_recv() {
+                true => socket.recv().ok().map(<[u8]>::to_vec),
+                false => None
+            }
+        });
+
+        let Some(packet) = packet else {
+            Timer::after(POLL_INTERVAL).await;
+            continue;
+        };
+
+        let reply = match remote_addr {
+            IpAddress::Ipv4(_) => parse_reply_v4(&packet, protocol, remote_addr, ident, sequence),
+            IpAddress::Ipv6(_) => parse_reply_v6(&packet, protocol, remote_addr, ident, sequence)
+        };
+
+        if let Some(reply) = reply {
+            return Some((reply, Clock::now()));
+        }
+    }
+
+    None
+}
+
+fn parse_reply_v4(packet: &[u8], protocol: &TracerouteProtocol, remote_addr: IpAddress, ident: u16, sequence: u16) -> Option<ProbeReply> {
+    let ip_packet = Ipv4Packet::new_checked(packet).ok()?;
+    let source = IpAddress::Ipv4(ip_packet.src_addr());
+    let icmp_packet = Icmpv4Packet::new_checked(ip_packet.payload()).ok()?;
+
+    if icmp_packet.msg_type() == Icmpv4Message::EchoReply {
+        let is_probe_reply = matches!(protocol, TracerouteProtocol::Icmp)
+            && icmp_packet.echo_ident() == ident
+            && icmp_packet.echo_seq_no() == sequence;
+
+        return is_probe_reply.then_some(ProbeReply::Reached(source));
+    }
+
+    // The errors quote the probe IP header, and the start of its payload
+    let quoted = icmp_packet.data();
+    if quoted.len() < IPV4_HEADER_MIN_LENGTH {
+        return None;
+    }
+
+    let quoted_packet = Ipv4Packet::new_unchecked(quoted);
+    let header_length = quoted_packet.header_len() as usize;
+
+    if quoted.len() < header_length + QUOTED_TRANSPORT_LENGTH
+        || IpAddress::Ipv4(quoted_packet.dst_addr()) != remote_addr
+        || !is_quoted_probe(quoted_packet.next_header(), &quoted[header_length..], protocol, ident, sequence) {
+        return None;
+    }
+
+    match icmp_packet.msg_type() {
+        Icmpv4Message::TimeExceeded => Some(ProbeReply::Hop(source)),
+        Icmpv4Message::DstUnreachable => Some(match Icmpv4DstUnreachable::from(icmp_packet.msg_code()) {
+            Icmpv4DstUnreachable::PortUnreachable => ProbeReply::Reached(source),
+            Icmpv4DstUnreachable::NetUnreachable => ProbeReply::Unreachable(source, String::from("!N")),
+            Icmpv4DstUnreachable::HostUnreachable => ProbeReply::Unreachable(source, String::from("!H")),
+            Icmpv4DstUnreachable::ProtoUnreachable => ProbeReply::Unreachable(source, String::from("!P")),
+            Icmpv4DstUnreachable::FragRequired => ProbeReply::Unreachable(source, String::from("!F")),
+            Icmpv4DstUnreachable::NetProhibited | Icmpv4DstUnreachable::HostProhibited | Icmpv4DstUnreachable::CommProhibited => ProbeReply::Unreachable(source, String::from("!X")),
+            code => ProbeReply::Unreachable(source, format!("!{}", u8::from(code)))
+        }),
+        _ => None
+    }
+}
+
+fn parse_reply_v6(packet: &[u8], protocol: &TracerouteProtocol, remote_addr: IpAddress, ident: u16, sequence: u16) -> Option<ProbeReply> {
+    let ip_packet = Ipv6Packet::new_checked(packet).ok()?;
+    let source = IpAddress::Ipv6(ip_packet.src_addr());
+    let icmp_packet = Icmpv6Packet::new_checked(ip_packet.payload()).ok()?;
+
+    if icmp_packet.msg_type() == Icmpv6Message::EchoReply {
+        let is_probe_reply = matches!(protocol, TracerouteProtocol::Icmp)
+            && icmp_packet.echo_ident() == ident
+            && icmp_packet.echo_seq_no() == sequence;
+
+        return is_probe_reply.then_some(ProbeReply::Reached(source));
+    }
+
+    if !matches!(icmp_packet.msg_type(), Icmpv6Message::TimeExceeded | Icmpv6Message::DstUnreachable) {
+        return None;
+    }
+
+    // The errors quote as much of the probe as they can
+    let quoted = icmp_packet.payload();
+    if quoted.len() < IPV6_HEADER_LENGTH + QUOTED_TRANSPORT_LENGTH {
+        return None;
+    }
+
+    let quoted_packet = Ipv6Packet::new_unchecked(quoted);
+
+    if IpAddress::Ipv6(quoted_packet.dst_addr()) != remote_addr
+        || !is_quoted_probe(quoted_packet.next_header(), &quoted[IPV6_HEADER_LENGTH..], protocol, ident, sequence) {
+        return None;
+    }
+
+    match icmp_packet.msg_type() {
+        Icmpv6Message::TimeExceeded => Some(ProbeReply::Hop(source)),
+        _ => Some(match Icmpv6DstUnreachable::from(icmp_packet.msg_code()) {
+            Icmpv6DstUnreachable::PortUnreachable => ProbeReply::Reached(source),
+            Icmpv6DstUnreachable::NoRoute => ProbeReply::Unreachable(source, String::from("!N")),
+            Icmpv6DstUnreachable::AddrUnreachable => ProbeReply::Unreachable(source, String::from("!H")),
+            Icmpv6DstUnreachable::AdminProhibit | Icmpv6DstUnreachable::FailedPolicy | Icmpv6DstUnreachable::RejectRoute => ProbeReply::Unreachable(source, String::from("!X")),
+            code => ProbeReply::Unreachable(source, format!("!{}", u8::from(code)))
+        })
+    }
+}
+
+/// Whether the quoted transport header is the one of the given probe
+fn is_quoted_probe(next_header: IpProtocol, transport: &[u8], protocol: &TracerouteProtocol, ident: u16, sequence: u16) -> bool {
+    match (protocol, next_header) {
+        (TracerouteProtocol::Udp, IpProtocol::Udp) => {
+            NetworkEndian::read_u16(&transport[0..2]) == ident
+                && NetworkEndian::read_u16(&transport[2..4]) == BASE_PORT.wrapping_add(sequence)
+        },
+        // Echo request identifier and sequence number
+        (TracerouteProtocol::Icmp, IpProtocol::Icmp | IpProtocol::Icmpv6) => {
+            NetworkEndian::read_u16(&transport[4..6]) == ident
+                && NetworkEndian::read_u16(&transport[6..8]) == sequence
+        },
+        _ => false
+    }
+}
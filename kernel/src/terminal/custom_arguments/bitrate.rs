@@ -0,0 +1,23 @@
+use alloc::format;
+use core::str::FromStr;
+use no_std_clap_core::arg::from_arg::FromArg;
+use no_std_clap_core::error::ParseError;
+
+/// Bitrate in bits per second, with an optional K, M or G suffix (e.g. "10M")
+pub struct BitrateArg(pub u64);
+
+impl FromArg for BitrateArg {
+    fn from_arg(arg: &str) -> Result<Self, ParseError> where Self: Sized {
+        let (value, multiplier) = match arg.char_indices().last() {
+            Some((index, 'k' | 'K')) => (&arg[..index], 1_000.0),
+            Some((index, 'm' | 'M')) => (&arg[..index], 1_000_000.0),
+            Some((index, 'g' | 'G')) => (&arg[..index], 1_000_000_000.0),
+            _ => (arg, 1.0)
+        };
+
+        match f64::from_str(value) {
+            Ok(value) if value.is_finite() && value >= 0.0 => Ok(BitrateArg((value * multiplier) as u64)),
+            _ => Err(ParseError::InvalidValue(format!("\"{arg}\", need a bitrate in bits per second (e.g. 10M)")))
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use alloc::format;
+use core::str::FromStr;
+use no_std_clap_core::arg::from_arg::FromArg;
+use no_std_clap_core::error::ParseError;
+use smoltcp::time::Duration;
+
+/// Duration given in seconds, fractions of a second included (e.g. "0.2")
+pub struct SecondsArg(pub Duration);
+
+impl FromArg for SecondsArg {
+    fn from_arg(arg: &str) -> Result<Self, ParseError> where Self: Sized {
+        match f64::from_str(arg) {
+            Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(SecondsArg(Duration::from_micros((seconds * 1_000_000.0) as u64))),
+            _ => Err(ParseError::InvalidValue(format!("\"{arg}\", need a positive number of seconds")))
+        }
+    }
+}
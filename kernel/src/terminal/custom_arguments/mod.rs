@@ -1,3 +1,6 @@
 pub mod verbosity;
 pub mod ip_address;
-pub mod network_interface;
\ No newline at end of file
+pub mod network_interface;
+pub mod duration;
+pub mod source;
+pub mod bitrate;
\ No newline at end of file
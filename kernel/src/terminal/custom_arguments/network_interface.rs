@@ -14,10 +14,9 @@ impl FromArg for NetworkInterfaceArg {
             return Ok(NetworkInterfaceArg(arg.to_string()));
         }
         
-        for (index, _) in network_manager.interfaces.iter().enumerate() {
-            if arg == format!("eth{index}") {
-                return Ok(NetworkInterfaceArg(arg.to_string()));
-            }
+        // The NICs and the tunnels
+        if network_manager.interfaces.contains_key(arg) {
+            return Ok(NetworkInterfaceArg(arg.to_string()));
         }
 
         Err(ParseError::InvalidValue(format!("Network interface \"{arg}\" not found")))
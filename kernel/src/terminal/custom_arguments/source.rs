@@ -0,0 +1,26 @@
+use crate::terminal::custom_arguments::ip_address::IpAddressArg;
+use crate::terminal::custom_arguments::network_interface::NetworkInterfaceArg;
+use alloc::format;
+use alloc::string::String;
+use no_std_clap_core::arg::from_arg::FromArg;
+use no_std_clap_core::error::ParseError;
+use smoltcp::wire::IpAddress;
+
+/// Where to send packets from, either an interface or one of its addresses
+pub enum SourceArg {
+    Interface(String),
+    Address(IpAddress),
+}
+
+impl FromArg for SourceArg {
+    fn from_arg(arg: &str) -> Result<Self, ParseError> where Self: Sized {
+        if let Ok(IpAddressArg(address)) = IpAddressArg::from_arg(arg) {
+            return Ok(SourceArg::Address(address));
+        }
+
+        match NetworkInterfaceArg::from_arg(arg) {
+            Ok(NetworkInterfaceArg(interface_name)) => Ok(SourceArg::Interface(interface_name)),
+            Err(_) => Err(ParseError::InvalidValue(format!("\"{arg}\", need a network interface or one of its IP addresses")))
+        }
+    }
+}
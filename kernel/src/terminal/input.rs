@@ -0,0 +1,76 @@
+use crate::task::executor::current_task;
+use crate::task::task::TaskId;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use spin::Mutex;
+
+/// Lines typed in the terminal of the foreground commands, by task
+static INPUTS: Mutex<BTreeMap<TaskId, CommandInput>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct CommandInput {
+    lines: VecDeque<String>,
+    /// The end of the input was typed (Ctrl-D)
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl CommandInput {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Give the lines typed in the terminal to the given command
+pub fn open_input(task_id: TaskId) {
+    INPUTS.lock().entry(task_id).or_default();
+}
+
+pub fn send_input(task_id: TaskId, line: String) {
+    if let Some(input) = INPUTS.lock().get_mut(&task_id) {
+        input.lines.push_back(line);
+        input.wake();
+    }
+}
+
+/// The command reads the end of its input once the pending lines are read
+pub fn close_input(task_id: TaskId) {
+    if let Some(input) = INPUTS.lock().get_mut(&task_id) {
+        input.closed = true;
+        input.wake();
+    }
+}
+
+pub fn remove_input(task_id: TaskId) {
+    INPUTS.lock().remove(&task_id);
+}
+
+/// Next line typed in the terminal of the current command, without its line ending.
+/// Returns None at the end of the input, or right away for the commands run in the background.
+pub async fn read_line() -> Option<String> {
+    let task_id = current_task()?;
+
+    poll_fn(|context| {
+        let mut inputs = INPUTS.lock();
+
+        let Some(input) = inputs.get_mut(&task_id) else {
+            return Poll::Ready(None);
+        };
+
+        if let Some(line) = input.lines.pop_front() {
+            return Poll::Ready(Some(line));
+        }
+
+        if input.closed {
+            return Poll::Ready(None);
+        }
+
+        input.waker = Some(context.waker().clone());
+        Poll::Pending
+    })
+    .await
+}
@@ -1,5 +1,7 @@
 pub mod args;
 pub mod custom_arguments;
 pub mod cli;
+pub mod remote;
+pub mod input;
 pub mod commands;
 mod error;
\ No newline at end of file
@@ -0,0 +1,347 @@
+use crate::printer::macros::{stop_capture, take_captured};
+use crate::task::executor::{cancel_task, is_task_running};
+use crate::task::task::TaskId;
+use crate::terminal::cli::{parse_background, spawn_command, Cli, CliTerminal};
+use crate::terminal::input::{close_input, open_input, remove_input, send_input};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use yansi::Paint;
+
+/// Terminal of a remote session, its output is sent over the network
+pub struct RemoteTerminal {
+    output: Vec<u8>,
+    /// Whether the server echoes the input, otherwise the client edits its lines locally
+    pub echo: bool,
+    prompt_shown: bool,
+}
+
+impl RemoteTerminal {
+    pub fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            echo: true,
+            prompt_shown: false,
+        }
+    }
+
+    /// Write bytes as is, such as protocol commands
+    pub fn write_raw(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.output)
+    }
+
+    /// Erase the prompt line, before writing output over it
+    fn erase_line(&mut self) {
+        if self.echo {
+            self.output.extend_from_slice(b"\r\x1b[K");
+        }
+        else if self.prompt_shown {
+            self.output.extend_from_slice(b"\r\n");
+        }
+
+        self.prompt_shown = false;
+    }
+}
+
+impl Default for RemoteTerminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CliTerminal for RemoteTerminal {
+    fn write_str(&mut self, string: &str) {
+        for byte in string.bytes() {
+            if byte == b'\n' {
+                self.output.push(b'\r');
+            }
+            self.output.push(byte);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.output.extend_from_slice(b"\r\n");
+        self.prompt_shown = false;
+    }
+
+    fn redraw_line(&mut self, prompt: &str, _prompt_length: usize, line: &str, cursor_index: usize, _show_cursor: bool) {
+        // In line mode, the client displays the line itself
+        if !self.echo {
+            if !self.prompt_shown {
+                self.write_str(prompt);
+                self.prompt_shown = true;
+            }
+            return;
+        }
+
+        // Go back to the line start, and erase it
+        self.output.extend_from_slice(b"\r\x1b[K");
+        self.write_str(prompt);
+        self.write_str(line);
+
+        let cursor_offset = line.len() - cursor_index;
+        if cursor_offset > 0 {
+            self.write_str(&format!("\x1b[{}D", cursor_offset));
+        }
+
+        self.prompt_shown = true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyState {
+    Data,
+    /// After a carriage return, which may be followed by a line feed or a null byte
+    CarriageReturn,
+    Escape,
+    /// Control sequence introducer, along with its numeric parameter
+    Csi(u16),
+}
+
+/// CLI driven by the keys a remote client sends
+pub struct RemoteSession {
+    cli: Cli<RemoteTerminal>,
+    state: KeyState,
+    pub closing: bool,
+    /// Command the session waits for, its output is captured
+    foreground: Option<TaskId>,
+    /// Commands run with "&", along with their command line
+    background: Vec<(TaskId, String)>,
+    /// Line typed for the foreground command
+    input_line: String,
+}
+
+impl RemoteSession {
+    pub fn new(mut terminal: RemoteTerminal) -> Self {
+        terminal.write_str("RetOS - A Router Network Operating System\n\n");
+
+        let mut cli = Cli::new(format!("{}{} ", "RetOS".dim(), '$'.white()), 7, terminal);
+        cli.reset_line();
+
+        Self {
+            cli,
+            state: KeyState::Data,
+            closing: false,
+            foreground: None,
+            background: Vec::new(),
+            input_line: String::new(),
+        }
+    }
+
+    pub fn terminal(&mut self) -> &mut RemoteTerminal {
+        self.cli.terminal()
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.cli.terminal().take_output()
+    }
+
+    /// Cancel the foreground command, or the line being edited
+    pub fn interrupt(&mut self) {
+        match self.foreground {
+            Some(task_id) => {
+                cancel_task(task_id);
+                self.cli.terminal().write_str("^C\n");
+            },
+            None => self.cli.cancel_line()
+        }
+    }
+
+    /// Write the output of the commands, and show the prompt again once the foreground one ended
+    pub fn poll(&mut self) {
+        if let Some(task_id) = self.foreground {
+            let output = take_captured(task_id);
+            self.cli.terminal().write_str(&output);
+
+            if !is_task_running(task_id) {
+                let output = stop_capture(task_id);
+                self.cli.terminal().write_str(&output);
+
+                remove_input(task_id);
+                self.input_line.clear();
+                self.foreground = None;
+                self.cli.reset_line();
+            }
+        }
+
+        let mut background_output = String::new();
+
+        self.background.retain(|(task_id, command)| {
+            background_output.push_str(&take_captured(*task_id));
+
+            match is_task_running(*task_id) {
+                true => true,
+                false => {
+                    background_output.push_str(&stop_capture(*task_id));
+                    background_output.push_str(&format!("[{}] Done {}\n", task_id.0, command));
+                    false
+                }
+            }
+        });
+
+        if background_output.is_empty() {
+            return;
+        }
+
+        // Written over the prompt, which is shown again below
+        match self.foreground {
+            Some(_) => self.cli.terminal().write_str(&background_output),
+            None => {
+                self.cli.terminal().erase_line();
+                self.cli.terminal().write_str(&background_output);
+                self.cli.reset_line();
+            }
+        }
+    }
+
+    pub fn receive(&mut self, byte: u8) {
+        if self.closing {
+            return;
+        }
+
+        self.state = match (self.state, byte) {
+            (KeyState::CarriageReturn, b'\n' | b'\0') => KeyState::Data,
+            (KeyState::Escape, b'[' | b'O') => KeyState::Csi(0),
+            (KeyState::Escape, _) => KeyState::Data,
+            (KeyState::Csi(parameter), b'0'..=b'9') => KeyState::Csi(parameter.saturating_mul(10).saturating_add((byte - b'0') as u16)),
+            (KeyState::Csi(parameter), _) => {
+                // The keys are not edited for the commands
+                if self.foreground.is_none() {
+                    self.handle_control_sequence(parameter, byte);
+                }
+                KeyState::Data
+            },
+            (KeyState::Data | KeyState::CarriageReturn, _) => self.handle_data(byte)
+        };
+    }
+
+    fn handle_data(&mut self, byte: u8) -> KeyState {
+        if let Some(task_id) = self.foreground {
+            return self.handle_input(task_id, byte);
+        }
+
+        match byte {
+            b'\r' => {
+                self.enter();
+                return KeyState::CarriageReturn;
+            },
+            b'\n' => self.enter(),
+            // Ctrl-C
+            0x03 => self.interrupt(),
+            // Ctrl-D
+            0x04 => self.closing = true,
+            // Backspace, which most clients send as DEL
+            0x08 | 0x7F => {
+                self.cli.handle_scancode(0x8);
+            },
+            0x1B => return KeyState::Escape,
+            0x20..=0x7E => {
+                self.cli.handle_scancode(byte);
+            },
+            _ => {}
+        }
+
+        KeyState::Data
+    }
+
+    /// Key typed while a command runs, the lines are given to it
+    fn handle_input(&mut self, task_id: TaskId, byte: u8) -> KeyState {
+        let echo = self.cli.terminal().echo;
+
+        match byte {
+            b'\r' | b'\n' => {
+                if echo {
+                    self.cli.terminal().newline();
+                }
+
+                send_input(task_id, core::mem::take(&mut self.input_line));
+
+                if byte == b'\r' {
+                    return KeyState::CarriageReturn;
+                }
+            },
+            // Ctrl-C
+            0x03 => self.interrupt(),
+            // Ctrl-D
+            0x04 => close_input(task_id),
+            0x08 | 0x7F => {
+                if self.input_line.pop().is_some() && echo {
+                    self.cli.terminal().write_str("\x08 \x08");
+                }
+            },
+            0x1B => return KeyState::Escape,
+            0x20..=0x7E => {
+                self.input_line.push(byte as char);
+
+                if echo {
+                    self.cli.terminal().write_raw(&[byte]);
+                }
+            },
+            _ => {}
+        }
+
+        KeyState::Data
+    }
+
+    fn handle_control_sequence(&mut self, parameter: u16, final_byte: u8) {
+        match (final_byte, parameter) {
+            (b'A', _) => self.cli.previous_command(),
+            (b'B', _) => self.cli.next_command(),
+            (b'C', _) => self.cli.move_cursor_right(),
+            (b'D', _) => self.cli.move_cursor_left(),
+            (b'~', 3) => {
+                self.cli.handle_scancode(0x7F);
+            },
+            _ => {}
+        }
+    }
+
+    fn enter(&mut self) {
+        let Some(command) = self.cli.handle_scancode(b'\n') else {
+            return;
+        };
+
+        if matches!(command.trim(), "exit" | "logout" | "quit") {
+            self.closing = true;
+            return;
+        }
+
+        let (command, background) = parse_background(&command);
+
+        if command.is_empty() {
+            self.cli.reset_line();
+            return;
+        }
+
+        let task_id = spawn_command(command, true);
+
+        match background {
+            true => {
+                self.cli.terminal().write_str(&format!("[{}] {}\n", task_id.0, command));
+                self.background.push((task_id, String::from(command)));
+                self.cli.reset_line();
+            },
+            false => {
+                open_input(task_id);
+                self.foreground = Some(task_id);
+            }
+        }
+    }
+}
+
+/// The commands of a closed session are stopped
+impl Drop for RemoteSession {
+    fn drop(&mut self) {
+        let task_ids = self.foreground.iter().chain(self.background.iter().map(|(task_id, _)| task_id));
+
+        for &task_id in task_ids {
+            cancel_task(task_id);
+            stop_capture(task_id);
+            remove_input(task_id);
+        }
+    }
+}